@@ -0,0 +1,95 @@
+// Text filter cost: the interned keys FilterEvaluator compares (see filters.rs) against
+// normalizing each record's category, company and ARN per request, as filtering did before.
+// Prints the best of a few passes over the table for each.
+//
+//   cargo bench --bench filters
+//   cargo bench --bench filters -- 200000
+use excel_to_sqlite::filters::{normalize_arn, FilterEvaluator, Interner, RecordKeys, SearchFilters};
+use excel_to_sqlite::{normalize_scheme_name, CombinedSchemeData};
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+const DEFAULT_RECORDS: usize = 50_000;
+const RUNS: usize = 5;
+const COMPANIES: [&str; 6] = ["PPFAS", "Quant", "HDFC", "ICICI Prudential", "SBI", "Axis"];
+const CATEGORIES: [&str; 5] = ["Flexi Cap", "Large Cap", "Mid Cap", "Small Cap", "Corporate Bond"];
+// Spelled the way requests spell them, not the way uploads did
+const QUERY: [(&str, &str); 4] = [
+    ("category", "flexi-cap"),
+    ("category", "SMALL CAP"),
+    ("company", "hdfc"),
+    ("arn", "arn 1010"),
+];
+
+// A fund per four records, each with four rates, like the production join
+fn record(n: usize) -> CombinedSchemeData {
+    let fund = n / 4;
+    let company = COMPANIES[fund % COMPANIES.len()];
+    let name = format!("{} {} Fund {}", company, CATEGORIES[fund % CATEGORIES.len()], fund);
+    serde_json::from_value(json!({
+        "fund_id": fund,
+        "fund_category": CATEGORIES[fund % CATEGORIES.len()],
+        "rate_id": n,
+        "arn": format!("ARN-{}", 1000 + n % 25),
+        "company": company,
+        "brokerage_type": "Trail",
+        "scheme_name": name,
+        "normalized_name": name.to_lowercase(),
+        "data_completeness": 0.36,
+        "incomplete": false,
+        "is_rate_active": true
+    }))
+    .expect("bench record deserializes")
+}
+
+fn best_of(runs: usize, mut run: impl FnMut() -> usize) -> (Duration, usize) {
+    let mut best = Duration::MAX;
+    let mut matches = 0;
+    for _ in 0..runs {
+        let started = Instant::now();
+        matches = run();
+        best = best.min(started.elapsed());
+    }
+    (best, matches)
+}
+
+fn main() {
+    let count = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(DEFAULT_RECORDS);
+    let records: Vec<CombinedSchemeData> = (0..count).map(record).collect();
+    let mut interner = Interner::default();
+    let keys: Vec<RecordKeys> = records.iter().map(|record| RecordKeys::new(record, &mut interner)).collect();
+
+    let pairs: Vec<(String, String)> = QUERY.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    let params: HashMap<String, String> = pairs.iter().cloned().collect();
+    let filters = SearchFilters::from_query(&params, &pairs).expect("bench filters parse");
+
+    let (interned, interned_matches) = best_of(RUNS, || {
+        let mut evaluator = FilterEvaluator::new(&filters);
+        evaluator.bind(&interner);
+        records.iter().zip(&keys).filter(|(record, keys)| evaluator.accepts(record, keys)).count()
+    });
+    let (strings, string_matches) = best_of(RUNS, || {
+        let categories: Vec<String> = filters.category.iter().map(|category| normalize_scheme_name(category)).collect();
+        let company = filters.company.as_deref().map(normalize_scheme_name);
+        let arn = filters.arn.as_deref().map(normalize_arn);
+        records
+            .iter()
+            .filter(|record| {
+                let category = record.fund_category.as_deref().map(normalize_scheme_name);
+                category.is_some_and(|category| categories.contains(&category))
+                    && record.company.as_deref().map(normalize_scheme_name) == company
+                    && record.arn.as_deref().map(normalize_arn) == arn
+            })
+            .count()
+    });
+    assert_eq!(interned_matches, string_matches);
+
+    println!("{} records, {} matches, best of {} passes", count, interned_matches, RUNS);
+    println!("  interned keys  {:>10.1?}", interned);
+    println!("  strings        {:>10.1?}", strings);
+    println!("  interned keys filter in {:.0}% of the string time", interned.as_secs_f64() / strings.as_secs_f64() * 100.0);
+}
@@ -0,0 +1,91 @@
+// Snapshot load time: the binary format (checksummed bincode, see snapshot.rs) against the same
+// records as a JSON array, each read from disk and indexed into a VirtualTable. Prints the best of
+// a few runs per format.
+//
+//   cargo bench --bench snapshot
+//   cargo bench --bench snapshot -- 200000
+use chrono::Utc;
+use excel_to_sqlite::table::VirtualTable;
+use excel_to_sqlite::{snapshot, CombinedSchemeData};
+use serde_json::json;
+use std::fs;
+use std::time::{Duration, Instant};
+
+const DEFAULT_RECORDS: usize = 50_000;
+const RUNS: usize = 5;
+const COMPANIES: [&str; 6] = ["PPFAS", "Quant", "HDFC", "ICICI Prudential", "SBI", "Axis"];
+const CATEGORIES: [&str; 5] = ["Flexi Cap", "Large Cap", "Mid Cap", "Small Cap", "Corporate Bond"];
+
+// A fund per four records, each with four rates, like the production join
+fn record(n: usize) -> CombinedSchemeData {
+    let fund = n / 4;
+    let company = COMPANIES[fund % COMPANIES.len()];
+    let name = format!("{} {} Fund {}", company, CATEGORIES[fund % CATEGORIES.len()], fund);
+    serde_json::from_value(json!({
+        "fund_id": fund,
+        "fund_category": CATEGORIES[fund % CATEGORIES.len()],
+        "launch_date": "2015-04-01",
+        "latest_nav": 10.0 + (n % 97) as f32,
+        "year_1": (n % 31) as f32 - 5.0,
+        "years_3": (n % 23) as f32,
+        "rate_id": n,
+        "arn": format!("ARN-{}", 1000 + n % 250),
+        "company": company,
+        "brokerage_type": "Trail",
+        "start_date": "2024-04-01",
+        "end_date": "2025-03-31",
+        "base_year_1": 0.75,
+        "scheme_name": name,
+        "normalized_name": name.to_lowercase(),
+        "data_completeness": 0.36,
+        "incomplete": false,
+        "is_rate_active": true
+    }))
+    .expect("bench record deserializes")
+}
+
+fn best_of(runs: usize, mut run: impl FnMut() -> usize) -> (Duration, usize) {
+    let mut best = Duration::MAX;
+    let mut records = 0;
+    for _ in 0..runs {
+        let started = Instant::now();
+        records = run();
+        best = best.min(started.elapsed());
+    }
+    (best, records)
+}
+
+fn main() {
+    let count = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(DEFAULT_RECORDS);
+    let mut table = VirtualTable::new();
+    for n in 0..count {
+        table.add_record(record(n));
+    }
+
+    let dir = tempfile::tempdir().expect("temp dir");
+    let binary_path = dir.path().join("table.snapshot");
+    let json_path = dir.path().join("table.json");
+    snapshot::write(&binary_path, &table, Utc::now()).expect("write snapshot");
+    fs::write(&json_path, serde_json::to_vec(table.records()).expect("encode JSON")).expect("write JSON");
+
+    let (binary, binary_records) = best_of(RUNS, || snapshot::read(&binary_path).expect("read snapshot").0.len());
+    let (json, json_records) = best_of(RUNS, || {
+        let bytes = fs::read(&json_path).expect("read JSON");
+        let records: Vec<CombinedSchemeData> = serde_json::from_slice(&bytes).expect("decode JSON");
+        let mut table = VirtualTable::new();
+        for record in records {
+            table.add_record(record);
+        }
+        table.len()
+    });
+    assert_eq!((binary_records, json_records), (count, count));
+
+    let size = |path| fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+    println!("{} records, best of {} loads", count, RUNS);
+    println!("  binary  {:>10.1?}  {:>12} bytes", binary, size(&binary_path));
+    println!("  json    {:>10.1?}  {:>12} bytes", json, size(&json_path));
+    println!("  binary loads in {:.0}% of the JSON time", binary.as_secs_f64() / json.as_secs_f64() * 100.0);
+}
@@ -0,0 +1,438 @@
+// Load generator: replays a weighted mix of searches, fuzzy name lookups, exports and periodic
+// uploads against a running instance, then reports per-endpoint latency percentiles and error
+// rates. Exits non-zero when the SLO thresholds in its config are violated.
+//
+//   cargo run --release --example perftracker-loadgen -- loadgen.json
+//   cargo run --example perftracker-loadgen -- loadgen.json --smoke
+use excel_to_sqlite::jobs::{JobState, JobStatus};
+use excel_to_sqlite::openapi::{ErrorResponse, UploadAccepted};
+use excel_to_sqlite::{xlsx_export, CombinedSchemeData};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const SMOKE_DURATION_SECS: u64 = 5;
+const SMOKE_CONCURRENCY: usize = 2;
+const SMOKE_UPLOAD_ROWS: usize = 20;
+const API_PREFIX: &str = "/api/v1";
+// How often, and how many times, an accepted upload's job is polled before it counts as failed
+const JOB_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const JOB_POLL_ATTEMPTS: usize = 600;
+const CATEGORIES: [&str; 3] = ["Large Cap", "Liquid", "Gilt"];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LoadConfig {
+    target_url: String,
+    concurrency: usize,
+    duration_secs: u64,
+    // One search term per line; a small built-in list is used when absent
+    terms_file: Option<PathBuf>,
+    mix: TrafficMix,
+    // None disables uploads
+    upload_interval_secs: Option<u64>,
+    upload_rows: usize,
+    // Sent as X-Api-Key; uploads need an uploader or admin key
+    api_key: Option<String>,
+    slo: Slo,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        Self {
+            target_url: "http://127.0.0.1:8081".to_string(),
+            concurrency: 16,
+            duration_secs: 60,
+            terms_file: None,
+            mix: TrafficMix::default(),
+            upload_interval_secs: Some(300),
+            upload_rows: 500,
+            api_key: None,
+            slo: Slo::default(),
+        }
+    }
+}
+
+// Relative weights of each request kind
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct TrafficMix {
+    search: u32,
+    suggest: u32,
+    export: u32,
+}
+
+impl Default for TrafficMix {
+    fn default() -> Self {
+        Self {
+            search: 70,
+            suggest: 25,
+            export: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Slo {
+    max_error_rate: f64,
+    // Endpoint name -> p95 latency ceiling in milliseconds
+    p95_ms: HashMap<String, u64>,
+}
+
+impl Default for Slo {
+    fn default() -> Self {
+        Self {
+            max_error_rate: 0.01,
+            p95_ms: HashMap::from([
+                ("search".to_string(), 200),
+                ("suggest".to_string(), 200),
+                ("export".to_string(), 2000),
+                ("upload".to_string(), 30000),
+            ]),
+        }
+    }
+}
+
+const DEFAULT_TERMS: [&str; 8] = [
+    "blue chip",
+    "flexi cap",
+    "liquid",
+    "gilt",
+    "small cap",
+    "corporate bond",
+    "index",
+    "balanced advantage",
+];
+
+#[derive(Debug, Default)]
+struct EndpointStats {
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+
+type Stats = Arc<Mutex<HashMap<&'static str, EndpointStats>>>;
+
+fn record(stats: &Stats, endpoint: &'static str, latency: Duration, ok: bool) {
+    let mut stats = stats.lock().unwrap();
+    let entry = stats.entry(endpoint).or_default();
+    entry.latencies.push(latency);
+    if !ok {
+        entry.errors += 1;
+    }
+}
+
+// xorshift64; good enough to spread traffic without pulling in an RNG crate
+struct Rng(u64);
+
+impl Rng {
+    fn seeded(salt: u64) -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(1);
+        Rng((nanos ^ salt.wrapping_mul(0x9E37_79B9_7F4A_7C15)).max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n.max(1)
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let smoke = args.iter().any(|arg| arg == "--smoke");
+
+    let mut config = match args.iter().find(|arg| !arg.starts_with("--")) {
+        Some(path) => match std::fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|contents| {
+            serde_json::from_str::<LoadConfig>(&contents).map_err(|e| e.to_string())
+        }) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Invalid loadgen config {}: {}", path, e);
+                std::process::exit(2);
+            }
+        },
+        None => LoadConfig::default(),
+    };
+    if smoke {
+        config.duration_secs = SMOKE_DURATION_SECS;
+        config.concurrency = SMOKE_CONCURRENCY;
+        config.upload_rows = SMOKE_UPLOAD_ROWS;
+        config.upload_interval_secs = config.upload_interval_secs.map(|_| SMOKE_DURATION_SECS / 2);
+    }
+    if config.concurrency == 0 || config.mix.search + config.mix.suggest + config.mix.export == 0 {
+        eprintln!("concurrency and at least one traffic weight must be non-zero");
+        std::process::exit(2);
+    }
+
+    let terms = match load_terms(&config) {
+        Ok(terms) => Arc::new(terms),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    println!(
+        "Running {}s against {} with {} concurrent clients{}",
+        config.duration_secs,
+        config.target_url,
+        config.concurrency,
+        if smoke { " (smoke)" } else { "" }
+    );
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(60))
+        .build()
+        .expect("Failed to build HTTP client");
+    let stats: Stats = Arc::new(Mutex::new(HashMap::new()));
+    let deadline = Instant::now() + Duration::from_secs(config.duration_secs);
+    let config = Arc::new(config);
+
+    let mut tasks = Vec::new();
+    for worker in 0..config.concurrency {
+        let (client, stats, config, terms) = (client.clone(), Arc::clone(&stats), Arc::clone(&config), Arc::clone(&terms));
+        tasks.push(tokio::spawn(async move {
+            let mut rng = Rng::seeded(worker as u64 + 1);
+            while Instant::now() < deadline {
+                run_one(&client, &config, &terms, &mut rng, &stats).await;
+            }
+        }));
+    }
+
+    if let Some(interval) = config.upload_interval_secs {
+        let (client, stats, config) = (client.clone(), Arc::clone(&stats), Arc::clone(&config));
+        tasks.push(tokio::spawn(async move {
+            let mut generation = 0;
+            loop {
+                tokio::time::sleep(Duration::from_secs(interval.max(1))).await;
+                if Instant::now() >= deadline {
+                    break;
+                }
+                generation += 1;
+                upload_workbook(&client, &config, generation, &stats).await;
+            }
+        }));
+    }
+
+    for task in tasks {
+        let _ = task.await;
+    }
+
+    let stats = stats.lock().unwrap();
+    let violations = report(&stats, &config.slo);
+    if violations.is_empty() {
+        println!("All SLOs met");
+    } else {
+        for violation in &violations {
+            eprintln!("SLO violated: {}", violation);
+        }
+        std::process::exit(1);
+    }
+}
+
+fn load_terms(config: &LoadConfig) -> Result<Vec<String>, String> {
+    let terms: Vec<String> = match &config.terms_file {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read terms file {}: {}", path.display(), e))?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+        None => DEFAULT_TERMS.iter().map(|term| term.to_string()).collect(),
+    };
+    if terms.is_empty() {
+        return Err("Terms file contains no terms".to_string());
+    }
+    Ok(terms)
+}
+
+async fn run_one(client: &reqwest::Client, config: &LoadConfig, terms: &[String], rng: &mut Rng, stats: &Stats) {
+    let mix = &config.mix;
+    let pick = rng.below(u64::from(mix.search + mix.suggest + mix.export)) as u32;
+    let term = &terms[rng.below(terms.len() as u64) as usize];
+
+    let (endpoint, request) = if pick < mix.search {
+        ("search", client.get(endpoint_url(config, "/search")).query(&[("q", term)]))
+    } else if pick < mix.search + mix.suggest {
+        (
+            "suggest",
+            client
+                .get(endpoint_url(config, "/funds/by-name"))
+                .query(&[("name", term.as_str()), ("fuzzy", "true")]),
+        )
+    } else {
+        ("export", client.get(endpoint_url(config, "/export/unmatched-rates.xlsx")))
+    };
+
+    let started = Instant::now();
+    let ok = match with_key(request, config).send().await {
+        // A fuzzy lookup that finds nothing is a normal 404, not an error
+        Ok(response) => response.status().is_success() || (endpoint == "suggest" && response.status() == 404),
+        Err(_) => false,
+    };
+    record(stats, endpoint, started.elapsed(), ok);
+}
+
+fn endpoint_url(config: &LoadConfig, path: &str) -> String {
+    format!("{}{}{}", config.target_url.trim_end_matches('/'), API_PREFIX, path)
+}
+
+fn with_key(request: reqwest::RequestBuilder, config: &LoadConfig) -> reqwest::RequestBuilder {
+    match &config.api_key {
+        Some(key) => request.header("X-Api-Key", key),
+        None => request,
+    }
+}
+
+// Timed from the POST until the job finishes; only a job that ends "done" counts as a success
+async fn upload_workbook(client: &reqwest::Client, config: &LoadConfig, generation: u64, stats: &Stats) {
+    let bytes = match xlsx_export::build_workbook(&upload_records(config.upload_rows, generation)) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Failed to generate workbook: {}", e);
+            return;
+        }
+    };
+
+    let part = reqwest::multipart::Part::bytes(bytes).file_name(format!("loadgen-{}.xlsx", generation));
+    let form = reqwest::multipart::Form::new().part("excel_file", part);
+
+    let started = Instant::now();
+    let request = with_key(client.post(endpoint_url(config, "/upload")).multipart(form), config);
+    let result = match request.send().await {
+        Ok(response) if response.status() == 202 => match response.json::<UploadAccepted>().await {
+            Ok(accepted) => wait_for_job(client, config, &accepted.job_url).await,
+            Err(e) => Err(format!("unexpected 202 body: {}", e)),
+        },
+        Ok(response) => {
+            let status = response.status();
+            Err(match response.json::<ErrorResponse>().await {
+                Ok(error) => format!("{}: {}", status, error.message),
+                Err(_) => status.to_string(),
+            })
+        }
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = &result {
+        eprintln!("Upload {} failed: {}", generation, e);
+    }
+    record(stats, "upload", started.elapsed(), result.is_ok());
+}
+
+async fn wait_for_job(client: &reqwest::Client, config: &LoadConfig, job_url: &str) -> Result<(), String> {
+    let url = format!("{}{}", config.target_url.trim_end_matches('/'), job_url);
+    for _ in 0..JOB_POLL_ATTEMPTS {
+        let response = with_key(client.get(&url), config).send().await.map_err(|e| e.to_string())?;
+        let job = response.json::<JobStatus>().await.map_err(|e| e.to_string())?;
+        match job.state {
+            JobState::Done => return Ok(()),
+            JobState::Failed => return Err(format!("job {} failed: {}", job.id, job.summary.unwrap_or_default())),
+            JobState::Queued | JobState::Processing => tokio::time::sleep(JOB_POLL_INTERVAL).await,
+        }
+    }
+    Err(format!("job at {} did not finish", job_url))
+}
+
+// Funds spread over a few categories, named per generation so each upload adds new schemes.
+// Written by the export's workbook builder, whose layout the importer reads back.
+fn upload_records(rows: usize, generation: u64) -> Vec<CombinedSchemeData> {
+    let mut rng = Rng::seeded(generation);
+    (0..rows)
+        .map(|i| {
+            let category = CATEGORIES[i % CATEGORIES.len()];
+            let name = format!("Loadgen {} Fund {} {} Direct Growth", category, generation, i);
+            let mut returns = || (rng.below(40_000) as f64) / 100.0 - 50.0;
+            serde_json::from_value(json!({
+                "fund_category": category,
+                "launch_date": "2015-01-01",
+                "fund_size_apr25": returns().abs() * 100.0,
+                "latest_nav": returns().abs() + 10.0,
+                "month_1": returns(),
+                "months_3": returns(),
+                "months_6": returns(),
+                "ytd": returns(),
+                "year_1": returns(),
+                "years_3": returns(),
+                "years_5": returns(),
+                "scheme_name": name,
+                "normalized_name": name.to_lowercase(),
+                "data_completeness": 1.0,
+                "incomplete": false,
+                "is_rate_active": false
+            }))
+            .expect("loadgen record deserializes")
+        })
+        .collect()
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((p / 100.0) * (sorted.len() - 1) as f64).round() as usize;
+    sorted[rank.min(sorted.len() - 1)]
+}
+
+// Print the per-endpoint table and return every SLO violation
+fn report(stats: &HashMap<&'static str, EndpointStats>, slo: &Slo) -> Vec<String> {
+    let mut violations = Vec::new();
+    let mut endpoints: Vec<_> = stats.keys().copied().collect();
+    endpoints.sort_unstable();
+
+    println!(
+        "{:<10} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}",
+        "endpoint", "requests", "errors", "p50 ms", "p95 ms", "p99 ms", "max ms"
+    );
+
+    let (mut total, mut total_errors) = (0, 0);
+    for endpoint in endpoints {
+        let entry = &stats[endpoint];
+        let mut sorted = entry.latencies.clone();
+        sorted.sort_unstable();
+        let p95 = percentile(&sorted, 95.0);
+
+        println!(
+            "{:<10} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}",
+            endpoint,
+            sorted.len(),
+            entry.errors,
+            percentile(&sorted, 50.0).as_millis(),
+            p95.as_millis(),
+            percentile(&sorted, 99.0).as_millis(),
+            sorted.last().copied().unwrap_or_default().as_millis()
+        );
+
+        if let Some(&ceiling) = slo.p95_ms.get(endpoint) {
+            if p95.as_millis() > u128::from(ceiling) {
+                violations.push(format!("{} p95 {}ms exceeds {}ms", endpoint, p95.as_millis(), ceiling));
+            }
+        }
+        total += sorted.len();
+        total_errors += entry.errors;
+    }
+
+    let error_rate = if total == 0 { 1.0 } else { total_errors as f64 / total as f64 };
+    println!("total {} requests, error rate {:.2}%", total, error_rate * 100.0);
+    if total == 0 {
+        violations.push("no requests completed".to_string());
+    } else if error_rate > slo.max_error_rate {
+        violations.push(format!(
+            "error rate {:.2}% exceeds {:.2}%",
+            error_rate * 100.0,
+            slo.max_error_rate * 100.0
+        ));
+    }
+
+    violations
+}
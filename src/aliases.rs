@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio_postgres::Client;
+use utoipa::ToSchema;
+
+use crate::normalize_scheme_name;
+
+// One row of name_aliases. Both sides are stored normalized, so the SQL join and the in-memory
+// index expand names the same way.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct NameAlias {
+    pub id: i32,
+    pub alias: String,
+    pub canonical: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewNameAlias {
+    pub alias: String,
+    pub canonical: String,
+}
+
+impl NewNameAlias {
+    // Same rules as the config alias dictionary: a single token mapping to non-empty text
+    pub fn normalized(&self) -> Result<(String, String), String> {
+        let alias = normalize_scheme_name(&self.alias);
+        let canonical = normalize_scheme_name(&self.canonical);
+
+        if alias.is_empty() || alias.contains(' ') {
+            return Err(format!("Alias '{}' must be a single non-empty token", self.alias));
+        }
+        if canonical.is_empty() {
+            return Err(format!("Alias '{}' maps to an empty name", self.alias));
+        }
+        if canonical == alias {
+            return Err(format!("Alias '{}' maps to itself", self.alias));
+        }
+        Ok((alias, canonical))
+    }
+}
+
+pub async fn list_aliases(client: &Client) -> Result<Vec<NameAlias>, tokio_postgres::Error> {
+    let rows = client
+        .query("SELECT id, alias, canonical FROM name_aliases ORDER BY alias", &[])
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| NameAlias {
+            id: row.get("id"),
+            alias: row.get("alias"),
+            canonical: row.get("canonical"),
+        })
+        .collect())
+}
+
+// alias token -> canonical text, as VirtualTable::set_name_aliases takes it
+pub async fn load_aliases(client: &Client) -> Result<HashMap<String, String>, tokio_postgres::Error> {
+    Ok(list_aliases(client)
+        .await?
+        .into_iter()
+        .map(|alias| (alias.alias, alias.canonical))
+        .collect())
+}
+
+// None when the alias is already defined
+pub async fn insert_alias(
+    client: &Client,
+    alias: &str,
+    canonical: &str,
+) -> Result<Option<NameAlias>, tokio_postgres::Error> {
+    let row = client
+        .query_opt(
+            "INSERT INTO name_aliases (alias, canonical) VALUES ($1, $2)
+             ON CONFLICT (alias) DO NOTHING
+             RETURNING id",
+            &[&alias, &canonical],
+        )
+        .await?;
+
+    Ok(row.map(|row| NameAlias {
+        id: row.get("id"),
+        alias: alias.to_string(),
+        canonical: canonical.to_string(),
+    }))
+}
+
+// Returns the number of aliases removed (0 or 1)
+pub async fn delete_alias(client: &Client, id: i32) -> Result<u64, tokio_postgres::Error> {
+    client.execute("DELETE FROM name_aliases WHERE id = $1", &[&id]).await
+}
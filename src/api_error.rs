@@ -0,0 +1,360 @@
+use actix_multipart::MultipartError;
+use actix_web::dev::{Payload, ServiceRequest};
+use actix_web::http::{header, StatusCode};
+use actix_web::{FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use futures_util::future::{self, Ready};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::future::Future;
+use utoipa::ToSchema;
+
+use crate::db;
+
+// Seconds a client is told to wait before retrying a 503
+pub const RETRY_AFTER_SECS: u64 = 5;
+
+// Longest X-Request-Id taken from a client; anything longer gets an id of our own
+const MAX_CLIENT_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+// Stable, machine-readable error codes. Clients branch on these rather than on messages, so a
+// code is never renamed or given a different status once released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    // 400: a required query parameter is absent
+    MissingParameter,
+    // 400: a query or path parameter has a value we can't use
+    InvalidParameter,
+    // 400: a JSON body that doesn't parse or fails field validation
+    InvalidBody,
+    // 400: a malformed multipart form, or one with unexpected fields or no file
+    InvalidMultipart,
+    // 401: a write (or, with require_api_key_for_reads, any request) without a valid X-Api-Key
+    // or bearer token, or an invalid X-Api-Key
+    Unauthorized,
+    // 401: a bearer token that is malformed, badly signed or not accepted here
+    InvalidToken,
+    // 401: a bearer token past its exp; fetch a new one
+    TokenExpired,
+    // 403: valid credentials whose role doesn't allow the request
+    Forbidden,
+    // 404
+    NotFound,
+    // 409: the resource changed or is busy with other work
+    Conflict,
+    // 413
+    PayloadTooLarge,
+    // 422: a category or company outside the known set; details.closest suggests some
+    UnknownValue,
+    // 422: well-formed but unacceptable, like a rolled-back upload or an invalid configuration
+    Unprocessable,
+    // 428: an edit without If-Match or a version
+    PreconditionRequired,
+    // 429 with Retry-After: the client's request budget for this kind of endpoint is spent
+    RateLimited,
+    // 500
+    Internal,
+    // 503 with Retry-After: the database pool is exhausted or unreachable
+    DatabaseUnavailable,
+    // 503 with Retry-After: shutting down, or another export is being built
+    Unavailable,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::MissingParameter
+            | ErrorCode::InvalidParameter
+            | ErrorCode::InvalidBody
+            | ErrorCode::InvalidMultipart => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized | ErrorCode::InvalidToken | ErrorCode::TokenExpired => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::UnknownValue | ErrorCode::Unprocessable => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
+            ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::DatabaseUnavailable | ErrorCode::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+// Every error the API reports, rendered as {"code", "message", "details", "request_id"}
+#[derive(Debug)]
+pub struct ApiError {
+    code: ErrorCode,
+    message: String,
+    details: Option<Value>,
+    // Extra response headers, like the ETag of the current version on a conflict
+    headers: Vec<(header::HeaderName, String)>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ApiError {
+            code,
+            message: message.into(),
+            details: None,
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn missing_parameter(name: &str) -> Self {
+        ApiError::new(ErrorCode::MissingParameter, format!("Query parameter '{}' is required", name))
+            .with_details(json!({"parameter": name}))
+    }
+
+    pub fn invalid_parameter(message: impl Into<String>) -> Self {
+        ApiError::new(ErrorCode::InvalidParameter, message)
+    }
+
+    // A category or company filter outside the known set, with the closest known values
+    pub fn unknown_value(parameter: &str, value: &str, closest: Vec<String>) -> Self {
+        ApiError::new(ErrorCode::UnknownValue, format!("Unknown {} '{}'", parameter, value))
+            .with_details(json!({"parameter": parameter, "closest": closest}))
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        ApiError::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        ApiError::new(ErrorCode::Internal, message)
+    }
+
+    // 503 when the pool is exhausted or the database unreachable, so clients retry; otherwise a
+    // 500 carrying `message`
+    pub fn database(message: String, e: &(dyn std::error::Error + 'static)) -> Self {
+        if db::is_pool_unavailable(e) {
+            ApiError::new(ErrorCode::DatabaseUnavailable, "Database is busy or unavailable, please retry shortly")
+        } else {
+            ApiError::internal(message)
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn with_header(mut self, name: header::HeaderName, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+
+    // The envelope, also stored as the summary of a failed upload job
+    pub fn body(&self) -> Value {
+        json!({
+            "code": self.code,
+            "message": self.message,
+            "details": self.details,
+            "request_id": request_id()
+        })
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.code.status()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        // Every 503 is worth retrying
+        if self.status_code() == StatusCode::SERVICE_UNAVAILABLE {
+            response.insert_header((header::RETRY_AFTER, RETRY_AFTER_SECS.to_string()));
+        }
+        for (name, value) in &self.headers {
+            response.insert_header((name.clone(), value.as_str()));
+        }
+        response.json(self.body())
+    }
+}
+
+// Client disconnects and truncated bodies surface here too, which is the client's problem to retry
+impl From<MultipartError> for ApiError {
+    fn from(e: MultipartError) -> Self {
+        ApiError::new(ErrorCode::InvalidMultipart, format!("Invalid multipart form: {}", e))
+    }
+}
+
+// A blocking task that panicked or was cancelled
+impl From<actix_web::error::BlockingError> for ApiError {
+    fn from(e: actix_web::error::BlockingError) -> Self {
+        ApiError::internal(e.to_string())
+    }
+}
+
+// Extractor failures (a query string, path or JSON body that doesn't deserialize), which actix
+// would otherwise answer in plain text
+pub fn invalid_query(e: actix_web::error::QueryPayloadError, _: &actix_web::HttpRequest) -> actix_web::Error {
+    ApiError::invalid_parameter(e.to_string()).into()
+}
+
+pub fn invalid_path(e: actix_web::error::PathError, _: &actix_web::HttpRequest) -> actix_web::Error {
+    ApiError::invalid_parameter(e.to_string()).into()
+}
+
+pub fn invalid_json(e: actix_web::error::JsonPayloadError, _: &actix_web::HttpRequest) -> actix_web::Error {
+    let code = match &e {
+        actix_web::error::JsonPayloadError::Overflow { .. } | actix_web::error::JsonPayloadError::OverflowKnownLength { .. } => {
+            ErrorCode::PayloadTooLarge
+        }
+        _ => ErrorCode::InvalidBody,
+    };
+    ApiError::new(code, e.to_string()).into()
+}
+
+// The id of the request being handled, also sent back as X-Request-Id; None outside a request
+pub fn request_id() -> Option<String> {
+    REQUEST_ID.try_with(String::clone).ok()
+}
+
+// The client's X-Request-Id when it sent a usable one, so its logs and ours line up. Also stored
+// in the request's extensions for handlers that pass it on.
+pub fn assign_request_id(req: &ServiceRequest) -> String {
+    let from_client = req
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_CLIENT_REQUEST_ID_LEN);
+    let id = match from_client {
+        Some(id) => id.to_string(),
+        None => next_request_id(),
+    };
+    req.extensions_mut().insert(RequestContext { request_id: id.clone() });
+    id
+}
+
+// Runs `f` with `id` as the request id; upload jobs carry their request's id into the background
+pub async fn with_request_id<F: Future>(id: String, f: F) -> F::Output {
+    REQUEST_ID.scope(id, f).await
+}
+
+// The synchronous part of a request, i.e. the middleware before the handler's future is returned
+pub fn in_request<R>(id: String, f: impl FnOnce() -> R) -> R {
+    REQUEST_ID.sync_scope(id, f)
+}
+
+fn next_request_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+// What the upload pipeline needs to know about the request it works for; passed down explicitly
+// as it outlives the request in a job, and has no request at all in the import CLI
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub request_id: String,
+}
+
+impl RequestContext {
+    // For work that doesn't come from a request
+    pub fn detached() -> Self {
+        RequestContext { request_id: next_request_id() }
+    }
+}
+
+impl FromRequest for RequestContext {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, ApiError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let context = req.extensions().get::<RequestContext>().cloned().unwrap_or_else(RequestContext::detached);
+        future::ready(Ok(context))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{self, TestRequest};
+
+    use crate::config::RuntimeConfig;
+    use crate::test_support;
+
+    // The status and envelope of one request, checking the envelope has exactly its four keys
+    // and carries the id sent back as X-Request-Id
+    async fn envelope(state: &crate::AppState, request: TestRequest) -> (StatusCode, Value) {
+        let response = test_support::call(state, request.insert_header(("X-Request-Id", "req-575"))).await;
+        let status = response.status();
+        assert_eq!(response.headers().get("x-request-id").unwrap(), "req-575");
+        let body: Value = serde_json::from_slice(&test::read_body(response).await).unwrap();
+        let mut keys: Vec<&String> = body.as_object().unwrap().keys().collect();
+        keys.sort();
+        assert_eq!(keys, ["code", "details", "message", "request_id"], "{}", body);
+        assert_eq!(body["request_id"], "req-575");
+        (status, body)
+    }
+
+    #[actix_web::test]
+    async fn failed_uploads_answer_with_the_envelope() {
+        let config = RuntimeConfig {
+            max_upload_bytes: 64,
+            ..test_support::runtime_config()
+        };
+        let state = test_support::state(config, vec![]);
+
+        let no_file = test_support::multipart("/api/v1/upload", &[("note", None, b"hello")]);
+        let (status, body) = envelope(&state, test_support::as_admin(no_file)).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("invalid_multipart")));
+
+        let garbled = TestRequest::post()
+            .uri("/api/v1/upload")
+            .insert_header((header::CONTENT_TYPE, "multipart/form-data; boundary=x"))
+            .set_payload("not a multipart body");
+        let (status, body) = envelope(&state, test_support::as_admin(garbled)).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("invalid_multipart")));
+
+        let large = [b'x'; 1024];
+        let too_large = test_support::multipart("/api/v1/upload", &[("excel_file", Some("funds.csv"), &large)]);
+        let (status, body) = envelope(&state, test_support::as_admin(too_large)).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::PAYLOAD_TOO_LARGE, Some("payload_too_large")));
+        assert_eq!(body["details"]["max_upload_bytes"], 64);
+
+        let anonymous = test_support::multipart("/api/v1/upload", &[("excel_file", Some("funds.csv"), b"a,b")]);
+        let (status, body) = envelope(&state, anonymous).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::UNAUTHORIZED, Some("unauthorized")));
+    }
+
+    #[actix_web::test]
+    async fn bad_searches_answer_with_the_envelope() {
+        let state = test_support::state(RuntimeConfig::default(), vec![]);
+
+        for (uri, status, code) in [
+            ("/api/v1/search?limit=lots", StatusCode::BAD_REQUEST, "invalid_parameter"),
+            ("/api/v1/search?min_year_1=high", StatusCode::BAD_REQUEST, "invalid_parameter"),
+            ("/api/v1/search?category=mega+cap", StatusCode::UNPROCESSABLE_ENTITY, "unknown_value"),
+            ("/api/v1/suggest", StatusCode::BAD_REQUEST, "missing_parameter"),
+            ("/api/v1/no-such-route", StatusCode::NOT_FOUND, "not_found"),
+        ] {
+            let (actual, body) = envelope(&state, TestRequest::get().uri(uri)).await;
+            assert_eq!((actual, body["code"].as_str()), (status, Some(code)), "{}: {}", uri, body);
+            assert!(!body["message"].as_str().unwrap().is_empty());
+        }
+    }
+
+    #[actix_web::test]
+    async fn an_unreachable_database_is_a_503_worth_retrying() {
+        // The state's pool points at a port nothing listens on
+        let state = test_support::state(RuntimeConfig::default(), vec![]);
+
+        let response = test_support::call(&state, TestRequest::get().uri("/api/v1/funds/1")).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers().get(header::RETRY_AFTER).unwrap(), &RETRY_AFTER_SECS.to_string());
+        let body: Value = serde_json::from_slice(&test::read_body(response).await).unwrap();
+        assert_eq!(body["code"], "database_unavailable");
+    }
+}
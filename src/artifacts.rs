@@ -0,0 +1,208 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactEntry {
+    pub dataset: String,
+    pub generation: u64,
+    pub timestamp: DateTime<Utc>,
+    pub file_name: String,
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub artifacts: Vec<ArtifactEntry>,
+}
+
+// Publishes export artifacts under deterministic names and keeps manifest.json in step with
+// the files on disk. Artifacts are fully written (temp file + rename) before the manifest that
+// lists them is swapped in, so a listed file is always complete.
+#[derive(Debug)]
+pub struct ArtifactStore {
+    dir: PathBuf,
+    keep_generations: usize,
+    publish_lock: Mutex<()>,
+}
+
+impl ArtifactStore {
+    pub fn new(dir: PathBuf, keep_generations: usize) -> std::io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            keep_generations: keep_generations.max(1),
+            publish_lock: Mutex::new(()),
+        })
+    }
+
+    pub fn manifest(&self) -> std::io::Result<Manifest> {
+        let path = self.dir.join(MANIFEST_FILE);
+        match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Manifest::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Path of a published artifact, only if the manifest lists it (no arbitrary file access)
+    pub fn artifact_path(&self, file_name: &str) -> std::io::Result<Option<PathBuf>> {
+        let manifest = self.manifest()?;
+        Ok(manifest
+            .artifacts
+            .iter()
+            .find(|entry| entry.file_name == file_name)
+            .map(|entry| self.dir.join(&entry.file_name)))
+    }
+
+    pub fn publish(&self, dataset: &str, extension: &str, contents: &[u8]) -> std::io::Result<ArtifactEntry> {
+        let _guard = self.publish_lock.lock().unwrap();
+
+        let mut manifest = self.manifest()?;
+        let generation = manifest
+            .artifacts
+            .iter()
+            .filter(|entry| entry.dataset == dataset)
+            .map(|entry| entry.generation)
+            .max()
+            .unwrap_or(0)
+            + 1;
+
+        let timestamp = Utc::now();
+        let file_name = format!(
+            "perftracker-{}-{}-{}.{}",
+            dataset,
+            generation,
+            timestamp.format("%Y%m%dT%H%M%SZ"),
+            extension
+        );
+
+        write_atomically(&self.dir.join(&file_name), contents)?;
+
+        let entry = ArtifactEntry {
+            dataset: dataset.to_string(),
+            generation,
+            timestamp,
+            file_name,
+            bytes: contents.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(contents)),
+        };
+        manifest.artifacts.push(entry.clone());
+
+        // Drop generations beyond the retention window for this dataset
+        manifest
+            .artifacts
+            .sort_by(|a, b| a.dataset.cmp(&b.dataset).then_with(|| b.generation.cmp(&a.generation)));
+        let mut pruned = Vec::new();
+        let mut kept_for_dataset = 0;
+        manifest.artifacts.retain(|existing| {
+            if existing.dataset != dataset {
+                return true;
+            }
+            kept_for_dataset += 1;
+            if kept_for_dataset > self.keep_generations {
+                pruned.push(existing.file_name.clone());
+                false
+            } else {
+                true
+            }
+        });
+
+        let manifest_json = serde_json::to_vec_pretty(&manifest)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        write_atomically(&self.dir.join(MANIFEST_FILE), &manifest_json)?;
+
+        // Only remove files once the manifest no longer references them
+        for file_name in pruned {
+            if let Err(e) = fs::remove_file(self.dir.join(&file_name)) {
+                log::warn!("Failed to remove pruned artifact {}: {}", file_name, e);
+            }
+        }
+
+        Ok(entry)
+    }
+}
+
+pub fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    // A bare file name has an empty parent, which tempfile can't create files in
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+    let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+    temp.write_all(contents)?;
+    temp.as_file().sync_all()?;
+    temp.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha256(contents: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(contents))
+    }
+
+    #[test]
+    fn publishing_keeps_the_newest_generations_listed_newest_first_and_prunes_the_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::new(dir.path().to_path_buf(), 2).unwrap();
+
+        let first = store.publish("funds", "json", b"[1]").unwrap();
+        store.publish("rates", "json", b"[]").unwrap();
+        let second = store.publish("funds", "json", b"[1,2]").unwrap();
+        let third = store.publish("funds", "json", b"[1,2,3]").unwrap();
+
+        assert_eq!((first.generation, second.generation, third.generation), (1, 2, 3));
+        assert!(third.file_name.starts_with("perftracker-funds-3-"));
+        assert!(third.file_name.ends_with("Z.json"));
+
+        let manifest = store.manifest().unwrap();
+        let listed: Vec<(&str, u64)> = manifest
+            .artifacts
+            .iter()
+            .map(|entry| (entry.dataset.as_str(), entry.generation))
+            .collect();
+        assert_eq!(listed, vec![("funds", 3), ("funds", 2), ("rates", 1)]);
+
+        for entry in &manifest.artifacts {
+            let contents = fs::read(dir.path().join(&entry.file_name)).unwrap();
+            assert_eq!(entry.bytes, contents.len() as u64);
+            assert_eq!(entry.sha256, sha256(&contents));
+        }
+        assert_eq!(third.sha256, sha256(b"[1,2,3]"));
+
+        assert!(!dir.path().join(&first.file_name).exists());
+        assert_eq!(store.artifact_path(&first.file_name).unwrap(), None);
+    }
+
+    #[test]
+    fn generations_continue_from_the_manifest_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        ArtifactStore::new(dir.path().to_path_buf(), 3)
+            .unwrap()
+            .publish("funds", "xlsx", b"first")
+            .unwrap();
+
+        let reopened = ArtifactStore::new(dir.path().to_path_buf(), 3).unwrap();
+        let entry = reopened.publish("funds", "xlsx", b"second").unwrap();
+        assert_eq!(entry.generation, 2);
+        assert_eq!(reopened.manifest().unwrap().artifacts.len(), 2);
+    }
+
+    #[test]
+    fn only_listed_files_can_be_fetched() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::new(dir.path().to_path_buf(), 1).unwrap();
+        fs::write(dir.path().join("secret.txt"), b"not an artifact").unwrap();
+
+        assert_eq!(store.artifact_path("secret.txt").unwrap(), None);
+        assert_eq!(store.artifact_path(MANIFEST_FILE).unwrap(), None);
+    }
+}
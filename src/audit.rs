@@ -0,0 +1,201 @@
+use chrono::NaiveDateTime;
+use log::warn;
+use serde::Serialize;
+use serde_json::Value;
+use tokio_postgres::Client;
+use utoipa::ToSchema;
+
+use crate::api_error;
+use crate::auth::RequestUser;
+use crate::{get_postgres_client, AppState};
+
+// One row of audit_log: who changed what, and the gist of the change
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub at: NaiveDateTime,
+    // Key id or token subject
+    pub actor: String,
+    // api_key or bearer_token
+    pub actor_type: String,
+    pub actor_role: Option<String>,
+    // <target>.<verb>, e.g. rate.approve or upload.purge
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<String>,
+    #[schema(value_type = Object)]
+    pub summary: Value,
+    pub request_id: Option<String>,
+}
+
+// Records a change that has already been made. A failed audit write is logged and otherwise
+// ignored, so it never turns a successful change into an error; the change's audit_id is then null.
+pub async fn record(
+    state: &AppState,
+    user: &RequestUser,
+    action: &str,
+    target_type: &str,
+    target_id: Option<String>,
+    summary: Value,
+) -> Option<i64> {
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        let row = client
+            .query_one(
+                "INSERT INTO audit_log (actor, actor_type, actor_role, action, target_type, target_id, summary, request_id)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                 RETURNING id",
+                &[
+                    &user.subject,
+                    &user.credential.name(),
+                    &user.role.map(|role| role.name()),
+                    &action,
+                    &target_type,
+                    &target_id,
+                    &summary,
+                    &api_error::request_id(),
+                ],
+            )
+            .await?;
+        Ok::<_, Box<dyn std::error::Error>>(row.get::<_, i64>("id"))
+    }
+    .await;
+
+    match result {
+        Ok(id) => Some(id),
+        Err(e) => {
+            warn!("Failed to record audit entry {} on {} {:?} by {}: {}", action, target_type, target_id, user, e);
+            None
+        }
+    }
+}
+
+// Newest first. An action filter matches that action and, given a prefix like "rate", every
+// rate.* action.
+pub async fn list(
+    client: &Client,
+    limit: i64,
+    action: Option<&str>,
+    since: Option<NaiveDateTime>,
+) -> Result<Vec<AuditEntry>, tokio_postgres::Error> {
+    let rows = client
+        .query(
+            "SELECT id, at, actor, actor_type, actor_role, action, target_type, target_id, summary, request_id
+             FROM audit_log
+             WHERE ($1::TEXT IS NULL OR action = $1 OR action LIKE $1 || '.%')
+               AND ($2::TIMESTAMP IS NULL OR at >= $2)
+             ORDER BY id DESC
+             LIMIT $3",
+            &[&action, &since, &limit],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| AuditEntry {
+            id: row.get("id"),
+            at: row.get("at"),
+            actor: row.get("actor"),
+            actor_type: row.get("actor_type"),
+            actor_role: row.get("actor_role"),
+            action: row.get("action"),
+            target_type: row.get("target_type"),
+            target_id: row.get("target_id"),
+            summary: row.get("summary"),
+            request_id: row.get("request_id"),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use serde_json::json;
+
+    use crate::auth::{Credential, Role};
+    use crate::test_support;
+
+    fn user(subject: &str, role: Role) -> RequestUser {
+        RequestUser {
+            subject: subject.to_string(),
+            role: Some(role),
+            credential: Credential::BearerToken,
+        }
+    }
+
+    #[actix_web::test]
+    async fn entries_list_newest_first_and_filter_by_action_prefix() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let admin = user("asha", Role::Admin);
+        let first = record(&db.state, &admin, "rate.approve", "rate", Some("7".to_string()), json!({"arn": "ARN-1"})).await;
+        let second = record(&db.state, &admin, "upload.purge", "upload", Some("3".to_string()), json!({})).await;
+        let third = record(&db.state, &admin, "rate.delete", "rate", Some("8".to_string()), json!({})).await;
+        assert!(first.is_some() && second.is_some() && third.is_some());
+
+        let client = db.state.pools.primary().get().await.unwrap();
+        let ids = |entries: Vec<AuditEntry>| entries.iter().map(|entry| Some(entry.id)).collect::<Vec<_>>();
+        assert_eq!(ids(list(&client, 10, None, None).await.unwrap()), vec![third, second, first]);
+        assert_eq!(ids(list(&client, 10, Some("rate"), None).await.unwrap()), vec![third, first]);
+        assert_eq!(ids(list(&client, 10, Some("rate.approve"), None).await.unwrap()), vec![first]);
+        // A prefix is a whole segment, not any leading substring
+        assert!(list(&client, 10, Some("rat"), None).await.unwrap().is_empty());
+        assert_eq!(ids(list(&client, 1, None, None).await.unwrap()), vec![third]);
+        let tomorrow = chrono::Utc::now().naive_utc() + chrono::Duration::days(1);
+        assert!(list(&client, 10, None, Some(tomorrow)).await.unwrap().is_empty());
+
+        let entry = &list(&client, 10, Some("rate.approve"), None).await.unwrap()[0];
+        assert_eq!(
+            (entry.actor.as_str(), entry.actor_type.as_str(), entry.actor_role.as_deref()),
+            ("asha", "bearer_token", Some("admin"))
+        );
+        assert_eq!((entry.target_type.as_str(), entry.target_id.as_deref()), ("rate", Some("7")));
+        assert_eq!(entry.summary, json!({"arn": "ARN-1"}));
+    }
+
+    #[actix_web::test]
+    async fn a_failed_audit_write_never_fails_the_change() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute("DROP TABLE audit_log").await;
+
+        let admin = user("asha", Role::Admin);
+        assert_eq!(record(&db.state, &admin, "table.refresh", "virtual_table", None, json!({})).await, None);
+        let refresh = test_support::as_admin(TestRequest::post().uri("/api/v1/refresh"));
+        let (status, body) = test_support::call_json(&db.state, refresh).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["audit_id"], json!(null));
+    }
+
+    #[actix_web::test]
+    async fn mutations_return_the_id_of_their_entry_which_admins_can_read() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let refresh = test_support::as_admin(TestRequest::post().uri("/api/v1/refresh"))
+            .insert_header(("X-Request-Id", "support-ticket-42"));
+        let (status, refreshed) = test_support::call_json(&db.state, refresh).await;
+        assert_eq!(status, 200, "{}", refreshed);
+
+        let audit = |uri: &str| test_support::as_admin(TestRequest::get().uri(uri));
+        let (status, body) = test_support::call_json(&db.state, audit("/api/v1/audit?action=table")).await;
+        assert_eq!(status, 200, "{}", body);
+        let entry = &body["entries"][0];
+        assert_eq!(entry["id"], refreshed["audit_id"]);
+        assert_eq!(entry["action"], "table.refresh");
+        assert_eq!(entry["actor"], "test-admin");
+        assert_eq!(entry["actor_type"], "api_key");
+        assert_eq!(entry["request_id"], "support-ticket-42");
+
+        let (status, _) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/audit")).await;
+        assert_eq!(status, 401);
+        let (status, _) = test_support::call_json(&db.state, audit("/api/v1/audit?since=soon")).await;
+        assert_eq!(status, 400);
+    }
+}
@@ -0,0 +1,376 @@
+use actix_web::body::BoxBody;
+use actix_web::dev::{Payload, Service, ServiceRequest, ServiceResponse};
+use actix_web::http::{header, Method};
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest, ResponseError};
+use futures_util::future::{self, Either, Ready};
+use log::{info, warn};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::api_error::{ApiError, ErrorCode};
+use crate::config::RuntimeConfig;
+use crate::routes;
+use crate::{jwt, AppState};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+// POSTs that only read, because their input is too big for a query string
+const READ_ONLY_POSTS: [&str; 1] = ["/funds/by-name"];
+
+// Each role can do everything the ones before it can: viewers search and export, uploaders also
+// upload and edit, admins also refresh, delete, approve rates and change settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Uploader,
+    Admin,
+}
+
+impl Role {
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Uploader => "uploader",
+            Role::Admin => "admin",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Role> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "viewer" => Some(Role::Viewer),
+            "uploader" => Some(Role::Uploader),
+            "admin" => Some(Role::Admin),
+            _ => None,
+        }
+    }
+}
+
+fn default_key_role() -> Role {
+    Role::Admin
+}
+
+// A named key from the config file or API_KEYS. Only the id is ever logged or reported.
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKey {
+    pub id: String,
+    secret: String,
+    // Keys predate roles, so one without a role can do everything
+    #[serde(default = "default_key_role")]
+    pub role: Role,
+}
+
+impl ApiKey {
+    pub fn new(id: String, secret: String) -> Self {
+        ApiKey { id, secret, role: default_key_role() }
+    }
+
+    pub fn secret_len(&self) -> usize {
+        self.secret.len()
+    }
+}
+
+impl std::fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKey")
+            .field("id", &self.id)
+            .field("secret", &"<redacted>")
+            .field("role", &self.role)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Credential {
+    ApiKey,
+    BearerToken,
+    // An open read without credentials
+    Anonymous,
+}
+
+impl Credential {
+    pub fn name(self) -> &'static str {
+        match self {
+            Credential::ApiKey => "api_key",
+            Credential::BearerToken => "bearer_token",
+            Credential::Anonymous => "anonymous",
+        }
+    }
+}
+
+// Who is making the request, set by require_credentials and taken by handlers as an extractor so
+// their logs record who did what
+#[derive(Debug, Clone)]
+pub struct RequestUser {
+    // The key id or the token's sub claim
+    pub subject: String,
+    // None only for anonymous reads
+    pub role: Option<Role>,
+    pub credential: Credential,
+}
+
+impl RequestUser {
+    fn anonymous() -> Self {
+        RequestUser {
+            subject: "anonymous".to_string(),
+            role: None,
+            credential: Credential::Anonymous,
+        }
+    }
+
+    // 401 without credentials, 403 with a role below `role`
+    pub fn require(&self, role: Role) -> Result<(), ApiError> {
+        match self.role {
+            None => Err(ApiError::new(ErrorCode::Unauthorized, "Credentials are required")),
+            Some(have) if have < role => Err(ApiError::new(
+                ErrorCode::Forbidden,
+                format!("Requires the {} role; {} has {}", role.name(), self, have.name()),
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Display for RequestUser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.credential {
+            Credential::ApiKey => write!(f, "API key '{}'", self.subject),
+            Credential::BearerToken => write!(f, "user '{}'", self.subject),
+            Credential::Anonymous => f.write_str("anonymous"),
+        }
+    }
+}
+
+impl FromRequest for RequestUser {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, ApiError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let user = req.extensions().get::<RequestUser>().cloned().unwrap_or_else(RequestUser::anonymous);
+        future::ready(Ok(user))
+    }
+}
+
+// Writes always need credentials; reads only when require_api_key_for_reads is set. Which role a
+// write needs is up to its handler.
+fn requires_credentials(method: &Method, path: &str, config: &RuntimeConfig) -> bool {
+    let path = path.strip_prefix(routes::V1_PREFIX).unwrap_or(path);
+    let read = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+        || (*method == Method::POST && READ_ONLY_POSTS.contains(&path));
+    !read || config.require_api_key_for_reads
+}
+
+// Compares SHA-256 digests so neither the secret's length nor the position of the first
+// differing byte shows in the timing, and checks every key rather than stopping at a match
+fn matching_key<'a>(presented: &str, keys: &'a [ApiKey]) -> Option<&'a ApiKey> {
+    let presented = Sha256::digest(presented.as_bytes());
+    let mut matched = None;
+    for key in keys {
+        let expected = Sha256::digest(key.secret.as_bytes());
+        let difference = presented.iter().zip(expected.iter()).fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        if difference == 0 && matched.is_none() {
+            matched = Some(key);
+        }
+    }
+    matched
+}
+
+fn header_value<'a>(req: &'a ServiceRequest, name: &str) -> Option<&'a str> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+// The user behind a bearer token or X-Api-Key, None when the request presented neither. Bad
+// credentials are refused even on a route that would be open without them.
+fn authenticate(req: &ServiceRequest, state: &AppState) -> Result<Option<RequestUser>, ApiError> {
+    let config = state.runtime_config.load();
+
+    if let Some(authorization) = header_value(req, header::AUTHORIZATION.as_str()) {
+        let token = match authorization.split_once(' ') {
+            Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => token.trim(),
+            _ => return Err(ApiError::new(ErrorCode::InvalidToken, "Authorization must be a Bearer token")),
+        };
+        let jwt_config = match &config.jwt {
+            Some(jwt_config) => jwt_config,
+            None => return Err(ApiError::new(ErrorCode::InvalidToken, "Bearer tokens are not accepted on this server")),
+        };
+        let claims = jwt::validate(token, jwt_config, &state.jwks)?;
+        let role = match claims.role.as_deref().and_then(Role::parse) {
+            Some(role) => role,
+            None => {
+                return Err(ApiError::new(
+                    ErrorCode::Forbidden,
+                    format!("Token for '{}' has no viewer, uploader or admin role claim", claims.sub),
+                ))
+            }
+        };
+        return Ok(Some(RequestUser {
+            subject: claims.sub,
+            role: Some(role),
+            credential: Credential::BearerToken,
+        }));
+    }
+
+    match header_value(req, API_KEY_HEADER) {
+        Some(presented) => match matching_key(presented, &config.api_keys) {
+            Some(key) => Ok(Some(RequestUser {
+                subject: key.id.clone(),
+                role: Some(key.role),
+                credential: Credential::ApiKey,
+            })),
+            None => Err(ApiError::new(ErrorCode::Unauthorized, "Invalid API key")),
+        },
+        None => Ok(None),
+    }
+}
+
+fn authorize(req: &ServiceRequest) -> Result<Option<RequestUser>, ApiError> {
+    let state = match req.app_data::<web::Data<AppState>>() {
+        Some(state) => state,
+        None => return Err(ApiError::internal("Application state is not configured")),
+    };
+    let user = authenticate(req, state)?;
+    let config = state.runtime_config.load();
+    if user.is_none() && requires_credentials(req.method(), req.path(), &config) {
+        // Fail closed: a server without credentials takes no writes rather than taking them from anyone
+        if config.api_keys.is_empty() && config.jwt.is_none() {
+            return Err(ApiError::new(ErrorCode::Unauthorized, "No API keys or tokens are configured on this server"));
+        }
+        return Err(ApiError::new(
+            ErrorCode::Unauthorized,
+            "An X-Api-Key header or Bearer token is required",
+        ));
+    }
+    Ok(user)
+}
+
+// Middleware for the API scopes: answers 401 before the handler runs when the request needs
+// credentials and didn't present valid ones, and logs who made each authenticated request
+pub fn require_credentials<S>(
+    req: ServiceRequest,
+    srv: &S,
+) -> Either<S::Future, Ready<Result<ServiceResponse<BoxBody>, actix_web::Error>>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<BoxBody>, Error = actix_web::Error>,
+{
+    match authorize(&req) {
+        Ok(Some(user)) => {
+            info!("{} ({}) calling {} {}", user, user.role.map_or("no role", Role::name), req.method(), req.path());
+            req.extensions_mut().insert(user);
+            Either::Left(srv.call(req))
+        }
+        Ok(None) => Either::Left(srv.call(req)),
+        Err(e) => {
+            warn!("Rejected {} {}: {}", req.method(), req.path(), e);
+            // Rendered here rather than returned as an Err so the envelope carries the request id
+            Either::Right(future::ready(Ok(req.into_response(e.error_response()))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+
+    use crate::test_support::{self, ADMIN_KEY};
+
+    fn keys() -> Vec<ApiKey> {
+        vec![
+            ApiKey::new("ops".to_string(), "ops-secret-0123456789".to_string()),
+            ApiKey::new("etl".to_string(), "etl-secret-0123456789".to_string()),
+        ]
+    }
+
+    #[test]
+    fn only_an_exact_secret_matches_and_picks_its_key() {
+        let keys = keys();
+        assert_eq!(matching_key("etl-secret-0123456789", &keys).map(|key| key.id.as_str()), Some("etl"));
+        assert_eq!(matching_key("ops-secret-0123456789", &keys).map(|key| key.id.as_str()), Some("ops"));
+        for presented in ["", "ops-secret", "ops-secret-01234567890", "OPS-SECRET-0123456789"] {
+            assert!(matching_key(presented, &keys).is_none(), "{}", presented);
+        }
+        assert!(matching_key("ops-secret-0123456789", &[]).is_none());
+    }
+
+    #[test]
+    fn secrets_never_show_in_debug_output() {
+        let debug = format!("{:?}", keys()[0]);
+        assert!(debug.contains("ops") && !debug.contains("ops-secret"), "{}", debug);
+    }
+
+    #[test]
+    fn writes_need_credentials_and_reads_only_when_locked_down() {
+        let open = RuntimeConfig::default();
+        let locked = RuntimeConfig {
+            require_api_key_for_reads: true,
+            ..RuntimeConfig::default()
+        };
+
+        for (method, path, writes) in [
+            (Method::GET, "/api/v1/search", false),
+            (Method::HEAD, "/funds/by-name", false),
+            (Method::POST, "/api/v1/funds/by-name", false),
+            (Method::POST, "/api/v1/upload", true),
+            (Method::POST, "/refresh", true),
+            (Method::DELETE, "/api/v1/funds/3", true),
+            (Method::PATCH, "/api/v1/rates/3", true),
+        ] {
+            assert_eq!(requires_credentials(&method, path, &open), writes, "{} {}", method, path);
+            assert!(requires_credentials(&method, path, &locked), "{} {}", method, path);
+        }
+    }
+
+    #[actix_web::test]
+    async fn writes_without_a_valid_key_are_refused_with_the_envelope() {
+        let state = test_support::state(test_support::runtime_config(), vec![]);
+
+        for uri in ["/api/v1/refresh", "/refresh"] {
+            let (status, body) = test_support::call_json(&state, TestRequest::post().uri(uri)).await;
+            assert_eq!((status, body["code"].as_str()), (StatusCode::UNAUTHORIZED, Some("unauthorized")), "{}", uri);
+
+            let wrong_key = TestRequest::post().uri(uri).insert_header((API_KEY_HEADER, "not-the-key"));
+            let (status, body) = test_support::call_json(&state, wrong_key).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", uri);
+            assert_eq!(body["message"], "Invalid API key");
+
+            // Past authentication; the refresh itself then fails on the unreachable database
+            let response = test_support::call(&state, test_support::as_admin(TestRequest::post().uri(uri))).await;
+            assert_ne!(response.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+        }
+
+        // A bad key is refused even on an open read
+        let search = TestRequest::get().uri("/api/v1/search").insert_header((API_KEY_HEADER, "not-the-key"));
+        assert_eq!(test_support::call(&state, search).await.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn reads_are_open_unless_locked_down() {
+        let state = test_support::state(test_support::runtime_config(), vec![]);
+        let search = || TestRequest::get().uri("/api/v1/search");
+        assert_eq!(test_support::call(&state, search()).await.status(), StatusCode::OK);
+
+        let locked = RuntimeConfig {
+            require_api_key_for_reads: true,
+            ..test_support::runtime_config()
+        };
+        let state = test_support::state(locked, vec![]);
+        assert_eq!(test_support::call(&state, search()).await.status(), StatusCode::UNAUTHORIZED);
+        let with_key = search().insert_header((API_KEY_HEADER, ADMIN_KEY));
+        assert_eq!(test_support::call(&state, with_key).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn a_server_without_keys_takes_no_writes() {
+        let state = test_support::state(RuntimeConfig::default(), vec![]);
+
+        let (status, body) = test_support::call_json(&state, TestRequest::post().uri("/api/v1/refresh")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["message"], "No API keys or tokens are configured on this server");
+    }
+}
@@ -0,0 +1,352 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio_postgres::Client;
+use utoipa::ToSchema;
+
+use crate::config::RuntimeConfig;
+use crate::{normalize_scheme_name, FundData};
+
+// Closest known values offered when a filter value cannot be resolved
+const RESOLVE_SUGGESTIONS: usize = 3;
+
+pub const UNCATEGORIZED: &str = "Uncategorized";
+
+// How uploads treat categories outside the controlled vocabulary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CategoryValidation {
+    // Accept any category (vocabulary is not enforced)
+    Off,
+    // Import unknown categories as "Uncategorized" and report them
+    Lenient,
+    // Reject the whole upload if any category is unknown
+    Strict,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CategoryIssue {
+    pub scheme_name: String,
+    pub category: String,
+    #[schema(value_type = String)]
+    pub action: &'static str,
+}
+
+pub async fn load_vocabulary(client: &Client) -> Result<Vec<String>, tokio_postgres::Error> {
+    let rows = client
+        .query("SELECT name FROM category_vocabulary ORDER BY name", &[])
+        .await?;
+    Ok(rows.iter().map(|row| row.get("name")).collect())
+}
+
+// Insert new canonical categories; returns how many were actually new
+pub async fn extend_vocabulary(client: &Client, names: &[String]) -> Result<u64, tokio_postgres::Error> {
+    let mut added = 0;
+    for name in names {
+        added += client
+            .execute(
+                "INSERT INTO category_vocabulary (name) VALUES ($1) ON CONFLICT (name) DO NOTHING",
+                &[name],
+            )
+            .await?;
+    }
+    Ok(added)
+}
+
+// The vocabulary's canonical spelling for a category, compared after normalization
+pub fn canonicalize<'a>(category: &str, vocabulary: &'a [String]) -> Option<&'a str> {
+    let normalized = normalize_scheme_name(category);
+    vocabulary
+        .iter()
+        .find(|entry| normalize_scheme_name(entry) == normalized)
+        .map(String::as_str)
+}
+
+// Sheet name -> fund category, so "Eq- Large Cap (2)" and "Large Cap Funds" both land in one category
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct CategoryMapping {
+    pub sheet_name: String,
+    pub category: String,
+}
+
+// Keyed by the normalized sheet name
+pub type CategoryMappings = HashMap<String, CategoryMapping>;
+
+pub async fn load_mappings(client: &Client) -> Result<CategoryMappings, tokio_postgres::Error> {
+    let rows = client
+        .query("SELECT sheet_key, sheet_name, category FROM category_mappings", &[])
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            (
+                row.get("sheet_key"),
+                CategoryMapping {
+                    sheet_name: row.get("sheet_name"),
+                    category: row.get("category"),
+                },
+            )
+        })
+        .collect())
+}
+
+// Every entry needs a sheet name and a category, and no two sheet names may normalize alike
+pub fn validate_mappings(mappings: &[CategoryMapping]) -> Result<(), String> {
+    let mut keys = HashMap::new();
+    for mapping in mappings {
+        let key = normalize_scheme_name(&mapping.sheet_name);
+        if key.is_empty() || mapping.category.trim().is_empty() {
+            return Err(format!("Mapping for '{}' needs a sheet name and a category", mapping.sheet_name));
+        }
+        if let Some(previous) = keys.insert(key, &mapping.sheet_name) {
+            return Err(format!("'{}' and '{}' name the same sheet", previous, mapping.sheet_name));
+        }
+    }
+    Ok(())
+}
+
+// Replace the whole mapping in one transaction; validate_mappings first
+pub async fn replace_mappings(client: &mut Client, mappings: &[CategoryMapping]) -> Result<(), tokio_postgres::Error> {
+    let transaction = client.transaction().await?;
+    transaction.execute("DELETE FROM category_mappings", &[]).await?;
+    for mapping in mappings {
+        transaction
+            .execute(
+                "INSERT INTO category_mappings (sheet_key, sheet_name, category) VALUES ($1, $2, $3)",
+                &[
+                    &normalize_scheme_name(&mapping.sheet_name),
+                    &mapping.sheet_name.trim(),
+                    &mapping.category.trim(),
+                ],
+            )
+            .await?;
+    }
+    transaction.commit().await
+}
+
+// The mapped category for a sheet, None when the sheet is unmapped (it then passes through as-is)
+pub fn map_sheet<'a>(sheet: &str, mappings: &'a CategoryMappings) -> Option<&'a str> {
+    mappings
+        .get(&normalize_scheme_name(sheet))
+        .map(|mapping| mapping.category.as_str())
+}
+
+// Comparison key for user-supplied category/company values: normalized, synonyms expanded,
+// a trailing "fund(s)" dropped and spaces removed, so "FLEXI CAP FUND", "Flexi-Cap" and
+// "flexicap" all meet "Flexi Cap"
+fn match_key(value: &str, config: &RuntimeConfig) -> String {
+    let expanded = config.expand_aliases(&normalize_scheme_name(value));
+    let mut tokens: Vec<&str> = expanded.split_whitespace().collect();
+    if tokens.len() > 1 && matches!(tokens.last(), Some(&"fund") | Some(&"funds")) {
+        tokens.pop();
+    }
+    tokens.concat()
+}
+
+// Resolve a filter value to one of the known canonical spellings; Err carries the closest ones
+pub fn resolve_known<'a, I>(value: &str, known: I, config: &RuntimeConfig) -> Result<String, Vec<String>>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let key = match_key(value, config);
+    let mut scored = Vec::new();
+
+    for candidate in known {
+        let candidate_key = match_key(candidate, config);
+        if candidate_key == key {
+            return Ok(candidate.clone());
+        }
+        scored.push((strsim::jaro_winkler(&key, &candidate_key), candidate));
+    }
+
+    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    Err(scored
+        .into_iter()
+        .take(RESOLVE_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.clone())
+        .collect())
+}
+
+// Rewrite fund categories to their canonical spelling. Unknown categories either fail the
+// upload (strict, Err lists every offender) or become "Uncategorized" with a report entry.
+pub fn apply_vocabulary(
+    funds: &mut [FundData],
+    vocabulary: &[String],
+    mode: CategoryValidation,
+) -> Result<Vec<CategoryIssue>, Vec<CategoryIssue>> {
+    if mode == CategoryValidation::Off || vocabulary.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut issues = Vec::new();
+
+    for fund in funds.iter_mut() {
+        match canonicalize(&fund.category, vocabulary) {
+            Some(canonical) => fund.category = canonical.to_string(),
+            None => {
+                let action = match mode {
+                    CategoryValidation::Strict => "rejected",
+                    _ => "imported as Uncategorized",
+                };
+                issues.push(CategoryIssue {
+                    scheme_name: fund.scheme_name.clone(),
+                    category: fund.category.clone(),
+                    action,
+                });
+                if mode == CategoryValidation::Lenient {
+                    fund.category = UNCATEGORIZED.to_string();
+                }
+            }
+        }
+    }
+
+    if mode == CategoryValidation::Strict && !issues.is_empty() {
+        Err(issues)
+    } else {
+        Ok(issues)
+    }
+}
+
+// Move every fund in `from` to `to` and record the reassignment; returns the number of funds moved
+pub async fn reassign(client: &mut Client, from: &str, to: &str) -> Result<u64, tokio_postgres::Error> {
+    let transaction = client.transaction().await?;
+    let moved = transaction
+        .execute("UPDATE funds SET category = $2, version = version + 1 WHERE category = $1", &[&from, &to])
+        .await?;
+    transaction
+        .execute(
+            "INSERT INTO category_reassignments (from_category, to_category, funds_affected)
+             VALUES ($1, $2, $3)",
+            &[&from, &to, &(moved as i64)],
+        )
+        .await?;
+    transaction.commit().await?;
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fund(scheme_name: &str, category: &str) -> FundData {
+        FundData {
+            sheet: category.to_string(),
+            row: 2,
+            category: category.to_string(),
+            scheme_name: scheme_name.to_string(),
+            launch_date: None,
+            fund_size_apr25: None,
+            fund_size_may25: None,
+            latest_nav: None,
+            month_1: None,
+            months_3: None,
+            months_6: None,
+            ytd: None,
+            year_1: None,
+            years_2: None,
+            years_3: None,
+            years_5: None,
+        }
+    }
+
+    fn vocabulary() -> Vec<String> {
+        vec!["Flexi Cap".to_string(), "Large Cap".to_string()]
+    }
+
+    fn upload() -> Vec<FundData> {
+        vec![
+            fund("Parag Parikh Flexi Cap Fund", "FLEXI  CAP "),
+            fund("Axis Bluechip Fund", "Large Cap"),
+            fund("Quant Small Cap Fund", "Eq- Small Cap (2)"),
+        ]
+    }
+
+    fn categories(funds: &[FundData]) -> Vec<&str> {
+        funds.iter().map(|fund| fund.category.as_str()).collect()
+    }
+
+    fn known_categories() -> Vec<String> {
+        ["Flexi Cap", "Large Cap", "Mid Cap", "Small Cap", "Tax Saver"]
+            .iter()
+            .map(|category| category.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn filter_values_resolve_through_casing_punctuation_and_synonyms() {
+        let config = RuntimeConfig {
+            aliases: HashMap::from([("elss".to_string(), "tax saver".to_string())]),
+            ..RuntimeConfig::default()
+        };
+        let known = known_categories();
+        for (value, canonical) in [
+            ("Flexi Cap", "Flexi Cap"),
+            ("FLEXI CAP", "Flexi Cap"),
+            ("flexicap", "Flexi Cap"),
+            ("Flexi-Cap", "Flexi Cap"),
+            ("FLEXI CAP FUND", "Flexi Cap"),
+            (" mid-cap funds ", "Mid Cap"),
+            ("ELSS", "Tax Saver"),
+            ("elss fund", "Tax Saver"),
+        ] {
+            assert_eq!(resolve_known(value, &known, &config), Ok(canonical.to_string()), "{}", value);
+        }
+    }
+
+    #[test]
+    fn an_unresolvable_value_offers_the_closest_known_values() {
+        let closest = resolve_known("Smal Capp", &known_categories(), &RuntimeConfig::default()).unwrap_err();
+        assert_eq!(closest.len(), RESOLVE_SUGGESTIONS);
+        assert_eq!(closest[0], "Small Cap");
+
+        // A lone "fund" is the value itself, not a suffix to drop
+        assert!(resolve_known("fund", &known_categories(), &RuntimeConfig::default()).is_err());
+    }
+
+    #[test]
+    fn strict_mode_rejects_the_upload_listing_every_unknown_category() {
+        let mut funds = upload();
+        let issues = apply_vocabulary(&mut funds, &vocabulary(), CategoryValidation::Strict).unwrap_err();
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].scheme_name, "Quant Small Cap Fund");
+        assert_eq!(issues[0].category, "Eq- Small Cap (2)");
+        assert_eq!(issues[0].action, "rejected");
+    }
+
+    #[test]
+    fn lenient_mode_imports_unknown_categories_as_uncategorized_and_reports_them() {
+        let mut funds = upload();
+        let issues = apply_vocabulary(&mut funds, &vocabulary(), CategoryValidation::Lenient).unwrap();
+
+        assert_eq!(categories(&funds), vec!["Flexi Cap", "Large Cap", UNCATEGORIZED]);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].category, "Eq- Small Cap (2)");
+        assert_eq!(issues[0].action, "imported as Uncategorized");
+    }
+
+    #[test]
+    fn known_categories_take_the_vocabulary_spelling_in_both_modes() {
+        for mode in [CategoryValidation::Strict, CategoryValidation::Lenient] {
+            let mut funds = upload();
+            funds.pop();
+            let issues = apply_vocabulary(&mut funds, &vocabulary(), mode).unwrap();
+
+            assert!(issues.is_empty());
+            assert_eq!(categories(&funds), vec!["Flexi Cap", "Large Cap"]);
+        }
+    }
+
+    #[test]
+    fn an_empty_vocabulary_or_off_mode_leaves_categories_alone() {
+        for (mode, vocabulary) in [
+            (CategoryValidation::Off, vocabulary()),
+            (CategoryValidation::Strict, Vec::new()),
+        ] {
+            let mut funds = upload();
+            let issues = apply_vocabulary(&mut funds, &vocabulary, mode).unwrap();
+
+            assert!(issues.is_empty());
+            assert_eq!(categories(&funds), vec!["FLEXI  CAP ", "Large Cap", "Eq- Small Cap (2)"]);
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use calamine::{Data, Range};
+use log::info;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// Minimum Jaro-Winkler similarity for a header that isn't one of the known spellings
+const FUZZY_HEADER_THRESHOLD: f64 = 0.9;
+
+// Joins a parent label and the label under it in a two-row header, e.g. "Returns (%) | 1M"
+const STACKED_SEPARATOR: &str = " | ";
+
+// Columns the importer reads from a fund sheet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FundColumn {
+    SchemeName,
+    LaunchDate,
+    FundSizeApr25,
+    FundSizeMay25,
+    LatestNav,
+    Month1,
+    Months3,
+    Months6,
+    Ytd,
+    Year1,
+    Years2,
+    Years3,
+    Years5,
+}
+
+const REQUIRED: [FundColumn; 2] = [FundColumn::SchemeName, FundColumn::LaunchDate];
+
+// Known spellings per column, already in header_key form
+const HEADERS: &[(FundColumn, &[&str])] = &[
+    (FundColumn::SchemeName, &["schemename", "scheme", "fundname"]),
+    (FundColumn::LaunchDate, &["launchdate", "inceptiondate", "dateoflaunch"]),
+    (FundColumn::FundSizeApr25, &["fundsizeapr25", "aumapr25"]),
+    (FundColumn::FundSizeMay25, &["fundsizemay25", "aummay25"]),
+    (FundColumn::LatestNav, &["latestnav", "nav"]),
+    (FundColumn::Month1, &["1month", "1m", "1mth"]),
+    (FundColumn::Months3, &["3month", "3m", "3mth"]),
+    (FundColumn::Months6, &["6month", "6m", "6mth"]),
+    (FundColumn::Ytd, &["ytd", "yeartodate"]),
+    (FundColumn::Year1, &["1year", "1yr", "1y"]),
+    (FundColumn::Years2, &["2year", "2yr", "2y"]),
+    (FundColumn::Years3, &["3year", "3yr", "3y"]),
+    (FundColumn::Years5, &["5year", "5yr", "5y"]),
+];
+
+impl FundColumn {
+    pub fn label(self) -> &'static str {
+        match self {
+            FundColumn::SchemeName => "Scheme Name",
+            FundColumn::LaunchDate => "Launch Date",
+            FundColumn::FundSizeApr25 => "Fund Size Apr25",
+            FundColumn::FundSizeMay25 => "Fund Size May25",
+            FundColumn::LatestNav => "Latest NAV",
+            FundColumn::Month1 => "1 Month",
+            FundColumn::Months3 => "3 Months",
+            FundColumn::Months6 => "6 Months",
+            FundColumn::Ytd => "YTD",
+            FundColumn::Year1 => "1 Year",
+            FundColumn::Years2 => "2 Years",
+            FundColumn::Years3 => "3 Years",
+            FundColumn::Years5 => "5 Years",
+        }
+    }
+
+    // Name of the funds column it feeds
+    pub fn field(self) -> &'static str {
+        match self {
+            FundColumn::SchemeName => "scheme_name",
+            FundColumn::LaunchDate => "launch_date",
+            FundColumn::FundSizeApr25 => "fund_size_apr25",
+            FundColumn::FundSizeMay25 => "fund_size_may25",
+            FundColumn::LatestNav => "latest_nav",
+            FundColumn::Month1 => "month_1",
+            FundColumn::Months3 => "months_3",
+            FundColumn::Months6 => "months_6",
+            FundColumn::Ytd => "ytd",
+            FundColumn::Year1 => "year_1",
+            FundColumn::Years2 => "years_2",
+            FundColumn::Years3 => "years_3",
+            FundColumn::Years5 => "years_5",
+        }
+    }
+}
+
+// Field -> the sheet header that fed it, as recorded per upload
+pub type ColumnMapping = BTreeMap<String, String>;
+
+// Comparison form of a header: lowercase alphanumerics only, "(Rs Crs)"-style units dropped and
+// plurals folded, so "Fund Size (Rs Crs) Apr25" -> "fundsizeapr25" and "3 Months" -> "3month"
+pub fn header_key(header: &str) -> String {
+    let without_units: String = {
+        let mut depth = 0usize;
+        header
+            .chars()
+            .filter(|c| match c {
+                '(' => {
+                    depth += 1;
+                    false
+                }
+                ')' => {
+                    depth = depth.saturating_sub(1);
+                    false
+                }
+                _ => depth == 0,
+            })
+            .collect()
+    };
+
+    without_units
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| match token {
+            "months" => "month",
+            "years" => "year",
+            "yrs" => "yr",
+            other => other,
+        })
+        .collect()
+}
+
+fn digits(key: &str) -> String {
+    key.chars().filter(char::is_ascii_digit).collect()
+}
+
+fn match_header(key: &str) -> Option<FundColumn> {
+    if let Some((column, _)) = HEADERS.iter().find(|(_, spellings)| spellings.contains(&key)) {
+        return Some(*column);
+    }
+
+    // Fuzzy fallback; periods must agree exactly so "1 Year" never lands in "2 Years"
+    HEADERS
+        .iter()
+        .flat_map(|(column, spellings)| spellings.iter().map(move |spelling| (*column, *spelling)))
+        .filter(|(_, spelling)| digits(spelling) == digits(key))
+        .map(|(column, spelling)| (column, strsim::jaro_winkler(key, spelling)))
+        .filter(|(_, score)| *score >= FUZZY_HEADER_THRESHOLD)
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(column, _)| column)
+}
+
+// A stacked header matches as a whole first ("Fund Size (Rs Crs) | Apr25"), then by its lower
+// label alone ("Returns (%) | 1M")
+fn match_effective_header(header: &str) -> Option<FundColumn> {
+    match_header(&header_key(header)).or_else(|| {
+        let (_, lower) = header.rsplit_once(STACKED_SEPARATOR)?;
+        match_header(&header_key(lower))
+    })
+}
+
+// Header row cell that marks the start of the fund table
+pub fn is_scheme_name_header(cell: &str) -> bool {
+    match_header(&header_key(cell)) == Some(FundColumn::SchemeName)
+}
+
+fn row_text(range: &Range<Data>, row: usize) -> Vec<String> {
+    (0..range.width())
+        .map(|col| range.get((row, col)).map(|cell| cell.to_string().trim().to_string()).unwrap_or_default())
+        .collect()
+}
+
+// Only text, so a data row is never mistaken for the second row of a header
+fn is_label_row(range: &Range<Data>, row: usize) -> bool {
+    let cells: Vec<&Data> = (0..range.width()).filter_map(|col| range.get((row, col))).collect();
+    cells.iter().all(|cell| matches!(cell, Data::String(_) | Data::Empty))
+        && cells.iter().any(|cell| !cell.to_string().trim().is_empty())
+}
+
+// Merged cells only keep their text in the first column, so an empty upper cell with a label
+// under it inherits the upper label to its left, up to the next gap in both rows
+fn stack_headers(upper: &[String], lower: &[String]) -> Vec<String> {
+    let mut parent = "";
+    upper
+        .iter()
+        .zip(lower)
+        .map(|(upper, lower)| {
+            if !upper.is_empty() {
+                parent = upper;
+            } else if lower.is_empty() {
+                parent = "";
+            }
+            match (parent.is_empty(), lower.is_empty()) {
+                (true, _) => lower.clone(),
+                (false, true) => upper.clone(),
+                (false, false) => format!("{}{}{}", parent, STACKED_SEPARATOR, lower),
+            }
+        })
+        .collect()
+}
+
+fn recognised_columns(headers: &[String]) -> usize {
+    headers
+        .iter()
+        .filter_map(|header| match_effective_header(header))
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnMap {
+    indices: HashMap<FundColumn, usize>,
+    mapping: ColumnMapping,
+    // Effective header text per column index, stacked headers already combined
+    headers: Vec<String>,
+}
+
+impl ColumnMap {
+    // `header_row` holds the Scheme Name header. The row below or above it joins the header when
+    // it is all labels and the stacked text recognises more columns than the row alone, as with
+    // "Returns (%)" merged over "1M / 3M / 6M / 1Y". Also returns the first data row.
+    pub fn from_header_rows(sheet_name: &str, range: &Range<Data>, header_row: usize) -> Result<(Self, usize), String> {
+        let row = row_text(range, header_row);
+        let mut best = (recognised_columns(&row), row.clone(), header_row + 1);
+
+        let mut candidates = Vec::new();
+        if header_row + 1 < range.height() && is_label_row(range, header_row + 1) {
+            candidates.push((stack_headers(&row, &row_text(range, header_row + 1)), header_row + 2));
+        }
+        if header_row > 0 && is_label_row(range, header_row - 1) {
+            candidates.push((stack_headers(&row_text(range, header_row - 1), &row), header_row + 1));
+        }
+        for (headers, data_row) in candidates {
+            let recognised = recognised_columns(&headers);
+            if recognised > best.0 {
+                best = (recognised, headers, data_row);
+            }
+        }
+
+        let (_, headers, data_row) = best;
+        Ok((Self::from_headers(sheet_name, &headers)?, data_row))
+    }
+
+    // Map each recognised header to its column; the first occurrence of a column wins
+    pub fn from_headers(sheet_name: &str, headers: &[String]) -> Result<Self, String> {
+        let mut indices = HashMap::new();
+        let mut mapping = ColumnMapping::new();
+
+        for (col, header) in headers.iter().enumerate() {
+            if header.trim().is_empty() {
+                continue;
+            }
+
+            match match_effective_header(header) {
+                Some(column) => {
+                    if let Entry::Vacant(entry) = indices.entry(column) {
+                        entry.insert(col);
+                        mapping.insert(column.field().to_string(), header.trim().to_string());
+                    }
+                }
+                None => info!("Sheet '{}': ignoring unrecognised column '{}'", sheet_name, header.trim()),
+            }
+        }
+
+        let missing: Vec<&str> = REQUIRED
+            .iter()
+            .filter(|column| !indices.contains_key(*column))
+            .map(|column| column.label())
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("missing required column(s): {}", missing.join(", ")));
+        }
+
+        let headers = headers.iter().map(|header| header.trim().to_string()).collect();
+        Ok(Self { indices, mapping, headers })
+    }
+
+    pub fn get<'a>(&self, range: &'a Range<Data>, row: usize, column: FundColumn) -> Option<&'a Data> {
+        range.get((row, self.index(column)?))
+    }
+
+    pub fn mapping(&self) -> &ColumnMapping {
+        &self.mapping
+    }
+
+    // Column index -> effective header, for every non-empty header whether mapped or not
+    pub fn resolved_headers(&self) -> BTreeMap<usize, String> {
+        self.headers
+            .iter()
+            .enumerate()
+            .filter(|(_, header)| !header.is_empty())
+            .map(|(col, header)| (col, header.clone()))
+            .collect()
+    }
+
+    pub fn index(&self, column: FundColumn) -> Option<usize> {
+        self.indices.get(&column).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn sheet(rows: &[&[Data]]) -> Range<Data> {
+        let width = rows.iter().map(|row| row.len()).max().unwrap();
+        let mut range = Range::new((0, 0), (rows.len() as u32 - 1, width as u32 - 1));
+        for (row, cells) in rows.iter().enumerate() {
+            for (col, cell) in cells.iter().enumerate() {
+                range.set_value((row as u32, col as u32), cell.clone());
+            }
+        }
+        range
+    }
+
+    fn text(value: &str) -> Data {
+        Data::String(value.to_string())
+    }
+
+    #[test]
+    fn columns_are_found_by_name_in_any_order() {
+        let map = ColumnMap::from_headers(
+            "Equity",
+            &headers(&["Latest NAV", "5 Yrs", "Launch Date", "Notes", "Scheme Name", "Fund Size (Rs Crs) Apr25"]),
+        )
+        .unwrap();
+
+        assert_eq!(map.index(FundColumn::LatestNav), Some(0));
+        assert_eq!(map.index(FundColumn::Years5), Some(1));
+        assert_eq!(map.index(FundColumn::LaunchDate), Some(2));
+        assert_eq!(map.index(FundColumn::SchemeName), Some(4));
+        assert_eq!(map.index(FundColumn::FundSizeApr25), Some(5));
+        assert_eq!(map.index(FundColumn::Year1), None);
+        assert_eq!(map.mapping().get("years_5").map(String::as_str), Some("5 Yrs"));
+        // Unrecognised headers are still listed for the upload's column report
+        assert_eq!(map.resolved_headers().get(&3).map(String::as_str), Some("Notes"));
+    }
+
+    #[test]
+    fn near_misses_match_but_never_across_periods() {
+        assert_eq!(match_header(&header_key("Scheme Nam")), Some(FundColumn::SchemeName));
+        assert_eq!(match_header(&header_key("Launch  Date.")), Some(FundColumn::LaunchDate));
+        assert_eq!(match_header(&header_key("3 Years")), Some(FundColumn::Years3));
+        assert_eq!(match_header(&header_key("4 Years")), None);
+        assert_eq!(match_header(&header_key("Expense Ratio")), None);
+        assert_eq!(header_key("Fund Size (Rs Crs) May25"), "fundsizemay25");
+    }
+
+    #[test]
+    fn missing_required_columns_are_named() {
+        let error = ColumnMap::from_headers("Debt", &headers(&["Fund Name", "NAV"])).unwrap_err();
+        assert_eq!(error, "missing required column(s): Launch Date");
+
+        let error = ColumnMap::from_headers("Debt", &headers(&["NAV"])).unwrap_err();
+        assert_eq!(error, "missing required column(s): Scheme Name, Launch Date");
+    }
+
+    #[test]
+    fn a_merged_label_over_the_periods_joins_the_header() {
+        let range = sheet(&[
+            &[text("Scheme Name"), text("Launch Date"), text("Returns (%)"), Data::Empty],
+            &[Data::Empty, Data::Empty, text("1M"), text("1Y")],
+            &[text("Alpha Fund"), text("2020-01-01"), Data::Float(1.5), Data::Float(12.0)],
+        ]);
+
+        let (map, data_row) = ColumnMap::from_header_rows("Equity", &range, 0).unwrap();
+        assert_eq!(data_row, 2);
+        assert_eq!(map.index(FundColumn::Month1), Some(2));
+        assert_eq!(map.index(FundColumn::Year1), Some(3));
+        assert_eq!(map.mapping().get("year_1").map(String::as_str), Some("Returns (%) | 1Y"));
+        assert_eq!(map.get(&range, data_row, FundColumn::Year1), Some(&Data::Float(12.0)));
+    }
+
+    #[test]
+    fn a_data_row_below_the_header_is_not_stacked() {
+        let range = sheet(&[
+            &[text("Scheme Name"), text("Launch Date"), text("1 Year")],
+            &[text("Alpha Fund"), text("2020-01-01"), Data::Float(12.0)],
+        ]);
+
+        let (map, data_row) = ColumnMap::from_header_rows("Equity", &range, 0).unwrap();
+        assert_eq!(data_row, 1);
+        assert_eq!(map.mapping().get("scheme_name").map(String::as_str), Some("Scheme Name"));
+    }
+}
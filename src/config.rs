@@ -0,0 +1,892 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::auth::ApiKey;
+use crate::categories::CategoryValidation;
+use crate::jwt::JwtConfig;
+use crate::rate_limit::Limit;
+use crate::sheets::SheetSelection;
+use crate::tls::TlsFiles;
+
+pub const CONFIG_PATH_ENV: &str = "PERFTRACKER_CONFIG";
+
+const DEFAULT_SKIP_SHEETS: [&str; 5] = ["Main Page", "Summary", "Glossary", "Load", "Disclaimer"];
+// Comma-separated skip patterns / include regex, overriding the config file
+const SKIP_SHEETS_ENV: &str = "SKIP_SHEETS";
+const INCLUDE_SHEETS_ENV: &str = "INCLUDE_SHEETS";
+// true/false, overriding the config file's require_rate_approval
+const REQUIRE_RATE_APPROVAL_ENV: &str = "REQUIRE_RATE_APPROVAL";
+// Comma-separated id:secret pairs, replacing the config file's api_keys
+const API_KEYS_ENV: &str = "API_KEYS";
+const MIN_API_KEY_SECRET_LEN: usize = 16;
+// Set up bearer tokens without the secret going into the config file; either one creates the
+// jwt section when the file has none
+const JWT_HS256_SECRET_ENV: &str = "JWT_HS256_SECRET";
+const JWT_JWKS_URL_ENV: &str = "JWT_JWKS_URL";
+const MIN_JWT_SECRET_LEN: usize = 32;
+// Per client: searches and other cheap requests, then uploads, refreshes and exports. 0 per
+// minute disables a limit.
+const DEFAULT_READ_RATE_LIMIT_PER_MINUTE: u32 = 600;
+const DEFAULT_READ_RATE_LIMIT_BURST: u32 = 100;
+const DEFAULT_EXPENSIVE_RATE_LIMIT_PER_MINUTE: u32 = 10;
+const DEFAULT_EXPENSIVE_RATE_LIMIT_BURST: u32 = 5;
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+pub const MAX_SEARCH_LIMIT: usize = 1000;
+// Rows a single /search.csv export may hold
+const DEFAULT_MAX_EXPORT_ROWS: usize = 10_000;
+pub const MAX_EXPORT_ROWS: usize = 100_000;
+const DEFAULT_FUZZY_BUDGET_MS: u64 = 50;
+// Prefix, token and substring matches a search ranks before giving up on the rest
+const DEFAULT_MAX_SEARCH_CANDIDATES: usize = 20_000;
+// Mean per-token Jaro-Winkler similarity a name needs to appear in the fuzzy search tier
+const DEFAULT_FUZZY_SEARCH_THRESHOLD: f64 = 0.88;
+const DEFAULT_SPARSE_FILTER_WARNING_RATIO: f64 = 0.5;
+const DEFAULT_POOL_SIZE: usize = 16;
+const DEFAULT_POOL_WAIT_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_EXPORT_KEEP_GENERATIONS: usize = 5;
+const DEFAULT_DB_RETRY_MAX_ATTEMPTS: u32 = 10;
+const DEFAULT_DB_RETRY_MAX_DELAY_SECS: u64 = 30;
+const DEFAULT_SHUTDOWN_GRACE_SECS: u64 = 30;
+// 0 disables the scheduled refresh
+const DEFAULT_REFRESH_INTERVAL_SECS: u64 = 0;
+const DEFAULT_MIN_DATA_COMPLETENESS: f32 = 0.25;
+// 0.0 rolls back an upload when the database rejects any of its rows; 1.0 commits it regardless
+const DEFAULT_MAX_UPLOAD_FAILURE_RATIO: f64 = 0.0;
+const DEFAULT_MAX_UPLOAD_BYTES: u64 = 50 * 1024 * 1024;
+
+// What POST /refresh does when another refresh is already running
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConcurrentRefresh {
+    // Wait for the running refresh and report its result instead of rebuilding again
+    Wait,
+    // Answer 409 Conflict straight away
+    Reject,
+}
+
+// On-disk configuration file (JSON). Everything except the server section is reloadable.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    pub bind_addr: String,
+    pub port: u16,
+    pub workers: Option<usize>,
+    pub tls_cert_path: Option<PathBuf>,
+    pub tls_key_path: Option<PathBuf>,
+    pub pool_size: usize,
+    pub pool_wait_timeout_secs: u64,
+    pub export_dir: Option<PathBuf>,
+    pub export_keep_generations: usize,
+    pub snapshot_path: Option<PathBuf>,
+    pub db_retry_max_attempts: u32,
+    pub db_retry_max_delay_secs: u64,
+    pub shutdown_grace_secs: u64,
+    pub refresh_interval_secs: u64,
+    pub skip_sheets: Vec<String>,
+    pub include_sheets: Option<String>,
+    pub search_limit: usize,
+    pub max_export_rows: usize,
+    pub fuzzy_budget_ms: u64,
+    pub max_search_candidates: usize,
+    pub fuzzy_search_threshold: f64,
+    pub sparse_filter_warning_ratio: f64,
+    pub min_data_completeness: f32,
+    pub max_upload_failure_ratio: f64,
+    pub max_upload_bytes: u64,
+    pub category_validation: CategoryValidation,
+    pub concurrent_refresh: ConcurrentRefresh,
+    pub require_rate_approval: bool,
+    pub alias_dictionary: Option<PathBuf>,
+    pub api_keys: Vec<ApiKey>,
+    pub require_api_key_for_reads: bool,
+    pub jwt: Option<JwtConfig>,
+    pub read_rate_limit_per_minute: u32,
+    pub read_rate_limit_burst: u32,
+    pub expensive_rate_limit_per_minute: u32,
+    pub expensive_rate_limit_burst: u32,
+}
+
+impl Default for ConfigFile {
+    fn default() -> Self {
+        Self {
+            bind_addr: "0.0.0.0".to_string(),
+            port: 8081,
+            workers: None,
+            tls_cert_path: None,
+            tls_key_path: None,
+            pool_size: DEFAULT_POOL_SIZE,
+            pool_wait_timeout_secs: DEFAULT_POOL_WAIT_TIMEOUT_SECS,
+            export_dir: None,
+            export_keep_generations: DEFAULT_EXPORT_KEEP_GENERATIONS,
+            snapshot_path: None,
+            db_retry_max_attempts: DEFAULT_DB_RETRY_MAX_ATTEMPTS,
+            db_retry_max_delay_secs: DEFAULT_DB_RETRY_MAX_DELAY_SECS,
+            shutdown_grace_secs: DEFAULT_SHUTDOWN_GRACE_SECS,
+            refresh_interval_secs: DEFAULT_REFRESH_INTERVAL_SECS,
+            skip_sheets: DEFAULT_SKIP_SHEETS.iter().map(|s| s.to_string()).collect(),
+            include_sheets: None,
+            search_limit: DEFAULT_SEARCH_LIMIT,
+            max_export_rows: DEFAULT_MAX_EXPORT_ROWS,
+            fuzzy_budget_ms: DEFAULT_FUZZY_BUDGET_MS,
+            max_search_candidates: DEFAULT_MAX_SEARCH_CANDIDATES,
+            fuzzy_search_threshold: DEFAULT_FUZZY_SEARCH_THRESHOLD,
+            sparse_filter_warning_ratio: DEFAULT_SPARSE_FILTER_WARNING_RATIO,
+            min_data_completeness: DEFAULT_MIN_DATA_COMPLETENESS,
+            max_upload_failure_ratio: DEFAULT_MAX_UPLOAD_FAILURE_RATIO,
+            max_upload_bytes: DEFAULT_MAX_UPLOAD_BYTES,
+            category_validation: CategoryValidation::Off,
+            concurrent_refresh: ConcurrentRefresh::Wait,
+            require_rate_approval: false,
+            alias_dictionary: None,
+            api_keys: Vec::new(),
+            require_api_key_for_reads: false,
+            jwt: None,
+            read_rate_limit_per_minute: DEFAULT_READ_RATE_LIMIT_PER_MINUTE,
+            read_rate_limit_burst: DEFAULT_READ_RATE_LIMIT_BURST,
+            expensive_rate_limit_per_minute: DEFAULT_EXPENSIVE_RATE_LIMIT_PER_MINUTE,
+            expensive_rate_limit_burst: DEFAULT_EXPENSIVE_RATE_LIMIT_BURST,
+        }
+    }
+}
+
+// Settings that are only read at startup; a reload reports differences instead of applying them
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticConfig {
+    pub bind_addr: IpAddr,
+    pub port: u16,
+    pub workers: Option<usize>,
+    // Serve HTTPS rather than plain HTTP
+    pub tls: Option<TlsFiles>,
+    pub pool_size: usize,
+    pub pool_wait_timeout_secs: u64,
+    pub export_dir: Option<PathBuf>,
+    pub export_keep_generations: usize,
+    // Binary virtual table snapshot used to skip the startup build when still current
+    pub snapshot_path: Option<PathBuf>,
+    pub db_retry_max_attempts: u32,
+    pub db_retry_max_delay_secs: u64,
+    // How long in-flight requests (uploads in particular) get to finish after SIGTERM
+    pub shutdown_grace_secs: u64,
+    // Period of the background full refresh (plus up to 10% jitter); 0 disables it
+    pub refresh_interval_secs: u64,
+}
+
+// The hot-reloadable subset, swapped atomically into AppState
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeConfig {
+    pub sheets: SheetSelection,
+    pub search_limit: usize,
+    // Default and maximum row count of a CSV search export
+    pub max_export_rows: usize,
+    pub fuzzy_budget_ms: u64,
+    // Caps the ranked prefix, token and substring matches one search keeps; the rest are dropped
+    // and the search reports truncated results
+    pub max_search_candidates: usize,
+    pub fuzzy_search_threshold: f64,
+    pub sparse_filter_warning_ratio: f64,
+    // Records with a smaller fraction of numeric fields populated are flagged incomplete at build time
+    pub min_data_completeness: f32,
+    // An upload whose rejected rows exceed this fraction of its rows is rolled back entirely. The
+    // default 0 rolls back on any rejected row; raise it to keep the rows that did go in.
+    pub max_upload_failure_ratio: f64,
+    // Total bytes accepted per upload request, across all its files
+    pub max_upload_bytes: u64,
+    pub category_validation: CategoryValidation,
+    pub concurrent_refresh: ConcurrentRefresh,
+    // Newly ingested rates start pending and stay out of the virtual table until approved
+    pub require_rate_approval: bool,
+    pub aliases: HashMap<String, String>,
+    // Keys accepted in X-Api-Key; writes always need one
+    pub api_keys: Vec<ApiKey>,
+    // Lock the read-only endpoints down too, to any key or token
+    pub require_api_key_for_reads: bool,
+    // Bearer tokens accepted alongside the API keys; None refuses them
+    pub jwt: Option<JwtConfig>,
+    // Token buckets per API key, token subject or client address
+    pub read_rate_limit: Limit,
+    pub expensive_rate_limit: Limit,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        let file = ConfigFile::default();
+        Self {
+            sheets: SheetSelection::new(file.skip_sheets),
+            search_limit: file.search_limit,
+            max_export_rows: file.max_export_rows,
+            fuzzy_budget_ms: file.fuzzy_budget_ms,
+            max_search_candidates: file.max_search_candidates,
+            fuzzy_search_threshold: file.fuzzy_search_threshold,
+            sparse_filter_warning_ratio: file.sparse_filter_warning_ratio,
+            min_data_completeness: file.min_data_completeness,
+            max_upload_failure_ratio: file.max_upload_failure_ratio,
+            max_upload_bytes: file.max_upload_bytes,
+            category_validation: file.category_validation,
+            concurrent_refresh: file.concurrent_refresh,
+            require_rate_approval: file.require_rate_approval,
+            aliases: HashMap::new(),
+            api_keys: file.api_keys,
+            require_api_key_for_reads: file.require_api_key_for_reads,
+            jwt: file.jwt,
+            read_rate_limit: Limit {
+                per_minute: file.read_rate_limit_per_minute,
+                burst: file.read_rate_limit_burst,
+            },
+            expensive_rate_limit: Limit {
+                per_minute: file.expensive_rate_limit_per_minute,
+                burst: file.expensive_rate_limit_burst,
+            },
+        }
+    }
+}
+
+impl RuntimeConfig {
+    pub fn fuzzy_budget(&self) -> Duration {
+        Duration::from_millis(self.fuzzy_budget_ms)
+    }
+
+    // Replace whole tokens of an already-normalized name with their canonical expansion
+    pub fn expand_aliases(&self, normalized: &str) -> String {
+        if self.aliases.is_empty() {
+            return normalized.to_string();
+        }
+
+        normalized
+            .split_whitespace()
+            .map(|token| self.aliases.get(token).map(String::as_str).unwrap_or(token))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// Server settings can be overridden per instance: --bind-addr/--port/--workers/--tls-cert-path/
+// --tls-key-path flags win over BIND_ADDR/PORT/WORKERS/TLS_CERT_PATH/TLS_KEY_PATH/
+// REFRESH_INTERVAL_SECS env vars, which win over the config file
+fn apply_server_overrides(
+    file: &mut ConfigFile,
+    errors: &mut Vec<String>,
+    server_setting: &dyn Fn(&str, &str) -> Option<String>,
+) {
+    if let Some(addr) = server_setting("--bind-addr", "BIND_ADDR") {
+        file.bind_addr = addr;
+    }
+    if let Some(port) = server_setting("--port", "PORT") {
+        match port.trim().parse::<u16>() {
+            Ok(port) => file.port = port,
+            Err(_) => errors.push(format!("port '{}' is not a valid port number", port)),
+        }
+    }
+    if let Some(workers) = server_setting("--workers", "WORKERS") {
+        match workers.trim().parse::<usize>() {
+            Ok(workers) => file.workers = Some(workers),
+            Err(_) => errors.push(format!("workers '{}' is not a valid number", workers)),
+        }
+    }
+    if let Some(path) = server_setting("--tls-cert-path", "TLS_CERT_PATH") {
+        file.tls_cert_path = Some(PathBuf::from(path));
+    }
+    if let Some(path) = server_setting("--tls-key-path", "TLS_KEY_PATH") {
+        file.tls_key_path = Some(PathBuf::from(path));
+    }
+    if let Some(interval) = server_setting("--refresh-interval-secs", "REFRESH_INTERVAL_SECS") {
+        match interval.trim().parse::<u64>() {
+            Ok(interval) => file.refresh_interval_secs = interval,
+            Err(_) => errors.push(format!("refresh_interval_secs '{}' is not a valid number", interval)),
+        }
+    }
+}
+
+fn server_setting(flag: &str, env_var: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    setting_from(&args, &|name| std::env::var(name).ok(), flag, env_var)
+}
+
+fn setting_from(
+    args: &[String],
+    env: &dyn Fn(&str) -> Option<String>,
+    flag: &str,
+    env_var: &str,
+) -> Option<String> {
+    cli_flag(args, flag).or_else(|| env(env_var).filter(|v| !v.trim().is_empty()))
+}
+
+// Accepts both `--flag value` and `--flag=value`
+fn cli_flag(args: &[String], flag: &str) -> Option<String> {
+    let prefix = format!("{}=", flag);
+
+    args.iter().enumerate().skip(1).find_map(|(i, arg)| {
+        if arg == flag {
+            args.get(i + 1).cloned()
+        } else {
+            arg.strip_prefix(&prefix).map(str::to_string)
+        }
+    })
+}
+
+pub fn config_path_from_env() -> Option<PathBuf> {
+    std::env::var_os(CONFIG_PATH_ENV).map(PathBuf::from)
+}
+
+// SKIP_SHEETS / INCLUDE_SHEETS replace the file's values; an empty INCLUDE_SHEETS clears the include
+fn apply_sheet_overrides(file: &mut ConfigFile) {
+    if let Ok(skip) = std::env::var(SKIP_SHEETS_ENV) {
+        file.skip_sheets = skip
+            .split(',')
+            .map(|sheet| sheet.trim().to_string())
+            .filter(|sheet| !sheet.is_empty())
+            .collect();
+    }
+    if let Ok(include) = std::env::var(INCLUDE_SHEETS_ENV) {
+        file.include_sheets = Some(include.trim().to_string()).filter(|include| !include.is_empty());
+    }
+}
+
+fn apply_approval_override(file: &mut ConfigFile, errors: &mut Vec<String>) {
+    if let Ok(value) = std::env::var(REQUIRE_RATE_APPROVAL_ENV) {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "true" | "1" => file.require_rate_approval = true,
+            "false" | "0" => file.require_rate_approval = false,
+            _ => errors.push(format!("{} '{}' must be true or false", REQUIRE_RATE_APPROVAL_ENV, value)),
+        }
+    }
+}
+
+// API_KEYS="ops:secret,ci:secret" replaces the file's keys; an empty value clears them
+fn apply_api_key_override(file: &mut ConfigFile, errors: &mut Vec<String>) {
+    if let Ok(value) = std::env::var(API_KEYS_ENV) {
+        file.api_keys = value
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+            .filter_map(|pair| match pair.split_once(':') {
+                Some((id, secret)) => Some(ApiKey::new(id.trim().to_string(), secret.trim().to_string())),
+                None => {
+                    errors.push(format!("{} entries must be id:secret pairs", API_KEYS_ENV));
+                    None
+                }
+            })
+            .collect();
+    }
+}
+
+fn apply_jwt_overrides(file: &mut ConfigFile) {
+    let secret = std::env::var(JWT_HS256_SECRET_ENV).ok().filter(|v| !v.trim().is_empty());
+    let jwks_url = std::env::var(JWT_JWKS_URL_ENV).ok().filter(|v| !v.trim().is_empty());
+    if secret.is_none() && jwks_url.is_none() {
+        return;
+    }
+    let jwt = file.jwt.get_or_insert_with(JwtConfig::default);
+    if let Some(secret) = secret {
+        jwt.set_hs256_secret(Some(secret.trim().to_string()));
+    }
+    if let Some(jwks_url) = jwks_url {
+        jwt.jwks_url = Some(jwks_url.trim().to_string());
+    }
+}
+
+// RATE_LIMIT_READ_PER_MINUTE, RATE_LIMIT_READ_BURST, RATE_LIMIT_EXPENSIVE_PER_MINUTE and
+// RATE_LIMIT_EXPENSIVE_BURST replace the file's values
+fn apply_rate_limit_overrides(file: &mut ConfigFile, errors: &mut Vec<String>) {
+    let overrides = [
+        ("RATE_LIMIT_READ_PER_MINUTE", &mut file.read_rate_limit_per_minute),
+        ("RATE_LIMIT_READ_BURST", &mut file.read_rate_limit_burst),
+        ("RATE_LIMIT_EXPENSIVE_PER_MINUTE", &mut file.expensive_rate_limit_per_minute),
+        ("RATE_LIMIT_EXPENSIVE_BURST", &mut file.expensive_rate_limit_burst),
+    ];
+    for (env_var, setting) in overrides {
+        if let Ok(value) = std::env::var(env_var) {
+            match value.trim().parse::<u32>() {
+                Ok(parsed) => *setting = parsed,
+                Err(_) => errors.push(format!("{} '{}' is not a valid number", env_var, value)),
+            }
+        }
+    }
+}
+
+// Read and validate the config file; all validation errors are collected so a reload can report them together
+pub fn load(path: Option<&Path>) -> Result<(StaticConfig, RuntimeConfig), Vec<String>> {
+    let mut file = match path {
+        Some(path) => {
+            let contents = fs::read_to_string(path)
+                .map_err(|e| vec![format!("Failed to read config file {}: {}", path.display(), e)])?;
+            serde_json::from_str::<ConfigFile>(&contents)
+                .map_err(|e| vec![format!("Invalid config file {}: {}", path.display(), e)])?
+        }
+        None => ConfigFile::default(),
+    };
+
+    let mut errors = Vec::new();
+
+    apply_server_overrides(&mut file, &mut errors, &server_setting);
+    apply_sheet_overrides(&mut file);
+    apply_approval_override(&mut file, &mut errors);
+    apply_api_key_override(&mut file, &mut errors);
+    apply_jwt_overrides(&mut file);
+    apply_rate_limit_overrides(&mut file, &mut errors);
+
+    let bind_addr = file.bind_addr.trim().parse::<IpAddr>().unwrap_or_else(|_| {
+        errors.push(format!("bind_addr '{}' is not a valid IP address", file.bind_addr));
+        IpAddr::from([0, 0, 0, 0])
+    });
+    if file.port == 0 {
+        errors.push("port must be between 1 and 65535".to_string());
+    }
+    if file.workers == Some(0) {
+        errors.push("workers must be at least 1".to_string());
+    }
+    // The files themselves are read at startup, see tls::server_config
+    let tls = match (&file.tls_cert_path, &file.tls_key_path) {
+        (Some(cert_path), Some(key_path)) => Some(TlsFiles {
+            cert_path: cert_path.clone(),
+            key_path: key_path.clone(),
+        }),
+        (None, None) => None,
+        _ => {
+            errors.push("tls_cert_path and tls_key_path must be set together".to_string());
+            None
+        }
+    };
+    if file.pool_size == 0 {
+        errors.push("pool_size must be at least 1".to_string());
+    }
+    if file.db_retry_max_attempts == 0 {
+        errors.push("db_retry_max_attempts must be at least 1".to_string());
+    }
+    if file.export_keep_generations == 0 {
+        errors.push("export_keep_generations must be at least 1".to_string());
+    }
+    if file.skip_sheets.iter().any(|sheet| sheet.trim().is_empty()) {
+        errors.push("skip_sheets must not contain empty entries".to_string());
+    }
+    let sheets = SheetSelection::new(file.skip_sheets.clone())
+        .with_include(file.include_sheets.as_deref())
+        .unwrap_or_else(|e| {
+            errors.push(e);
+            SheetSelection::new(Vec::new())
+        });
+    if file.fuzzy_budget_ms == 0 {
+        errors.push("fuzzy_budget_ms must be at least 1".to_string());
+    }
+    if file.max_search_candidates == 0 {
+        errors.push("max_search_candidates must be at least 1".to_string());
+    }
+    if !(0.0..=1.0).contains(&file.fuzzy_search_threshold) {
+        errors.push("fuzzy_search_threshold must be between 0 and 1".to_string());
+    }
+    if !(0.0..=1.0).contains(&file.sparse_filter_warning_ratio) {
+        errors.push("sparse_filter_warning_ratio must be between 0 and 1".to_string());
+    }
+    if !(0.0..=1.0).contains(&file.min_data_completeness) {
+        errors.push("min_data_completeness must be between 0 and 1".to_string());
+    }
+    if !(0.0..=1.0).contains(&file.max_upload_failure_ratio) {
+        errors.push("max_upload_failure_ratio must be between 0 and 1".to_string());
+    }
+    if file.max_upload_bytes == 0 {
+        errors.push("max_upload_bytes must be at least 1".to_string());
+    }
+    if file.search_limit == 0 || file.search_limit > MAX_SEARCH_LIMIT {
+        errors.push(format!("search_limit must be between 1 and {}", MAX_SEARCH_LIMIT));
+    }
+    if file.max_export_rows == 0 || file.max_export_rows > MAX_EXPORT_ROWS {
+        errors.push(format!("max_export_rows must be between 1 and {}", MAX_EXPORT_ROWS));
+    }
+
+    let mut key_ids = std::collections::HashSet::new();
+    for key in &file.api_keys {
+        if key.id.trim().is_empty() {
+            errors.push("api_keys entries need a non-empty id".to_string());
+        } else if !key_ids.insert(key.id.as_str()) {
+            errors.push(format!("api_keys id '{}' is used more than once", key.id));
+        }
+        if key.secret_len() < MIN_API_KEY_SECRET_LEN {
+            errors.push(format!(
+                "api_keys secret for '{}' must be at least {} characters",
+                key.id, MIN_API_KEY_SECRET_LEN
+            ));
+        }
+    }
+
+    if let Some(jwt) = &file.jwt {
+        match (jwt.hs256_secret_len(), &jwt.jwks_url) {
+            (Some(_), Some(_)) | (None, None) => {
+                errors.push("jwt needs exactly one of hs256_secret and jwks_url".to_string())
+            }
+            (Some(len), None) if len < MIN_JWT_SECRET_LEN => {
+                errors.push(format!("jwt hs256_secret must be at least {} characters", MIN_JWT_SECRET_LEN))
+            }
+            (None, Some(url)) if !url.starts_with("https://") && !url.starts_with("http://") => {
+                errors.push(format!("jwt jwks_url '{}' must be an http(s) URL", url))
+            }
+            _ => {}
+        }
+    }
+
+    if file.read_rate_limit_per_minute > 0 && file.read_rate_limit_burst == 0 {
+        errors.push("read_rate_limit_burst must be at least 1 while the limit is on".to_string());
+    }
+    if file.expensive_rate_limit_per_minute > 0 && file.expensive_rate_limit_burst == 0 {
+        errors.push("expensive_rate_limit_burst must be at least 1 while the limit is on".to_string());
+    }
+
+    let aliases = match &file.alias_dictionary {
+        Some(dictionary) => {
+            // Relative dictionary paths are resolved against the config file's directory
+            let dictionary = match path.and_then(Path::parent) {
+                Some(dir) if dictionary.is_relative() => dir.join(dictionary),
+                _ => dictionary.clone(),
+            };
+            load_alias_dictionary(&dictionary).unwrap_or_else(|mut dictionary_errors| {
+                errors.append(&mut dictionary_errors);
+                HashMap::new()
+            })
+        }
+        None => HashMap::new(),
+    };
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok((
+        StaticConfig {
+            bind_addr,
+            port: file.port,
+            workers: file.workers,
+            tls,
+            pool_size: file.pool_size,
+            pool_wait_timeout_secs: file.pool_wait_timeout_secs,
+            export_dir: file.export_dir,
+            export_keep_generations: file.export_keep_generations,
+            snapshot_path: file.snapshot_path,
+            db_retry_max_attempts: file.db_retry_max_attempts,
+            db_retry_max_delay_secs: file.db_retry_max_delay_secs,
+            shutdown_grace_secs: file.shutdown_grace_secs,
+            refresh_interval_secs: file.refresh_interval_secs,
+        },
+        RuntimeConfig {
+            sheets,
+            search_limit: file.search_limit,
+            max_export_rows: file.max_export_rows,
+            fuzzy_budget_ms: file.fuzzy_budget_ms,
+            max_search_candidates: file.max_search_candidates,
+            fuzzy_search_threshold: file.fuzzy_search_threshold,
+            sparse_filter_warning_ratio: file.sparse_filter_warning_ratio,
+            min_data_completeness: file.min_data_completeness,
+            max_upload_failure_ratio: file.max_upload_failure_ratio,
+            max_upload_bytes: file.max_upload_bytes,
+            category_validation: file.category_validation,
+            concurrent_refresh: file.concurrent_refresh,
+            require_rate_approval: file.require_rate_approval,
+            aliases,
+            api_keys: file.api_keys,
+            require_api_key_for_reads: file.require_api_key_for_reads,
+            jwt: file.jwt,
+            read_rate_limit: Limit {
+                per_minute: file.read_rate_limit_per_minute,
+                burst: file.read_rate_limit_burst,
+            },
+            expensive_rate_limit: Limit {
+                per_minute: file.expensive_rate_limit_per_minute,
+                burst: file.expensive_rate_limit_burst,
+            },
+        },
+    ))
+}
+
+// Alias dictionaries are JSON objects of alias -> canonical text, e.g. {"bcf": "blue chip fund"}
+fn load_alias_dictionary(path: &Path) -> Result<HashMap<String, String>, Vec<String>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| vec![format!("Failed to read alias dictionary {}: {}", path.display(), e)])?;
+    let raw: HashMap<String, String> = serde_json::from_str(&contents)
+        .map_err(|e| vec![format!("Invalid alias dictionary {}: {}", path.display(), e)])?;
+
+    let mut aliases = HashMap::new();
+    let mut errors = Vec::new();
+
+    for (alias, canonical) in raw {
+        let normalized_alias = crate::normalize_scheme_name(&alias);
+        let normalized_canonical = crate::normalize_scheme_name(&canonical);
+
+        if normalized_alias.is_empty() || normalized_alias.contains(' ') {
+            errors.push(format!("Alias '{}' must be a single non-empty token", alias));
+        } else if normalized_canonical.is_empty() {
+            errors.push(format!("Alias '{}' maps to an empty name", alias));
+        } else {
+            aliases.insert(normalized_alias, normalized_canonical);
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(aliases)
+    } else {
+        Err(errors)
+    }
+}
+
+// Human-readable list of what a reload would change
+pub fn diff_runtime(old: &RuntimeConfig, new: &RuntimeConfig) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    if old.sheets.skip() != new.sheets.skip() {
+        changes.push(format!("skip_sheets: {:?} -> {:?}", old.sheets.skip(), new.sheets.skip()));
+    }
+    if old.sheets.include_pattern() != new.sheets.include_pattern() {
+        changes.push(format!(
+            "include_sheets: {:?} -> {:?}",
+            old.sheets.include_pattern(),
+            new.sheets.include_pattern()
+        ));
+    }
+    if old.search_limit != new.search_limit {
+        changes.push(format!("search_limit: {} -> {}", old.search_limit, new.search_limit));
+    }
+    if old.max_export_rows != new.max_export_rows {
+        changes.push(format!("max_export_rows: {} -> {}", old.max_export_rows, new.max_export_rows));
+    }
+    if old.fuzzy_budget_ms != new.fuzzy_budget_ms {
+        changes.push(format!("fuzzy_budget_ms: {} -> {}", old.fuzzy_budget_ms, new.fuzzy_budget_ms));
+    }
+    if old.max_search_candidates != new.max_search_candidates {
+        changes.push(format!(
+            "max_search_candidates: {} -> {}",
+            old.max_search_candidates, new.max_search_candidates
+        ));
+    }
+    if old.fuzzy_search_threshold != new.fuzzy_search_threshold {
+        changes.push(format!(
+            "fuzzy_search_threshold: {} -> {}",
+            old.fuzzy_search_threshold, new.fuzzy_search_threshold
+        ));
+    }
+    if old.sparse_filter_warning_ratio != new.sparse_filter_warning_ratio {
+        changes.push(format!(
+            "sparse_filter_warning_ratio: {} -> {}",
+            old.sparse_filter_warning_ratio, new.sparse_filter_warning_ratio
+        ));
+    }
+    if old.min_data_completeness != new.min_data_completeness {
+        changes.push(format!(
+            "min_data_completeness: {} -> {} (applies from the next refresh)",
+            old.min_data_completeness, new.min_data_completeness
+        ));
+    }
+    if old.max_upload_failure_ratio != new.max_upload_failure_ratio {
+        changes.push(format!(
+            "max_upload_failure_ratio: {} -> {}",
+            old.max_upload_failure_ratio, new.max_upload_failure_ratio
+        ));
+    }
+    if old.max_upload_bytes != new.max_upload_bytes {
+        changes.push(format!("max_upload_bytes: {} -> {}", old.max_upload_bytes, new.max_upload_bytes));
+    }
+    if old.category_validation != new.category_validation {
+        changes.push(format!(
+            "category_validation: {:?} -> {:?}",
+            old.category_validation, new.category_validation
+        ));
+    }
+    if old.require_rate_approval != new.require_rate_approval {
+        changes.push(format!(
+            "require_rate_approval: {} -> {}",
+            old.require_rate_approval, new.require_rate_approval
+        ));
+    }
+    if old.concurrent_refresh != new.concurrent_refresh {
+        changes.push(format!(
+            "concurrent_refresh: {:?} -> {:?}",
+            old.concurrent_refresh, new.concurrent_refresh
+        ));
+    }
+    if old.aliases != new.aliases {
+        let added = new.aliases.keys().filter(|k| !old.aliases.contains_key(*k)).count();
+        let removed = old.aliases.keys().filter(|k| !new.aliases.contains_key(*k)).count();
+        let changed = new
+            .aliases
+            .iter()
+            .filter(|(k, v)| old.aliases.get(*k).is_some_and(|old_v| old_v != *v))
+            .count();
+        changes.push(format!("aliases: {} added, {} removed, {} changed", added, removed, changed));
+    }
+    // Ids only; a rotated secret shows up as a change without saying what it was
+    if old.api_keys != new.api_keys {
+        let old_ids: Vec<&str> = old.api_keys.iter().map(|key| key.id.as_str()).collect();
+        let new_ids: Vec<&str> = new.api_keys.iter().map(|key| key.id.as_str()).collect();
+        if old_ids == new_ids {
+            changes.push(format!("api_keys: secrets rotated for {:?}", new_ids));
+        } else {
+            changes.push(format!("api_keys: {:?} -> {:?}", old_ids, new_ids));
+        }
+    }
+    if old.require_api_key_for_reads != new.require_api_key_for_reads {
+        changes.push(format!(
+            "require_api_key_for_reads: {} -> {}",
+            old.require_api_key_for_reads, new.require_api_key_for_reads
+        ));
+    }
+    if old.read_rate_limit != new.read_rate_limit {
+        changes.push(format!("read_rate_limit: {:?} -> {:?}", old.read_rate_limit, new.read_rate_limit));
+    }
+    if old.expensive_rate_limit != new.expensive_rate_limit {
+        changes.push(format!(
+            "expensive_rate_limit: {:?} -> {:?}",
+            old.expensive_rate_limit, new.expensive_rate_limit
+        ));
+    }
+    if old.jwt != new.jwt {
+        let describe = |jwt: &Option<JwtConfig>| jwt.as_ref().map_or("off".to_string(), JwtConfig::describe);
+        if old.jwt.as_ref().map(JwtConfig::describe) == new.jwt.as_ref().map(JwtConfig::describe) {
+            changes.push("jwt: hs256_secret rotated".to_string());
+        } else {
+            changes.push(format!("jwt: {} -> {}", describe(&old.jwt), describe(&new.jwt)));
+        }
+    }
+
+    changes
+}
+
+pub fn diff_static(running: &StaticConfig, on_disk: &StaticConfig) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    if running.bind_addr != on_disk.bind_addr {
+        changes.push(format!(
+            "bind_addr: {} -> {} (requires restart)",
+            running.bind_addr, on_disk.bind_addr
+        ));
+    }
+    if running.port != on_disk.port {
+        changes.push(format!("port: {} -> {} (requires restart)", running.port, on_disk.port));
+    }
+    if running.workers != on_disk.workers {
+        changes.push(format!(
+            "workers: {:?} -> {:?} (requires restart)",
+            running.workers, on_disk.workers
+        ));
+    }
+    if running.tls != on_disk.tls {
+        changes.push(format!("tls: {:?} -> {:?} (requires restart)", running.tls, on_disk.tls));
+    }
+    if running.pool_size != on_disk.pool_size {
+        changes.push(format!(
+            "pool_size: {} -> {} (requires restart)",
+            running.pool_size, on_disk.pool_size
+        ));
+    }
+    if running.db_retry_max_attempts != on_disk.db_retry_max_attempts
+        || running.db_retry_max_delay_secs != on_disk.db_retry_max_delay_secs
+    {
+        changes.push("db_retry_max_attempts/db_retry_max_delay_secs changed (requires restart)".to_string());
+    }
+    if running.export_dir != on_disk.export_dir {
+        changes.push(format!(
+            "export_dir: {:?} -> {:?} (requires restart)",
+            running.export_dir, on_disk.export_dir
+        ));
+    }
+    if running.snapshot_path != on_disk.snapshot_path {
+        changes.push(format!(
+            "snapshot_path: {:?} -> {:?} (requires restart)",
+            running.snapshot_path, on_disk.snapshot_path
+        ));
+    }
+    if running.export_keep_generations != on_disk.export_keep_generations {
+        changes.push(format!(
+            "export_keep_generations: {} -> {} (requires restart)",
+            running.export_keep_generations, on_disk.export_keep_generations
+        ));
+    }
+    if running.pool_wait_timeout_secs != on_disk.pool_wait_timeout_secs {
+        changes.push(format!(
+            "pool_wait_timeout_secs: {} -> {} (requires restart)",
+            running.pool_wait_timeout_secs, on_disk.pool_wait_timeout_secs
+        ));
+    }
+    if running.shutdown_grace_secs != on_disk.shutdown_grace_secs {
+        changes.push(format!(
+            "shutdown_grace_secs: {} -> {} (requires restart)",
+            running.shutdown_grace_secs, on_disk.shutdown_grace_secs
+        ));
+    }
+    if running.refresh_interval_secs != on_disk.refresh_interval_secs {
+        changes.push(format!(
+            "refresh_interval_secs: {} -> {} (requires restart)",
+            running.refresh_interval_secs, on_disk.refresh_interval_secs
+        ));
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_json(json: &str) -> Result<(StaticConfig, RuntimeConfig), Vec<String>> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        fs::write(&path, json).unwrap();
+        load(Some(&path))
+    }
+
+    // Flags as the server would see them after its own name, and the environment
+    fn overridden(args: &[&str], env: &[(&str, &str)]) -> (ConfigFile, Vec<String>) {
+        let args: Vec<String> = std::iter::once("excel-to-sqlite").chain(args.iter().copied()).map(String::from).collect();
+        let env = |name: &str| env.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string());
+        let (mut file, mut errors) = (ConfigFile::default(), Vec::new());
+        apply_server_overrides(&mut file, &mut errors, &|flag, env_var| setting_from(&args, &env, flag, env_var));
+        (file, errors)
+    }
+
+    #[test]
+    fn server_flags_win_over_the_environment_which_wins_over_the_file() {
+        let (file, errors) = overridden(&[], &[]);
+        assert_eq!((file.bind_addr.as_str(), file.port, file.workers), ("0.0.0.0", 8081, None));
+        assert!(errors.is_empty());
+
+        let env = [("BIND_ADDR", "127.0.0.1"), ("PORT", "9000"), ("WORKERS", "2")];
+        let (file, _) = overridden(&[], &env);
+        assert_eq!((file.bind_addr.as_str(), file.port, file.workers), ("127.0.0.1", 9000, Some(2)));
+
+        let (file, _) = overridden(&["--port", "9100", "--workers=4", "--bind-addr=::1"], &env);
+        assert_eq!((file.bind_addr.as_str(), file.port, file.workers), ("::1", 9100, Some(4)));
+
+        // A blank variable counts as unset
+        let (file, _) = overridden(&[], &[("PORT", " ")]);
+        assert_eq!(file.port, 8081);
+    }
+
+    #[test]
+    fn bad_server_settings_are_reported() {
+        let (_, errors) = overridden(&["--port", "http", "--workers=many"], &[]);
+        assert_eq!(errors, ["port 'http' is not a valid port number", "workers 'many' is not a valid number"]);
+
+        let errors = load_json(r#"{"bind_addr": "localhost", "port": 0, "workers": 0}"#).unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                "bind_addr 'localhost' is not a valid IP address",
+                "port must be between 1 and 65535",
+                "workers must be at least 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn pool_settings_are_checked_and_only_change_on_restart() {
+        let errors = load_json(r#"{"pool_size": 0}"#).unwrap_err();
+        assert_eq!(errors, ["pool_size must be at least 1"]);
+
+        let (running, _) = load_json("{}").unwrap();
+        assert_eq!((running.pool_size, running.pool_wait_timeout_secs), (16, 5));
+        let (on_disk, _) = load_json(r#"{"pool_size": 4, "pool_wait_timeout_secs": 1}"#).unwrap();
+        assert_eq!(
+            diff_static(&running, &on_disk),
+            ["pool_size: 16 -> 4 (requires restart)", "pool_wait_timeout_secs: 5 -> 1 (requires restart)"]
+        );
+    }
+}
+
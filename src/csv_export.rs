@@ -0,0 +1,103 @@
+use actix_web::web::Bytes;
+use chrono::NaiveDate;
+use futures_util::stream::{self, Stream, StreamExt};
+
+use crate::preferences::FIELD_NAMES;
+use crate::CombinedSchemeData;
+
+// Rows serialized per body chunk
+const CHUNK_ROWS: usize = 1000;
+// Longest query text carried into the download's file name
+const FILE_NAME_QUERY_CHARS: usize = 40;
+
+// The header row, then the records a chunk at a time, so a large export is not built up front
+pub fn stream(records: Vec<CombinedSchemeData>) -> impl Stream<Item = Result<Bytes, csv::Error>> {
+    // An empty export still gets its header
+    let chunk_count = records.len().div_ceil(CHUNK_ROWS).max(1);
+    stream::iter(0..chunk_count).map(move |chunk| {
+        let start = chunk * CHUNK_ROWS;
+        let end = (start + CHUNK_ROWS).min(records.len());
+        write_rows(&records[start..end], chunk == 0).map(Bytes::from)
+    })
+}
+
+fn write_rows(records: &[CombinedSchemeData], header: bool) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    if header {
+        writer.write_record(FIELD_NAMES)?;
+    }
+    for record in records {
+        writer.write_record(row(record))?;
+    }
+    writer.into_inner().map_err(|e| csv::Error::from(e.into_error()))
+}
+
+// In FIELD_NAMES order
+fn row(record: &CombinedSchemeData) -> [String; 29] {
+    [
+        integer(record.fund_id),
+        text(record.fund_category.as_deref()),
+        date(record.launch_date),
+        number(record.fund_size_apr25),
+        number(record.fund_size_may25),
+        number(record.latest_nav),
+        number(record.month_1),
+        number(record.months_3),
+        number(record.months_6),
+        number(record.ytd),
+        number(record.year_1),
+        number(record.years_2),
+        number(record.years_3),
+        number(record.years_5),
+        integer(record.rate_id),
+        text(record.arn.as_deref()),
+        text(record.company.as_deref()),
+        text(record.scheme_category.as_deref()),
+        text(record.brokerage_type.as_deref()),
+        date(record.start_date),
+        date(record.end_date),
+        number(record.base_year_1),
+        number(record.base_year_2),
+        number(record.base_year_3),
+        record.scheme_name.to_string(),
+        record.normalized_name.to_string(),
+        format!("{:.4}", record.data_completeness),
+        record.incomplete.to_string(),
+        record.is_rate_active.to_string(),
+    ]
+}
+
+// Fixed precision so spreadsheets don't show f32 noise like 12.340000152587891
+fn number(value: Option<f32>) -> String {
+    value.map(|value| format!("{:.4}", value)).unwrap_or_default()
+}
+
+fn integer(value: Option<i32>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn text(value: Option<&str>) -> String {
+    value.unwrap_or_default().to_string()
+}
+
+fn date(value: Option<NaiveDate>) -> String {
+    value.map(|value| value.format("%Y-%m-%d").to_string()).unwrap_or_default()
+}
+
+// "Axis Bluechip/Growth" on 2025-06-01 -> search-axis-bluechip-growth-2025-06-01.csv
+pub fn file_name(query: &str, date: NaiveDate) -> String {
+    let mut slug = String::new();
+    for c in query.chars().flat_map(char::to_lowercase) {
+        if slug.chars().count() == FILE_NAME_QUERY_CHARS {
+            break;
+        }
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    let slug = if slug.is_empty() { "all" } else { slug };
+    format!("search-{}-{}.csv", slug, date.format("%Y-%m-%d"))
+}
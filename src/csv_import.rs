@@ -0,0 +1,201 @@
+use calamine::Data;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::columns::{self, ColumnMap};
+use crate::sniff::{self, SNIFF_BYTES};
+use crate::{fund_from_cells, FundData, FundRowError};
+
+const UTF8_BOM: char = '\u{feff}';
+
+// The extension decides when there is one we know; otherwise sniff the content
+pub fn is_csv(file_name: Option<&str>, path: &Path) -> std::io::Result<bool> {
+    let extension = file_name
+        .and_then(|name| Path::new(name).extension())
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("csv") => return Ok(true),
+        Some(ext) if crate::workbook::SUPPORTED_EXTENSIONS.contains(&ext) => return Ok(false),
+        _ => {}
+    }
+
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    File::open(path)?.take(SNIFF_BYTES as u64).read_to_end(&mut head)?;
+    // Text with at least one comma
+    Ok(sniff::detect(&head) == sniff::Format::Text && head.contains(&b','))
+}
+
+// "Large Cap Fund.csv" -> "Large Cap Fund", mirroring how sheet names become categories
+pub fn category_from_file_name(file_name: Option<&str>) -> String {
+    file_name
+        .and_then(|name| Path::new(name).file_stem())
+        .map(|stem| stem.to_string_lossy().trim().to_string())
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or_else(|| "CSV".to_string())
+}
+
+// Stream the file record by record. `sheet` names the file in errors, `category` goes on the funds.
+// Err means the file as a whole is unusable; malformed rows are skipped and returned alongside the
+// parsed funds and the header columns.
+pub fn extract_fund_data(
+    sheet: &str,
+    category: &str,
+    path: &Path,
+) -> Result<(Vec<FundData>, Vec<FundRowError>, ColumnMap), String> {
+    // Flexible, because title lines above the header rarely have the full column count. Records end
+    // at \n alone: with the default terminator the \n of a \r\n starts the next record, and every
+    // line number after the first comes out one short. The \r left on the last field is dropped below.
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .terminator(csv::Terminator::Any(b'\n'))
+        .from_path(path)
+        .map_err(|e| format!("cannot open CSV: {}", e))?;
+
+    let mut funds = Vec::new();
+    let mut errors = Vec::new();
+    let mut layout: Option<(ColumnMap, usize)> = None;
+    let mut record = csv::StringRecord::new();
+
+    for scanned in 0.. {
+        let line = reader.position().line();
+        match reader.read_record(&mut record) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => {
+                if let csv::ErrorKind::Io(_) = e.kind() {
+                    return Err(format!("cannot read CSV: {}", e));
+                }
+                errors.push(row_error(sheet, line, e.to_string()));
+                continue;
+            }
+        }
+
+        let last = record.len().saturating_sub(1);
+        let cells: Vec<String> = record
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let field = if i == 0 { field.trim_start_matches(UTF8_BOM) } else { field };
+                if i == last { field.strip_suffix('\r').unwrap_or(field) } else { field }.to_string()
+            })
+            .collect();
+
+        let (column_map, width) = match &layout {
+            Some(found) => found,
+            None => {
+                if cells.iter().any(|cell| columns::is_scheme_name_header(cell)) {
+                    layout = Some((ColumnMap::from_headers(sheet, &cells)?, cells.len()));
+                } else if scanned >= 15 {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        if cells.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        if cells.len() != *width {
+            errors.push(row_error(
+                sheet,
+                line,
+                format!("expected {} fields, found {}", width, cells.len()),
+            ));
+            continue;
+        }
+
+        let cells: Vec<Data> = cells.into_iter().map(|cell| Data::String(cell.trim().to_string())).collect();
+        let cell = |column| column_map.index(column).and_then(|i| cells.get(i));
+        if let Some(fund) = fund_from_cells(sheet, category, line as usize, cell, &mut errors) {
+            funds.push(fund);
+        }
+    }
+
+    match layout {
+        Some((column_map, _)) => Ok((funds, errors, column_map)),
+        None => Err("header row not found (no 'Scheme Name' column in the first 15 rows)".to_string()),
+    }
+}
+
+fn row_error(sheet: &str, line: u64, reason: String) -> FundRowError {
+    FundRowError {
+        sheet: sheet.to_string(),
+        row: line as usize,
+        scheme_name: None,
+        reason,
+        skipped: true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn csv_file(dir: &tempfile::TempDir, name: &str, contents: &str) -> std::path::PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn csv_is_told_apart_by_extension_then_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let text = csv_file(&dir, "upload", "Scheme Name,Launch Date\r\n");
+        let zip = dir.path().join("zipped");
+        std::fs::write(&zip, b"PK\x03\x04 not,text").unwrap();
+
+        assert!(is_csv(Some("funds.CSV"), &zip).unwrap());
+        assert!(!is_csv(Some("funds.xlsx"), &text).unwrap());
+        assert!(is_csv(Some("funds.txt"), &text).unwrap());
+        assert!(is_csv(None, &text).unwrap());
+        assert!(!is_csv(None, &zip).unwrap());
+    }
+
+    #[test]
+    fn the_category_is_the_file_stem() {
+        assert_eq!(category_from_file_name(Some("Large Cap Fund.csv")), "Large Cap Fund");
+        assert_eq!(category_from_file_name(Some(" .csv")), "CSV");
+        assert_eq!(category_from_file_name(None), "CSV");
+    }
+
+    #[test]
+    fn quoted_fields_bom_and_crlf_are_read_and_bad_rows_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = csv_file(
+            &dir,
+            "funds.csv",
+            "\u{feff}Monthly factsheet\r\n\
+             Latest NAV,Scheme Name,Launch Date\r\n\
+             12.5,\"Alpha Fund, Growth\",2020-01-15\r\n\
+             13.0,Beta Fund\r\n\
+             \r\n\
+             \"14.25\",\"Gamma \"\"Direct\"\"\",\"2019-06-01\"\r\n",
+        );
+
+        let (funds, errors, column_map) = extract_fund_data("funds", "Large Cap", &path).unwrap();
+
+        let names: Vec<&str> = funds.iter().map(|fund| fund.scheme_name.as_str()).collect();
+        assert_eq!(names, ["Alpha Fund, Growth", "Gamma \"Direct\""]);
+        assert_eq!(funds[0].latest_nav, Some(12.5));
+        assert_eq!(funds[0].launch_date, NaiveDate::from_ymd_opt(2020, 1, 15));
+        assert_eq!(funds[1].launch_date, NaiveDate::from_ymd_opt(2019, 6, 1));
+        assert_eq!(funds[1].category, "Large Cap");
+        assert_eq!(column_map.index(columns::FundColumn::SchemeName), Some(1));
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].row, errors[0].reason.as_str()), (4, "expected 3 fields, found 2"));
+        assert!(errors[0].skipped);
+    }
+
+    #[test]
+    fn a_file_without_a_header_row_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = csv_file(&dir, "funds.csv", "Name,Date\nAlpha,2020-01-01\n");
+
+        let error = extract_fund_data("funds", "CSV", &path).unwrap_err();
+        assert!(error.starts_with("header row not found"), "{}", error);
+    }
+}
@@ -0,0 +1,127 @@
+use calamine::Data;
+use chrono::NaiveDate;
+
+// Excel stores dates as days since 1899-12-30 (accounting for the 1900 leap-year bug)
+const EXCEL_EPOCH: (i32, u32, u32) = (1899, 12, 30);
+const DATE_FORMATS: [&str; 9] = [
+    "%Y-%m-%d",
+    "%d-%m-%Y",
+    "%d/%m/%Y",
+    "%Y/%m/%d",
+    "%d-%b-%Y",
+    "%d %b %Y",
+    "%d-%B-%Y",
+    "%d %B %Y",
+    "%d.%m.%Y",
+];
+
+// Dates from workbook cells: Excel serials, ISO datetimes and the string formats above.
+// Err is a reason fragment meant to follow the column label ("Start Date is empty").
+pub fn parse_date(cell: Option<&Data>) -> Result<NaiveDate, String> {
+    match cell {
+        Some(Data::DateTime(value)) => from_excel_serial(value.as_f64()),
+        Some(Data::Float(value)) => from_excel_serial(*value),
+        Some(Data::Int(value)) => from_excel_serial(*value as f64),
+        Some(Data::DateTimeIso(value)) | Some(Data::String(value)) => {
+            let value = value.trim();
+            if value.is_empty() {
+                return Err("is empty".to_string());
+            }
+            // ISO datetimes carry a time part we don't need
+            let date_part = value.split('T').next().unwrap_or(value);
+            DATE_FORMATS
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(date_part, format).ok())
+                .ok_or_else(|| format!("'{}' is not a recognised date", value))
+        }
+        _ => Err("is empty".to_string()),
+    }
+}
+
+// `since` query parameters: YYYY-MM-DDTHH:MM:SS, or YYYY-MM-DD for midnight
+pub fn parse_since(value: &str) -> Option<chrono::NaiveDateTime> {
+    chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0))
+}
+
+fn from_excel_serial(serial: f64) -> Result<NaiveDate, String> {
+    let (year, month, day) = EXCEL_EPOCH;
+    if !serial.is_finite() || serial < 1.0 {
+        return Err(format!("{} is not a valid Excel date", serial));
+    }
+    NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|epoch| epoch.checked_add_days(chrono::Days::new(serial.trunc() as u64)))
+        .ok_or_else(|| format!("{} is not a valid Excel date", serial))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::{ExcelDateTime, ExcelDateTimeType};
+
+    fn date(year: i32, month: u32, day: u32) -> Result<NaiveDate, String> {
+        Ok(NaiveDate::from_ymd_opt(year, month, day).unwrap())
+    }
+
+    #[test]
+    fn excel_serials_count_days_from_the_1899_epoch_and_drop_the_time() {
+        let datetime = |serial| Data::DateTime(ExcelDateTime::new(serial, ExcelDateTimeType::DateTime, false));
+        let cases = [
+            (datetime(45000.0), date(2023, 3, 15)),
+            (datetime(43831.75), date(2020, 1, 1)),
+            (Data::Float(43831.0), date(2020, 1, 1)),
+            (Data::Int(1), date(1899, 12, 31)),
+            // Serials past 60 skip Excel's phantom 29 February 1900
+            (Data::Int(61), date(1900, 3, 1)),
+            (Data::Float(0.5), Err("0.5 is not a valid Excel date".to_string())),
+            (Data::Int(-3), Err("-3 is not a valid Excel date".to_string())),
+            (Data::Float(f64::NAN), Err("NaN is not a valid Excel date".to_string())),
+            (Data::Float(1e15), Err("1000000000000000 is not a valid Excel date".to_string())),
+        ];
+
+        for (cell, expected) in cases {
+            assert_eq!(parse_date(Some(&cell)), expected, "{:?}", cell);
+        }
+    }
+
+    #[test]
+    fn text_dates_parse_in_every_listed_format_day_first() {
+        let text = |value: &str| Data::String(value.to_string());
+        let cases = [
+            (text("2013-05-24"), date(2013, 5, 24)),
+            (text(" 24-05-2013 "), date(2013, 5, 24)),
+            (text("24/05/2013"), date(2013, 5, 24)),
+            (text("2013/05/24"), date(2013, 5, 24)),
+            (text("24-May-2013"), date(2013, 5, 24)),
+            (text("24 May 2013"), date(2013, 5, 24)),
+            (text("24-September-2013"), date(2013, 9, 24)),
+            (text("24 September 2013"), date(2013, 9, 24)),
+            (text("24.05.2013"), date(2013, 5, 24)),
+            // Slashes are read day first, as Indian AMC sheets write them
+            (text("03/04/2013"), date(2013, 4, 3)),
+            (Data::DateTimeIso("2013-05-24T10:30:00".to_string()), date(2013, 5, 24)),
+            (text("05/24/2013"), Err("'05/24/2013' is not a recognised date".to_string())),
+            (text("31/02/2013"), Err("'31/02/2013' is not a recognised date".to_string())),
+            (text("2013-13-01"), Err("'2013-13-01' is not a recognised date".to_string())),
+            (text("launched in 2013"), Err("'launched in 2013' is not a recognised date".to_string())),
+            (text("  "), Err("is empty".to_string())),
+            (Data::Empty, Err("is empty".to_string())),
+            (Data::Bool(true), Err("is empty".to_string())),
+        ];
+
+        for (cell, expected) in cases {
+            assert_eq!(parse_date(Some(&cell)), expected, "{:?}", cell);
+        }
+        assert_eq!(parse_date(None), Err("is empty".to_string()));
+    }
+
+    #[test]
+    fn since_takes_a_datetime_or_a_date_at_midnight() {
+        let at = |value: &str| parse_since(value).map(|since| since.to_string());
+        assert_eq!(at("2025-04-01T09:15:00"), Some("2025-04-01 09:15:00".to_string()));
+        assert_eq!(at("2025-04-01"), Some("2025-04-01 00:00:00".to_string()));
+        assert_eq!(at("01-04-2025"), None);
+        assert_eq!(at("2025-04-01T25:00:00"), None);
+    }
+}
@@ -0,0 +1,832 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashSet;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Client, Row};
+use utoipa::ToSchema;
+
+use crate::rate_upload::RateRow;
+
+// Every write to funds/scheme_rates bumps `version`, uploads included. Manual edits must name the
+// version they were based on; a mismatch means someone else wrote in between.
+pub enum EditOutcome<T> {
+    Updated(T),
+    // Carries the current server state so the client can merge
+    Conflict(T),
+    NotFound,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FundRecord {
+    pub id: i32,
+    pub category: String,
+    pub scheme_name: String,
+    pub launch_date: Option<NaiveDate>,
+    pub fund_size_apr25: Option<f32>,
+    pub fund_size_may25: Option<f32>,
+    pub latest_nav: Option<f32>,
+    pub month_1: Option<f32>,
+    pub months_3: Option<f32>,
+    pub months_6: Option<f32>,
+    pub ytd: Option<f32>,
+    pub year_1: Option<f32>,
+    pub years_2: Option<f32>,
+    pub years_3: Option<f32>,
+    pub years_5: Option<f32>,
+    pub version: i32,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+// Full replacement of a fund's editable fields
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct FundEdit {
+    pub category: String,
+    pub scheme_name: String,
+    pub launch_date: Option<NaiveDate>,
+    pub fund_size_apr25: Option<f32>,
+    pub fund_size_may25: Option<f32>,
+    pub latest_nav: Option<f32>,
+    pub month_1: Option<f32>,
+    pub months_3: Option<f32>,
+    pub months_6: Option<f32>,
+    pub ytd: Option<f32>,
+    pub year_1: Option<f32>,
+    pub years_2: Option<f32>,
+    pub years_3: Option<f32>,
+    pub years_5: Option<f32>,
+    // Alternative to If-Match
+    pub version: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RateRecord {
+    pub id: i32,
+    pub arn: String,
+    pub company: String,
+    pub scheme_name: String,
+    pub scheme_category: String,
+    pub brokerage_type: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub is_approved: Option<bool>,
+    pub base_year_1: Option<f32>,
+    pub base_year_2: Option<f32>,
+    pub base_year_3: Option<f32>,
+    pub version: i32,
+    // Set by POST /rates/{id}/reject; a rejected rate keeps is_approved false
+    pub rejection_reason: Option<String>,
+}
+
+impl RateRecord {
+    // Neither pending nor rejected; NULL counts as approved, as in the virtual table's join
+    pub fn approved(&self) -> bool {
+        self.is_approved != Some(false)
+    }
+
+    pub fn active_on(&self, date: NaiveDate) -> bool {
+        self.start_date <= date && self.end_date >= date
+    }
+}
+
+// Partial update of a fund: a field left out keeps its value, an explicit null clears it.
+// scheme_name is only accepted so it can be refused by name; it is the join and index key, so
+// renames go through PUT.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FundPatch {
+    pub category: Option<String>,
+    #[schema(value_type = Option<String>)]
+    pub scheme_name: Option<serde::de::IgnoredAny>,
+    #[serde(default, deserialize_with = "present")]
+    pub launch_date: Option<Option<NaiveDate>>,
+    #[serde(default, deserialize_with = "present")]
+    pub fund_size_apr25: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub fund_size_may25: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub latest_nav: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub month_1: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub months_3: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub months_6: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub ytd: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub year_1: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub years_2: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub years_3: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub years_5: Option<Option<f32>>,
+    // Alternative to If-Match
+    pub version: Option<i32>,
+}
+
+// Tells a field sent as null (Some(None)) from one left out (None, via serde default)
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+impl FundPatch {
+    // column = value for each field the patch sets, in FUND_COLUMNS order
+    fn assignments(&self) -> Vec<(&'static str, &(dyn ToSql + Sync))> {
+        let mut assignments: Vec<(&'static str, &(dyn ToSql + Sync))> = Vec::new();
+        if let Some(category) = &self.category {
+            assignments.push(("category", category));
+        }
+        if let Some(launch_date) = &self.launch_date {
+            assignments.push(("launch_date", launch_date));
+        }
+        let numeric = [
+            ("fund_size_apr25", &self.fund_size_apr25),
+            ("fund_size_may25", &self.fund_size_may25),
+            ("latest_nav", &self.latest_nav),
+            ("month_1", &self.month_1),
+            ("months_3", &self.months_3),
+            ("months_6", &self.months_6),
+            ("ytd", &self.ytd),
+            ("year_1", &self.year_1),
+            ("years_2", &self.years_2),
+            ("years_3", &self.years_3),
+            ("years_5", &self.years_5),
+        ];
+        for (column, value) in numeric {
+            if let Some(value) = value {
+                assignments.push((column, value));
+            }
+        }
+        assignments
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.scheme_name.is_some() {
+            return Err("scheme_name cannot be changed with PATCH; use PUT /funds/{id} to rename a fund".to_string());
+        }
+        if self.category.as_deref().is_some_and(|category| category.trim().is_empty()) {
+            return Err("category must not be empty".to_string());
+        }
+        if self.assignments().is_empty() {
+            return Err("Nothing to change: the body sets no fund fields".to_string());
+        }
+        Ok(())
+    }
+}
+
+// One invalid field of a create or patch body
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    #[schema(value_type = String)]
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    fn new(field: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field,
+            message: message.into(),
+        }
+    }
+}
+
+// POST /rates body. Everything is optional here so a missing field is reported per field by
+// validate() rather than as a bare deserialization error.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NewRate {
+    pub arn: Option<String>,
+    pub company: Option<String>,
+    pub scheme_name: Option<String>,
+    pub scheme_category: Option<String>,
+    pub brokerage_type: Option<String>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub base_year_1: Option<f32>,
+    pub base_year_2: Option<f32>,
+    pub base_year_3: Option<f32>,
+}
+
+impl NewRate {
+    // The same checks a rate sheet row gets: required text non-empty, start on or before end
+    pub fn validate(&self) -> Result<RateRow, Vec<FieldError>> {
+        let mut errors = Vec::new();
+        let mut text = |field: &'static str, value: &Option<String>| {
+            let value = value.as_deref().map(str::trim).unwrap_or_default().to_string();
+            if value.is_empty() {
+                errors.push(FieldError::new(field, "is required"));
+            }
+            value
+        };
+        let arn = text("arn", &self.arn);
+        let company = text("company", &self.company);
+        let scheme_name = text("scheme_name", &self.scheme_name);
+        let scheme_category = text("scheme_category", &self.scheme_category);
+        let brokerage_type = text("brokerage_type", &self.brokerage_type);
+        if self.start_date.is_none() {
+            errors.push(FieldError::new("start_date", "is required"));
+        }
+        if self.end_date.is_none() {
+            errors.push(FieldError::new("end_date", "is required"));
+        }
+        if let (Some(start_date), Some(end_date)) = (self.start_date, self.end_date) {
+            if let Err(error) = check_rate_dates(start_date, end_date) {
+                errors.push(error);
+            }
+        }
+
+        match (self.start_date, self.end_date) {
+            (Some(start_date), Some(end_date)) if errors.is_empty() => Ok(RateRow {
+                arn,
+                company,
+                scheme_name,
+                scheme_category,
+                brokerage_type,
+                start_date,
+                end_date,
+                base_year_1: self.base_year_1,
+                base_year_2: self.base_year_2,
+                base_year_3: self.base_year_3,
+            }),
+            _ => Err(errors),
+        }
+    }
+}
+
+// Partial update of a rate, like FundPatch: left out keeps the value, null clears a base year.
+// The text fields and dates are NOT NULL, so null is not accepted for them.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RatePatch {
+    pub arn: Option<String>,
+    pub company: Option<String>,
+    pub scheme_name: Option<String>,
+    pub scheme_category: Option<String>,
+    pub brokerage_type: Option<String>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "present")]
+    pub base_year_1: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub base_year_2: Option<Option<f32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub base_year_3: Option<Option<f32>>,
+    // Alternative to If-Match
+    pub version: Option<i32>,
+}
+
+impl RatePatch {
+    fn assignments(&self) -> Vec<(&'static str, &(dyn ToSql + Sync))> {
+        let mut assignments: Vec<(&'static str, &(dyn ToSql + Sync))> = Vec::new();
+        let text = [
+            ("arn", &self.arn),
+            ("company", &self.company),
+            ("scheme_name", &self.scheme_name),
+            ("scheme_category", &self.scheme_category),
+            ("brokerage_type", &self.brokerage_type),
+        ];
+        for (column, value) in text {
+            if let Some(value) = value {
+                assignments.push((column, value));
+            }
+        }
+        if let Some(start_date) = &self.start_date {
+            assignments.push(("start_date", start_date));
+        }
+        if let Some(end_date) = &self.end_date {
+            assignments.push(("end_date", end_date));
+        }
+        let numeric = [
+            ("base_year_1", &self.base_year_1),
+            ("base_year_2", &self.base_year_2),
+            ("base_year_3", &self.base_year_3),
+        ];
+        for (column, value) in numeric {
+            if let Some(value) = value {
+                assignments.push((column, value));
+            }
+        }
+        assignments
+    }
+
+    // Text is trimmed before this; dates are checked against `current`, the row the patch applies to
+    pub fn validate(&self, current: &RateRecord) -> Result<(), Vec<FieldError>> {
+        let mut errors: Vec<FieldError> = [
+            ("arn", &self.arn),
+            ("company", &self.company),
+            ("scheme_name", &self.scheme_name),
+            ("scheme_category", &self.scheme_category),
+            ("brokerage_type", &self.brokerage_type),
+        ]
+        .into_iter()
+        .filter(|(_, value)| value.as_deref().is_some_and(|value| value.trim().is_empty()))
+        .map(|(field, _)| FieldError::new(field, "must not be empty"))
+        .collect();
+        let start_date = self.start_date.unwrap_or(current.start_date);
+        let end_date = self.end_date.unwrap_or(current.end_date);
+        if let Err(error) = check_rate_dates(start_date, end_date) {
+            errors.push(error);
+        }
+        if errors.is_empty() && self.assignments().is_empty() {
+            errors.push(FieldError::new("body", "sets no rate fields"));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn trim(&mut self) {
+        for text in [
+            &mut self.arn,
+            &mut self.company,
+            &mut self.scheme_name,
+            &mut self.scheme_category,
+            &mut self.brokerage_type,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *text = text.trim().to_string();
+        }
+    }
+}
+
+fn check_rate_dates(start_date: NaiveDate, end_date: NaiveDate) -> Result<(), FieldError> {
+    if end_date < start_date {
+        Err(FieldError::new(
+            "end_date",
+            format!("{} is before start_date {}", end_date, start_date),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+// Full replacement of a rate's editable fields
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct RateEdit {
+    pub arn: String,
+    pub company: String,
+    pub scheme_name: String,
+    pub scheme_category: String,
+    pub brokerage_type: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub is_approved: Option<bool>,
+    pub base_year_1: Option<f32>,
+    pub base_year_2: Option<f32>,
+    pub base_year_3: Option<f32>,
+    // Alternative to If-Match
+    pub version: Option<i32>,
+}
+
+const FUND_COLUMNS: &str = "id, category, scheme_name, launch_date, fund_size_apr25, fund_size_may25, latest_nav,
+    month_1, months_3, months_6, ytd, year_1, years_2, years_3, years_5, version, created_at";
+
+const RATE_COLUMNS: &str = "id, arn, company, scheme_name, scheme_category, brokerage_type, start_date, end_date,
+    is_approved, base_year_1, base_year_2, base_year_3, version, rejection_reason";
+
+fn fund_from_row(row: &Row) -> FundRecord {
+    FundRecord {
+        id: row.get("id"),
+        category: row.get("category"),
+        scheme_name: row.get("scheme_name"),
+        launch_date: row.get("launch_date"),
+        fund_size_apr25: row.get("fund_size_apr25"),
+        fund_size_may25: row.get("fund_size_may25"),
+        latest_nav: row.get("latest_nav"),
+        month_1: row.get("month_1"),
+        months_3: row.get("months_3"),
+        months_6: row.get("months_6"),
+        ytd: row.get("ytd"),
+        year_1: row.get("year_1"),
+        years_2: row.get("years_2"),
+        years_3: row.get("years_3"),
+        years_5: row.get("years_5"),
+        version: row.get("version"),
+        created_at: row.get("created_at"),
+    }
+}
+
+fn rate_from_row(row: &Row) -> RateRecord {
+    RateRecord {
+        id: row.get("id"),
+        arn: row.get("arn"),
+        company: row.get("company"),
+        scheme_name: row.get("scheme_name"),
+        scheme_category: row.get("scheme_category"),
+        brokerage_type: row.get("brokerage_type"),
+        start_date: row.get("start_date"),
+        end_date: row.get("end_date"),
+        is_approved: row.get("is_approved"),
+        base_year_1: row.get("base_year_1"),
+        base_year_2: row.get("base_year_2"),
+        base_year_3: row.get("base_year_3"),
+        version: row.get("version"),
+        rejection_reason: row.get("rejection_reason"),
+    }
+}
+
+pub async fn load_fund(client: &Client, id: i32) -> Result<Option<FundRecord>, tokio_postgres::Error> {
+    let query = format!("SELECT {} FROM funds WHERE id = $1", FUND_COLUMNS);
+    Ok(client.query_opt(&query, &[&id]).await?.as_ref().map(fund_from_row))
+}
+
+// Columns GET /funds may order by; anything else is rejected rather than spliced into SQL
+pub const FUND_ORDER_COLUMNS: [&str; 16] = [
+    "id",
+    "category",
+    "scheme_name",
+    "launch_date",
+    "fund_size_apr25",
+    "fund_size_may25",
+    "latest_nav",
+    "month_1",
+    "months_3",
+    "months_6",
+    "ytd",
+    "year_1",
+    "years_2",
+    "years_3",
+    "years_5",
+    "created_at",
+];
+
+// One page of funds straight from the table, with the total matching the same filter. `order_by`
+// must come from FUND_ORDER_COLUMNS; id breaks ties so pages don't overlap.
+pub async fn list_funds(
+    client: &Client,
+    category: Option<&str>,
+    order_by: &str,
+    descending: bool,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<FundRecord>, i64), tokio_postgres::Error> {
+    debug_assert!(FUND_ORDER_COLUMNS.contains(&order_by));
+    let query = format!(
+        "SELECT {} FROM funds
+         WHERE $1::TEXT IS NULL OR category = $1
+         ORDER BY {} {} NULLS LAST, id
+         LIMIT $2 OFFSET $3",
+        FUND_COLUMNS,
+        order_by,
+        if descending { "DESC" } else { "ASC" }
+    );
+    let funds = client.query(&query, &[&category, &limit, &offset]).await?;
+    let total: i64 = client
+        .query_one("SELECT COUNT(*) FROM funds WHERE $1::TEXT IS NULL OR category = $1", &[&category])
+        .await?
+        .get(0);
+    Ok((funds.iter().map(fund_from_row).collect(), total))
+}
+
+// Rates sr whose name joins fund $1 as the virtual table joins them: same expanded name, or a
+// confirmed alias. Approval is up to the caller.
+const JOINS_FUND: &str = "EXISTS (
+               SELECT 1 FROM funds f
+               WHERE f.id = $1
+                 AND (
+                     expand_name_aliases(f.scheme_name) = expand_name_aliases(sr.scheme_name)
+                     OR EXISTS (
+                         SELECT 1 FROM scheme_aliases sa
+                         WHERE sa.fund_id = f.id
+                           AND expand_name_aliases(sa.alias_name) = expand_name_aliases(sr.scheme_name)
+                     )
+                 )
+           )";
+
+// The approved rates the virtual table joins to the fund
+pub async fn load_fund_rates(client: &Client, fund_id: i32) -> Result<Vec<RateRecord>, tokio_postgres::Error> {
+    let query = format!(
+        "SELECT {} FROM scheme_rates sr
+         WHERE (sr.is_approved IS NULL OR sr.is_approved = true)
+           AND {}
+         ORDER BY sr.start_date, sr.id",
+        RATE_COLUMNS, JOINS_FUND
+    );
+    Ok(client.query(&query, &[&fund_id]).await?.iter().map(rate_from_row).collect())
+}
+
+// Every rate whose name joins the fund, pending and rejected ones included, by company and then
+// newest first
+pub async fn load_all_fund_rates(client: &Client, fund_id: i32) -> Result<Vec<RateRecord>, tokio_postgres::Error> {
+    let query = format!(
+        "SELECT {} FROM scheme_rates sr
+         WHERE {}
+         ORDER BY sr.company, sr.start_date DESC, sr.id",
+        RATE_COLUMNS, JOINS_FUND
+    );
+    Ok(client.query(&query, &[&fund_id]).await?.iter().map(rate_from_row).collect())
+}
+
+pub async fn load_rate(client: &Client, id: i32) -> Result<Option<RateRecord>, tokio_postgres::Error> {
+    let query = format!("SELECT {} FROM scheme_rates WHERE id = $1", RATE_COLUMNS);
+    Ok(client.query_opt(&query, &[&id]).await?.as_ref().map(rate_from_row))
+}
+
+// The deleted fund, or None when there was no such id. Its scheme_aliases go with it (ON DELETE
+// CASCADE); its rates stay and become unmatched.
+pub async fn delete_fund(client: &Client, id: i32) -> Result<Option<FundRecord>, tokio_postgres::Error> {
+    let query = format!("DELETE FROM funds WHERE id = $1 RETURNING {}", FUND_COLUMNS);
+    Ok(client.query_opt(&query, &[&id]).await?.as_ref().map(fund_from_row))
+}
+
+// Compare-and-set on version: the UPDATE only matches the row the caller last saw
+pub async fn update_fund(
+    client: &Client,
+    id: i32,
+    expected_version: i32,
+    edit: &FundEdit,
+) -> Result<EditOutcome<FundRecord>, tokio_postgres::Error> {
+    let query = format!(
+        "UPDATE funds SET
+            category = $3, scheme_name = $4, launch_date = $5, fund_size_apr25 = $6, fund_size_may25 = $7,
+            latest_nav = $8, month_1 = $9, months_3 = $10, months_6 = $11, ytd = $12, year_1 = $13,
+            years_2 = $14, years_3 = $15, years_5 = $16, version = version + 1
+         WHERE id = $1 AND version = $2
+         RETURNING {}",
+        FUND_COLUMNS
+    );
+    let updated = client
+        .query_opt(
+            &query,
+            &[
+                &id,
+                &expected_version,
+                &edit.category,
+                &edit.scheme_name,
+                &edit.launch_date,
+                &edit.fund_size_apr25,
+                &edit.fund_size_may25,
+                &edit.latest_nav,
+                &edit.month_1,
+                &edit.months_3,
+                &edit.months_6,
+                &edit.ytd,
+                &edit.year_1,
+                &edit.years_2,
+                &edit.years_3,
+                &edit.years_5,
+            ],
+        )
+        .await?;
+
+    Ok(match updated {
+        Some(row) => EditOutcome::Updated(fund_from_row(&row)),
+        None => match load_fund(client, id).await? {
+            Some(current) => EditOutcome::Conflict(current),
+            None => EditOutcome::NotFound,
+        },
+    })
+}
+
+// Compare-and-set like update_fund, writing only the fields the patch sets. Call validate() first.
+pub async fn patch_fund(
+    client: &Client,
+    id: i32,
+    expected_version: i32,
+    patch: &FundPatch,
+) -> Result<EditOutcome<FundRecord>, tokio_postgres::Error> {
+    let assignments = patch.assignments();
+    let mut params: Vec<&(dyn ToSql + Sync)> = vec![&id, &expected_version];
+    let mut sets = Vec::with_capacity(assignments.len());
+    for (column, value) in assignments {
+        params.push(value);
+        sets.push(format!("{} = ${}", column, params.len()));
+    }
+    let query = format!(
+        "UPDATE funds SET {}, version = version + 1
+         WHERE id = $1 AND version = $2
+         RETURNING {}",
+        sets.join(", "),
+        FUND_COLUMNS
+    );
+    let updated = client.query_opt(&query, &params).await?;
+
+    Ok(match updated {
+        Some(row) => EditOutcome::Updated(fund_from_row(&row)),
+        None => match load_fund(client, id).await? {
+            Some(current) => EditOutcome::Conflict(current),
+            None => EditOutcome::NotFound,
+        },
+    })
+}
+
+pub async fn update_rate(
+    client: &Client,
+    id: i32,
+    expected_version: i32,
+    edit: &RateEdit,
+) -> Result<EditOutcome<RateRecord>, tokio_postgres::Error> {
+    let query = format!(
+        "UPDATE scheme_rates SET
+            arn = $3, company = $4, scheme_name = $5, scheme_category = $6, brokerage_type = $7,
+            start_date = $8, end_date = $9, is_approved = $10, base_year_1 = $11, base_year_2 = $12,
+            base_year_3 = $13, version = version + 1
+         WHERE id = $1 AND version = $2
+         RETURNING {}",
+        RATE_COLUMNS
+    );
+    let updated = client
+        .query_opt(
+            &query,
+            &[
+                &id,
+                &expected_version,
+                &edit.arn,
+                &edit.company,
+                &edit.scheme_name,
+                &edit.scheme_category,
+                &edit.brokerage_type,
+                &edit.start_date,
+                &edit.end_date,
+                &edit.is_approved,
+                &edit.base_year_1,
+                &edit.base_year_2,
+                &edit.base_year_3,
+            ],
+        )
+        .await?;
+
+    Ok(match updated {
+        Some(row) => EditOutcome::Updated(rate_from_row(&row)),
+        None => match load_rate(client, id).await? {
+            Some(current) => EditOutcome::Conflict(current),
+            None => EditOutcome::NotFound,
+        },
+    })
+}
+
+// Rates created through the API are recorded with this source_file
+pub const API_SOURCE_FILE: &str = "api";
+
+// `is_approved` false creates the rate pending, see require_rate_approval
+pub async fn insert_rate(client: &Client, rate: &RateRow, is_approved: bool) -> Result<RateRecord, tokio_postgres::Error> {
+    let query = format!(
+        "INSERT INTO scheme_rates (
+            arn, company, scheme_name, scheme_category, brokerage_type, start_date, end_date,
+            source_file, base_year_1, base_year_2, base_year_3, is_approved
+         )
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+         RETURNING {}",
+        RATE_COLUMNS
+    );
+    let row = client
+        .query_one(
+            &query,
+            &[
+                &rate.arn,
+                &rate.company,
+                &rate.scheme_name,
+                &rate.scheme_category,
+                &rate.brokerage_type,
+                &rate.start_date,
+                &rate.end_date,
+                &API_SOURCE_FILE,
+                &rate.base_year_1,
+                &rate.base_year_2,
+                &rate.base_year_3,
+                &is_approved,
+            ],
+        )
+        .await?;
+    Ok(rate_from_row(&row))
+}
+
+// Compare-and-set like update_rate, writing only the fields the patch sets. Call validate() first.
+pub async fn patch_rate(
+    client: &Client,
+    id: i32,
+    expected_version: i32,
+    patch: &RatePatch,
+) -> Result<EditOutcome<RateRecord>, tokio_postgres::Error> {
+    let assignments = patch.assignments();
+    let mut params: Vec<&(dyn ToSql + Sync)> = vec![&id, &expected_version];
+    let mut sets = Vec::with_capacity(assignments.len());
+    for (column, value) in assignments {
+        params.push(value);
+        sets.push(format!("{} = ${}", column, params.len()));
+    }
+    let query = format!(
+        "UPDATE scheme_rates SET {}, version = version + 1
+         WHERE id = $1 AND version = $2
+         RETURNING {}",
+        sets.join(", "),
+        RATE_COLUMNS
+    );
+    let updated = client.query_opt(&query, &params).await?;
+
+    Ok(match updated {
+        Some(row) => EditOutcome::Updated(rate_from_row(&row)),
+        None => match load_rate(client, id).await? {
+            Some(current) => EditOutcome::Conflict(current),
+            None => EditOutcome::NotFound,
+        },
+    })
+}
+
+// Rates waiting for review, oldest first, with the total waiting
+pub async fn list_pending_rates(
+    client: &Client,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<RateRecord>, i64), tokio_postgres::Error> {
+    let query = format!(
+        "SELECT {} FROM scheme_rates
+         WHERE is_approved = false AND rejection_reason IS NULL
+         ORDER BY id
+         LIMIT $1 OFFSET $2",
+        RATE_COLUMNS
+    );
+    let rates = client.query(&query, &[&limit, &offset]).await?;
+    let total: i64 = client
+        .query_one(
+            "SELECT COUNT(*) FROM scheme_rates WHERE is_approved = false AND rejection_reason IS NULL",
+            &[],
+        )
+        .await?
+        .get(0);
+    Ok((rates.iter().map(rate_from_row).collect(), total))
+}
+
+// Approve, or reject with a reason (None approves). Either way the rate leaves the pending list;
+// a rejected rate is kept, not deleted, and can still be approved later.
+pub async fn review_rate(
+    client: &Client,
+    id: i32,
+    rejection_reason: Option<&str>,
+) -> Result<Option<RateRecord>, tokio_postgres::Error> {
+    let query = format!(
+        "UPDATE scheme_rates
+         SET is_approved = $2::TEXT IS NULL, rejection_reason = $2, version = version + 1
+         WHERE id = $1
+         RETURNING {}",
+        RATE_COLUMNS
+    );
+    Ok(client.query_opt(&query, &[&id, &rejection_reason]).await?.as_ref().map(rate_from_row))
+}
+
+pub async fn delete_rate(client: &Client, id: i32) -> Result<Option<RateRecord>, tokio_postgres::Error> {
+    let query = format!("DELETE FROM scheme_rates WHERE id = $1 RETURNING {}", RATE_COLUMNS);
+    Ok(client.query_opt(&query, &[&id]).await?.as_ref().map(rate_from_row))
+}
+
+// Every rate ingested from `source_file` (a rate workbook's name, or API_SOURCE_FILE); the count removed
+pub async fn delete_rates_by_source(client: &Client, source_file: &str) -> Result<u64, tokio_postgres::Error> {
+    client
+        .execute("DELETE FROM scheme_rates WHERE source_file = $1", &[&source_file])
+        .await
+}
+
+// Names of the funds a rate called `rate_scheme_name` joins to, by the virtual table's join
+pub async fn funds_joined_to(client: &Client, rate_scheme_name: &str) -> Result<Vec<String>, tokio_postgres::Error> {
+    let rows = client
+        .query(
+            "SELECT f.scheme_name FROM funds f
+             WHERE expand_name_aliases(f.scheme_name) = expand_name_aliases($1)
+                OR EXISTS (
+                    SELECT 1 FROM scheme_aliases sa
+                    WHERE sa.fund_id = f.id
+                      AND expand_name_aliases(sa.alias_name) = expand_name_aliases($1)
+                )
+             ORDER BY f.scheme_name",
+            &[&rate_scheme_name],
+        )
+        .await?;
+    Ok(rows.iter().map(|row| row.get("scheme_name")).collect())
+}
+
+// Ids of the funds and of the rates written at or after `since`, by their updated_at
+pub async fn changed_since(
+    client: &Client,
+    since: NaiveDateTime,
+) -> Result<(HashSet<i32>, HashSet<i32>), tokio_postgres::Error> {
+    let funds = client.query("SELECT id FROM funds WHERE updated_at >= $1", &[&since]).await?;
+    let rates = client.query("SELECT id FROM scheme_rates WHERE updated_at >= $1", &[&since]).await?;
+    Ok((
+        funds.iter().map(|row| row.get("id")).collect(),
+        rates.iter().map(|row| row.get("id")).collect(),
+    ))
+}
+
+// If-Match carries the version as an entity tag: "3", W/"3" or a bare 3
+pub fn parse_if_match(value: &str) -> Option<i32> {
+    value
+        .trim()
+        .trim_start_matches("W/")
+        .trim_matches('"')
+        .parse()
+        .ok()
+}
+
+pub fn etag(version: i32) -> String {
+    format!("\"{}\"", version)
+}
@@ -0,0 +1,737 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::{normalize_scheme_name, CombinedSchemeData};
+
+// Normalized filterable strings, stored once per distinct value and referred to by id. Categories,
+// companies, brokerage types and ARNs repeat across thousands of records, so this is far smaller than a copy each.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    values: Vec<String>,
+    ids: HashMap<String, u32>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, value: &str) -> u32 {
+        let normalized = normalize_scheme_name(value);
+        if let Some(&id) = self.ids.get(&normalized) {
+            return id;
+        }
+        let id = self.values.len() as u32;
+        self.values.push(normalized.clone());
+        self.ids.insert(normalized, id);
+        id
+    }
+
+    // Id of an already-normalized value, if any record has it
+    pub fn get(&self, normalized: &str) -> Option<u32> {
+        self.ids.get(normalized).copied()
+    }
+}
+
+// Comparison form of an ARN code: lowercase letters and digits only, with a bare number read as
+// "ARN-<number>", so "ARN-12345", "arn 12345" and "12345" are the same code
+pub fn normalize_arn(value: &str) -> String {
+    let compact: String = value
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if !compact.is_empty() && compact.chars().all(|c| c.is_ascii_digit()) {
+        format!("arn{}", compact)
+    } else {
+        compact
+    }
+}
+
+// Whether a search query is an ARN code ("ARN-12345") rather than a scheme name
+pub fn looks_like_arn(query: &str) -> bool {
+    let trimmed = query.trim();
+    match (trimmed.get(..3), trimmed.get(3..)) {
+        (Some(prefix), Some(rest)) if prefix.eq_ignore_ascii_case("arn") => {
+            let digits = rest.trim_start_matches(['-', ' ', '_']);
+            !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+        }
+        _ => false,
+    }
+}
+
+// Interned normalized forms of one record's filterable strings, computed in add_record
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecordKeys {
+    pub category: Option<u32>,
+    pub company: Option<u32>,
+    pub brokerage_type: Option<u32>,
+    pub arn: Option<u32>,
+}
+
+impl RecordKeys {
+    pub fn new(record: &CombinedSchemeData, interner: &mut Interner) -> Self {
+        Self {
+            category: record.fund_category.as_deref().map(|value| interner.intern(value)),
+            company: record.company.as_deref().map(|value| interner.intern(value)),
+            brokerage_type: record.brokerage_type.as_deref().map(|value| interner.intern(value)),
+            arn: record.arn.as_deref().map(|value| interner.intern(&normalize_arn(value))),
+        }
+    }
+}
+
+// Numeric columns of CombinedSchemeData that can be range-filtered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericField {
+    FundSizeApr25,
+    FundSizeMay25,
+    LatestNav,
+    Month1,
+    Months3,
+    Months6,
+    Ytd,
+    Year1,
+    Years2,
+    Years3,
+    Years5,
+}
+
+impl NumericField {
+    pub const ALL: [NumericField; 11] = [
+        NumericField::FundSizeApr25,
+        NumericField::FundSizeMay25,
+        NumericField::LatestNav,
+        NumericField::Month1,
+        NumericField::Months3,
+        NumericField::Months6,
+        NumericField::Ytd,
+        NumericField::Year1,
+        NumericField::Years2,
+        NumericField::Years3,
+        NumericField::Years5,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NumericField::FundSizeApr25 => "fund_size_apr25",
+            NumericField::FundSizeMay25 => "fund_size_may25",
+            NumericField::LatestNav => "latest_nav",
+            NumericField::Month1 => "month_1",
+            NumericField::Months3 => "months_3",
+            NumericField::Months6 => "months_6",
+            NumericField::Ytd => "ytd",
+            NumericField::Year1 => "year_1",
+            NumericField::Years2 => "years_2",
+            NumericField::Years3 => "years_3",
+            NumericField::Years5 => "years_5",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<NumericField> {
+        NumericField::ALL.into_iter().find(|field| field.name() == name)
+    }
+
+    // Fraction of the numeric fields that are populated, 0.0..=1.0
+    pub fn completeness(record: &CombinedSchemeData) -> f32 {
+        let populated = NumericField::ALL.iter().filter(|field| field.value(record).is_some()).count();
+        populated as f32 / NumericField::ALL.len() as f32
+    }
+
+    pub fn missing(record: &CombinedSchemeData) -> Vec<&'static str> {
+        NumericField::ALL
+            .iter()
+            .filter(|field| field.value(record).is_none())
+            .map(|field| field.name())
+            .collect()
+    }
+
+    pub fn value(self, record: &CombinedSchemeData) -> Option<f32> {
+        match self {
+            NumericField::FundSizeApr25 => record.fund_size_apr25,
+            NumericField::FundSizeMay25 => record.fund_size_may25,
+            NumericField::LatestNav => record.latest_nav,
+            NumericField::Month1 => record.month_1,
+            NumericField::Months3 => record.months_3,
+            NumericField::Months6 => record.months_6,
+            NumericField::Ytd => record.ytd,
+            NumericField::Year1 => record.year_1,
+            NumericField::Years2 => record.years_2,
+            NumericField::Years3 => record.years_3,
+            NumericField::Years5 => record.years_5,
+        }
+    }
+}
+
+// Shorter parameter names for range filters: min_fund_size/max_fund_size screen on the latest
+// fund size
+const RANGE_ALIASES: [(&str, NumericField); 1] = [("fund_size", NumericField::FundSizeMay25)];
+
+#[derive(Debug, Clone)]
+pub struct RangeFilter {
+    pub field: NumericField,
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilters {
+    pub ranges: Vec<RangeFilter>,
+    pub has_rates: Option<bool>,
+    // Fund categories (any of them matches) and rate company, resolved to canonical spellings
+    // before searching
+    pub category: Vec<String>,
+    pub company: Option<String>,
+    // Rate brokerage type, compared case-insensitively as given
+    pub brokerage_type: Option<String>,
+    // Distributor ARN code, see normalize_arn
+    pub arn: Option<String>,
+    // Incomplete records are hidden unless asked for
+    pub include_incomplete: bool,
+    // Records whose rate isn't in force are hidden unless asked for. Activeness is the build-time
+    // flag, or judged by the rate's dates on `as_of` when given.
+    pub include_expired: bool,
+    pub as_of: Option<NaiveDate>,
+}
+
+// Why a record failed one filter: a real mismatch, or the field was simply absent
+enum Exclusion {
+    Mismatch,
+    MissingField,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FilterDiagnostic {
+    pub filter: String,
+    pub excluded: usize,
+    pub missing_field: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FilterWarning {
+    pub filter: String,
+    pub message: String,
+}
+
+impl SearchFilters {
+    // Parse min_<field>/max_<field> and has_rates from the raw query map. `pairs` is the query
+    // string in order, for parameters that may repeat (category).
+    pub fn from_query(params: &HashMap<String, String>, pairs: &[(String, String)]) -> Result<Self, String> {
+        let mut filters = SearchFilters::default();
+
+        let names = NumericField::ALL
+            .iter()
+            .map(|field| (field.name(), *field))
+            .chain(RANGE_ALIASES);
+        for (name, field) in names {
+            let min = parse_bound(params, &format!("min_{}", name))?;
+            let max = parse_bound(params, &format!("max_{}", name))?;
+            if min.is_none() && max.is_none() {
+                continue;
+            }
+            if filters.ranges.iter().any(|range| range.field == field) {
+                return Err(format!(
+                    "Parameters min_/max_{} and min_/max_{} filter the same field; use one",
+                    name,
+                    field.name()
+                ));
+            }
+            filters.ranges.push(RangeFilter { field, min, max });
+        }
+
+        if let Some(value) = params.get("has_rates") {
+            filters.has_rates = Some(
+                value
+                    .parse::<bool>()
+                    .map_err(|_| format!("Parameter 'has_rates' must be true or false, got '{}'", value))?,
+            );
+        }
+
+        if let Some(value) = params.get("include_incomplete") {
+            filters.include_incomplete = value.parse::<bool>().map_err(|_| {
+                format!("Parameter 'include_incomplete' must be true or false, got '{}'", value)
+            })?;
+        }
+
+        if let Some(value) = params.get("include_expired") {
+            filters.include_expired = value.parse::<bool>().map_err(|_| {
+                format!("Parameter 'include_expired' must be true or false, got '{}'", value)
+            })?;
+        }
+
+        if let Some(value) = params.get("as_of") {
+            filters.as_of = Some(
+                NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+                    .map_err(|_| format!("Parameter 'as_of' must be a date (YYYY-MM-DD), got '{}'", value))?,
+            );
+        }
+
+        filters.category = pairs
+            .iter()
+            .filter(|(key, _)| key == "category")
+            .map(|(_, value)| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .collect();
+        filters.company = non_empty_param(params, "company");
+        filters.brokerage_type = non_empty_param(params, "brokerage_type");
+        filters.arn = non_empty_param(params, "arn");
+
+        Ok(filters)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+            && self.has_rates.is_none()
+            && self.category.is_empty()
+            && self.company.is_none()
+            && self.brokerage_type.is_none()
+            && self.arn.is_none()
+    }
+
+    // The filters as applied, for echoing back in responses
+    pub fn applied(&self) -> Value {
+        let ranges: serde_json::Map<String, Value> = self
+            .ranges
+            .iter()
+            .map(|range| (range.field.name().to_string(), json!({"min": range.min, "max": range.max})))
+            .collect();
+        json!({
+            "category": self.category,
+            "company": self.company,
+            "brokerage_type": self.brokerage_type,
+            "arn": self.arn,
+            "has_rates": self.has_rates,
+            "include_incomplete": self.include_incomplete,
+            "include_expired": self.include_expired,
+            "as_of": self.as_of,
+            "ranges": ranges
+        })
+    }
+
+    fn labels(&self) -> Vec<String> {
+        let mut labels: Vec<String> = self.ranges.iter().map(|range| range.field.name().to_string()).collect();
+        if self.has_rates.is_some() {
+            labels.push("has_rates".to_string());
+        }
+        if !self.category.is_empty() {
+            labels.push("category".to_string());
+        }
+        if self.company.is_some() {
+            labels.push("company".to_string());
+        }
+        if self.brokerage_type.is_some() {
+            labels.push("brokerage_type".to_string());
+        }
+        if self.arn.is_some() {
+            labels.push("arn".to_string());
+        }
+        labels
+    }
+}
+
+fn non_empty_param(params: &HashMap<String, String>, key: &str) -> Option<String> {
+    params
+        .get(key)
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn parse_bound(params: &HashMap<String, String>, key: &str) -> Result<Option<f32>, String> {
+    match params.get(key) {
+        Some(value) => value
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|bound| bound.is_finite())
+            .map(Some)
+            .ok_or_else(|| format!("Parameter '{}' must be a number, got '{}'", key, value)),
+        None => Ok(None),
+    }
+}
+
+// Evaluates every active filter against every candidate (no short-circuit) so each filter's
+// exclusion counts are exact, then reports filters that mostly exclude records for lack of data.
+pub struct FilterEvaluator<'a> {
+    filters: &'a SearchFilters,
+    normalized_categories: Vec<String>,
+    normalized_company: Option<String>,
+    normalized_brokerage_type: Option<String>,
+    normalized_arn: Option<String>,
+    // Interned ids of the wanted values, set by bind; values no record has are left out
+    category_keys: Vec<u32>,
+    company_key: Option<u32>,
+    brokerage_type_key: Option<u32>,
+    arn_key: Option<u32>,
+    labels: Vec<String>,
+    examined: usize,
+    excluded_incomplete: usize,
+    excluded_expired: usize,
+    excluded: Vec<usize>,
+    missing: Vec<usize>,
+}
+
+impl<'a> FilterEvaluator<'a> {
+    pub fn new(filters: &'a SearchFilters) -> Self {
+        let labels = filters.labels();
+        let count = labels.len();
+        Self {
+            filters,
+            normalized_categories: filters.category.iter().map(|category| normalize_scheme_name(category)).collect(),
+            normalized_company: filters.company.as_deref().map(normalize_scheme_name),
+            normalized_brokerage_type: filters.brokerage_type.as_deref().map(normalize_scheme_name),
+            normalized_arn: filters.arn.as_deref().map(normalize_arn),
+            category_keys: Vec::new(),
+            company_key: None,
+            brokerage_type_key: None,
+            arn_key: None,
+            labels,
+            examined: 0,
+            excluded_incomplete: 0,
+            excluded_expired: 0,
+            excluded: vec![0; count],
+            missing: vec![0; count],
+        }
+    }
+
+    // Resolve the text filters against the table's interner; must precede accepts
+    pub fn bind(&mut self, interner: &Interner) {
+        self.category_keys = self
+            .normalized_categories
+            .iter()
+            .filter_map(|value| interner.get(value))
+            .collect();
+        self.company_key = self.normalized_company.as_deref().and_then(|value| interner.get(value));
+        self.brokerage_type_key = self
+            .normalized_brokerage_type
+            .as_deref()
+            .and_then(|value| interner.get(value));
+        self.arn_key = self.normalized_arn.as_deref().and_then(|value| interner.get(value));
+    }
+
+    pub fn accepts(&mut self, record: &CombinedSchemeData, keys: &RecordKeys) -> bool {
+        self.examined += 1;
+        let filters = self.filters;
+        if record.incomplete && !filters.include_incomplete {
+            self.excluded_incomplete += 1;
+            return false;
+        }
+        if !filters.include_expired {
+            let active = match filters.as_of {
+                Some(date) => record.rate_active_on(date),
+                None => record.is_rate_active,
+            };
+            if !active {
+                self.excluded_expired += 1;
+                return false;
+            }
+        }
+        if filters.is_empty() {
+            return true;
+        }
+
+        let mut accepted = true;
+
+        for (i, range) in filters.ranges.iter().enumerate() {
+            if let Some(exclusion) = check_range(range, record) {
+                self.record(i, exclusion);
+                accepted = false;
+            }
+        }
+
+        if let Some(wanted) = filters.has_rates {
+            if record.rate_id.is_some() != wanted {
+                let i = filters.ranges.len();
+                self.record(i, Exclusion::Mismatch);
+                accepted = false;
+            }
+        }
+
+        let mut i = filters.ranges.len() + usize::from(filters.has_rates.is_some());
+        let text_checks = [
+            (!self.normalized_categories.is_empty(), check_key(&self.category_keys, keys.category)),
+            (self.normalized_company.is_some(), check_key(self.company_key.as_slice(), keys.company)),
+            (
+                self.normalized_brokerage_type.is_some(),
+                check_key(self.brokerage_type_key.as_slice(), keys.brokerage_type),
+            ),
+            (self.normalized_arn.is_some(), check_key(self.arn_key.as_slice(), keys.arn)),
+        ];
+        for (active, exclusion) in text_checks {
+            if !active {
+                continue;
+            }
+            if let Some(exclusion) = exclusion {
+                self.record(i, exclusion);
+                accepted = false;
+            }
+            i += 1;
+        }
+
+        accepted
+    }
+
+    fn record(&mut self, i: usize, exclusion: Exclusion) {
+        self.excluded[i] += 1;
+        if let Exclusion::MissingField = exclusion {
+            self.missing[i] += 1;
+        }
+    }
+
+    pub fn excluded_incomplete(&self) -> usize {
+        self.excluded_incomplete
+    }
+
+    pub fn excluded_expired(&self) -> usize {
+        self.excluded_expired
+    }
+
+    // Per-filter counts plus warnings for filters whose exclusions exceed `warning_ratio` of
+    // the candidates examined purely because the field was unpopulated
+    pub fn finish(self, warning_ratio: f64) -> (Vec<FilterDiagnostic>, Vec<FilterWarning>) {
+        let examined = self.examined;
+        let mut diagnostics = Vec::new();
+        let mut warnings = Vec::new();
+
+        for (i, label) in self.labels.into_iter().enumerate() {
+            let missing = self.missing[i];
+            if examined > 0 && missing > 0 && missing as f64 / examined as f64 > warning_ratio {
+                warnings.push(FilterWarning {
+                    filter: label.clone(),
+                    message: format!(
+                        "{} of {} candidate records have no {} value; consider relaxing this filter",
+                        missing, examined, label
+                    ),
+                });
+            }
+            diagnostics.push(FilterDiagnostic {
+                filter: label,
+                excluded: self.excluded[i],
+                missing_field: missing,
+            });
+        }
+
+        (diagnostics, warnings)
+    }
+}
+
+// Equal ids mean equal normalized strings; matching any wanted id is enough, and with none
+// (no record has the wanted value) nothing matches
+fn check_key(wanted: &[u32], value: Option<u32>) -> Option<Exclusion> {
+    match value {
+        Some(id) if wanted.contains(&id) => None,
+        Some(_) => Some(Exclusion::Mismatch),
+        None => Some(Exclusion::MissingField),
+    }
+}
+
+fn check_range(range: &RangeFilter, record: &CombinedSchemeData) -> Option<Exclusion> {
+    let value = match range.field.value(record) {
+        Some(value) => value,
+        None => return Some(Exclusion::MissingField),
+    };
+
+    let below = range.min.is_some_and(|min| value < min);
+    let above = range.max.is_some_and(|max| value > max);
+    if below || above {
+        Some(Exclusion::Mismatch)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    // Ten funds: three with 5-year history (12, 8, 15) and four with rates, two each from PPFAS
+    // and Quant. Only fund 1 has both a 5-year return of at least 10 and a PPFAS rate.
+    fn sparse_fixture() -> Vec<CombinedSchemeData> {
+        let years_5 = [Some(12.0), Some(8.0), Some(15.0)];
+        let companies = [(0, "PPFAS"), (1, "Quant"), (3, "PPFAS"), (4, "Quant")];
+        (0..10)
+            .map(|n| {
+                let mut record = CombinedSchemeData::test_fund(n as i32 + 1, &format!("Fund {}", n + 1));
+                record.years_5 = years_5.get(n).copied().flatten();
+                if let Some((_, company)) = companies.iter().find(|(index, _)| *index == n) {
+                    record.rate_id = Some(n as i32 + 1);
+                    record.company = Some(Arc::from(*company));
+                }
+                record
+            })
+            .collect()
+    }
+
+    fn filters(query: &[(&str, &str)]) -> SearchFilters {
+        let pairs: Vec<(String, String)> = query.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let params: HashMap<String, String> = pairs.iter().cloned().collect();
+        SearchFilters::from_query(&params, &pairs).unwrap()
+    }
+
+    // Run every record through the evaluator; the accepted funds' ids and the evaluator
+    fn evaluate<'a>(filters: &'a SearchFilters, records: &[CombinedSchemeData]) -> (Vec<i32>, FilterEvaluator<'a>) {
+        let mut interner = Interner::default();
+        let keys: Vec<RecordKeys> = records.iter().map(|record| RecordKeys::new(record, &mut interner)).collect();
+        let mut evaluator = FilterEvaluator::new(filters);
+        evaluator.bind(&interner);
+        let accepted = records
+            .iter()
+            .zip(&keys)
+            .filter(|(record, keys)| evaluator.accepts(record, keys))
+            .filter_map(|(record, _)| record.fund_id)
+            .collect();
+        (accepted, evaluator)
+    }
+
+    fn counts(diagnostics: &[FilterDiagnostic]) -> Vec<(&str, usize, usize)> {
+        diagnostics
+            .iter()
+            .map(|d| (d.filter.as_str(), d.excluded, d.missing_field))
+            .collect()
+    }
+
+    #[test]
+    fn every_filter_counts_its_exclusions_even_after_another_has_failed() {
+        let records = sparse_fixture();
+        let filters = filters(&[("min_years_5", "10"), ("has_rates", "true"), ("company", "PPFAS")]);
+        let (accepted, evaluator) = evaluate(&filters, &records);
+        assert_eq!(accepted, vec![1]);
+
+        let (diagnostics, _) = evaluator.finish(0.5);
+        assert_eq!(
+            counts(&diagnostics),
+            vec![("years_5", 8, 7), ("has_rates", 6, 0), ("company", 8, 6)]
+        );
+    }
+
+    #[test]
+    fn filters_mostly_excluding_on_missing_data_get_a_warning() {
+        let records = sparse_fixture();
+        let filters = filters(&[("min_years_5", "10"), ("has_rates", "true"), ("company", "PPFAS")]);
+
+        let (_, evaluator) = evaluate(&filters, &records);
+        let (_, warnings) = evaluator.finish(0.5);
+        let warned: Vec<&str> = warnings.iter().map(|w| w.filter.as_str()).collect();
+        assert_eq!(warned, vec!["years_5", "company"]);
+        assert_eq!(
+            warnings[0].message,
+            "7 of 10 candidate records have no years_5 value; consider relaxing this filter"
+        );
+
+        let (_, evaluator) = evaluate(&filters, &records);
+        let (_, warnings) = evaluator.finish(0.65);
+        let warned: Vec<&str> = warnings.iter().map(|w| w.filter.as_str()).collect();
+        assert_eq!(warned, vec!["years_5"]);
+    }
+
+    #[test]
+    fn hidden_records_are_counted_apart_from_the_filters() {
+        let mut records = sparse_fixture();
+        records[0].incomplete = true;
+        records[3].is_rate_active = false;
+        let filters = filters(&[("min_years_5", "10")]);
+
+        let (accepted, evaluator) = evaluate(&filters, &records);
+        assert_eq!(accepted, vec![3]);
+        assert_eq!(evaluator.excluded_incomplete(), 1);
+        assert_eq!(evaluator.excluded_expired(), 1);
+        // The two hidden records never reach the range filter
+        let (diagnostics, _) = evaluator.finish(0.5);
+        assert_eq!(counts(&diagnostics), vec![("years_5", 7, 6)]);
+    }
+
+    #[test]
+    fn no_filters_accept_everything_with_no_diagnostics() {
+        let records = sparse_fixture();
+        let filters = SearchFilters::default();
+
+        let (accepted, evaluator) = evaluate(&filters, &records);
+        assert_eq!(accepted.len(), 10);
+        let (diagnostics, warnings) = evaluator.finish(0.0);
+        assert!(diagnostics.is_empty());
+        assert!(warnings.is_empty());
+    }
+
+    // Records spelling the same category, company and ARN several ways
+    fn spelling_fixture() -> Vec<CombinedSchemeData> {
+        let spellings = [
+            ("Flexi Cap", "PPFAS Mutual Fund", "ARN-1010"),
+            ("flexi  cap", "ppfas mutual fund", "arn 1010"),
+            ("FLEXI CAP", "PPFAS Mutual-Fund", "1010"),
+            ("Small Cap", "Quant Mutual Fund", "ARN-2020"),
+            ("SMALL CAP ", "quant mutual fund", "ARN_2020"),
+            ("Large Cap", "HDFC Mutual Fund", "ARN-3030"),
+        ];
+        let mut records: Vec<CombinedSchemeData> = spellings
+            .iter()
+            .enumerate()
+            .map(|(n, (category, company, arn))| {
+                let mut record = CombinedSchemeData::test_fund(n as i32 + 1, &format!("Fund {}", n + 1));
+                record.fund_category = Some(Arc::from(*category));
+                record.company = Some(Arc::from(*company));
+                record.arn = Some(Arc::from(*arn));
+                record.rate_id = Some(n as i32 + 1);
+                record
+            })
+            .collect();
+        // A fund without a rate has no company or ARN at all
+        records.push(CombinedSchemeData::test_fund(7, "Fund 7"));
+        records
+    }
+
+    // What filtering did before keys were interned: normalize both sides per record
+    fn by_strings(filters: &SearchFilters, records: &[CombinedSchemeData]) -> Vec<i32> {
+        let categories: Vec<String> = filters.category.iter().map(|category| normalize_scheme_name(category)).collect();
+        let same = |value: Option<&str>, wanted: Option<&str>, normalize: fn(&str) -> String| match wanted {
+            Some(wanted) => value.map(normalize) == Some(normalize(wanted)),
+            None => true,
+        };
+        records
+            .iter()
+            .filter(|record| {
+                (categories.is_empty()
+                    || record
+                        .fund_category
+                        .as_deref()
+                        .is_some_and(|category| categories.contains(&normalize_scheme_name(category))))
+                    && same(record.company.as_deref(), filters.company.as_deref(), normalize_scheme_name)
+                    && same(record.arn.as_deref(), filters.arn.as_deref(), normalize_arn)
+            })
+            .filter_map(|record| record.fund_id)
+            .collect()
+    }
+
+    #[test]
+    fn interned_keys_filter_exactly_like_normalized_strings() {
+        let records = spelling_fixture();
+        let queries: [&[(&str, &str)]; 8] = [
+            &[("category", "flexi cap")],
+            &[("category", "FLEXI-CAP"), ("category", "small cap")],
+            &[("company", "ppfas  MUTUAL fund")],
+            &[("arn", "1010")],
+            &[("arn", "ARN 2020"), ("category", "Small-Cap")],
+            &[("category", "Mid Cap")],
+            &[("company", "Unknown AMC")],
+            &[("category", "large cap"), ("company", "hdfc mutual fund"), ("arn", "arn-3030")],
+        ];
+        for query in queries {
+            let filters = filters(query);
+            let (accepted, _) = evaluate(&filters, &records);
+            assert_eq!(accepted, by_strings(&filters, &records), "{:?}", query);
+        }
+
+        let (accepted, _) = evaluate(&filters(&[("category", "flexi cap")]), &records);
+        assert_eq!(accepted, vec![1, 2, 3]);
+        let (accepted, _) = evaluate(&filters(&[("arn", "ARN 2020"), ("category", "small  cap")]), &records);
+        assert_eq!(accepted, vec![4, 5]);
+    }
+
+    #[test]
+    fn equal_normalized_values_share_one_interned_id() {
+        let mut interner = Interner::default();
+        let ids: Vec<u32> = ["Flexi Cap", "flexi  cap", "FLEXI CAP ", "Small Cap"]
+            .iter()
+            .map(|value| interner.intern(value))
+            .collect();
+        assert_eq!(ids, vec![0, 0, 0, 1]);
+        assert_eq!(interner.get(&normalize_scheme_name("FLEXI CAP")), Some(0));
+        assert_eq!(interner.get("mid cap"), None);
+    }
+}
@@ -0,0 +1,167 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::Serialize;
+use std::collections::HashMap;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{GenericClient, Row, Transaction};
+
+use crate::CombinedSchemeData;
+
+// Fund values as each upload wrote them, recorded in fund_history by the funds_history trigger
+// while an upload's transaction is open. Only what uploads write is kept: manual edits, category
+// reassignments and rates are not, and a fund has no history before the first upload after
+// migration 7. Uploads are ordered by id.
+pub const AS_OF_UPLOAD_ID: &str = "as_of_upload_id";
+pub const AS_OF_DATE: &str = "as_of_date";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsOf {
+    // Right after this upload committed
+    Upload(i32),
+    // At the end of this day, by upload time
+    Date(NaiveDate),
+}
+
+impl AsOf {
+    pub fn from_query(query: &HashMap<String, String>) -> Result<Option<AsOf>, String> {
+        match (query.get(AS_OF_UPLOAD_ID), query.get(AS_OF_DATE)) {
+            (None, None) => Ok(None),
+            (Some(_), Some(_)) => Err(format!("Give either '{}' or '{}', not both", AS_OF_UPLOAD_ID, AS_OF_DATE)),
+            (Some(value), None) => match value.parse::<i32>() {
+                Ok(id) if id > 0 => Ok(Some(AsOf::Upload(id))),
+                _ => Err(format!("Parameter '{}' must be a positive upload id, got '{}'", AS_OF_UPLOAD_ID, value)),
+            },
+            (None, Some(value)) => match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+                Ok(date) => Ok(Some(AsOf::Date(date))),
+                Err(_) => Err(format!("Parameter '{}' must be a YYYY-MM-DD date, got '{}'", AS_OF_DATE, value)),
+            },
+        }
+    }
+
+    // The meta entry naming the point asked for
+    pub fn meta(&self) -> (&'static str, serde_json::Value) {
+        match self {
+            AsOf::Upload(id) => (AS_OF_UPLOAD_ID, serde_json::json!(id)),
+            AsOf::Date(date) => (AS_OF_DATE, serde_json::json!(date)),
+        }
+    }
+}
+
+// A fund as one upload left it
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoricalFund {
+    pub id: i32,
+    pub upload_id: i32,
+    pub uploaded_at: Option<NaiveDateTime>,
+    pub category: String,
+    pub scheme_name: String,
+    pub launch_date: Option<NaiveDate>,
+    pub fund_size_apr25: Option<f32>,
+    pub fund_size_may25: Option<f32>,
+    pub latest_nav: Option<f32>,
+    pub month_1: Option<f32>,
+    pub months_3: Option<f32>,
+    pub months_6: Option<f32>,
+    pub ytd: Option<f32>,
+    pub year_1: Option<f32>,
+    pub years_2: Option<f32>,
+    pub years_3: Option<f32>,
+    pub years_5: Option<f32>,
+}
+
+impl HistoricalFund {
+    fn from_row(row: &Row) -> Self {
+        HistoricalFund {
+            id: row.get("fund_id"),
+            upload_id: row.get("upload_id"),
+            uploaded_at: row.get("uploaded_at"),
+            category: row.get("category"),
+            scheme_name: row.get("scheme_name"),
+            launch_date: row.get("launch_date"),
+            fund_size_apr25: row.get("fund_size_apr25"),
+            fund_size_may25: row.get("fund_size_may25"),
+            latest_nav: row.get("latest_nav"),
+            month_1: row.get("month_1"),
+            months_3: row.get("months_3"),
+            months_6: row.get("months_6"),
+            ytd: row.get("ytd"),
+            year_1: row.get("year_1"),
+            years_2: row.get("years_2"),
+            years_3: row.get("years_3"),
+            years_5: row.get("years_5"),
+        }
+    }
+
+    // The fund fields of a served record replaced by these; rate fields are left as served
+    pub fn apply(&self, record: &mut CombinedSchemeData) {
+        record.fund_category = Some(self.category.as_str().into());
+        record.launch_date = self.launch_date;
+        record.fund_size_apr25 = self.fund_size_apr25;
+        record.fund_size_may25 = self.fund_size_may25;
+        record.latest_nav = self.latest_nav;
+        record.month_1 = self.month_1;
+        record.months_3 = self.months_3;
+        record.months_6 = self.months_6;
+        record.ytd = self.ytd;
+        record.year_1 = self.year_1;
+        record.years_2 = self.years_2;
+        record.years_3 = self.years_3;
+        record.years_5 = self.years_5;
+    }
+}
+
+// Has the funds_history trigger record every fund row written until the transaction ends under upload_id
+pub async fn record_writes(transaction: &Transaction<'_>, upload_id: i32) -> Result<(), tokio_postgres::Error> {
+    transaction
+        .execute("SELECT set_config('perftracker.upload_id', $1, true)", &[&upload_id.to_string()])
+        .await?;
+    Ok(())
+}
+
+// Each fund's latest history entry at or before as_of; funds with none are left out
+pub async fn funds_as_of<C: GenericClient>(
+    client: &C,
+    fund_ids: &[i32],
+    as_of: AsOf,
+) -> Result<HashMap<i32, HistoricalFund>, tokio_postgres::Error> {
+    let (condition, point): (&str, &(dyn ToSql + Sync)) = match &as_of {
+        AsOf::Upload(id) => ("h.upload_id <= $2", id),
+        AsOf::Date(date) => ("u.uploaded_at < $2::DATE + 1", date),
+    };
+    let query = format!(
+        "SELECT DISTINCT ON (h.fund_id) h.*, u.uploaded_at
+         FROM fund_history h JOIN uploads u ON u.id = h.upload_id
+         WHERE h.fund_id = ANY($1) AND {}
+         ORDER BY h.fund_id, h.upload_id DESC",
+        condition
+    );
+    let rows = client.query(&query, &[&fund_ids, point]).await?;
+    Ok(rows.iter().map(HistoricalFund::from_row).map(|fund| (fund.id, fund)).collect())
+}
+
+// Fund fields of records whose fund has no history at as_of are cleared rather than left current
+pub fn reconstruct(records: &mut [CombinedSchemeData], history: &HashMap<i32, HistoricalFund>) {
+    for record in records.iter_mut() {
+        let fund_id = match record.fund_id {
+            Some(fund_id) => fund_id,
+            None => continue,
+        };
+        match history.get(&fund_id) {
+            Some(fund) => fund.apply(record),
+            None => {
+                record.fund_category = None;
+                record.launch_date = None;
+                record.fund_size_apr25 = None;
+                record.fund_size_may25 = None;
+                record.latest_nav = None;
+                record.month_1 = None;
+                record.months_3 = None;
+                record.months_6 = None;
+                record.ytd = None;
+                record.year_1 = None;
+                record.years_2 = None;
+                record.years_3 = None;
+                record.years_5 = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use log::{error, info};
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+
+use crate::{api_error, process_excel_file, providers, AppState, UploadSource};
+
+// Exit codes of `perftracker import`, for cron jobs
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+// Some rows were not applied, but no more than --fail-on-unmatched-threshold of them
+pub const EXIT_PARTIAL: i32 = 2;
+
+// Server-wide flags that may appear alongside the import flags
+const GLOBAL_FLAGS: [&str; 7] = [
+    "--bind-addr",
+    "--port",
+    "--workers",
+    "--tls-cert-path",
+    "--tls-key-path",
+    "--refresh-interval-secs",
+    "--reset-db",
+];
+
+#[derive(Debug, Clone)]
+pub struct ImportArgs {
+    pub file: PathBuf,
+    pub provider: Option<String>,
+    pub summary_json: Option<PathBuf>,
+    // Fraction of rows (0..=1) that may go unapplied before partial becomes failure; unset means never
+    pub fail_on_unmatched_threshold: Option<f64>,
+}
+
+// Ok(None) unless the first argument is `import`
+pub fn parse_args(args: &[String]) -> Result<Option<ImportArgs>, String> {
+    if args.first().map(String::as_str) != Some("import") {
+        return Ok(None);
+    }
+
+    let mut file = None;
+    let mut provider = None;
+    let mut summary_json = None;
+    let mut threshold = None;
+
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        if flag == "--reset-db" {
+            continue;
+        }
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| rest.next().cloned())
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        match flag {
+            "--file" => file = Some(PathBuf::from(value()?)),
+            "--provider" => provider = Some(value()?),
+            "--summary-json" => summary_json = Some(PathBuf::from(value()?)),
+            "--fail-on-unmatched-threshold" => {
+                let raw = value()?;
+                match raw.trim().parse::<f64>() {
+                    Ok(parsed) if (0.0..=1.0).contains(&parsed) => threshold = Some(parsed),
+                    _ => {
+                        return Err(format!(
+                            "--fail-on-unmatched-threshold must be between 0 and 1, got '{}'",
+                            raw
+                        ))
+                    }
+                }
+            }
+            _ if GLOBAL_FLAGS.contains(&flag) => {
+                value()?;
+            }
+            _ => return Err(format!("unknown import argument '{}'", arg)),
+        }
+    }
+
+    Ok(Some(ImportArgs {
+        file: file.ok_or("import needs --file <path>")?,
+        provider,
+        summary_json,
+        fail_on_unmatched_threshold: threshold,
+    }))
+}
+
+// Run one import through the same pipeline as POST /upload and return the process exit code.
+// The running server picks the new data up on its next refresh.
+pub async fn run(state: &AppState, args: &ImportArgs) -> i32 {
+    let file_name = args.file.file_name().map(|name| name.to_string_lossy().to_string());
+    let source = UploadSource {
+        provider: providers::provider_key(args.provider.as_deref(), file_name.as_deref()),
+        file_name,
+    };
+    let config = state.runtime_config.load_full();
+
+    let context = api_error::RequestContext::detached();
+    info!("Importing {} as request {}", args.file.display(), context.request_id);
+    let rows_processed = AtomicUsize::new(0);
+    let import = process_excel_file(&args.file, &source, &config, state.pools.primary(), &context, &rows_processed);
+    let (code, summary) = match api_error::with_request_id(context.request_id.clone(), import).await {
+        Ok(report) => {
+            let attempted = report.processed + report.failed;
+            let unapplied = if attempted == 0 {
+                0.0
+            } else {
+                report.failed as f64 / attempted as f64
+            };
+            let (code, outcome) = if !report.committed {
+                (EXIT_FAILURE, "failure")
+            } else if report.failed == 0 && report.sheet_errors.is_empty() {
+                (EXIT_SUCCESS, "success")
+            } else if args.fail_on_unmatched_threshold.is_some_and(|threshold| unapplied > threshold) {
+                (EXIT_FAILURE, "failure")
+            } else {
+                (EXIT_PARTIAL, "partial")
+            };
+            let message = match &report.rollback_reason {
+                Some(reason) => format!("{}: rolled back, {}", outcome, reason),
+                None => format!(
+                    "{}: {} fund records applied, {} rows not applied ({:.1}%)",
+                    outcome,
+                    report.processed,
+                    report.failed,
+                    unapplied * 100.0
+                ),
+            };
+            let status = if code == EXIT_SUCCESS { "success" } else { "error" };
+            (
+                code,
+                json!({"status": status, "outcome": outcome, "message": message, "report": report}),
+            )
+        }
+        Err(e) => (
+            EXIT_FAILURE,
+            json!({"status": "error", "outcome": "failure", "message": format!("Error processing file: {}", e)}),
+        ),
+    };
+
+    if code == EXIT_SUCCESS {
+        info!("{}", summary["message"]);
+    } else {
+        error!("{}", summary["message"]);
+    }
+
+    if let Some(path) = &args.summary_json {
+        let written = serde_json::to_vec_pretty(&summary)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| crate::artifacts::write_atomically(path, &bytes).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            error!("Failed to write summary to {}: {}", path.display(), e);
+            return EXIT_FAILURE;
+        }
+    }
+
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_support;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn import_arguments_parse_alongside_server_flags() {
+        assert!(parse_args(&args("--port 8080")).unwrap().is_none());
+        assert!(parse_args(&[]).unwrap().is_none());
+
+        let parsed = parse_args(&args(
+            "import --file funds.csv --port 8080 --reset-db --provider=hdfc --summary-json out.json --fail-on-unmatched-threshold 0.1",
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(parsed.file, PathBuf::from("funds.csv"));
+        assert_eq!(parsed.provider.as_deref(), Some("hdfc"));
+        assert_eq!(parsed.summary_json, Some(PathBuf::from("out.json")));
+        assert_eq!(parsed.fail_on_unmatched_threshold, Some(0.1));
+    }
+
+    #[test]
+    fn bad_import_arguments_are_reported() {
+        let cases = [
+            ("import", "import needs --file <path>"),
+            ("import --file", "--file needs a value"),
+            ("import --file a.csv --verbose", "unknown import argument '--verbose'"),
+            (
+                "import --file a.csv --fail-on-unmatched-threshold 1.5",
+                "--fail-on-unmatched-threshold must be between 0 and 1, got '1.5'",
+            ),
+        ];
+        for (line, message) in cases {
+            assert_eq!(parse_args(&args(line)).unwrap_err(), message, "{}", line);
+        }
+    }
+
+    // One row of four has no launch date and is not applied
+    const PARTIAL_CSV: &str = "Scheme Name,Launch Date,Latest NAV
+Parag Parikh Flexi Cap Fund,2013-05-24,80
+Quant Flexi Cap Fund,2008-10-17,90
+HDFC Flexi Cap Fund,1995-01-01,1800
+Axis Flexi Cap Fund,,22
+";
+
+    struct Import {
+        code: i32,
+        summary: serde_json::Value,
+    }
+
+    async fn import(state: &AppState, csv: &str, threshold: Option<f64>) -> Import {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("Flexi Cap.csv");
+        std::fs::write(&file, csv).unwrap();
+        let summary_json = dir.path().join("summary.json");
+        let args = ImportArgs {
+            file,
+            provider: None,
+            summary_json: Some(summary_json.clone()),
+            fail_on_unmatched_threshold: threshold,
+        };
+        let code = run(state, &args).await;
+        let summary = serde_json::from_slice(&std::fs::read(&summary_json).unwrap()).unwrap();
+        Import { code, summary }
+    }
+
+    #[actix_web::test]
+    async fn a_clean_import_succeeds_and_writes_its_summary() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let csv = PARTIAL_CSV.replace("Axis Flexi Cap Fund,,22", "Axis Flexi Cap Fund,2013-01-01,22");
+        let import = import(&db.state, &csv, None).await;
+        assert_eq!(import.code, EXIT_SUCCESS);
+        assert_eq!(import.summary["status"], "success");
+        assert_eq!(import.summary["outcome"], "success");
+        assert_eq!(import.summary["report"]["inserted"], 4);
+        assert_eq!(import.summary["report"]["provider"], "flexi-cap");
+        assert_eq!(db.query("SELECT id FROM funds").await.len(), 4);
+    }
+
+    #[actix_web::test]
+    async fn unapplied_rows_are_partial_until_they_pass_the_threshold() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let import_with = |threshold| import(&db.state, PARTIAL_CSV, threshold);
+
+        let partial = import_with(None).await;
+        assert_eq!(partial.code, EXIT_PARTIAL, "{}", partial.summary);
+        assert_eq!(partial.summary["outcome"], "partial");
+        assert_eq!(
+            partial.summary["message"],
+            "partial: 3 fund records applied, 1 rows not applied (25.0%)"
+        );
+
+        assert_eq!(import_with(Some(0.5)).await.code, EXIT_PARTIAL);
+        let failure = import_with(Some(0.2)).await;
+        assert_eq!(failure.code, EXIT_FAILURE);
+        assert_eq!(failure.summary["status"], "error");
+        assert_eq!(failure.summary["outcome"], "failure");
+    }
+
+    #[actix_web::test]
+    async fn sheet_errors_are_partial_and_a_missing_file_fails() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let headerless = import(&db.state, "Parag Parikh Flexi Cap Fund,2013-05-24,80\n", None).await;
+        assert_eq!(headerless.code, EXIT_PARTIAL, "{}", headerless.summary);
+        assert_eq!(headerless.summary["report"]["sheet_errors"][0]["sheet"], "Flexi Cap");
+
+        let args = ImportArgs {
+            file: PathBuf::from("/nonexistent/Flexi Cap.csv"),
+            provider: None,
+            summary_json: None,
+            fail_on_unmatched_threshold: None,
+        };
+        assert_eq!(run(&db.state, &args).await, EXIT_FAILURE);
+        assert!(db.query("SELECT id FROM funds").await.is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+
+// Finished jobs stay listed this long so clients polling late still get the summary
+const JOB_RETENTION_SECS: i64 = 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Processing,
+    Done,
+    Failed,
+}
+
+#[derive(Debug)]
+struct Job {
+    state: JobState,
+    files: Vec<String>,
+    created_at: DateTime<Utc>,
+    finished_at: Option<DateTime<Utc>>,
+    // Shared with the upload pipeline, which bumps it as rows are applied
+    rows_processed: Arc<AtomicUsize>,
+    summary: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct JobStatus {
+    pub id: u64,
+    pub state: JobState,
+    pub files: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub rows_processed: usize,
+    // The response body a synchronous upload would have returned; set once the job ends
+    #[schema(value_type = Option<Object>)]
+    pub summary: Option<serde_json::Value>,
+}
+
+// Background upload jobs, in memory only: a restart forgets them (and rolls back any running one)
+#[derive(Debug, Default)]
+pub struct JobRegistry {
+    next_id: AtomicU64,
+    jobs: Mutex<HashMap<u64, Job>>,
+}
+
+impl JobRegistry {
+    // Returns the job id and the row counter to hand to the pipeline
+    pub fn create(&self, files: Vec<String>) -> (u64, Arc<AtomicUsize>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let rows_processed = Arc::new(AtomicUsize::new(0));
+
+        let mut jobs = self.jobs.lock().unwrap();
+        prune(&mut jobs);
+        jobs.insert(
+            id,
+            Job {
+                state: JobState::Queued,
+                files,
+                created_at: Utc::now(),
+                finished_at: None,
+                rows_processed: Arc::clone(&rows_processed),
+                summary: None,
+            },
+        );
+        (id, rows_processed)
+    }
+
+    pub fn start(&self, id: u64) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(&id) {
+            job.state = JobState::Processing;
+        }
+    }
+
+    pub fn finish(&self, id: u64, succeeded: bool, summary: serde_json::Value) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(&id) {
+            job.state = if succeeded { JobState::Done } else { JobState::Failed };
+            job.finished_at = Some(Utc::now());
+            job.summary = Some(summary);
+        }
+    }
+
+    pub fn status(&self, id: u64) -> Option<JobStatus> {
+        let mut jobs = self.jobs.lock().unwrap();
+        prune(&mut jobs);
+        jobs.get(&id).map(|job| JobStatus {
+            id,
+            state: job.state,
+            files: job.files.clone(),
+            created_at: job.created_at,
+            finished_at: job.finished_at,
+            rows_processed: job.rows_processed.load(Ordering::Relaxed),
+            summary: job.summary.clone(),
+        })
+    }
+}
+
+fn prune(jobs: &mut HashMap<u64, Job>) {
+    let cutoff = Utc::now() - chrono::Duration::seconds(JOB_RETENTION_SECS);
+    jobs.retain(|_, job| job.finished_at.is_none_or(|finished| finished > cutoff));
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_support;
+
+    #[test]
+    fn a_job_moves_from_queued_through_processing_to_done_with_its_summary() {
+        let jobs = JobRegistry::default();
+        let (id, rows_processed) = jobs.create(vec!["Flexi Cap.xlsx".to_string()]);
+        let status = jobs.status(id).unwrap();
+        assert_eq!((status.state, status.finished_at, status.summary), (JobState::Queued, None, None));
+        assert_eq!(status.files, ["Flexi Cap.xlsx"]);
+
+        jobs.start(id);
+        rows_processed.fetch_add(40, Ordering::Relaxed);
+        let status = jobs.status(id).unwrap();
+        assert_eq!((status.state, status.rows_processed, status.finished_at), (JobState::Processing, 40, None));
+
+        jobs.finish(id, true, json!({"status": "success"}));
+        let status = jobs.status(id).unwrap();
+        assert_eq!((status.state, status.summary), (JobState::Done, Some(json!({"status": "success"}))));
+        assert!(status.finished_at.unwrap() >= status.created_at);
+        // Ids are never reused
+        assert_eq!(jobs.create(vec![]).0, id + 1);
+    }
+
+    #[test]
+    fn a_failed_job_keeps_its_error_until_it_expires() {
+        let jobs = JobRegistry::default();
+        let (id, _) = jobs.create(vec!["Mid Cap.xlsx".to_string()]);
+        jobs.start(id);
+        jobs.finish(id, false, json!({"status": "error", "code": "database_error"}));
+
+        let status = jobs.status(id).unwrap();
+        assert_eq!(status.state, JobState::Failed);
+        assert_eq!(status.summary.unwrap()["code"], "database_error");
+        assert!(status.finished_at.is_some());
+
+        // Finished jobs are dropped after the retention period; running ones never are
+        let (running, _) = jobs.create(vec![]);
+        jobs.jobs.lock().unwrap().values_mut().for_each(|job| {
+            job.created_at -= chrono::Duration::seconds(JOB_RETENTION_SECS + 1);
+            if let Some(finished_at) = job.finished_at.as_mut() {
+                *finished_at -= chrono::Duration::seconds(JOB_RETENTION_SECS + 1);
+            }
+        });
+        assert!(jobs.status(id).is_none());
+        assert_eq!(jobs.status(running).map(|status| status.state), Some(JobState::Queued));
+    }
+
+    #[actix_web::test]
+    async fn polling_an_unknown_job_is_not_found_and_a_bad_id_is_rejected() {
+        let state = test_support::state(test_support::runtime_config(), vec![]);
+        // Ending a job that isn't there is a no-op rather than a panic
+        state.jobs.start(7);
+        state.jobs.finish(7, true, json!({}));
+        assert!(state.jobs.status(7).is_none());
+
+        let (status, body) = test_support::call_json(&state, TestRequest::get().uri("/api/v1/jobs/7")).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("not_found")), "{}", body);
+        let (status, body) = test_support::call_json(&state, TestRequest::get().uri("/api/v1/jobs/latest")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+
+        let (id, _) = state.jobs.create(vec!["Flexi Cap.xlsx".to_string()]);
+        let request = TestRequest::get().uri(&format!("/api/v1/jobs/{}", id));
+        let (status, body) = test_support::call_json(&state, request).await;
+        assert_eq!((status, &body["state"]), (StatusCode::OK, &json!("queued")), "{}", body);
+        assert_eq!(body["files"], json!(["Flexi Cap.xlsx"]));
+    }
+}
@@ -0,0 +1,309 @@
+use arc_swap::ArcSwap;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{errors::ErrorKind, Algorithm, DecodingKey, Validation};
+use log::{info, warn};
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::api_error::{ApiError, ErrorCode};
+use crate::config::RuntimeConfig;
+
+// How often the JWKS is re-read even when every token's kid is already known
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(3600);
+// A token with an unknown kid refetches the JWKS, but no more often than this
+const JWKS_MIN_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+const JWKS_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Bearer token validation: HS256 with a shared secret, or RS256 against the portal's JWKS. Exactly
+// one of hs256_secret and jwks_url is set.
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JwtConfig {
+    #[serde(default)]
+    hs256_secret: Option<String>,
+    #[serde(default)]
+    pub jwks_url: Option<String>,
+    // Checked against the iss / aud claims when set
+    #[serde(default)]
+    pub issuer: Option<String>,
+    #[serde(default)]
+    pub audience: Option<String>,
+}
+
+impl JwtConfig {
+    pub fn set_hs256_secret(&mut self, secret: Option<String>) {
+        self.hs256_secret = secret;
+    }
+
+    pub fn hs256_secret_len(&self) -> Option<usize> {
+        self.hs256_secret.as_ref().map(String::len)
+    }
+
+    // For logs and config diffs, which never show the secret
+    pub fn describe(&self) -> String {
+        let mode = match (&self.hs256_secret, &self.jwks_url) {
+            (Some(_), _) => "HS256 shared secret".to_string(),
+            (None, Some(url)) => format!("RS256 keys from {}", url),
+            (None, None) => "no signing key".to_string(),
+        };
+        match (&self.issuer, &self.audience) {
+            (None, None) => mode,
+            (issuer, audience) => format!("{} (issuer {:?}, audience {:?})", mode, issuer, audience),
+        }
+    }
+}
+
+impl std::fmt::Debug for JwtConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.describe())
+    }
+}
+
+// The claims we read; `role` is one of viewer, uploader or admin
+#[derive(Debug, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+// The last JWKS fetched, with the URL it came from so a reload pointing elsewhere isn't served
+// stale keys
+#[derive(Debug)]
+pub struct JwksCache {
+    keys: ArcSwap<Option<(String, JwkSet)>>,
+    last_attempt: Mutex<Option<Instant>>,
+    client: reqwest::Client,
+}
+
+impl Default for JwksCache {
+    fn default() -> Self {
+        Self {
+            keys: ArcSwap::from_pointee(None),
+            last_attempt: Mutex::new(None),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+impl JwksCache {
+    fn find(&self, url: &str, kid: &str) -> Option<jsonwebtoken::jwk::Jwk> {
+        match self.keys.load().as_ref() {
+            Some((fetched_from, keys)) if fetched_from == url => keys.find(kid).cloned(),
+            _ => None,
+        }
+    }
+
+    async fn fetch(&self, url: &str) {
+        *self.last_attempt.lock().unwrap() = Some(Instant::now());
+        let result = async {
+            self.client
+                .get(url)
+                .timeout(JWKS_FETCH_TIMEOUT)
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<JwkSet>()
+                .await
+        }
+        .await;
+
+        match result {
+            Ok(keys) => {
+                info!("Fetched {} signing keys from {}", keys.keys.len(), url);
+                self.keys.store(Arc::new(Some((url.to_string(), keys))));
+            }
+            Err(e) => warn!("Failed to fetch signing keys from {}: {}", url, e),
+        }
+    }
+
+    // An unknown kid usually means the portal rotated its keys
+    fn refetch_soon(self: &Arc<Self>, url: &str) {
+        let due = match *self.last_attempt.lock().unwrap() {
+            Some(attempt) => attempt.elapsed() >= JWKS_MIN_REFETCH_INTERVAL,
+            None => true,
+        };
+        if due {
+            let cache = Arc::clone(self);
+            let url = url.to_string();
+            actix_web::rt::spawn(async move { cache.fetch(&url).await });
+        }
+    }
+}
+
+// Keeps the JWKS of whatever jwks_url the current config names, re-reading it every hour
+pub fn spawn_jwks_refresh(config: Arc<ArcSwap<RuntimeConfig>>, cache: Arc<JwksCache>) {
+    actix_web::rt::spawn(async move {
+        loop {
+            let url = config.load().jwt.as_ref().and_then(|jwt| jwt.jwks_url.clone());
+            if let Some(url) = url {
+                cache.fetch(&url).await;
+            }
+            tokio::time::sleep(JWKS_REFRESH_INTERVAL).await;
+        }
+    });
+}
+
+fn invalid_token(message: impl Into<String>) -> ApiError {
+    ApiError::new(ErrorCode::InvalidToken, message)
+}
+
+// Signature, expiry and (when configured) issuer and audience; an expired token is reported
+// separately so clients know to fetch a new one rather than give up
+pub fn validate(token: &str, config: &JwtConfig, jwks: &Arc<JwksCache>) -> Result<Claims, ApiError> {
+    let header = jsonwebtoken::decode_header(token).map_err(|e| invalid_token(format!("Malformed bearer token: {}", e)))?;
+
+    let (key, algorithm) = match (&config.hs256_secret, &config.jwks_url) {
+        (Some(secret), _) => (DecodingKey::from_secret(secret.as_bytes()), Algorithm::HS256),
+        (None, Some(url)) => {
+            let kid = match &header.kid {
+                Some(kid) => kid,
+                None => return Err(invalid_token("Bearer token has no kid")),
+            };
+            let jwk = match jwks.find(url, kid) {
+                Some(jwk) => jwk,
+                None => {
+                    jwks.refetch_soon(url);
+                    return Err(invalid_token(format!("Bearer token signed with unknown key '{}'", kid)));
+                }
+            };
+            let key = DecodingKey::from_jwk(&jwk)
+                .map_err(|e| invalid_token(format!("Signing key '{}' is unusable: {}", kid, e)))?;
+            (key, Algorithm::RS256)
+        }
+        (None, None) => return Err(invalid_token("Bearer tokens are not accepted")),
+    };
+    if header.alg != algorithm {
+        return Err(invalid_token(format!("Bearer token must be signed with {:?}", algorithm)));
+    }
+
+    let mut validation = Validation::new(algorithm);
+    validation.set_required_spec_claims(&["exp", "sub"]);
+    if let Some(issuer) = &config.issuer {
+        validation.set_issuer(&[issuer]);
+    }
+    match &config.audience {
+        Some(audience) => validation.set_audience(&[audience]),
+        None => validation.validate_aud = false,
+    }
+
+    match jsonwebtoken::decode::<Claims>(token, &key, &validation) {
+        Ok(data) => Ok(data.claims),
+        Err(e) => match e.kind() {
+            ErrorKind::ExpiredSignature => Err(ApiError::new(ErrorCode::TokenExpired, "Bearer token has expired")),
+            _ => Err(invalid_token(format!("Invalid bearer token: {}", e))),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::{header, StatusCode};
+    use actix_web::test::TestRequest;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::{json, Value};
+
+    use crate::test_support;
+
+    const SECRET: &str = "portal-shared-secret-0123456789abcdef";
+
+    fn config() -> JwtConfig {
+        let mut config = JwtConfig {
+            issuer: Some("portal".to_string()),
+            ..JwtConfig::default()
+        };
+        config.set_hs256_secret(Some(SECRET.to_string()));
+        config
+    }
+
+    fn token(claims: Value, algorithm: Algorithm, secret: &str) -> String {
+        jsonwebtoken::encode(&Header::new(algorithm), &claims, &EncodingKey::from_secret(secret.as_bytes())).unwrap()
+    }
+
+    fn in_minutes(minutes: i64) -> i64 {
+        chrono::Utc::now().timestamp() + minutes * 60
+    }
+
+    // A token from the portal for `sub` with `role`, valid for the next ten minutes
+    fn portal_token(sub: &str, role: &str) -> String {
+        let claims = json!({"sub": sub, "role": role, "iss": "portal", "exp": in_minutes(10)});
+        token(claims, Algorithm::HS256, SECRET)
+    }
+
+    fn code(result: Result<Claims, ApiError>) -> Value {
+        result.expect_err("token is refused").body()["code"].clone()
+    }
+
+    #[test]
+    fn a_valid_token_yields_its_subject_and_role() {
+        let claims = validate(&portal_token("asha", "uploader"), &config(), &Arc::default()).unwrap();
+        assert_eq!((claims.sub.as_str(), claims.role.as_deref()), ("asha", Some("uploader")));
+    }
+
+    #[test]
+    fn expired_and_invalid_tokens_get_distinct_codes() {
+        let jwks = Arc::default();
+        let expired = json!({"sub": "asha", "role": "admin", "iss": "portal", "exp": in_minutes(-10)});
+        assert_eq!(code(validate(&token(expired, Algorithm::HS256, SECRET), &config(), &jwks)), "token_expired");
+
+        let other_issuer = json!({"sub": "asha", "iss": "elsewhere", "exp": in_minutes(10)});
+        let no_expiry = json!({"sub": "asha", "iss": "portal"});
+        for bad in [
+            "not.a.token".to_string(),
+            "garbage".to_string(),
+            token(json!({"sub": "asha", "iss": "portal", "exp": in_minutes(10)}), Algorithm::HS256, "some-other-secret"),
+            token(json!({"sub": "asha", "iss": "portal", "exp": in_minutes(10)}), Algorithm::HS384, SECRET),
+            token(other_issuer, Algorithm::HS256, SECRET),
+            token(no_expiry, Algorithm::HS256, SECRET),
+        ] {
+            assert_eq!(code(validate(&bad, &config(), &jwks)), "invalid_token", "{}", bad);
+        }
+    }
+
+    #[test]
+    fn jwks_tokens_need_a_known_kid() {
+        let config = JwtConfig {
+            jwks_url: Some("http://127.0.0.1:1/jwks.json".to_string()),
+            ..JwtConfig::default()
+        };
+        let result = validate(&portal_token("asha", "admin"), &config, &Arc::default());
+        assert_eq!(result.err().unwrap().body()["message"], "Bearer token has no kid");
+    }
+
+    #[test]
+    fn the_config_never_shows_the_secret() {
+        let debug = format!("{:?}", config());
+        assert!(!debug.contains(SECRET), "{}", debug);
+        assert_eq!(debug, "HS256 shared secret (issuer Some(\"portal\"), audience None)");
+    }
+
+    #[actix_web::test]
+    async fn each_role_reaches_only_its_routes() {
+        let runtime_config = RuntimeConfig {
+            jwt: Some(config()),
+            ..test_support::runtime_config()
+        };
+        let state = test_support::state(runtime_config, vec![]);
+        let status = |request: TestRequest, role: &str| {
+            let request = request.insert_header((header::AUTHORIZATION, format!("Bearer {}", portal_token("asha", role))));
+            let state = state.clone();
+            async move { test_support::call(&state, request).await.status() }
+        };
+
+        assert_eq!(status(TestRequest::get().uri("/api/v1/search"), "viewer").await, StatusCode::OK);
+        let upload = || test_support::multipart("/api/v1/upload", &[("note", None, b"x")]);
+        assert_eq!(status(upload(), "viewer").await, StatusCode::FORBIDDEN);
+        // Past the role check; the form itself is what's wrong
+        assert_eq!(status(upload(), "uploader").await, StatusCode::BAD_REQUEST);
+        let refresh = || TestRequest::post().uri("/api/v1/refresh");
+        assert_eq!(status(refresh(), "uploader").await, StatusCode::FORBIDDEN);
+        assert_ne!(status(refresh(), "admin").await, StatusCode::FORBIDDEN);
+        assert_eq!(status(refresh(), "superuser").await, StatusCode::FORBIDDEN);
+
+        let not_bearer = TestRequest::post().uri("/api/v1/refresh").insert_header((header::AUTHORIZATION, "Basic abc"));
+        let (code, body) = test_support::call_json(&state, not_bearer).await;
+        assert_eq!((code, body["code"].as_str()), (StatusCode::UNAUTHORIZED, Some("invalid_token")));
+    }
+}
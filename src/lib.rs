@@ -0,0 +1,3292 @@
+use actix_web::{dev::Service as _, http::header, middleware::{Condition, DefaultHeaders, Logger}, web, App, HttpRequest, HttpResponse, HttpServer, Result};
+use calamine::{Data, Range, Reader};
+use futures_util::TryStreamExt as _;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::NamedTempFile;
+use tokio_postgres::{Client, Transaction};
+use utoipa::{OpenApi, ToSchema};
+use utoipa_swagger_ui::SwaggerUi;
+use deadpool_postgres::Pool;
+use chrono::NaiveDate;
+use log::{info, warn, error};
+use arc_swap::ArcSwap;
+
+mod aliases;
+mod api_error;
+mod artifacts;
+mod audit;
+mod auth;
+mod categories;
+mod columns;
+mod config;
+mod csv_export;
+mod csv_import;
+mod dates;
+mod db;
+mod edits;
+pub mod filters;
+mod history;
+mod import_cli;
+pub mod jobs;
+mod jwt;
+mod metrics;
+mod migrations;
+mod ndjson_export;
+pub mod openapi;
+mod preferences;
+mod providers;
+mod rate_limit;
+mod rate_matches;
+mod rate_upload;
+mod representation;
+mod routes;
+mod scheduler;
+mod sheets;
+mod shutdown;
+mod sniff;
+pub mod snapshot;
+pub mod table;
+#[cfg(test)]
+mod test_support;
+mod tls;
+mod uploads;
+mod workbook;
+pub mod xlsx_export;
+
+use api_error::{ApiError, ErrorCode};
+use columns::{ColumnMap, ColumnMapping, FundColumn};
+use config::{RuntimeConfig, StaticConfig};
+use edits::EditOutcome;
+use filters::NumericField;
+use preferences::PreferenceMap;
+use rate_matches::{FundSuggestion, UnmatchedRate};
+use table::{TableBuilder, VirtualTable};
+
+// Combined virtual table structure for search. The string fields repeat across many records (a
+// fund's name on each of its rates, one company on thousands) and are shared through the table's
+// string pool, see VirtualTable::add_record; they still serialize as plain strings.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CombinedSchemeData {
+    // From funds table
+    pub fund_id: Option<i32>,
+    #[schema(value_type = Option<String>)]
+    pub fund_category: Option<Arc<str>>,
+    pub launch_date: Option<NaiveDate>,
+    pub fund_size_apr25: Option<f32>,
+    pub fund_size_may25: Option<f32>,
+    pub latest_nav: Option<f32>,
+    pub month_1: Option<f32>,
+    pub months_3: Option<f32>,
+    pub months_6: Option<f32>,
+    pub ytd: Option<f32>,
+    pub year_1: Option<f32>,
+    pub years_2: Option<f32>,
+    pub years_3: Option<f32>,
+    pub years_5: Option<f32>,
+
+    // From scheme_rates table
+    pub rate_id: Option<i32>,
+    #[schema(value_type = Option<String>)]
+    pub arn: Option<Arc<str>>,
+    #[schema(value_type = Option<String>)]
+    pub company: Option<Arc<str>>,
+    #[schema(value_type = Option<String>)]
+    pub scheme_category: Option<Arc<str>>,
+    #[schema(value_type = Option<String>)]
+    pub brokerage_type: Option<Arc<str>>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub base_year_1: Option<f32>,
+    pub base_year_2: Option<f32>,
+    pub base_year_3: Option<f32>,
+
+    // Common fields
+    #[schema(value_type = String)]
+    pub scheme_name: Arc<str>,
+    #[schema(value_type = String)]
+    pub normalized_name: Arc<str>,
+
+    // Computed at build time: fraction of numeric fields populated, and whether that is below
+    // min_data_completeness (usually a row whose cells failed to parse)
+    pub data_completeness: f32,
+    pub incomplete: bool,
+    // Computed at build time: whether the rate was in force on the build date. Records without
+    // a rate count as active.
+    pub is_rate_active: bool,
+}
+
+impl CombinedSchemeData {
+    // Whether the rate was in force on `date` by its start and end dates; true without a rate
+    pub fn rate_active_on(&self, date: NaiveDate) -> bool {
+        self.rate_id.is_none()
+            || (self.start_date.is_none_or(|start| start <= date) && self.end_date.is_none_or(|end| end >= date))
+    }
+
+    // The fund half of a combined record, for funds whose every rate has lapsed
+    fn without_rate(&self) -> Self {
+        Self {
+            rate_id: None,
+            arn: None,
+            company: None,
+            scheme_category: None,
+            brokerage_type: None,
+            start_date: None,
+            end_date: None,
+            base_year_1: None,
+            base_year_2: None,
+            base_year_3: None,
+            is_rate_active: true,
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+impl CombinedSchemeData {
+    // A fund without a rate or any numeric field, for building tables in tests
+    pub fn test_fund(fund_id: i32, scheme_name: &str) -> Self {
+        Self {
+            fund_id: Some(fund_id),
+            fund_category: None,
+            launch_date: None,
+            fund_size_apr25: None,
+            fund_size_may25: None,
+            latest_nav: None,
+            month_1: None,
+            months_3: None,
+            months_6: None,
+            ytd: None,
+            year_1: None,
+            years_2: None,
+            years_3: None,
+            years_5: None,
+            rate_id: None,
+            arn: None,
+            company: None,
+            scheme_category: None,
+            brokerage_type: None,
+            start_date: None,
+            end_date: None,
+            base_year_1: None,
+            base_year_2: None,
+            base_year_3: None,
+            scheme_name: scheme_name.into(),
+            normalized_name: normalize_scheme_name(scheme_name).into(),
+            data_completeness: 0.0,
+            incomplete: false,
+            is_rate_active: true,
+        }
+    }
+}
+
+const DID_YOU_MEAN_COUNT: usize = 5;
+// Row problems listed in an upload response; the counts always cover all of them
+const UPLOAD_ERROR_LIMIT: usize = 100;
+
+// Outcome of processing one uploaded workbook
+#[derive(Debug, Default, Serialize, ToSchema)]
+struct UploadReport {
+    // The uploads row recording this file, see GET /uploads/{id}
+    upload_id: i32,
+    // False when the upload was rolled back; the counts then describe what would have been applied
+    committed: bool,
+    rollback_reason: Option<String>,
+    // inserted + updated
+    processed: usize,
+    inserted: usize,
+    updated: usize,
+    // Duplicate rows folded into their scheme's first occurrence
+    skipped_duplicate: usize,
+    merged_count: usize,
+    merged_schemes: Vec<MergedScheme>,
+    // Rows not stored: unparsable or rejected by the database
+    failed: usize,
+    category_issues: Vec<categories::CategoryIssue>,
+    // Sheets that could not be imported; the rest of the workbook still is
+    sheet_errors: Vec<SheetError>,
+    error_count: usize,
+    // The first UPLOAD_ERROR_LIMIT row problems: parse problems in file order, then database rejections
+    row_errors: Vec<FundRowError>,
+    provider: String,
+    #[schema(value_type = BTreeMap<String, BTreeMap<String, String>>)]
+    column_mappings: BTreeMap<String, ColumnMapping>,
+    // What the skip/include configuration decided for every sheet
+    sheets: Vec<sheets::SheetDecision>,
+    // Sheets imported under their own name for lack of a category mapping; usually new sheets
+    unmapped_sheets: Vec<String>,
+    // Scheme names written by a committed upload; empty when rolled back
+    #[serde(skip)]
+    touched_schemes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct SheetError {
+    sheet: String,
+    message: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+struct FundRowError {
+    sheet: String,
+    // 1-based, as shown in Excel (line number for CSV)
+    row: usize,
+    scheme_name: Option<String>,
+    reason: String,
+    // False for problems that only emptied one field of an otherwise imported row
+    skipped: bool,
+}
+
+#[derive(Debug)]
+struct FundData {
+    // Where the row came from, for error reporting; category may be rewritten, sheet is not
+    sheet: String,
+    row: usize,
+    category: String,
+    scheme_name: String,
+    // None when the sheet's value could not be parsed (reported as a row error)
+    launch_date: Option<NaiveDate>,
+    fund_size_apr25: Option<f32>,
+    fund_size_may25: Option<f32>,
+    latest_nav: Option<f32>,
+    month_1: Option<f32>,
+    months_3: Option<f32>,
+    months_6: Option<f32>,
+    ytd: Option<f32>,
+    year_1: Option<f32>,
+    years_2: Option<f32>,
+    years_3: Option<f32>,
+    years_5: Option<f32>,
+}
+
+// Application state to hold the virtual table
+#[derive(Debug, Clone)]
+pub struct AppState {
+    // Rebuilt off to the side and swapped in whole, so readers never wait on a refresh
+    pub virtual_table: Arc<ArcSwap<VirtualTable>>,
+    pub runtime_config: Arc<ArcSwap<RuntimeConfig>>,
+    pub static_config: Arc<StaticConfig>,
+    pub config_path: Option<std::path::PathBuf>,
+    pub pools: db::DbPools,
+    // Count of queries whose fuzzy/suggestion tier ran out of its time budget
+    pub budget_exhaustions: Arc<AtomicU64>,
+    // Published export artifacts; None when no export_dir is configured
+    pub artifacts: Option<Arc<artifacts::ArtifactStore>>,
+    // Upload/refresh coordination, see begin_mutation
+    pub mutation_gate: Arc<tokio::sync::RwLock<()>>,
+    // Per-category search display defaults, reloaded on refresh and on every admin change
+    pub category_preferences: Arc<ArcSwap<PreferenceMap>>,
+    // In-flight uploads, drained on shutdown
+    pub uploads: Arc<shutdown::UploadTracker>,
+    // Background upload jobs, polled at /jobs/{id}
+    pub jobs: Arc<jobs::JobRegistry>,
+    pub snapshot_status: Arc<std::sync::Mutex<snapshot::SnapshotStatus>>,
+    // Held for the whole of a full refresh, so a scheduled tick can tell one is already running
+    pub refresh_lock: Arc<tokio::sync::Mutex<()>>,
+    pub refresh_status: Arc<std::sync::Mutex<scheduler::RefreshStatus>>,
+    // Row counts reported by /stats, see cached_db_counts
+    pub db_counts: Arc<std::sync::Mutex<Option<DbCounts>>>,
+    // Held while GET /export/xlsx builds its workbook; one at a time bounds the memory it takes
+    pub xlsx_export: Arc<tokio::sync::Mutex<()>>,
+    // Signing keys for bearer tokens when jwt.jwks_url is set
+    pub jwks: Arc<jwt::JwksCache>,
+    pub rate_limiter: Arc<rate_limit::RateLimiter>,
+    // Served at /metrics
+    pub metrics: Arc<metrics::Metrics>,
+    pub started_at: std::time::Instant,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct DbCounts {
+    pub funds: i64,
+    pub scheme_rates: i64,
+    pub counted_at: chrono::DateTime<chrono::Utc>,
+    #[serde(skip)]
+    counted: std::time::Instant,
+}
+
+impl AppState {
+    pub fn new(
+        static_config: StaticConfig,
+        runtime_config: RuntimeConfig,
+        config_path: Option<std::path::PathBuf>,
+        pools: db::DbPools,
+        artifacts: Option<artifacts::ArtifactStore>,
+    ) -> Self {
+        Self {
+            virtual_table: Arc::new(ArcSwap::from_pointee(VirtualTable::new())),
+            runtime_config: Arc::new(ArcSwap::from_pointee(runtime_config)),
+            static_config: Arc::new(static_config),
+            config_path,
+            pools,
+            budget_exhaustions: Arc::new(AtomicU64::new(0)),
+            artifacts: artifacts.map(Arc::new),
+            mutation_gate: Arc::new(tokio::sync::RwLock::new(())),
+            category_preferences: Arc::new(ArcSwap::from_pointee(PreferenceMap::new())),
+            uploads: Arc::new(shutdown::UploadTracker::default()),
+            jobs: Arc::new(jobs::JobRegistry::default()),
+            snapshot_status: Arc::new(std::sync::Mutex::new(snapshot::SnapshotStatus::default())),
+            refresh_lock: Arc::new(tokio::sync::Mutex::new(())),
+            refresh_status: Arc::new(std::sync::Mutex::new(scheduler::RefreshStatus::default())),
+            db_counts: Arc::new(std::sync::Mutex::new(None)),
+            xlsx_export: Arc::new(tokio::sync::Mutex::new(())),
+            jwks: Arc::new(jwt::JwksCache::default()),
+            rate_limiter: Arc::new(rate_limit::RateLimiter::default()),
+            metrics: Arc::new(metrics::Metrics::new()),
+            started_at: std::time::Instant::now(),
+        }
+    }
+
+    // Every batch of writes to funds/scheme_rates/aliases holds this token until its writes are
+    // finished. Refreshes take the exclusive side of the same lock while they read the database and
+    // swap the new table in, so they never observe a half-applied upload; the served table is
+    // always either fully before or fully after a batch. Mutations may run concurrently with each
+    // other. The refresh that follows a batch is a method on its token and consumes it.
+    pub async fn begin_mutation(&self) -> MutationToken<'_> {
+        MutationToken {
+            state: self,
+            _gate: self.mutation_gate.read().await,
+        }
+    }
+}
+
+// A batch of writes in progress, see begin_mutation
+pub struct MutationToken<'a> {
+    state: &'a AppState,
+    _gate: tokio::sync::RwLockReadGuard<'a, ()>,
+}
+
+// Each of these releases the gate before refreshing, so a token can't be held into the refresh
+// that waits for it
+impl MutationToken<'_> {
+    pub async fn refresh(self) -> Result<(), Box<dyn std::error::Error>> {
+        let state = self.state;
+        drop(self);
+        refresh_virtual_table(state).await
+    }
+
+    pub async fn refresh_for(self, scheme_names: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let state = self.state;
+        drop(self);
+        refresh_virtual_table_for(state, scheme_names).await
+    }
+
+    pub async fn refresh_for_rates(self, rate_names: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        let state = self.state;
+        drop(self);
+        refresh_virtual_table_for_rates(state, rate_names).await
+    }
+
+    pub async fn remove_fund(self, fund_id: i32) -> usize {
+        let state = self.state;
+        drop(self);
+        remove_fund_from_virtual_table(state, fund_id).await
+    }
+}
+
+// Re-read the config file and swap in the reloadable subset; rejected wholesale on any validation error
+fn reload_runtime_config(state: &AppState) -> Result<Vec<String>, Vec<String>> {
+    let (static_config, runtime_config) = config::load(state.config_path.as_deref())?;
+
+    let current = state.runtime_config.load();
+    let mut changes = config::diff_runtime(&current, &runtime_config);
+    changes.extend(config::diff_static(&state.static_config, &static_config));
+
+    state.runtime_config.store(Arc::new(runtime_config));
+
+    for change in &changes {
+        info!("Config reload: {}", change);
+    }
+    if changes.is_empty() {
+        info!("Config reload: no changes");
+    }
+
+    Ok(changes)
+}
+
+async fn get_postgres_client(pool: &Pool) -> Result<deadpool_postgres::Client, Box<dyn std::error::Error>> {
+    Ok(pool.get().await?)
+}
+
+// Rows between progress lines while a build streams the combined query
+const BUILD_PROGRESS_ROWS: usize = 10_000;
+
+// Every fund LEFT JOINed to the approved rates whose name (or a confirmed alias) matches after
+// alias expansion. Callers add the WHERE/ORDER BY they need.
+const COMBINED_QUERY: &str = "
+        SELECT
+            f.id as fund_id,
+            f.category as fund_category,
+            f.launch_date,
+            f.fund_size_apr25,
+            f.fund_size_may25,
+            f.latest_nav,
+            f.month_1,
+            f.months_3,
+            f.months_6,
+            f.ytd,
+            f.year_1,
+            f.years_2,
+            f.years_3,
+            f.years_5,
+            sr.id as rate_id,
+            sr.arn,
+            sr.company,
+            sr.scheme_category,
+            sr.brokerage_type,
+            sr.start_date,
+            sr.end_date,
+            sr.base_year_1,
+            sr.base_year_2,
+            sr.base_year_3,
+            f.scheme_name
+        FROM funds f
+        LEFT JOIN scheme_rates sr ON
+            (
+                expand_name_aliases(f.scheme_name) = expand_name_aliases(sr.scheme_name)
+                OR EXISTS (
+                    SELECT 1 FROM scheme_aliases sa
+                    WHERE sa.fund_id = f.id
+                      AND expand_name_aliases(sa.alias_name) = expand_name_aliases(sr.scheme_name)
+                )
+            )
+            AND (sr.is_approved IS NULL OR sr.is_approved = true)
+";
+
+// Records for rows of COMBINED_QUERY, converted one row at a time so a build can stream them.
+// Expired rates are kept (searches can ask for them); a fund all of whose rates have lapsed also
+// gets a rate-less record so default searches still find it, which is only known once every row
+// has been seen, so those come from finish().
+struct CombinedRecords {
+    today: NaiveDate,
+    min_data_completeness: f32,
+    lapsed_funds: BTreeMap<i32, CombinedSchemeData>,
+    active_funds: std::collections::HashSet<i32>,
+}
+
+impl CombinedRecords {
+    fn new(min_data_completeness: f32) -> Self {
+        Self {
+            today: chrono::Local::now().date_naive(),
+            min_data_completeness,
+            lapsed_funds: BTreeMap::new(),
+            active_funds: std::collections::HashSet::new(),
+        }
+    }
+
+    fn record(&mut self, row: &tokio_postgres::Row) -> CombinedSchemeData {
+        let scheme_name: String = row.get("scheme_name");
+        let normalized_name = normalize_scheme_name(&scheme_name);
+        let text = |column: &str| row.get::<_, Option<String>>(column).map(Arc::<str>::from);
+
+        let mut combined_data = CombinedSchemeData {
+            fund_id: row.get("fund_id"),
+            fund_category: text("fund_category"),
+            launch_date: row.get("launch_date"),
+            fund_size_apr25: row.get("fund_size_apr25"),
+            fund_size_may25: row.get("fund_size_may25"),
+            latest_nav: row.get("latest_nav"),
+            month_1: row.get("month_1"),
+            months_3: row.get("months_3"),
+            months_6: row.get("months_6"),
+            ytd: row.get("ytd"),
+            year_1: row.get("year_1"),
+            years_2: row.get("years_2"),
+            years_3: row.get("years_3"),
+            years_5: row.get("years_5"),
+            rate_id: row.get("rate_id"),
+            arn: text("arn"),
+            company: text("company"),
+            scheme_category: text("scheme_category"),
+            brokerage_type: text("brokerage_type"),
+            start_date: row.get("start_date"),
+            end_date: row.get("end_date"),
+            base_year_1: row.get("base_year_1"),
+            base_year_2: row.get("base_year_2"),
+            base_year_3: row.get("base_year_3"),
+            scheme_name: Arc::from(scheme_name),
+            normalized_name: Arc::from(normalized_name),
+            data_completeness: 0.0,
+            incomplete: false,
+            is_rate_active: true,
+        };
+        combined_data.data_completeness = NumericField::completeness(&combined_data);
+        combined_data.incomplete = combined_data.data_completeness < self.min_data_completeness;
+        combined_data.is_rate_active = combined_data.rate_active_on(self.today);
+
+        if let Some(fund_id) = combined_data.fund_id {
+            if combined_data.is_rate_active {
+                self.active_funds.insert(fund_id);
+            } else {
+                self.lapsed_funds.entry(fund_id).or_insert_with(|| combined_data.without_rate());
+            }
+        }
+        combined_data
+    }
+
+    // Rate-less records of the funds with no active rate among the rows seen
+    fn finish(self) -> Vec<CombinedSchemeData> {
+        let active_funds = self.active_funds;
+        self.lapsed_funds
+            .into_iter()
+            .filter(|(fund_id, _)| !active_funds.contains(fund_id))
+            .map(|(_, fund_only)| fund_only)
+            .collect()
+    }
+}
+
+fn combined_records(rows: Vec<tokio_postgres::Row>, min_data_completeness: f32) -> Vec<CombinedSchemeData> {
+    let mut combined = CombinedRecords::new(min_data_completeness);
+    let mut records: Vec<CombinedSchemeData> = rows.iter().map(|row| combined.record(row)).collect();
+    records.extend(combined.finish());
+    records
+}
+
+async fn build_virtual_table(
+    client: &Client,
+    min_data_completeness: f32,
+) -> Result<VirtualTable, Box<dyn std::error::Error>> {
+    info!("Building virtual table from combined data...");
+
+    let mut virtual_table = VirtualTable::new();
+    // Read before the data so a concurrent write can only make the snapshot look stale, never current
+    virtual_table.generation = db::data_generation(client).await?;
+    // Before any record is added: names are indexed under their alias expansions too
+    virtual_table.set_name_aliases(aliases::load_aliases(client).await?);
+
+    // Ordered so record positions, and with them any order that falls back on position, are the
+    // same on every build of the same data
+    // Rows are streamed into the table as they arrive rather than collected first, which would
+    // hold every row and every record at once. A stream that fails partway fails the build; the
+    // half-built table is dropped and the caller keeps serving the old one.
+    let query = format!("{} ORDER BY f.id, sr.id", COMBINED_QUERY);
+    let rows = client.query_raw(query.as_str(), std::iter::empty::<i32>()).await?;
+    futures_util::pin_mut!(rows);
+    let mut combined = CombinedRecords::new(min_data_completeness);
+    let mut builder = TableBuilder::new(virtual_table);
+    let mut streamed = 0usize;
+    while let Some(row) = rows
+        .try_next()
+        .await
+        .map_err(|e| format!("combined query failed after {} rows: {}", streamed, e))?
+    {
+        builder.add_record(combined.record(&row));
+        streamed += 1;
+        if streamed.is_multiple_of(BUILD_PROGRESS_ROWS) {
+            info!("Virtual table build: {} rows read", streamed);
+        }
+    }
+    for record in combined.finish() {
+        builder.add_record(record);
+    }
+    let mut virtual_table = builder.finish();
+
+    // Active rates that neither the normalized join nor a confirmed alias attached to a fund
+    let unmatched_query = "
+        SELECT sr.id, sr.arn, sr.company, sr.scheme_name, sr.scheme_category, sr.brokerage_type,
+               sr.start_date, sr.end_date, sr.base_year_1, sr.base_year_2, sr.base_year_3
+        FROM scheme_rates sr
+        WHERE (sr.is_approved IS NULL OR sr.is_approved = true)
+          AND (sr.end_date IS NULL OR sr.end_date >= CURRENT_DATE)
+          AND NOT EXISTS (
+              SELECT 1 FROM funds f
+              WHERE expand_name_aliases(f.scheme_name) = expand_name_aliases(sr.scheme_name)
+          )
+          AND NOT EXISTS (
+              SELECT 1 FROM scheme_aliases sa
+              WHERE expand_name_aliases(sa.alias_name) = expand_name_aliases(sr.scheme_name)
+          )
+        ORDER BY sr.company, sr.scheme_name, sr.id
+    ";
+
+    for row in client.query(unmatched_query, &[]).await? {
+        virtual_table.unmatched_rates.push(UnmatchedRate {
+            rate_id: row.get("id"),
+            arn: row.get("arn"),
+            company: row.get("company"),
+            scheme_name: row.get("scheme_name"),
+            scheme_category: row.get("scheme_category"),
+            brokerage_type: row.get("brokerage_type"),
+            start_date: row.get("start_date"),
+            end_date: row.get("end_date"),
+            base_year_1: row.get("base_year_1"),
+            base_year_2: row.get("base_year_2"),
+            base_year_3: row.get("base_year_3"),
+        });
+    }
+
+    info!("Virtual table built with {} combined records", virtual_table.len());
+    let incomplete = virtual_table.incomplete_count();
+    if incomplete > 0 {
+        warn!(
+            "{} of {} records are below {:.0}% data completeness; check the latest upload",
+            incomplete,
+            virtual_table.len(),
+            min_data_completeness * 100.0
+        );
+    }
+    Ok(virtual_table)
+}
+
+// Every full rebuild and in-place update goes through refresh_lock, so two never run at once and
+// the later one can't store a table that misses what the earlier one saw
+async fn refresh_virtual_table(state: &AppState) -> Result<(), Box<dyn std::error::Error>> {
+    let _refreshing = state.refresh_lock.lock().await;
+    run_refresh(state).await
+}
+
+// None without refreshing when another refresh already holds the lock
+async fn try_refresh_virtual_table(state: &AppState) -> Option<Result<(), Box<dyn std::error::Error>>> {
+    let _refreshing = state.refresh_lock.try_lock().ok()?;
+    Some(run_refresh(state).await)
+}
+
+// Callers hold refresh_lock
+async fn run_refresh(state: &AppState) -> Result<(), Box<dyn std::error::Error>> {
+    let started = std::time::Instant::now();
+    let rebuilt = rebuild_virtual_table(state).await;
+    let mut refresh_status = state.refresh_status.lock().unwrap();
+    match &rebuilt {
+        Ok(()) => refresh_status.record_success(started, state.virtual_table.load().len()),
+        Err(e) => refresh_status.record_failure(e.to_string()),
+    }
+    drop(refresh_status);
+    rebuilt
+}
+
+// Callers hold refresh_lock
+async fn rebuild_virtual_table(state: &AppState) -> Result<(), Box<dyn std::error::Error>> {
+    // Transient outages are retried briefly; a still-unavailable database surfaces as a 503.
+    // Builds read from the replica when configured, so replication lag delays new writes by that much.
+    // Each attempt waits for in-flight mutation batches and keeps new ones out while it reads, and
+    // the one that succeeds until its table is swapped in; not while backing off between attempts.
+    let min_data_completeness = state.runtime_config.load().min_data_completeness;
+    let (no_mutations, new_table, preferences) =
+        db::with_retry(&db::RetryPolicy::REFRESH, "Virtual table refresh", || async {
+            let no_mutations = state.mutation_gate.write().await;
+            let client = state.pools.read_client().await?;
+            let table = build_virtual_table(&client, min_data_completeness).await?;
+            let preferences = preferences::load_preferences(&client).await?;
+            Ok::<_, Box<dyn std::error::Error>>((no_mutations, table, preferences))
+        })
+        .await?;
+    let new_table = Arc::new(new_table);
+    state.virtual_table.store(Arc::clone(&new_table));
+    state.category_preferences.store(Arc::new(preferences));
+    drop(no_mutations);
+
+    save_snapshot(state, new_table).await;
+
+    if state.artifacts.is_some() {
+        if let Err(e) = publish_export_artifacts(state).await {
+            warn!("Failed to publish export artifacts: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+// Drop a deleted fund's records from the served table, patched on a copy like update_virtual_table.
+// Returns how many records went.
+async fn remove_fund_from_virtual_table(state: &AppState, fund_id: i32) -> usize {
+    let _refreshing = state.refresh_lock.lock().await;
+    let removed = {
+        let _no_mutations = state.mutation_gate.write().await;
+        let mut patched = VirtualTable::clone(&state.virtual_table.load());
+        let removed = patched.remove_fund(fund_id).len();
+        if removed > 0 {
+            state.virtual_table.store(Arc::new(patched));
+        }
+        removed
+    };
+    if removed > 0 && state.artifacts.is_some() {
+        if let Err(e) = publish_export_artifacts(state).await {
+            warn!("Failed to publish export artifacts: {}", e);
+        }
+    }
+    removed
+}
+
+// After an upload: re-read only the funds it wrote instead of rebuilding the whole table, falling
+// back to refresh_virtual_table when the patch can't be made consistently. The snapshot (and the
+// table's generation) stay as the last full build left them, so a restart rebuilds rather than
+// trusting a snapshot that misses the upload.
+async fn refresh_virtual_table_for(state: &AppState, scheme_names: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    if scheme_names.is_empty() {
+        return Ok(());
+    }
+    match update_virtual_table(state, scheme_names).await {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            warn!("Incremental virtual table update failed ({}), rebuilding the whole table", e);
+            refresh_virtual_table(state).await
+        }
+    }
+}
+
+// After a rate write: re-read the funds each affected rate name joins to (a rename affects both the
+// old and the new name). A name that joins no fund means the rate is, or was, in unmatched_rates,
+// which only a full build recomputes.
+async fn refresh_virtual_table_for_rates(state: &AppState, rate_names: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let client = get_postgres_client(state.pools.primary()).await?;
+    let mut fund_names = Vec::new();
+    for rate_name in rate_names {
+        let joined = edits::funds_joined_to(&client, rate_name).await?;
+        if joined.is_empty() {
+            drop(client);
+            return refresh_virtual_table(state).await;
+        }
+        fund_names.extend(joined);
+    }
+    drop(client);
+    fund_names.sort();
+    fund_names.dedup();
+    refresh_virtual_table_for(state, &fund_names).await
+}
+
+async fn update_virtual_table(state: &AppState, scheme_names: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let _refreshing = state.refresh_lock.lock().await;
+
+    let min_data_completeness = state.runtime_config.load().min_data_completeness;
+    // From the primary: a lagging replica may not have the rows the write just committed yet.
+    // Mutations are kept out from the read to the swap, as in rebuild_virtual_table.
+    let query = format!("{} WHERE f.scheme_name = ANY($1) ORDER BY f.id, sr.id", COMBINED_QUERY);
+    let (no_mutations, rows) = db::with_retry(&db::RetryPolicy::REFRESH, "Incremental virtual table update", || async {
+        let no_mutations = state.mutation_gate.write().await;
+        let client = get_postgres_client(state.pools.primary()).await?;
+        let rows = client.query(query.as_str(), &[&scheme_names]).await?;
+        Ok::<_, Box<dyn std::error::Error>>((no_mutations, rows))
+    })
+    .await?;
+    let records = combined_records(rows, min_data_completeness);
+
+    // Every name the upload wrote has to come back; a missing one means the database no longer
+    // has what the upload reported
+    let found: std::collections::HashSet<&str> = records.iter().map(|record| &*record.scheme_name).collect();
+    if let Some(missing) = scheme_names.iter().find(|name| !found.contains(name.as_str())) {
+        return Err(format!("scheme '{}' was written but is not in the database", missing).into());
+    }
+    let fund_ids: std::collections::HashSet<i32> = records.iter().filter_map(|record| record.fund_id).collect();
+    let record_count = records.len();
+
+    // Patched on a copy so a failed invariant check leaves the served table untouched
+    let mut patched = VirtualTable::clone(&state.virtual_table.load());
+    patched.upsert_records(&fund_ids, records);
+    patched.check_invariants()?;
+    state.virtual_table.store(Arc::new(patched));
+    drop(no_mutations);
+    info!(
+        "Virtual table updated in place: {} records for {} funds",
+        record_count,
+        fund_ids.len()
+    );
+
+    if state.artifacts.is_some() {
+        if let Err(e) = publish_export_artifacts(state).await {
+            warn!("Failed to publish export artifacts: {}", e);
+        }
+    }
+    Ok(())
+}
+
+// The snapshot to serve straight away, if any, and whether a background rebuild must replace it.
+// An older snapshot is served while it is rebuilt; a newer one (the database was restored since)
+// or an unusable one (wrong format version, corrupt, undecodable) means building before serving.
+async fn load_startup_snapshot(state: &AppState, client: &Client) -> (Option<VirtualTable>, bool) {
+    let path = match &state.static_config.snapshot_path {
+        Some(path) => path,
+        None => return (None, false),
+    };
+    let (table, header) = match snapshot::read(path) {
+        Ok(loaded) => loaded,
+        Err(reason) => {
+            warn!("Ignoring snapshot {}: {}; building from the database", path.display(), reason);
+            state.snapshot_status.lock().unwrap().rejected = Some(reason);
+            return (None, false);
+        }
+    };
+    info!(
+        "Loaded snapshot of {} records at generation {} (built {})",
+        header.record_count, header.generation, header.source_timestamp
+    );
+
+    let stale = match db::data_generation(client).await {
+        Ok(db_generation) => snapshot::check_generation(&header, db_generation),
+        Err(e) => Ok(Some(format!("cannot read database generation: {}", e))),
+    };
+    let mut status = state.snapshot_status.lock().unwrap();
+    match stale {
+        Ok(None) => {
+            status.loaded = Some(header);
+            (Some(table), false)
+        }
+        Ok(Some(reason)) => {
+            info!("Serving snapshot while the table is rebuilt: {}", reason);
+            status.loaded = Some(header);
+            status.stale = Some(reason);
+            (Some(table), true)
+        }
+        Err(reason) => {
+            warn!("Ignoring snapshot {}: {}; building from the database", path.display(), reason);
+            status.rejected = Some(reason);
+            (None, false)
+        }
+    }
+}
+
+// Persist a freshly built table so the next startup can skip the build if nothing has changed
+async fn save_snapshot(state: &AppState, table: Arc<VirtualTable>) -> Arc<VirtualTable> {
+    let path = match &state.static_config.snapshot_path {
+        Some(path) => path.clone(),
+        None => return table,
+    };
+
+    let to_write = Arc::clone(&table);
+    let outcome = web::block(move || snapshot::write(&path, &to_write, chrono::Utc::now()))
+        .await
+        .unwrap_or_else(|e| Err(format!("snapshot writer failed: {}", e)));
+
+    {
+        let mut status = state.snapshot_status.lock().unwrap();
+        match outcome {
+            Ok(header) => {
+                info!("Wrote snapshot of {} records at generation {}", header.record_count, header.generation);
+                status.last_written = Some(header);
+                status.last_write_error = None;
+            }
+            Err(e) => {
+                warn!("Failed to write snapshot: {}", e);
+                status.last_write_error = Some(e);
+            }
+        }
+    }
+
+    table
+}
+
+// Publish a new generation of each export dataset from the current virtual table
+async fn publish_export_artifacts(state: &AppState) -> Result<(), Box<dyn std::error::Error>> {
+    let store = match &state.artifacts {
+        Some(store) => Arc::clone(store),
+        None => return Ok(()),
+    };
+
+    let combined = {
+        let virtual_table = state.virtual_table.load();
+        serde_json::to_vec(virtual_table.records())?
+    };
+    let unmatched = rate_matches::build_worksheet(&unmatched_rate_rows(state))?;
+
+    web::block(move || -> std::io::Result<()> {
+        store.publish("combined", "json", &combined)?;
+        store.publish("unmatched-rates", "xlsx", &unmatched)?;
+        Ok(())
+    })
+    .await??;
+
+    Ok(())
+}
+
+// Swagger UI serves its page under /swagger/
+async fn swagger_redirect() -> HttpResponse {
+    HttpResponse::PermanentRedirect().insert_header((header::LOCATION, "/swagger/")).finish()
+}
+
+async fn upload_page() -> Result<HttpResponse> {
+    let html = r#"
+<!DOCTYPE html>
+<html>
+<head>
+    <title>Combined Fund & Scheme Search</title>
+    <style>
+        body { font-family: Arial, sans-serif; margin: 40px; }
+        .container { max-width: 800px; margin: 0 auto; }
+        .search-section { margin: 20px 0; }
+        .upload-area {
+            border: 2px dashed #ccc;
+            padding: 40px;
+            text-align: center;
+            margin: 20px 0;
+        }
+        input[type="file"], input[type="text"], input[type="password"] { margin: 10px 0; padding: 8px; }
+        button {
+            background: #007bff;
+            color: white;
+            padding: 10px 20px;
+            border: none;
+            cursor: pointer;
+            margin: 5px;
+        }
+        .result { margin-top: 20px; padding: 20px; background: #f8f9fa; }
+        .search-results { margin-top: 20px; }
+        .scheme-card {
+            border: 1px solid #ddd;
+            padding: 15px;
+            margin: 10px 0;
+            border-radius: 5px;
+        }
+        .performance { color: #28a745; }
+        .brokerage { color: #dc3545; }
+    </style>
+</head>
+<body>
+    <div class="container">
+        <h1>Combined Fund & Scheme Search System</h1>
+
+        <div>
+            <!-- Uploads and refreshes need an uploader or admin key; it stays in this tab only -->
+            <input type="password" id="apiKey" autocomplete="off" placeholder="API key (for uploads and refresh)" style="width: 300px;">
+        </div>
+
+        <div class="search-section">
+            <h2>Search Combined Data</h2>
+            <input type="text" id="searchQuery" list="schemeSuggestions" autocomplete="off" placeholder="Enter scheme name to search..." style="width: 300px;">
+            <datalist id="schemeSuggestions"></datalist>
+            <button onclick="searchSchemes()">Search</button>
+            <button onclick="browseAll(0)">Browse all</button>
+            <button onclick="refreshData()">Refresh Data</button>
+        </div>
+
+        <div id="searchResults" class="search-results"></div>
+
+        <hr>
+
+        <h2>Upload Excel Data</h2>
+        <form id="uploadForm">
+            <div class="upload-area">
+                <p>Select one or more Excel files containing mutual fund data</p>
+                <input type="file" name="excel_file" accept=".xlsx,.xlsm,.xlsb,.xls,.ods,.csv" multiple required>
+                <br>
+                <input type="text" name="provider" placeholder="Provider (optional)">
+                <br>
+                <button type="submit">Upload and Convert</button>
+            </div>
+        </form>
+        <div id="uploadResult" class="result" style="display: none;"></div>
+    </div>
+
+    <script>
+        // The key entered above as X-Api-Key, on every request so deployments that require a key
+        // for reads work too
+        function apiHeaders() {
+            const key = document.getElementById('apiKey').value.trim();
+            sessionStorage.setItem('apiKey', key);
+            return key ? { 'X-Api-Key': key } : {};
+        }
+        document.getElementById('apiKey').value = sessionStorage.getItem('apiKey') || '';
+
+        // Submitted with fetch rather than as a plain form, which couldn't send the key header
+        document.getElementById('uploadForm').addEventListener('submit', async function(e) {
+            e.preventDefault();
+            const output = document.getElementById('uploadResult');
+            output.style.display = 'block';
+            try {
+                const response = await fetch('/api/v1/upload', {
+                    method: 'POST',
+                    headers: apiHeaders(),
+                    body: new FormData(e.target),
+                });
+                const result = await response.json();
+                output.textContent = result.message || JSON.stringify(result);
+                if (response.status === 401 || response.status === 403) {
+                    output.textContent += ' Enter an uploader or admin API key above.';
+                }
+            } catch (error) {
+                output.textContent = 'Upload failed: ' + error;
+            }
+        });
+
+        async function searchSchemes() {
+            const query = document.getElementById('searchQuery').value;
+            if (!query.trim()) return;
+
+            try {
+                const response = await fetch(`/api/v1/search?q=${encodeURIComponent(query)}`, { headers: apiHeaders() });
+                const results = await response.json();
+                displayResults(results);
+            } catch (error) {
+                console.error('Search error:', error);
+            }
+        }
+
+        const BROWSE_PAGE_SIZE = 20;
+
+        async function browseAll(offset) {
+            try {
+                const response = await fetch(`/api/v1/search?limit=${BROWSE_PAGE_SIZE}&offset=${offset}`, { headers: apiHeaders() });
+                const results = await response.json();
+                displayResults(results);
+                if (results.total_matches > results.count) {
+                    const from = results.offset + 1;
+                    const to = results.offset + results.count;
+                    let pager = `<p>Showing ${from}-${to} of ${results.total_matches} `;
+                    if (results.offset > 0) {
+                        pager += `<button onclick="browseAll(${Math.max(results.offset - BROWSE_PAGE_SIZE, 0)})">Previous</button>`;
+                    }
+                    if (to < results.total_matches) {
+                        pager += `<button onclick="browseAll(${to})">Next</button>`;
+                    }
+                    document.getElementById('searchResults').insertAdjacentHTML('beforeend', pager + '</p>');
+                }
+            } catch (error) {
+                console.error('Browse error:', error);
+            }
+        }
+
+        async function refreshData() {
+            try {
+                const response = await fetch('/api/v1/refresh', { method: 'POST', headers: apiHeaders() });
+                const result = await response.json();
+                alert(result.message);
+            } catch (error) {
+                console.error('Refresh error:', error);
+            }
+        }
+
+        function displayResults(results) {
+            const container = document.getElementById('searchResults');
+            if (!results.data || results.data.length === 0) {
+                container.innerHTML = '<p>No results found.</p>';
+                return;
+            }
+
+            let html = `<h3>Found ${results.total_matches} combined schemes:</h3>`;
+
+            results.data.forEach(scheme => {
+                html += `
+                    <div class="scheme-card">
+                        <h4>${scheme.scheme_name}</h4>
+                        <div class="performance">
+                            <strong>Performance:</strong>
+                            1M: ${scheme.month_1 || 'N/A'}% |
+                            3M: ${scheme.months_3 || 'N/A'}% |
+                            1Y: ${scheme.year_1 || 'N/A'}% |
+                            NAV: ₹${scheme.latest_nav || 'N/A'}
+                        </div>
+                        <div class="brokerage">
+                            <strong>Brokerage:</strong>
+                            Company: ${scheme.company || 'N/A'} |
+                            Type: ${scheme.brokerage_type || 'N/A'} |
+                            Year 1: ${scheme.base_year_1 || 'N/A'}%
+                        </div>
+                        <small>Category: ${scheme.fund_category || scheme.scheme_category || 'N/A'}</small>
+                    </div>
+                `;
+            });
+
+            container.innerHTML = html;
+        }
+
+        // Search on Enter key
+        document.getElementById('searchQuery').addEventListener('keypress', function(e) {
+            if (e.key === 'Enter') {
+                searchSchemes();
+            }
+        });
+
+        // Type-ahead, debounced so fast typing sends one request
+        let suggestTimer;
+        document.getElementById('searchQuery').addEventListener('input', function(e) {
+            clearTimeout(suggestTimer);
+            const query = e.target.value;
+            suggestTimer = setTimeout(async () => {
+                const list = document.getElementById('schemeSuggestions');
+                if (query.trim().length < 2) {
+                    list.innerHTML = '';
+                    return;
+                }
+                try {
+                    const response = await fetch(`/api/v1/suggest?q=${encodeURIComponent(query)}&limit=10`, { headers: apiHeaders() });
+                    const result = await response.json();
+                    list.innerHTML = '';
+                    (result.suggestions || []).forEach(suggestion => {
+                        const option = document.createElement('option');
+                        option.value = suggestion.scheme_name;
+                        list.appendChild(option);
+                    });
+                } catch (error) {
+                    console.error('Suggest error:', error);
+                }
+            }, 150);
+        });
+    </script>
+</body>
+</html>
+"#;
+    Ok(HttpResponse::Ok().content_type("text/html").body(html))
+}
+
+// Non-negative integer page parameter, `default` when absent
+fn parse_page_param(query: &HashMap<String, String>, key: &str, default: usize, max: usize) -> Result<usize, String> {
+    let value = match query.get(key) {
+        Some(value) => value,
+        None => return Ok(default),
+    };
+    match value.trim().parse::<usize>() {
+        Ok(parsed) if parsed <= max => Ok(parsed),
+        _ => Err(format!("Parameter '{}' must be an integer between 0 and {}, got '{}'", key, max, value)),
+    }
+}
+
+// Version an edit was based on: If-Match wins over a `version` field in the body
+fn expected_version(req: &HttpRequest, body_version: Option<i32>) -> std::result::Result<i32, ApiError> {
+    match req.headers().get(header::IF_MATCH) {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .and_then(edits::parse_if_match)
+            .ok_or_else(|| ApiError::invalid_parameter("If-Match must be a version number")),
+        None => body_version.ok_or_else(|| {
+            ApiError::new(ErrorCode::PreconditionRequired, "Edits require an If-Match header or a 'version' field")
+        }),
+    }
+}
+
+// audit_id is the audit entry of an update, see audit::record
+fn edit_outcome_response<T: Serialize>(
+    kind: &str,
+    id: i32,
+    outcome: EditOutcome<T>,
+    audit_id: Option<i64>,
+    version: impl Fn(&T) -> i32,
+) -> Result<HttpResponse, ApiError> {
+    match outcome {
+        EditOutcome::Updated(record) => Ok(HttpResponse::Ok()
+            .insert_header((header::ETAG, edits::etag(version(&record))))
+            .json(json!({"status": "success", kind: record, "audit_id": audit_id}))),
+        EditOutcome::Conflict(current) => Err(ApiError::new(
+            ErrorCode::Conflict,
+            format!("{} {} was changed by another write (now version {}); merge and retry", kind, id, version(&current)),
+        )
+        .with_header(header::ETAG, edits::etag(version(&current)))
+        .with_details(json!({"current": current}))),
+        EditOutcome::NotFound => Err(ApiError::not_found(format!("{} {} not found", kind, id))),
+    }
+}
+
+fn invalid_rate(errors: Vec<edits::FieldError>) -> ApiError {
+    ApiError::new(ErrorCode::InvalidBody, "Invalid rate").with_details(json!({"errors": errors}))
+}
+
+// How long /stats reuses its funds/scheme_rates counts, so polling it can't load Postgres
+const DB_COUNTS_TTL: Duration = Duration::from_secs(60);
+
+async fn cached_db_counts(state: &AppState) -> Result<DbCounts, Box<dyn std::error::Error>> {
+    if let Some(counts) = *state.db_counts.lock().unwrap() {
+        if counts.counted.elapsed() < DB_COUNTS_TTL {
+            return Ok(counts);
+        }
+    }
+
+    let client = state.pools.read_client().await?;
+    let row = client
+        .query_one(
+            "SELECT (SELECT COUNT(*) FROM funds) AS funds, (SELECT COUNT(*) FROM scheme_rates) AS scheme_rates",
+            &[],
+        )
+        .await?;
+    let counts = DbCounts {
+        funds: row.get("funds"),
+        scheme_rates: row.get("scheme_rates"),
+        counted_at: chrono::Utc::now(),
+        counted: std::time::Instant::now(),
+    };
+    *state.db_counts.lock().unwrap() = Some(counts);
+    Ok(counts)
+}
+
+fn pool_status(pool: &Pool) -> serde_json::Value {
+    let status = pool.status();
+    json!({
+        "max_size": status.max_size,
+        "size": status.size,
+        "available": status.available,
+        "waiting": status.waiting
+    })
+}
+
+// Apply every file (each in its own transaction, so one failing leaves the others applied),
+// then refresh the virtual table once for the whole batch
+async fn run_upload(
+    state: &AppState,
+    context: &api_error::RequestContext,
+    files: &[UploadedFile],
+    provider: Option<&str>,
+    rows_processed: &AtomicUsize,
+) -> Result<serde_json::Value, ApiError> {
+    let config = state.runtime_config.load_full();
+    let mut results = Vec::new();
+    let mut failures = Vec::new();
+    let mut processed = 0;
+    let mut touched_schemes = Vec::new();
+    for file in files {
+        let result = {
+            let _mutation = state.begin_mutation().await;
+            // An explicit provider applies to every file in the request
+            let source = UploadSource {
+                provider: providers::provider_key(provider, file.file_name.as_deref()),
+                file_name: file.file_name.clone(),
+            };
+            let started = std::time::Instant::now();
+            let result =
+                process_excel_file(file.temp_file.path(), &source, &config, state.pools.primary(), context, rows_processed).await;
+            let outcome = match &result {
+                Ok(report) if report.committed => {
+                    state.metrics.add_upload_rows("funds", report.inserted, report.updated, report.failed);
+                    "committed"
+                }
+                Ok(report) => {
+                    state.metrics.add_upload_rows("funds", 0, 0, report.processed + report.failed);
+                    "rolled_back"
+                }
+                Err(_) => "error",
+            };
+            state.metrics.observe_upload_file(outcome, started.elapsed());
+            result
+        };
+
+        match result {
+            Ok(mut report) if report.committed => {
+                processed += report.processed;
+                touched_schemes.append(&mut report.touched_schemes);
+                results.push(json!({
+                    "file_name": file.file_name,
+                    "status": "success",
+                    "committed": true,
+                    "message": format!("Processed {} fund records", report.processed),
+                    "report": report
+                }));
+            }
+            Ok(report) => {
+                let body = json!({
+                    "file_name": file.file_name,
+                    "status": "error",
+                    "committed": false,
+                    "message": format!(
+                        "Upload rolled back: {}",
+                        report.rollback_reason.as_deref().unwrap_or("too many rejected rows")
+                    ),
+                    "report": report
+                });
+                results.push(body.clone());
+                failures.push((ErrorCode::Unprocessable, body));
+            }
+            Err(e) => {
+                warn!("Upload of '{}' failed: {}", file.file_name.as_deref().unwrap_or("unnamed"), e);
+                let failure = upload_failure(file, "Error processing file; nothing was committed", e.as_ref());
+                results.push(failure.1.clone());
+                failures.push(failure);
+            }
+        }
+    }
+
+    // Only committed files changed anything, and only the funds they wrote need re-reading
+    let mut incomplete = None;
+    if failures.len() < files.len() {
+        touched_schemes.sort();
+        touched_schemes.dedup();
+        if let Err(e) = refresh_virtual_table_for(state, &touched_schemes).await {
+            warn!("Failed to refresh virtual table after upload: {}", e);
+        }
+        incomplete = Some(state.virtual_table.load().incomplete_count());
+    }
+
+    multi_upload_summary(results, failures, |succeeded, total| {
+        json!({
+            "message": format!(
+                "Processed {} fund records from {} of {} files and refreshed search index",
+                processed, succeeded, total
+            ),
+            "incomplete_records": incomplete
+        })
+    })
+}
+
+// Multipart field carrying workbook files, as named by the upload form
+const UPLOAD_FILE_FIELD: &str = "excel_file";
+
+// Log a payload error (usually the client going away) before it ends the request
+async fn next_upload_chunk(field: &mut actix_multipart::Field) -> Result<Option<web::Bytes>, ApiError> {
+    field.try_next().await.map_err(|e| {
+        warn!("Upload aborted while receiving data: {}", e);
+        ApiError::from(e)
+    })
+}
+
+fn upload_too_large(max_bytes: u64) -> ApiError {
+    ApiError::new(
+        ErrorCode::PayloadTooLarge,
+        format!("Upload exceeds the {} byte limit (max_upload_bytes)", max_bytes),
+    )
+    .with_details(json!({"max_upload_bytes": max_bytes}))
+}
+
+// One file field of a multipart upload, spooled to disk with its original extension
+struct UploadedFile {
+    file_name: Option<String>,
+    temp_file: NamedTempFile,
+    bytes: usize,
+}
+
+// Per-file error entry, with the error code the request would get if no file succeeded
+fn upload_failure(
+    file: &UploadedFile,
+    context: &str,
+    e: &(dyn std::error::Error + 'static),
+) -> (ErrorCode, serde_json::Value) {
+    if let Some(rejection) = e.downcast_ref::<CategoryRejection>() {
+        return (
+            ErrorCode::Unprocessable,
+            json!({
+                "file_name": file.file_name,
+                "status": "error",
+                "committed": false,
+                "message": "Upload rejected: categories outside the controlled vocabulary",
+                "category_issues": rejection.0
+            }),
+        );
+    }
+
+    let (code, message) = if db::is_pool_unavailable(e) {
+        (ErrorCode::DatabaseUnavailable, "Database is busy or unavailable, please retry shortly".to_string())
+    } else {
+        (ErrorCode::Internal, format!("{}: {}", context, e))
+    };
+    (code, json!({"file_name": file.file_name, "status": "error", "committed": false, "message": message}))
+}
+
+// "success" when every file went through and "partial" when some did, both 200. When none did,
+// the error of the first failure, with every file's entry under details.files. `summary` adds the
+// message and any extra fields.
+fn multi_upload_summary(
+    results: Vec<serde_json::Value>,
+    failures: Vec<(ErrorCode, serde_json::Value)>,
+    summary: impl FnOnce(usize, usize) -> serde_json::Value,
+) -> Result<serde_json::Value, ApiError> {
+    let total = results.len();
+    let succeeded = total - failures.len();
+    let mut body = summary(succeeded, total);
+    body["files"] = json!(results);
+
+    match failures.first() {
+        Some((code, failure)) if succeeded == 0 => {
+            let message = match (total, failure["message"].as_str()) {
+                (1, Some(message)) => message.to_string(),
+                _ => format!("None of the {} files could be applied; see details.files", total),
+            };
+            if let Some(fields) = body.as_object_mut() {
+                fields.remove("message");
+            }
+            Err(ApiError::new(*code, message).with_details(body))
+        }
+        _ => {
+            body["status"] = json!(if failures.is_empty() { "success" } else { "partial" });
+            Ok(body)
+        }
+    }
+}
+
+// Destructive admin endpoints act only with ?confirm=true
+fn require_confirmation(query: &HashMap<String, String>, what: &str) -> std::result::Result<(), ApiError> {
+    if query.get("confirm").map(|value| value.trim()) == Some("true") {
+        Ok(())
+    } else {
+        Err(ApiError::new(
+            ErrorCode::MissingParameter,
+            format!("This deletes {}; repeat the request with confirm=true", what),
+        )
+        .with_details(json!({"parameter": "confirm"})))
+    }
+}
+
+// Re-read preferences so admin changes apply to the next search without a restart
+async fn reload_category_preferences(state: &AppState, client: &Client) -> Result<(), tokio_postgres::Error> {
+    let preferences = preferences::load_preferences(client).await?;
+    state.category_preferences.store(Arc::new(preferences));
+    Ok(())
+}
+
+// Unmatched rates from the latest build paired with their closest fund names
+fn unmatched_rate_rows(state: &AppState) -> Vec<(UnmatchedRate, Vec<FundSuggestion>)> {
+    let budget = state.runtime_config.load().fuzzy_budget();
+    let virtual_table = state.virtual_table.load();
+
+    virtual_table
+        .unmatched_rates
+        .iter()
+        .map(|rate| {
+            let (suggestions, budget_exhausted) =
+                virtual_table.suggest_funds(&rate.scheme_name, rate_matches::SUGGESTIONS_PER_RATE, Some(budget));
+            if budget_exhausted {
+                state.budget_exhaustions.fetch_add(1, Ordering::Relaxed);
+            }
+            (rate.clone(), suggestions)
+        })
+        .collect()
+}
+
+// Validate confirmed names against existing funds and record them as scheme aliases
+async fn apply_rate_matches(
+    file_path: &Path,
+    pool: &Pool,
+) -> Result<(usize, Vec<rate_matches::RowError>), Box<dyn std::error::Error>> {
+    let (matches, mut errors) = rate_matches::parse_confirmed_matches(file_path)?;
+    let client = get_postgres_client(pool).await?;
+
+    let mut funds_by_name: HashMap<String, i32> = HashMap::new();
+    for row in client.query("SELECT id, scheme_name FROM funds", &[]).await? {
+        let scheme_name: String = row.get("scheme_name");
+        funds_by_name.insert(normalize_scheme_name(&scheme_name), row.get("id"));
+    }
+
+    let mut applied = 0;
+    for confirmed in matches {
+        let fund_id = match funds_by_name.get(&normalize_scheme_name(&confirmed.confirmed_fund_name)) {
+            Some(&fund_id) => fund_id,
+            None => {
+                errors.push(rate_matches::RowError {
+                    row: confirmed.row,
+                    rate_id: confirmed.rate_id,
+                    value: confirmed.confirmed_fund_name,
+                    reason: "Confirmed fund name does not match any existing fund".to_string(),
+                });
+                continue;
+            }
+        };
+
+        let result = client.execute(
+            "INSERT INTO scheme_aliases (alias_name, fund_id, source)
+             VALUES ($1, $2, 'rate-matches import')
+             ON CONFLICT (alias_name) DO UPDATE SET fund_id = EXCLUDED.fund_id, source = EXCLUDED.source",
+            &[&confirmed.rate_scheme_name, &fund_id],
+        ).await;
+
+        match result {
+            Ok(_) => applied += 1,
+            Err(e) => errors.push(rate_matches::RowError {
+                row: confirmed.row,
+                rate_id: confirmed.rate_id,
+                value: confirmed.confirmed_fund_name,
+                reason: format!("Failed to save alias: {}", e),
+            }),
+        }
+    }
+
+    errors.sort_by_key(|e| e.row);
+    Ok((applied, errors))
+}
+
+// Everything read out of one uploaded file, before anything touches the database
+struct ParsedUpload {
+    funds: Vec<FundData>,
+    sheet_errors: Vec<SheetError>,
+    row_errors: Vec<FundRowError>,
+    // Sheet -> the columns that fed it
+    column_mappings: BTreeMap<String, ColumnMapping>,
+    // Sheet -> column index -> header text as resolved, stacked headers combined
+    resolved_headers: BTreeMap<String, BTreeMap<usize, String>>,
+    sheets: Vec<sheets::SheetDecision>,
+    // Processed sheets with no category mapping, imported under their own name
+    unmapped_sheets: Vec<String>,
+}
+
+// Where an upload came from, recorded alongside its column mappings
+struct UploadSource {
+    file_name: Option<String>,
+    provider: String,
+}
+
+fn parse_upload(
+    file_path: &Path,
+    file_name: Option<&str>,
+    config: &RuntimeConfig,
+    category_mappings: &categories::CategoryMappings,
+) -> Result<ParsedUpload, Box<dyn std::error::Error>> {
+    let mut parsed = ParsedUpload {
+        funds: Vec::new(),
+        sheet_errors: Vec::new(),
+        row_errors: Vec::new(),
+        column_mappings: BTreeMap::new(),
+        resolved_headers: BTreeMap::new(),
+        sheets: Vec::new(),
+        unmapped_sheets: Vec::new(),
+    };
+
+    // The mapped category, or the sheet name itself with the sheet flagged as unmapped
+    let mut category_for = |sheet: &str| match categories::map_sheet(sheet, category_mappings) {
+        Some(category) => category.to_string(),
+        None => {
+            parsed.unmapped_sheets.push(sheet.to_string());
+            sheet.to_string()
+        }
+    };
+
+    if csv_import::is_csv(file_name, file_path)? {
+        // A CSV file is a single sheet; its name stands in for the sheet name
+        let sheet = csv_import::category_from_file_name(file_name);
+        let category = category_for(&sheet);
+        info!("Processing CSV file '{}' as category: {}", sheet, category);
+        parsed.sheets.push(sheets::SheetDecision::new(&sheet, None));
+        match csv_import::extract_fund_data(&sheet, &category, file_path) {
+            Ok((mut records, mut errors, columns)) => {
+                info!(
+                    "Collected {} records from CSV ({} malformed rows skipped)",
+                    records.len(),
+                    errors.len()
+                );
+                parsed.funds.append(&mut records);
+                parsed.row_errors.append(&mut errors);
+                parsed.resolved_headers.insert(sheet.clone(), columns.resolved_headers());
+                parsed.column_mappings.insert(sheet, columns.mapping().clone());
+            }
+            Err(message) => {
+                warn!("Skipping CSV file '{}': {}", sheet, message);
+                parsed.sheet_errors.push(SheetError { sheet, message });
+            }
+        }
+        return Ok(parsed);
+    }
+
+    let mut workbook = workbook::open(file_path)?;
+
+    // Process Excel sheets
+    for sheet_name in workbook.sheet_names().clone() {
+        let skip_reason = config.sheets.skip_reason(&sheet_name);
+        parsed.sheets.push(sheets::SheetDecision::new(&sheet_name, skip_reason.as_deref()));
+        if let Some(reason) = skip_reason {
+            info!("Skipping sheet '{}': {}", sheet_name, reason);
+            continue;
+        }
+
+        let category = category_for(&sheet_name);
+        info!("Processing sheet: {} (category: {})", sheet_name, category);
+
+        if let Ok(range) = workbook.worksheet_range(&sheet_name) {
+            match extract_fund_data(&sheet_name, &category, &range) {
+                Ok((mut records, mut errors, columns)) => {
+                    info!("Collected {} records from sheet: {}", records.len(), sheet_name);
+                    parsed.funds.append(&mut records);
+                    parsed.row_errors.append(&mut errors);
+                    parsed.resolved_headers.insert(sheet_name.clone(), columns.resolved_headers());
+                    parsed.column_mappings.insert(sheet_name, columns.mapping().clone());
+                }
+                Err(message) => {
+                    warn!("Skipping sheet '{}': {}", sheet_name, message);
+                    parsed.sheet_errors.push(SheetError { sheet: sheet_name, message });
+                }
+            }
+        }
+    }
+
+    Ok(parsed)
+}
+
+async fn process_excel_file(
+    file_path: &Path,
+    source: &UploadSource,
+    config: &RuntimeConfig,
+    pool: &Pool,
+    // Recorded on the upload so a user's report leads to its log lines
+    context: &api_error::RequestContext,
+    // Bumped as each row is applied, for job progress
+    rows_processed: &AtomicUsize,
+) -> Result<UploadReport, Box<dyn std::error::Error>> {
+    let mut client = get_postgres_client(pool).await?;
+    let category_mappings = categories::load_mappings(&client).await?;
+    let parsed = parse_upload(file_path, source.file_name.as_deref(), config, &category_mappings)?;
+
+    // Merge duplicates, enforce the category vocabulary and insert
+    let (mut unique_funds, merged_schemes) = merge_duplicates(parsed.funds);
+    let vocabulary = categories::load_vocabulary(&client).await?;
+    let category_issues = categories::apply_vocabulary(&mut unique_funds, &vocabulary, config.category_validation)
+        .map_err(CategoryRejection)?;
+
+    let sha256 = uploads::file_sha256(file_path)?;
+    let new_upload = uploads::NewUpload {
+        provider: &source.provider,
+        file_name: source.file_name.as_deref(),
+        sha256: &sha256,
+        column_mappings: &parsed.column_mappings,
+        request_id: &context.request_id,
+    };
+
+    // One transaction per upload: if the handler is dropped (e.g. shutdown grace period expired)
+    // the uncommitted transaction is rolled back, never left half-applied or open
+    let mut transaction = client.transaction().await?;
+    let upload_id = uploads::begin(&*transaction, &new_upload).await?;
+    history::record_writes(&transaction, upload_id).await?;
+    let attempted = unique_funds.len();
+    let outcome = insert_fund_data(&mut transaction, unique_funds, upload_id, rows_processed).await?;
+    let processed = outcome.inserted + outcome.updated;
+
+    // Too many rejected rows means the file is wrong, not a few rows: keep the previous data whole
+    let failure_ratio = if attempted == 0 { 0.0 } else { outcome.failed.len() as f64 / attempted as f64 };
+    let rollback_reason = if failure_ratio > config.max_upload_failure_ratio {
+        Some(format!(
+            "{} of {} rows were rejected, more than max_upload_failure_ratio {}",
+            outcome.failed.len(),
+            attempted,
+            config.max_upload_failure_ratio
+        ))
+    } else {
+        None
+    };
+
+    let mut row_errors = parsed.row_errors;
+    row_errors.extend(outcome.failed);
+    let failed = row_errors.iter().filter(|error| error.skipped).count();
+    let error_count = row_errors.len();
+    row_errors.truncate(UPLOAD_ERROR_LIMIT);
+
+    let counts = uploads::UploadCounts {
+        processed,
+        inserted: outcome.inserted,
+        updated: outcome.updated,
+        failed,
+    };
+    let upload_id = match &rollback_reason {
+        Some(reason) => {
+            warn!("Rolling back upload of {:?}: {}", source.file_name, reason);
+            transaction.rollback().await?;
+            uploads::record_rolled_back(&client, &new_upload, counts).await?
+        }
+        None => {
+            uploads::finish(&*transaction, upload_id, counts, uploads::STATUS_COMMITTED).await?;
+            transaction.commit().await?;
+            upload_id
+        }
+    };
+
+    Ok(UploadReport {
+        upload_id,
+        committed: rollback_reason.is_none(),
+        touched_schemes: if rollback_reason.is_none() { outcome.touched } else { Vec::new() },
+        rollback_reason,
+        processed,
+        inserted: outcome.inserted,
+        updated: outcome.updated,
+        skipped_duplicate: merged_schemes.iter().map(|merge| merge.rows_merged).sum(),
+        merged_count: merged_schemes.len(),
+        merged_schemes,
+        failed,
+        category_issues,
+        sheet_errors: parsed.sheet_errors,
+        error_count,
+        row_errors,
+        provider: source.provider.clone(),
+        column_mappings: parsed.column_mappings,
+        sheets: parsed.sheets,
+        unmapped_sheets: parsed.unmapped_sheets,
+    })
+}
+
+// What an upload would do, computed without writing anything
+#[derive(Debug, Serialize)]
+struct DryRunReport {
+    // Parsed rows per sheet, before duplicates are merged
+    sheet_rows: BTreeMap<String, usize>,
+    new_schemes: usize,
+    updated_schemes: usize,
+    merged_count: usize,
+    merged_schemes: Vec<MergedScheme>,
+    row_errors: Vec<FundRowError>,
+    sheet_errors: Vec<SheetError>,
+    category_issues: Vec<categories::CategoryIssue>,
+    // Strict category validation would reject the real upload
+    would_reject: bool,
+    column_mappings: BTreeMap<String, ColumnMapping>,
+    // Debugging aid for header detection: sheet -> column index -> resolved header text
+    resolved_headers: BTreeMap<String, BTreeMap<usize, String>>,
+    sheets: Vec<sheets::SheetDecision>,
+    unmapped_sheets: Vec<String>,
+}
+
+// Same parse, merge and vocabulary steps as process_excel_file, but only reads the database
+async fn validate_excel_file(
+    file_path: &Path,
+    file_name: Option<&str>,
+    config: &RuntimeConfig,
+    pool: &Pool,
+) -> Result<DryRunReport, Box<dyn std::error::Error>> {
+    let client = get_postgres_client(pool).await?;
+    let category_mappings = categories::load_mappings(&client).await?;
+    let parsed = parse_upload(file_path, file_name, config, &category_mappings)?;
+
+    let mut sheet_rows = BTreeMap::new();
+    for fund in &parsed.funds {
+        *sheet_rows.entry(fund.sheet.clone()).or_insert(0) += 1;
+    }
+
+    let (mut unique_funds, merged_schemes) = merge_duplicates(parsed.funds);
+    let vocabulary = categories::load_vocabulary(&client).await?;
+    let (category_issues, would_reject) =
+        match categories::apply_vocabulary(&mut unique_funds, &vocabulary, config.category_validation) {
+            Ok(issues) => (issues, false),
+            Err(issues) => (issues, true),
+        };
+
+    // insert_fund_data updates by cleaned scheme name, so match on the same form
+    let names: Vec<String> = unique_funds
+        .iter()
+        .map(|fund| clean_scheme_name(fund.scheme_name.clone()))
+        .collect();
+    let existing: std::collections::HashSet<String> = client
+        .query("SELECT scheme_name FROM funds WHERE scheme_name = ANY($1)", &[&names])
+        .await?
+        .iter()
+        .map(|row| row.get("scheme_name"))
+        .collect();
+    let updated_schemes = names.iter().filter(|name| existing.contains(*name)).count();
+
+    Ok(DryRunReport {
+        sheet_rows,
+        new_schemes: names.len() - updated_schemes,
+        updated_schemes,
+        merged_count: merged_schemes.len(),
+        merged_schemes,
+        row_errors: parsed.row_errors,
+        sheet_errors: parsed.sheet_errors,
+        category_issues,
+        would_reject,
+        column_mappings: parsed.column_mappings,
+        resolved_headers: parsed.resolved_headers,
+        sheets: parsed.sheets,
+        unmapped_sheets: parsed.unmapped_sheets,
+    })
+}
+
+// Strict-mode upload failure carrying every out-of-vocabulary category
+#[derive(Debug)]
+struct CategoryRejection(Vec<categories::CategoryIssue>);
+
+impl std::fmt::Display for CategoryRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} rows have categories outside the controlled vocabulary", self.0.len())
+    }
+}
+
+impl std::error::Error for CategoryRejection {}
+
+// Helper functions (keeping the existing logic but adapting for PostgreSQL)
+pub fn normalize_scheme_name(scheme_name: &str) -> String {
+    scheme_name
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim()
+        .to_string()
+}
+
+// Rows of one sheet filed under `category` (the sheet name unless a category mapping says
+// otherwise), the non-blank rows skipped and the columns they came from; Err describes why the
+// whole sheet was unusable. The header may span two rows, see ColumnMap::from_header_rows.
+fn extract_fund_data(
+    sheet: &str,
+    category: &str,
+    range: &Range<Data>,
+) -> Result<(Vec<FundData>, Vec<FundRowError>, ColumnMap), String> {
+    let mut funds = Vec::new();
+    let mut errors = Vec::new();
+    let header_row_idx = find_header_row(range)?;
+    let (columns, first_data_row) = ColumnMap::from_header_rows(sheet, range, header_row_idx)?;
+
+    for row_idx in first_data_row..range.height() {
+        if is_blank_row(range, row_idx) {
+            continue;
+        }
+        if let Some(fund) = parse_fund_row(sheet, category, range, &columns, row_idx, &mut errors) {
+            funds.push(fund);
+        }
+    }
+
+    Ok((funds, errors, columns))
+}
+
+fn is_blank_row(range: &Range<Data>, row: usize) -> bool {
+    (0..range.width()).all(|col| range.get((row, col)).is_none_or(|cell| cell.to_string().trim().is_empty()))
+}
+
+fn find_header_row(range: &Range<Data>) -> Result<usize, String> {
+    for row_idx in 0..std::cmp::min(15, range.height()) {
+        for col in 0..range.width() {
+            if let Some(cell) = range.get((row_idx, col)) {
+                if columns::is_scheme_name_header(&cell.to_string()) {
+                    return Ok(row_idx);
+                }
+            }
+        }
+    }
+    Err("header row not found (no 'Scheme Name' column in the first 15 rows)".to_string())
+}
+
+fn parse_fund_row(
+    sheet: &str,
+    category: &str,
+    range: &Range<Data>,
+    columns: &ColumnMap,
+    row_idx: usize,
+    errors: &mut Vec<FundRowError>,
+) -> Option<FundData> {
+    fund_from_cells(sheet, category, row_idx + 1, |column| columns.get(range, row_idx, column), errors)
+}
+
+// Shared by the Excel and CSV importers; `cell` looks up one column of the current row. A missing
+// name or launch date drops the row; an unparsable number only empties that field. Both are
+// reported in `errors`.
+fn fund_from_cells<'a>(
+    sheet: &str,
+    category: &str,
+    row: usize,
+    cell: impl Fn(FundColumn) -> Option<&'a Data>,
+    errors: &mut Vec<FundRowError>,
+) -> Option<FundData> {
+    let scheme_name = cell(FundColumn::SchemeName).map(|c| c.to_string()).unwrap_or_default();
+    let launch_cell = cell(FundColumn::LaunchDate);
+    let launch_text = launch_cell.map(|c| c.to_string()).unwrap_or_default();
+
+    let mut report = |scheme_name: Option<&str>, reason: String, skipped: bool| {
+        errors.push(FundRowError {
+            sheet: sheet.to_string(),
+            row,
+            scheme_name: scheme_name.map(str::to_string),
+            reason,
+            skipped,
+        })
+    };
+
+    if scheme_name.trim().is_empty() {
+        report(None, "empty Scheme Name".to_string(), true);
+        return None;
+    }
+    if launch_text.trim().is_empty() {
+        report(Some(&scheme_name), "empty Launch Date".to_string(), true);
+        return None;
+    }
+    let launch_date = match dates::parse_date(launch_cell) {
+        Ok(date) => Some(date),
+        Err(reason) => {
+            report(Some(&scheme_name), format!("Launch Date {} (stored as empty)", reason), false);
+            None
+        }
+    };
+
+    let mut value = |column: FundColumn| {
+        let raw = cell(column);
+        let parsed = parse_float_option(raw);
+        if let (None, Some(raw)) = (parsed, raw) {
+            let text = raw.to_string();
+            if !is_empty_value(&text) {
+                let reason = format!("unparsable number in '{}': '{}' (stored as empty)", column.label(), text.trim());
+                report(Some(&scheme_name), reason, false);
+            }
+        }
+        parsed
+    };
+
+    Some(FundData {
+        sheet: sheet.to_string(),
+        row,
+        category: category.to_string(),
+        scheme_name: scheme_name.clone(),
+        launch_date,
+        fund_size_apr25: value(FundColumn::FundSizeApr25),
+        fund_size_may25: value(FundColumn::FundSizeMay25),
+        latest_nav: value(FundColumn::LatestNav),
+        month_1: value(FundColumn::Month1),
+        months_3: value(FundColumn::Months3),
+        months_6: value(FundColumn::Months6),
+        ytd: value(FundColumn::Ytd),
+        year_1: value(FundColumn::Year1),
+        years_2: value(FundColumn::Years2),
+        years_3: value(FundColumn::Years3),
+        years_5: value(FundColumn::Years5),
+    })
+}
+
+// First occurrence of each scheme wins; later ones are returned separately
+// A scheme listed more than once in an upload (e.g. in its category sheet and in "All Funds")
+#[derive(Debug, Serialize, ToSchema)]
+struct MergedScheme {
+    scheme_name: String,
+    // First occurrence, then every later one that filled in a missing value
+    sheets: Vec<String>,
+    // Rows folded into the first occurrence
+    rows_merged: usize,
+    // Fields the first occurrence lacked and a later one supplied
+    #[schema(value_type = Vec<String>)]
+    filled_fields: Vec<&'static str>,
+}
+
+// One row per normalized cleaned name: the first occurrence wins, and its missing values are
+// filled from later occurrences in file order. Returns the merged rows and what was merged into
+// which. insert_fund_data upserts on the cleaned name, so two rows that clean to one name
+// ("X Fund - Growth", "X Fund - Reg - Growth") must merge here or they'd collide in one batch.
+fn merge_duplicates(all_funds: Vec<FundData>) -> (Vec<FundData>, Vec<MergedScheme>) {
+    let mut unique_funds: Vec<FundData> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut merges: BTreeMap<usize, MergedScheme> = BTreeMap::new();
+
+    for fund in all_funds {
+        let normalized = normalize_scheme_name(&clean_scheme_name(fund.scheme_name.clone()));
+        let idx = match positions.get(&normalized) {
+            Some(&idx) => idx,
+            None => {
+                positions.insert(normalized, unique_funds.len());
+                unique_funds.push(fund);
+                continue;
+            }
+        };
+
+        let target = &mut unique_funds[idx];
+        let merge = merges.entry(idx).or_insert_with(|| MergedScheme {
+            scheme_name: target.scheme_name.clone(),
+            sheets: vec![target.sheet.clone()],
+            rows_merged: 0,
+            filled_fields: Vec::new(),
+        });
+        merge.rows_merged += 1;
+
+        let filled_before = merge.filled_fields.len();
+        macro_rules! fill {
+            ($($field:ident),+) => {
+                $(
+                    if target.$field.is_none() && fund.$field.is_some() {
+                        target.$field = fund.$field;
+                        merge.filled_fields.push(stringify!($field));
+                    }
+                )+
+            };
+        }
+        fill!(
+            launch_date,
+            fund_size_apr25,
+            fund_size_may25,
+            latest_nav,
+            month_1,
+            months_3,
+            months_6,
+            ytd,
+            year_1,
+            years_2,
+            years_3,
+            years_5
+        );
+        if merge.filled_fields.len() > filled_before && !merge.sheets.contains(&fund.sheet) {
+            merge.sheets.push(fund.sheet);
+        }
+    }
+
+    (unique_funds, merges.into_values().collect())
+}
+
+// Updated parse functions to handle None values properly
+fn parse_float_option(cell: Option<&Data>) -> Option<f32> {
+    match cell {
+        Some(Data::Float(f)) => {
+            if f.is_finite() && !f.is_nan() {
+                Some(*f as f32)
+            } else {
+                None
+            }
+        },
+        Some(Data::Int(i)) => Some(*i as f32),
+        // Numbers stored as text keep their display formatting. Floats above are never rescaled:
+        // a cell formatted as a percentage arrives as its 0-1 value and is stored as such.
+        Some(val) => {
+            let s_owned = val.to_string();
+            let s = s_owned.trim();
+            if is_empty_value(s) {
+                None
+            } else {
+                parse_formatted_number(s)
+            }
+        },
+        None => None,
+    }
+}
+
+// "12.45%", "1,23,456.78" (any digit grouping, commas or thin spaces) and "(3.2)" for -3.2.
+// The percent sign is only dropped: "12.45%" is 12.45, the unit the return columns use.
+fn parse_formatted_number(text: &str) -> Option<f32> {
+    let (negative, text) = match text.strip_prefix('(').and_then(|rest| rest.strip_suffix(')')) {
+        Some(inner) => (true, inner.trim()),
+        None => (false, text),
+    };
+    let text = text.strip_suffix('%').unwrap_or(text).trim_end();
+    let digits: String = text
+        .chars()
+        .filter(|c| !matches!(c, ',' | '\u{2009}' | '\u{202F}' | '\u{00A0}'))
+        .collect();
+    let value: f32 = digits.parse().ok()?;
+    if !value.is_finite() {
+        return None;
+    }
+    Some(if negative { -value } else { value })
+}
+
+// Cell contents parse_float_option deliberately reads as "no value"
+fn is_empty_value(text: &str) -> bool {
+    let text = text.trim();
+    text.is_empty() || text == "N/A" || text == "-"
+}
+
+// Fixed insert function with proper error handling
+#[derive(Debug, Default)]
+struct InsertOutcome {
+    inserted: usize,
+    updated: usize,
+    // Cleaned scheme names of the stored rows, for the incremental table update
+    touched: Vec<String>,
+    // Rows the database rejected; the rest of the upload still commits
+    failed: Vec<FundRowError>,
+}
+
+// Rows per UNNEST upsert. A batch the database rejects is retried row by row so only the bad
+// rows are reported.
+const INSERT_BATCH_SIZE: usize = 500;
+
+async fn insert_fund_data(
+    transaction: &mut Transaction<'_>,
+    mut funds: Vec<FundData>,
+    upload_id: i32,
+    rows_processed: &AtomicUsize,
+) -> Result<InsertOutcome, Box<dyn std::error::Error>> {
+    let mut outcome = InsertOutcome::default();
+
+    // Clean the scheme names before insertion or update
+    for fund in &mut funds {
+        fund.scheme_name = clean_scheme_name(std::mem::take(&mut fund.scheme_name));
+    }
+
+    for batch in funds.chunks(INSERT_BATCH_SIZE) {
+        // The savepoint keeps a rejected batch from aborting the whole upload transaction
+        let savepoint = transaction.savepoint("fund_batch").await?;
+        match upsert_funds(&savepoint, batch, upload_id).await {
+            Ok((inserted, updated)) => {
+                savepoint.commit().await?;
+                outcome.inserted += inserted;
+                outcome.updated += updated;
+                outcome.touched.extend(batch.iter().map(|fund| fund.scheme_name.clone()));
+                rows_processed.fetch_add(inserted + updated, Ordering::Relaxed);
+                continue;
+            }
+            Err(e) => {
+                savepoint.rollback().await?;
+                warn!("Batch of {} funds rejected ({}), retrying row by row", batch.len(), e);
+            }
+        }
+
+        for fund in batch {
+            let savepoint = transaction.savepoint("fund_row").await?;
+            match upsert_funds(&savepoint, std::slice::from_ref(fund), upload_id).await {
+                Ok((inserted, updated)) => {
+                    savepoint.commit().await?;
+                    outcome.inserted += inserted;
+                    outcome.updated += updated;
+                    outcome.touched.push(fund.scheme_name.clone());
+                    rows_processed.fetch_add(inserted + updated, Ordering::Relaxed);
+                }
+                Err(e) => {
+                    // Report the row but continue processing other records
+                    savepoint.rollback().await?;
+                    warn!("Failed to insert fund '{}': {}", fund.scheme_name, e);
+                    let reason = match e.as_db_error() {
+                        Some(db_error) => format!("database rejected the row: {}", db_error.message()),
+                        None => format!("database error: {}", e),
+                    };
+                    outcome.failed.push(FundRowError {
+                        sheet: fund.sheet.clone(),
+                        row: fund.row,
+                        scheme_name: Some(fund.scheme_name.clone()),
+                        reason,
+                        skipped: true,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(outcome)
+}
+
+// One round trip for the whole slice: insert new scheme names, update existing ones. Conflicts
+// on unique_scheme_name (guaranteed by migration 10), so there is no update-then-insert race.
+// Returns (inserted, updated); xmax is 0 only on rows this statement inserted.
+async fn upsert_funds(
+    transaction: &Transaction<'_>,
+    funds: &[FundData],
+    upload_id: i32,
+) -> Result<(usize, usize), tokio_postgres::Error> {
+    let column = |value: fn(&FundData) -> Option<f32>| funds.iter().map(value).collect::<Vec<_>>();
+    let categories: Vec<&str> = funds.iter().map(|fund| fund.category.as_str()).collect();
+    let scheme_names: Vec<&str> = funds.iter().map(|fund| fund.scheme_name.as_str()).collect();
+    let launch_dates: Vec<Option<NaiveDate>> = funds.iter().map(|fund| fund.launch_date).collect();
+    let fund_size_apr25 = column(|fund| fund.fund_size_apr25);
+    let fund_size_may25 = column(|fund| fund.fund_size_may25);
+    let latest_nav = column(|fund| fund.latest_nav);
+    let month_1 = column(|fund| fund.month_1);
+    let months_3 = column(|fund| fund.months_3);
+    let months_6 = column(|fund| fund.months_6);
+    let ytd = column(|fund| fund.ytd);
+    let year_1 = column(|fund| fund.year_1);
+    let years_2 = column(|fund| fund.years_2);
+    let years_3 = column(|fund| fund.years_3);
+    let years_5 = column(|fund| fund.years_5);
+
+    let rows = transaction
+        .query(
+            "INSERT INTO funds (
+                category, scheme_name, launch_date, fund_size_apr25, fund_size_may25,
+                latest_nav, month_1, months_3, months_6, ytd, year_1, years_2, years_3, years_5, upload_id
+            )
+            SELECT *, $15::INTEGER FROM UNNEST(
+                $1::TEXT[], $2::TEXT[], $3::DATE[], $4::REAL[], $5::REAL[], $6::REAL[], $7::REAL[],
+                $8::REAL[], $9::REAL[], $10::REAL[], $11::REAL[], $12::REAL[], $13::REAL[], $14::REAL[]
+            )
+            ON CONFLICT (scheme_name) DO UPDATE SET
+                category = EXCLUDED.category,
+                launch_date = EXCLUDED.launch_date,
+                fund_size_apr25 = EXCLUDED.fund_size_apr25,
+                fund_size_may25 = EXCLUDED.fund_size_may25,
+                latest_nav = EXCLUDED.latest_nav,
+                month_1 = EXCLUDED.month_1,
+                months_3 = EXCLUDED.months_3,
+                months_6 = EXCLUDED.months_6,
+                ytd = EXCLUDED.ytd,
+                year_1 = EXCLUDED.year_1,
+                years_2 = EXCLUDED.years_2,
+                years_3 = EXCLUDED.years_3,
+                years_5 = EXCLUDED.years_5,
+                upload_id = EXCLUDED.upload_id,
+                version = funds.version + 1
+            RETURNING (xmax = 0) AS inserted",
+            &[
+                &categories,
+                &scheme_names,
+                &launch_dates,
+                &fund_size_apr25,
+                &fund_size_may25,
+                &latest_nav,
+                &month_1,
+                &months_3,
+                &months_6,
+                &ytd,
+                &year_1,
+                &years_2,
+                &years_3,
+                &years_5,
+                &upload_id,
+            ],
+        )
+        .await?;
+
+    let inserted = rows.iter().filter(|row| row.get::<_, bool>("inserted")).count();
+    Ok((inserted, rows.len() - inserted))
+}
+
+fn clean_scheme_name(mut name: String) -> String {
+    // Step 1: Initial trim of whitespace and special characters
+    // Remove leading special characters
+    while let Some(first_char) = name.chars().next() {
+        if first_char.is_alphanumeric() {
+            break;
+        }
+        name = name.chars().skip(1).collect();
+    }
+
+    // Remove trailing special characters
+    while let Some(last_char) = name.chars().last() {
+        if last_char.is_alphanumeric() {
+            break;
+        }
+        name = name.chars().take(name.len() - 1).collect();
+    }
+
+    // Trim whitespace and normalize multiple spaces
+    name = name.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    // Step 2: Remove specific strings globally (anywhere in the string)
+    let global_remove = [
+        "- Reg - Growth",// Common parenthetical terms
+        " - Reg - Growth",// Common parenthetical terms
+        "- Reg - Gth",
+        " - Reg - Gth",
+        " - Reg - G P",
+        "- Reg - G P",
+        " - Reg ",
+        "- Reg ",
+        "-Reg-Growth",
+    ];
+
+    for pattern in global_remove.iter() {
+        name = name.replace(pattern, "");
+    }
+
+    // Step 2: Remove specific suffixes (in order of preference, longest to shortest)
+    let suffixes = [
+        "- Reg - Growth",
+        "- Reg - Gth",
+        "- Growth",
+        "-Reg",
+        "-Reg ",
+        "-Growth",
+        "Growth",
+        "- Reg",
+        "Regular",
+        " Regular",
+        "- Reg - G P",
+        " - Reg",
+        " - Reg - G P",
+        " - Reg - Growth (Re-launched",
+        " - Regular",
+        " -Reg",
+        " - Reg ",
+        "- Regular",
+        "- Regular ",
+        " - Regular ",
+        " -Reg ",
+        "-Reg-Growth",
+    ];
+
+    for suffix in suffixes.iter() {
+        name = name.strip_suffix(suffix).unwrap_or(&name).to_string();
+    }
+
+    // Step 4: Second trim to clean up residual whitespace or special characters
+    // Remove leading special characters
+    while let Some(first_char) = name.chars().next() {
+        if first_char.is_alphanumeric() {
+            break;
+        }
+        name = name.chars().skip(1).collect();
+    }
+
+    // Remove trailing special characters
+    while let Some(last_char) = name.chars().last() {
+        if last_char.is_alphanumeric() {
+            break;
+        }
+        name = name.chars().take(name.len() - 1).collect();
+    }
+
+    // Final trim and normalize multiple spaces
+    name.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// Every route and middleware of the server; the tests run requests through it too. `https`
+// adds HSTS.
+fn app(
+    state: AppState,
+    https: bool,
+) -> App<
+    impl actix_web::dev::ServiceFactory<
+        actix_web::dev::ServiceRequest,
+        Config = (),
+        Response = actix_web::dev::ServiceResponse<impl actix_web::body::MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    App::new()
+        .app_data(web::Data::new(state))
+        // Batched name lookups carry up to 1,000 names, well past the 32KB default
+        .app_data(web::JsonConfig::default().limit(1024 * 1024).error_handler(api_error::invalid_json))
+        .app_data(web::QueryConfig::default().error_handler(api_error::invalid_query))
+        .app_data(web::PathConfig::default().error_handler(api_error::invalid_path))
+        .wrap(Logger::default())
+        .wrap(Condition::new(
+            https,
+            DefaultHeaders::new().add((header::STRICT_TRANSPORT_SECURITY, tls::STRICT_TRANSPORT_SECURITY)),
+        ))
+        .wrap_fn(metrics::record_request)
+        // Outermost, so even an error rendered by another middleware carries the request id
+        .wrap_fn(|req, srv| {
+            let request_id = api_error::assign_request_id(&req);
+            // The inner middleware log (and may reject) before the handler's future is polled
+            let response = api_error::in_request(request_id.clone(), || srv.call(req));
+            api_error::with_request_id(request_id.clone(), async move {
+                let mut response = response.await?;
+                if let Ok(value) = header::HeaderValue::from_str(&request_id) {
+                    response.headers_mut().insert(header::HeaderName::from_static("x-request-id"), value);
+                }
+                Ok::<_, actix_web::Error>(response)
+            })
+        })
+        .route("/", web::get().to(upload_page))
+        .route("/swagger", web::get().to(swagger_redirect))
+        .route("/metrics", web::get().to(metrics::serve))
+        .service(SwaggerUi::new("/swagger/{_:.*}").url("/api-docs/openapi.json", openapi::ApiDoc::openapi()))
+        .service(
+            web::scope(routes::V1_PREFIX)
+                .wrap_fn(rate_limit::limit_requests)
+                // Registered after the limiter so it runs first and the limiter can key on the user
+                .wrap_fn(auth::require_credentials)
+                .configure(routes::v1::configure),
+        )
+        // The unprefixed routes, answered by the same v1 handlers for clients that predate the
+        // prefix. An empty scope matches every path, so it has to come last.
+        .service(
+            web::scope("")
+                .wrap_fn(|req, srv| {
+                    let path = req.path().to_string();
+                    let response = srv.call(req);
+                    async move {
+                        let mut response = response.await?;
+                        routes::mark_deprecated(&mut response, &path);
+                        Ok(response)
+                    }
+                })
+                .wrap_fn(rate_limit::limit_requests)
+                .wrap_fn(auth::require_credentials)
+                .configure(routes::v1::configure),
+        )
+        .default_service(web::to(routes::unknown_route))
+}
+
+// The whole server, as main.rs runs it; the library exists so benches can reach the table and
+// the load generator can reuse the workbook builder and response models
+pub async fn run() -> std::io::Result<()> {
+    // The default format plus the request id, when the line was logged while handling a request
+    env_logger::Builder::from_default_env()
+        .format(|buf, record| {
+            let request_id = api_error::request_id().map_or_else(String::new, |id| format!(" {}", id));
+            writeln!(
+                buf,
+                "[{} {} {}{}] {}",
+                buf.timestamp(),
+                buf.default_styled_level(record.level()),
+                record.target(),
+                request_id,
+                record.args()
+            )
+        })
+        .init();
+
+    // `perftracker import --file ...` runs one import and exits instead of serving
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let import_args = match import_cli::parse_args(&args) {
+        Ok(import_args) => import_args,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(import_cli::EXIT_FAILURE);
+        }
+    };
+
+    // Load configuration; an invalid file at startup is fatal rather than silently defaulted
+    let config_path = config::config_path_from_env();
+    let (static_config, runtime_config) = match config::load(config_path.as_deref()) {
+        Ok(loaded) => loaded,
+        Err(errors) => {
+            for e in &errors {
+                error!("Invalid configuration: {}", e);
+            }
+            std::process::exit(1);
+        }
+    };
+    if runtime_config.api_keys.is_empty() && runtime_config.jwt.is_none() {
+        warn!("No API keys or JWT configured; every upload, refresh and other write will be refused with 401");
+    } else {
+        let keys: Vec<String> = runtime_config
+            .api_keys
+            .iter()
+            .map(|key| format!("{} ({})", key.id, key.role.name()))
+            .collect();
+        info!(
+            "Accepting API keys {:?} and {} bearer tokens (reads {})",
+            keys,
+            runtime_config.jwt.as_ref().map_or("no".to_string(), |jwt| jwt.describe()),
+            if runtime_config.require_api_key_for_reads { "need credentials too" } else { "are open" }
+        );
+    }
+    let bind_addr = static_config.bind_addr;
+    let port = static_config.port;
+    let workers = static_config.workers;
+    let shutdown_grace_secs = static_config.shutdown_grace_secs;
+
+    // Unreadable files or a key for another certificate are fatal rather than a server nobody can reach
+    let tls_config = match &static_config.tls {
+        Some(files) => match tls::server_config(files) {
+            Ok(tls_config) => {
+                info!("Serving HTTPS with {}", files.cert_path.display());
+                Some(tls_config)
+            }
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let https = tls_config.is_some();
+
+    // Database settings come from the environment and are validated before anything else starts
+    let db_config = match db::config_from_env() {
+        Ok(db_config) => db_config,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let replica_config = match db::replica_config_from_env(&db_config) {
+        Ok(replica_config) => replica_config,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Create application state
+    let tls_settings = match db::tls_from_env(&db_config) {
+        Ok(tls_settings) => tls_settings,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    info!("Database TLS mode: {:?}", tls_settings.mode);
+
+    let pool = match db::create_pool(
+        db_config,
+        &tls_settings,
+        static_config.pool_size,
+        std::time::Duration::from_secs(static_config.pool_wait_timeout_secs),
+    ) {
+        Ok(pool) => pool,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    info!("Database pool sized at {} connections", static_config.pool_size);
+
+    let replica_pool = match replica_config {
+        Some(replica_config) => match db::create_pool(
+            replica_config,
+            &tls_settings,
+            static_config.pool_size,
+            std::time::Duration::from_secs(static_config.pool_wait_timeout_secs),
+        ) {
+            Ok(replica_pool) => {
+                info!("Read replica configured for virtual table builds and read-only endpoints");
+                Some(replica_pool)
+            }
+            Err(e) => {
+                error!("Replica: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let pools = db::DbPools::new(pool, replica_pool);
+
+    let artifact_store = match &static_config.export_dir {
+        Some(dir) => match artifacts::ArtifactStore::new(dir.clone(), static_config.export_keep_generations) {
+            Ok(store) => Some(store),
+            Err(e) => {
+                error!("Cannot use export_dir {}: {}", dir.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let app_state = AppState::new(static_config, runtime_config, config_path, pools.clone(), artifact_store);
+
+    // Reload the reloadable config subset on SIGHUP without touching open connections
+    #[cfg(unix)]
+    {
+        let state = app_state.clone();
+        tokio::spawn(async move {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut hangups = match signal(SignalKind::hangup()) {
+                Ok(hangups) => hangups,
+                Err(e) => {
+                    warn!("Failed to install SIGHUP handler: {}", e);
+                    return;
+                }
+            };
+
+            while hangups.recv().await.is_some() {
+                info!("SIGHUP received, reloading configuration");
+                if let Err(errors) = reload_runtime_config(&state) {
+                    warn!("Config reload rejected: {:?}", errors);
+                }
+            }
+        });
+    }
+
+    // Initialize database and virtual table, waiting for Postgres to come up (e.g. under docker-compose)
+    let startup_retry = db::RetryPolicy {
+        max_attempts: app_state.static_config.db_retry_max_attempts,
+        initial_delay: std::time::Duration::from_millis(500),
+        max_delay: std::time::Duration::from_secs(app_state.static_config.db_retry_max_delay_secs),
+    };
+    // Past the retries the database is down for good; exit non-zero and let the supervisor decide
+    let connect = || get_postgres_client(app_state.pools.primary());
+    let mut client = match db::with_retry(&startup_retry, "Connecting to PostgreSQL", connect).await {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Failed to connect to PostgreSQL after {} attempts: {}", startup_retry.max_attempts, e);
+            std::process::exit(1);
+        }
+    };
+    if migrations::reset_requested() {
+        if let Err(e) = migrations::reset_database(&client).await {
+            error!("Failed to reset database: {}", e);
+            std::process::exit(1);
+        }
+    }
+    let applied = match migrations::run_migrations(&mut client).await {
+        Ok(applied) => applied,
+        Err(e) => {
+            error!("Failed to run migrations: {}", e);
+            std::process::exit(1);
+        }
+    };
+    info!("Database schema up to date ({} migrations applied)", applied);
+
+    if let Some(import_args) = import_args {
+        drop(client);
+        let code = import_cli::run(&app_state, &import_args).await;
+        pools.close();
+        std::process::exit(code);
+    }
+
+    let (snapshot_table, rebuild_in_background) = load_startup_snapshot(&app_state, &client).await;
+
+    let min_data_completeness = app_state.runtime_config.load().min_data_completeness;
+    let build_started = std::time::Instant::now();
+    let initial_table = match snapshot_table {
+        Some(table) => Some(Arc::new(table)),
+        None => match db::with_retry(&startup_retry, "Initial virtual table build", || {
+            build_virtual_table(&client, min_data_completeness)
+        })
+        .await
+        {
+            Ok(table) => {
+                info!("Initial virtual table built with {} records", table.len());
+                app_state.refresh_status.lock().unwrap().record_success(build_started, table.len());
+                Some(save_snapshot(&app_state, Arc::new(table)).await)
+            }
+            Err(e) => {
+                warn!("Failed to build initial virtual table: {}", e);
+                None
+            }
+        },
+    };
+    if let Some(table) = initial_table {
+        app_state.virtual_table.store(table);
+    }
+    match reload_category_preferences(&app_state, &client).await {
+        Ok(()) => info!("Loaded display preferences for {} categories", app_state.category_preferences.load().len()),
+        Err(e) => warn!("Failed to load category preferences: {}", e),
+    }
+    // Return the startup connection to the pool
+    drop(client);
+
+    if rebuild_in_background {
+        let state = app_state.clone();
+        actix_web::rt::spawn(async move {
+            let rebuilt = db::with_retry(&startup_retry, "Background virtual table rebuild", || {
+                refresh_virtual_table(&state)
+            })
+            .await;
+            match rebuilt {
+                Ok(()) => info!("Background rebuild replaced the startup snapshot"),
+                Err(e) => warn!("Background rebuild failed, still serving the snapshot: {}", e),
+            }
+        });
+    }
+    jwt::spawn_jwks_refresh(Arc::clone(&app_state.runtime_config), Arc::clone(&app_state.jwks));
+    rate_limit::spawn_cleanup(Arc::clone(&app_state.rate_limiter));
+    if app_state.static_config.refresh_interval_secs > 0 {
+        scheduler::spawn(
+            app_state.clone(),
+            Duration::from_secs(app_state.static_config.refresh_interval_secs),
+        );
+    }
+
+    info!(
+        "Starting server at {}://{}:{} with {} workers",
+        if https { "https" } else { "http" },
+        bind_addr,
+        port,
+        workers.map_or_else(|| "default".to_string(), |w| w.to_string())
+    );
+
+    let uploads = Arc::clone(&app_state.uploads);
+    let mut server = HttpServer::new(move || app(app_state.clone(), https))
+        // Signals are handled below so in-flight uploads can be reported before draining
+        .disable_signals()
+        .shutdown_timeout(shutdown_grace_secs);
+    server = match tls_config {
+        Some(tls_config) => server.bind_rustls_0_23((bind_addr, port), tls_config)?,
+        None => server.bind((bind_addr, port))?,
+    };
+    if let Some(workers) = workers {
+        server = server.workers(workers);
+    }
+    let server = server.run();
+
+    // Stop accepting connections, then give in-flight requests up to the grace period to finish
+    let handle = server.handle();
+    tokio::spawn(async move {
+        shutdown::wait_for_signal().await;
+        shutdown::drain(&uploads, Duration::from_secs(shutdown_grace_secs), || handle.stop(true)).await;
+    });
+
+    let server_result = server.await;
+
+    info!("Server stopped, closing database pools");
+    pools.close();
+
+    server_result
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    use crate::test_support;
+
+    async fn search_fund_ids(state: &AppState, q: &str) -> Vec<i64> {
+        let uri = format!("/api/v1/search?q={}", q);
+        let (status, body) = test_support::call_json(state, TestRequest::get().uri(&uri)).await;
+        assert_eq!(status, 200, "{}", body);
+        body["data"].as_array().unwrap().iter().map(|record| record["fund_id"].as_i64().unwrap()).collect()
+    }
+
+    // Uploads and refreshes are key-protected, so the page has to send the key itself
+    #[actix_web::test]
+    async fn the_page_sends_the_entered_key_with_uploads_and_refreshes() {
+        let state = test_support::state(test_support::runtime_config(), vec![]);
+        let response = test_support::call(&state, TestRequest::get().uri("/")).await;
+        assert_eq!(response.status(), 200);
+        let page = String::from_utf8(actix_web::body::to_bytes(response.into_body()).await.unwrap().to_vec()).unwrap();
+
+        assert!(page.contains(r#"<input type="password" id="apiKey""#));
+        assert!(page.contains("{ 'X-Api-Key': key }"));
+        assert!(!page.contains(r#"action="/api/v1/upload""#), "a plain form post can't carry the key");
+        assert!(page.contains("fetch('/api/v1/upload'") && page.contains("fetch('/api/v1/refresh'"));
+        // Every request the page makes carries the key
+        for (at, _) in page.match_indices("await fetch(") {
+            let call: String = page[at..].chars().take(200).collect();
+            assert!(call.contains("headers: apiHeaders()"), "{}", call);
+        }
+        assert!(auth::API_KEY_HEADER.eq_ignore_ascii_case("X-Api-Key"));
+    }
+
+    #[actix_web::test]
+    async fn reloading_an_edited_alias_dictionary_changes_the_next_search() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        let dictionary = dir.path().join("aliases.json");
+        std::fs::write(
+            &config_path,
+            r#"{"alias_dictionary": "aliases.json", "api_keys": [{"id": "ops", "secret": "0123456789abcdef"}]}"#,
+        )
+        .unwrap();
+        std::fs::write(&dictionary, r#"{"ppfas": "parag parikh"}"#).unwrap();
+
+        let (_, runtime_config) = config::load(Some(&config_path)).unwrap();
+        let mut state = test_support::state(
+            runtime_config,
+            vec![
+                CombinedSchemeData::test_fund(1, "Parag Parikh Flexi Cap Fund"),
+                CombinedSchemeData::test_fund(2, "Quant Small Cap Fund"),
+            ],
+        );
+        state.config_path = Some(config_path);
+        assert_eq!(search_fund_ids(&state, "ppfas").await, vec![1]);
+        assert_eq!(search_fund_ids(&state, "qsc").await, Vec::<i64>::new());
+
+        std::fs::write(&dictionary, r#"{"qsc": "quant small cap"}"#).unwrap();
+        let reload = TestRequest::post()
+            .uri("/api/v1/admin/reload-config")
+            .insert_header((auth::API_KEY_HEADER, "0123456789abcdef"));
+        let (status, body) = test_support::call_json(&state, reload).await;
+
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["changes"], json!(["aliases: 1 added, 1 removed, 0 changed"]));
+        assert_eq!(search_fund_ids(&state, "qsc").await, vec![2]);
+        assert_eq!(search_fund_ids(&state, "ppfas").await, Vec::<i64>::new());
+    }
+
+    // As on SIGHUP
+    #[actix_web::test]
+    async fn an_invalid_dictionary_rejects_the_whole_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        let dictionary = dir.path().join("aliases.json");
+        std::fs::write(&config_path, r#"{"alias_dictionary": "aliases.json", "search_limit": 20}"#).unwrap();
+        std::fs::write(&dictionary, r#"{"ppfas": "parag parikh"}"#).unwrap();
+
+        let (_, runtime_config) = config::load(Some(&config_path)).unwrap();
+        let mut state = test_support::state(runtime_config, vec![CombinedSchemeData::test_fund(1, "Parag Parikh Flexi Cap Fund")]);
+        state.config_path = Some(config_path.clone());
+
+        std::fs::write(&config_path, r#"{"alias_dictionary": "aliases.json", "search_limit": 5}"#).unwrap();
+        std::fs::write(&dictionary, r#"{"two words": "parag parikh"}"#).unwrap();
+        let errors = reload_runtime_config(&state).unwrap_err();
+
+        assert_eq!(errors, vec!["Alias 'two words' must be a single non-empty token"]);
+        assert_eq!(state.runtime_config.load().search_limit, 20);
+        assert_eq!(search_fund_ids(&state, "ppfas").await, vec![1]);
+    }
+
+    #[actix_web::test]
+    async fn records_below_the_completeness_threshold_are_flagged_and_hidden_by_default() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        // 0, 2, 6 and 11 of the 11 numeric fields, against the default threshold of 25%
+        db.execute(
+            "INSERT INTO funds (category, scheme_name) VALUES ('Flexi Cap', 'Empty Fund');
+             INSERT INTO funds (category, scheme_name, latest_nav, year_1) VALUES ('Flexi Cap', 'Sparse Fund', 10, 5);
+             INSERT INTO funds (category, scheme_name, latest_nav, month_1, months_3, months_6, ytd, year_1)
+                 VALUES ('Flexi Cap', 'Partial Fund', 10, 1, 2, 3, 4, 5);
+             INSERT INTO funds (category, scheme_name, fund_size_apr25, fund_size_may25, latest_nav, month_1,
+                                months_3, months_6, ytd, year_1, years_2, years_3, years_5)
+                 VALUES ('Flexi Cap', 'Full Fund', 100, 110, 10, 1, 2, 3, 4, 5, 6, 7, 8);",
+        )
+        .await;
+        refresh_virtual_table(&db.state).await.unwrap();
+
+        let table = db.state.virtual_table.load();
+        let flags: Vec<(&str, u32, bool)> = ["Empty Fund", "Sparse Fund", "Partial Fund", "Full Fund"]
+            .iter()
+            .map(|name| {
+                let (record, _) = table.lookup_name(name, false, 0.85, None, 1000).unwrap();
+                (*name, (record.data_completeness * 11.0).round() as u32, record.incomplete)
+            })
+            .collect();
+        assert_eq!(
+            flags,
+            vec![("Empty Fund", 0, true), ("Sparse Fund", 2, true), ("Partial Fund", 6, false), ("Full Fund", 11, false)]
+        );
+
+        let (_, search) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/search")).await;
+        assert_eq!(search["total_matches"], 2);
+        assert_eq!(search["meta"]["excluded_incomplete"], 2);
+        let uri = "/api/v1/search?include_incomplete=true";
+        let (_, search) = test_support::call_json(&db.state, TestRequest::get().uri(uri)).await;
+        assert_eq!(search["total_matches"], 4);
+
+        let (_, status) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/status")).await;
+        assert_eq!(status["incomplete_records"], 2);
+
+        let uri = "/api/v1/funds/by-name?name=Sparse+Fund";
+        let (code, detail) = test_support::call_json(&db.state, TestRequest::get().uri(uri)).await;
+        assert_eq!(code, 200);
+        assert_eq!(detail["incomplete"], true);
+        assert_eq!(
+            detail["missing_fields"],
+            json!(["fund_size_apr25", "fund_size_may25", "month_1", "months_3", "months_6", "ytd", "years_2", "years_3", "years_5"])
+        );
+        let uri = "/api/v1/funds/by-name?name=Full+Fund";
+        let (_, detail) = test_support::call_json(&db.state, TestRequest::get().uri(uri)).await;
+        assert_eq!(detail["data_completeness"], 1.0);
+        assert_eq!(detail["missing_fields"], json!([]));
+        let uri = "/api/v1/funds/by-name?name=Missing+Fund";
+        let (code, _) = test_support::call_json(&db.state, TestRequest::get().uri(uri)).await;
+        assert_eq!(code, 404);
+    }
+
+    // The build reads the combined query as a stream; one that errors leaves the old table in place
+    #[actix_web::test]
+    async fn a_build_whose_query_fails_keeps_serving_the_old_table() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(
+            "INSERT INTO funds (category, scheme_name, latest_nav) VALUES
+                 ('Flexi Cap', 'Quant Flexi Cap Fund', 90), ('Flexi Cap', 'Axis Flexi Cap Fund', 20),
+                 ('Small Cap', 'Quant Small Cap Fund', 200);
+             INSERT INTO scheme_rates (arn, company, scheme_name, scheme_category, brokerage_type, start_date,
+                                       end_date, source_file)
+                 VALUES ('ARN-1', 'Quant Mutual Fund', 'Quant Flexi Cap Fund', 'Flexi Cap', 'Trail', '2025-04-01',
+                         '2099-03-31', 'rates.xlsx');",
+        )
+        .await;
+        refresh_virtual_table(&db.state).await.unwrap();
+        let fund_ids = |state: &AppState| -> Vec<Option<i32>> {
+            state.virtual_table.load().iter().map(|record| record.fund_id).collect()
+        };
+        assert_eq!(fund_ids(&db.state), vec![Some(1), Some(2), Some(3)]);
+
+        db.execute(
+            "INSERT INTO funds (category, scheme_name, latest_nav) VALUES ('Flexi Cap', 'Poison Fund', 1);
+             CREATE OR REPLACE FUNCTION expand_name_aliases(name TEXT) RETURNS TEXT AS $$
+             BEGIN
+                 IF name = 'Poison Fund' THEN
+                     RAISE EXCEPTION 'cannot expand %', name;
+                 END IF;
+                 RETURN LOWER(name);
+             END
+             $$ LANGUAGE plpgsql STABLE;",
+        )
+        .await;
+        let error = refresh_virtual_table(&db.state).await.unwrap_err().to_string();
+        assert!(error.contains("combined query failed"), "{}", error);
+        assert_eq!(fund_ids(&db.state), vec![Some(1), Some(2), Some(3)]);
+        assert_eq!(db.state.refresh_status.lock().unwrap().last_error.as_deref(), Some(error.as_str()));
+
+        let refresh = test_support::as_admin(TestRequest::post().uri("/api/v1/refresh"));
+        let (status, body) = test_support::call_json(&db.state, refresh).await;
+        assert_eq!(status, 500, "{}", body);
+        let search = TestRequest::get().uri("/api/v1/search?q=quant&include_incomplete=true");
+        let (_, body) = test_support::call_json(&db.state, search).await;
+        assert_eq!(body["total_matches"], 2, "{}", body);
+    }
+
+    // A batch that commits row by row, slowly, like a large upload: a refresh that read the
+    // database part-way through would serve some of its rows but not all
+    #[actix_web::test]
+    async fn a_refresh_during_an_upload_batch_serves_the_table_fully_before_or_after_it() {
+        const BATCH: usize = 5;
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute("INSERT INTO funds (category, scheme_name) VALUES ('Flexi Cap', 'Existing Fund')").await;
+        refresh_virtual_table(&db.state).await.unwrap();
+
+        let (started, batch_started) = tokio::sync::oneshot::channel();
+        let finished = std::sync::atomic::AtomicBool::new(false);
+        let slow_batch = async {
+            let _mutation = db.state.begin_mutation().await;
+            let client = get_postgres_client(db.state.pools.primary()).await.unwrap();
+            let mut started = Some(started);
+            for n in 0..BATCH {
+                client
+                    .execute(
+                        "INSERT INTO funds (category, scheme_name) VALUES ('Flexi Cap', $1)",
+                        &[&format!("Batch Fund {}", n)],
+                    )
+                    .await
+                    .unwrap();
+                if let Some(started) = started.take() {
+                    started.send(()).unwrap();
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        };
+        let refresh_mid_batch = async {
+            batch_started.await.unwrap();
+            refresh_virtual_table(&db.state).await.unwrap();
+            finished.store(true, std::sync::atomic::Ordering::SeqCst);
+        };
+        let watch_served_table = async {
+            let mut served = Vec::new();
+            while !finished.load(std::sync::atomic::Ordering::SeqCst) {
+                served.push(db.state.virtual_table.load().len());
+                tokio::time::sleep(Duration::from_millis(2)).await;
+            }
+            served.push(db.state.virtual_table.load().len());
+            served
+        };
+        let ((), (), served) = tokio::join!(slow_batch, refresh_mid_batch, watch_served_table);
+
+        assert!(served.iter().all(|&len| len == 1 || len == 1 + BATCH), "{:?}", served);
+        assert_eq!(served.last(), Some(&(1 + BATCH)));
+    }
+
+    // The refresh after a batch consumes the batch's token, so it can't wait on its own caller
+    #[actix_web::test]
+    async fn the_refresh_after_a_batch_releases_the_batch_first() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let mutation = db.state.begin_mutation().await;
+        db.execute("INSERT INTO funds (category, scheme_name) VALUES ('Flexi Cap', 'Batch Fund')").await;
+
+        let refreshed = tokio::time::timeout(Duration::from_secs(10), mutation.refresh()).await;
+
+        refreshed.expect("the refresh did not wait on the batch's own token").unwrap();
+        assert_eq!(db.state.virtual_table.load().len(), 1);
+        assert!(db.state.mutation_gate.try_write().is_ok());
+    }
+
+    #[actix_web::test]
+    async fn uploading_the_same_workbook_twice_updates_every_row() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let workbook = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/funds.xlsx")).unwrap();
+        let counts = |job: &serde_json::Value| {
+            let report = &job["summary"]["files"][0]["report"];
+            (report["inserted"].clone(), report["updated"].clone(), report["failed"].clone())
+        };
+
+        let first = test_support::upload(&db.state, &[("funds.xlsx", &workbook)]).await;
+        assert_eq!(counts(&first), (json!(2), json!(0), json!(0)), "{}", first);
+        let second = test_support::upload(&db.state, &[("funds.xlsx", &workbook)]).await;
+        assert_eq!(counts(&second), (json!(0), json!(2), json!(0)), "{}", second);
+
+        // Updated in place: same ids, one row per name, versions bumped
+        let rows = db.query("SELECT id, version FROM funds ORDER BY id").await;
+        let funds: Vec<(i32, i32)> = rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+        assert_eq!(funds, vec![(1, 2), (2, 2)]);
+    }
+
+    fn fund_row(row: usize, scheme_name: &str, latest_nav: f32) -> FundData {
+        FundData {
+            sheet: "Flexi Cap".to_string(),
+            row,
+            category: "Flexi Cap".to_string(),
+            scheme_name: scheme_name.to_string(),
+            launch_date: None,
+            fund_size_apr25: None,
+            fund_size_may25: None,
+            latest_nav: Some(latest_nav),
+            month_1: None,
+            months_3: None,
+            months_6: None,
+            ytd: None,
+            year_1: None,
+            years_2: None,
+            years_3: None,
+            years_5: None,
+        }
+    }
+
+    #[test]
+    fn duplicate_schemes_merge_into_the_first_row_filling_its_gaps() {
+        let all_funds = |row, latest_nav| FundData {
+            sheet: "All Funds".to_string(),
+            years_5: Some(18.0),
+            launch_date: NaiveDate::from_ymd_opt(2013, 5, 24),
+            ..fund_row(row, "PARAG PARIKH  Flexi Cap Fund.", latest_nav)
+        };
+        let funds = vec![
+            fund_row(2, "Parag Parikh Flexi Cap Fund", 84.0),
+            fund_row(3, "Quant Flexi Cap Fund", 90.0),
+            all_funds(7, 99.0),
+            // A third copy with nothing new is folded in but not credited
+            FundData { sheet: "Index".to_string(), ..fund_row(2, "Parag Parikh Flexi Cap Fund", 1.0) },
+        ];
+
+        let (unique, merges) = merge_duplicates(funds);
+
+        let names: Vec<&str> = unique.iter().map(|fund| fund.scheme_name.as_str()).collect();
+        assert_eq!(names, ["Parag Parikh Flexi Cap Fund", "Quant Flexi Cap Fund"]);
+        // The first occurrence keeps its own values
+        assert_eq!(unique[0].latest_nav, Some(84.0));
+        assert_eq!(unique[0].years_5, Some(18.0));
+        assert_eq!(unique[0].launch_date, NaiveDate::from_ymd_opt(2013, 5, 24));
+
+        assert_eq!(merges.len(), 1);
+        assert_eq!(merges[0].scheme_name, "Parag Parikh Flexi Cap Fund");
+        assert_eq!(merges[0].sheets, ["Flexi Cap", "All Funds"]);
+        assert_eq!(merges[0].rows_merged, 2);
+        assert_eq!(merges[0].filled_fields, ["launch_date", "years_5"]);
+    }
+
+    #[actix_web::test]
+    async fn a_rejected_batch_is_retried_row_by_row_and_only_the_bad_row_fails() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(
+            "INSERT INTO uploads (provider, column_mappings, processed) VALUES ('test', '{}', 0);
+             INSERT INTO funds (category, scheme_name, latest_nav) VALUES ('Flexi Cap', 'Quant Flexi Cap Fund', 80)",
+        )
+        .await;
+        // PostgreSQL refuses NUL in text, so the batch fails as a whole
+        let funds = vec![
+            fund_row(2, "Parag Parikh Flexi Cap Fund", 84.0),
+            fund_row(3, "Broken\0Fund", 10.0),
+            fund_row(4, "Quant Flexi Cap Fund", 90.0),
+        ];
+        let rows_processed = AtomicUsize::new(0);
+
+        let mut client = db.state.pools.primary().get().await.unwrap();
+        let mut transaction = client.transaction().await.unwrap();
+        let outcome = insert_fund_data(&mut transaction, funds, 1, &rows_processed).await.unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!((outcome.inserted, outcome.updated), (1, 1));
+        assert_eq!(outcome.touched, ["Parag Parikh Flexi Cap Fund", "Quant Flexi Cap Fund"]);
+        assert_eq!(rows_processed.load(Ordering::Relaxed), 2);
+        assert_eq!(outcome.failed.len(), 1);
+        assert_eq!((outcome.failed[0].row, outcome.failed[0].skipped), (3, true));
+        assert!(outcome.failed[0].reason.starts_with("database"), "{}", outcome.failed[0].reason);
+
+        let rows = db.query("SELECT scheme_name, latest_nav, version FROM funds ORDER BY scheme_name").await;
+        let funds: Vec<(String, Option<f32>, i32)> = rows.iter().map(|row| (row.get(0), row.get(1), row.get(2))).collect();
+        assert_eq!(
+            funds,
+            [
+                ("Parag Parikh Flexi Cap Fund".to_string(), Some(84.0), 1),
+                ("Quant Flexi Cap Fund".to_string(), Some(90.0), 2)
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn rows_that_clean_to_one_scheme_name_merge_before_the_upsert() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        // Different normalized names, one cleaned name: a single batch would update its own row
+        let csv = b"Scheme Name,Launch Date,Latest NAV,1 Year\nAxis Bluechip Fund - Growth,2010-01-05,52,\n\
+                    Axis Bluechip Fund - Reg - Growth,2010-01-05,61,14.5\n";
+        let job = test_support::upload(&db.state, &[("Large Cap.csv", csv)]).await;
+
+        let report = &job["summary"]["files"][0]["report"];
+        assert_eq!(report["committed"], true, "{}", job);
+        assert_eq!((&report["inserted"], &report["failed"]), (&json!(1), &json!(0)), "{}", report);
+        assert_eq!(report["merged_schemes"][0]["filled_fields"], json!(["year_1"]));
+        let rows = db.query("SELECT scheme_name, latest_nav, year_1 FROM funds").await;
+        let funds: Vec<(String, Option<f32>, Option<f32>)> =
+            rows.iter().map(|row| (row.get(0), row.get(1), row.get(2))).collect();
+        assert_eq!(funds, [("Axis Bluechip Fund".to_string(), Some(52.0), Some(14.5))]);
+    }
+
+    #[actix_web::test]
+    async fn an_upload_with_too_many_rejected_rows_is_rolled_back_whole() {
+        let config = RuntimeConfig {
+            max_upload_failure_ratio: 0.4,
+            ..test_support::runtime_config()
+        };
+        let db = match test_support::database(config).await {
+            Some(db) => db,
+            None => return,
+        };
+        // The constraint stands in for any row the database refuses
+        db.execute(
+            "ALTER TABLE funds ADD CONSTRAINT test_nav_positive CHECK (latest_nav > 0);
+             INSERT INTO funds (category, scheme_name, latest_nav) VALUES ('Flexi Cap', 'Quant Flexi Cap Fund', 80)",
+        )
+        .await;
+        refresh_virtual_table(&db.state).await.unwrap();
+
+        let csv = b"Scheme Name,Launch Date,Latest NAV\nQuant Flexi Cap Fund,2008-10-17,90\nBad One,2020-01-01,-1\n\
+                    Bad Two,2020-01-01,-2\nParag Parikh Flexi Cap Fund,2013-05-24,84\n";
+        let job = test_support::upload(&db.state, &[("Flexi Cap.csv", csv)]).await;
+
+        // The only file failed, so the job did
+        assert_eq!(job["state"], "failed", "{}", job);
+        let file = &job["summary"]["details"]["files"][0];
+        assert_eq!(file["committed"], false);
+        assert_eq!(file["report"]["rollback_reason"], "2 of 4 rows were rejected, more than max_upload_failure_ratio 0.4");
+        let rows = db.query("SELECT scheme_name, latest_nav FROM funds").await;
+        let funds: Vec<(String, Option<f32>)> = rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+        assert_eq!(funds, [("Quant Flexi Cap Fund".to_string(), Some(80.0))]);
+        let status = db.query("SELECT status, failed FROM uploads").await;
+        assert_eq!((status[0].get::<_, String>(0), status[0].get::<_, i32>(1)), ("rolled_back".to_string(), 2));
+        assert_eq!(db.state.virtual_table.load().records()[0].latest_nav, Some(80.0));
+
+        // One bad row in three is within the limit, so the rest commits
+        let csv = b"Scheme Name,Launch Date,Latest NAV\nQuant Flexi Cap Fund,2008-10-17,90\nBad One,2020-01-01,-1\n\
+                    Parag Parikh Flexi Cap Fund,2013-05-24,84\n";
+        let job = test_support::upload(&db.state, &[("Flexi Cap.csv", csv)]).await;
+        assert_eq!(job["summary"]["files"][0]["report"]["committed"], true, "{}", job);
+        assert_eq!(db.state.virtual_table.load().len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn by_default_one_rejected_row_rolls_the_upload_back() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute("ALTER TABLE funds ADD CONSTRAINT test_nav_positive CHECK (latest_nav > 0)").await;
+
+        let csv = b"Scheme Name,Launch Date,Latest NAV\nQuant Flexi Cap Fund,2008-10-17,90\nBad One,2020-01-01,-1\n\
+                    Parag Parikh Flexi Cap Fund,2013-05-24,84\n";
+        let job = test_support::upload(&db.state, &[("Flexi Cap.csv", csv)]).await;
+
+        let report = &job["summary"]["details"]["files"][0]["report"];
+        let reason = "1 of 3 rows were rejected, more than max_upload_failure_ratio 0";
+        assert_eq!(report["rollback_reason"], reason, "{}", job);
+        assert!(db.query("SELECT id FROM funds").await.is_empty());
+    }
+
+    #[test]
+    fn number_cells_parse_with_their_display_formatting() {
+        let text = |value: &str| Data::String(value.to_string());
+        let cases = [
+            (Data::Float(12.45), Some(12.45)),
+            (Data::Float(0.1245), Some(0.1245)),
+            (Data::Int(-3), Some(-3.0)),
+            (Data::Float(f64::NAN), None),
+            (Data::Float(f64::INFINITY), None),
+            (text("12.45"), Some(12.45)),
+            (text(" 12.45% "), Some(12.45)),
+            (text("12.45 %"), Some(12.45)),
+            (text("1,23,456.78"), Some(123456.78)),
+            (text("123,456.78"), Some(123456.78)),
+            (text("1\u{2009}234.5"), Some(1234.5)),
+            (text("1\u{202F}234.5"), Some(1234.5)),
+            (text("1\u{00A0}234.5"), Some(1234.5)),
+            (text("(3.2)"), Some(-3.2)),
+            (text("( 3.2% )"), Some(-3.2)),
+            (text("-3.2"), Some(-3.2)),
+            (text("N/A"), None),
+            (text("-"), None),
+            (text("  "), None),
+            (text("12.4.5"), None),
+            (text("abc"), None),
+            (text("(3.2"), None),
+            (text("1e40"), None),
+            (Data::Empty, None),
+        ];
+        for (cell, expected) in cases {
+            assert_eq!(parse_float_option(Some(&cell)), expected, "{:?}", cell);
+        }
+        assert_eq!(parse_float_option(None), None);
+    }
+
+    #[actix_web::test]
+    async fn uploads_patch_the_table_from_the_primary_not_a_lagging_replica() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        // A replica that stopped replicating right after the migrations: same tables, no rows
+        db.execute(
+            "DROP SCHEMA IF EXISTS lagging_replica CASCADE;
+             CREATE SCHEMA lagging_replica;
+             CREATE TABLE lagging_replica.funds (LIKE public.funds INCLUDING ALL);
+             CREATE TABLE lagging_replica.scheme_rates (LIKE public.scheme_rates INCLUDING ALL);
+             CREATE TABLE lagging_replica.category_preferences (LIKE public.category_preferences INCLUDING ALL);",
+        )
+        .await;
+        let url = std::env::var("TEST_DATABASE_URL").unwrap();
+        let mut replica_config: tokio_postgres::Config = url.parse().unwrap();
+        replica_config.options("-c search_path=lagging_replica");
+        let tls = db::TlsSettings {
+            mode: db::TlsMode::Disable,
+            root_cert: None,
+        };
+        let replica = db::create_pool(replica_config, &tls, 2, Duration::from_secs(5)).unwrap();
+        let (static_config, _) = config::load(None).unwrap();
+        let state = AppState::new(
+            static_config,
+            test_support::runtime_config(),
+            None,
+            db::DbPools::new(db.state.pools.primary().clone(), Some(replica)),
+            None,
+        );
+
+        let csv = b"Scheme Name,Launch Date\nQuant Small Cap Fund,2013-01-01\n";
+        let job = test_support::upload(&state, &[("Small Cap.csv", csv)]).await;
+        assert_eq!(job["state"], "done", "{}", job);
+
+        let table = state.virtual_table.load();
+        let names: Vec<&str> = table.records().iter().map(|record| &*record.scheme_name).collect();
+        assert_eq!(names, vec!["Quant Small Cap Fund"]);
+        db.execute("DROP SCHEMA lagging_replica CASCADE").await;
+    }
+
+    #[actix_web::test]
+    async fn searches_are_not_held_up_by_a_running_refresh() {
+        let config = RuntimeConfig {
+            read_rate_limit: rate_limit::Limit {
+                per_minute: 1_000_000,
+                burst: 1_000_000,
+            },
+            ..test_support::runtime_config()
+        };
+        let db = match test_support::database(config).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(
+            "INSERT INTO funds (category, scheme_name, latest_nav, month_1, months_3, months_6, ytd, year_1)
+             SELECT 'Flexi Cap', 'Scheme ' || n || ' Flexi Cap Fund', n, 1, 2, 3, 4, 5
+             FROM generate_series(1, 5000) AS n",
+        )
+        .await;
+        let started = std::time::Instant::now();
+        refresh_virtual_table(&db.state).await.unwrap();
+        let build = started.elapsed();
+
+        let state = db.state.clone();
+        // On its own thread, so the build and the searches really run at the same time
+        let refresh = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(refresh_virtual_table(&state)).map_err(|e| e.to_string())
+        });
+        let mut slowest = Duration::ZERO;
+        let mut during_refresh = 0;
+        while !refresh.is_finished() {
+            let started = std::time::Instant::now();
+            let ids = search_fund_ids(&db.state, "scheme+2345+flexi").await;
+            slowest = slowest.max(started.elapsed());
+            assert_eq!(ids.first(), Some(&2345));
+            during_refresh += 1;
+            // Lets this runtime drive the database connections the build checked out
+            tokio::task::yield_now().await;
+        }
+        refresh.join().unwrap().unwrap();
+
+        assert!(during_refresh > 0);
+        // Well under a build: no search waited for the swap
+        assert!(slowest < build / 2, "slowest search {:?}, build {:?}", slowest, build);
+    }
+
+    #[actix_web::test]
+    async fn only_snapshots_no_newer_than_the_database_are_served_at_startup() {
+        let mut db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute("INSERT INTO funds (category, scheme_name) VALUES ('Flexi Cap', 'Quant Flexi Cap Fund')").await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("table.snapshot");
+        db.state.static_config = Arc::new(StaticConfig {
+            snapshot_path: Some(path.clone()),
+            ..(*db.state.static_config).clone()
+        });
+        let client = db.state.pools.primary().get().await.unwrap();
+        let db_generation = db::data_generation(&client).await.unwrap();
+        let startup = |generation: i64| {
+            let mut table = VirtualTable::new();
+            table.add_record(CombinedSchemeData::test_fund(1, "Quant Flexi Cap Fund"));
+            table.generation = generation;
+            snapshot::write(&path, &table, chrono::Utc::now()).unwrap();
+            *db.state.snapshot_status.lock().unwrap() = snapshot::SnapshotStatus::default();
+            load_startup_snapshot(&db.state, &client)
+        };
+
+        let (table, rebuild) = startup(db_generation).await;
+        assert_eq!((table.map(|table| table.len()), rebuild), (Some(1), false));
+
+        // Older: served until the background rebuild replaces it
+        let (table, rebuild) = startup(db_generation - 1).await;
+        assert_eq!((table.map(|table| table.len()), rebuild), (Some(1), true));
+        let status = db.state.snapshot_status.lock().unwrap().clone();
+        assert!(status.loaded.is_some() && status.rejected.is_none());
+        assert!(status.stale.unwrap().contains("older than the database"));
+
+        // Newer: the database was restored since, so the table is built before serving
+        let (table, rebuild) = startup(db_generation + 1).await;
+        assert_eq!((table.map(|table| table.len()), rebuild), (None, false));
+        let status = db.state.snapshot_status.lock().unwrap().clone();
+        assert!(status.loaded.is_none() && status.stale.is_none());
+        assert_eq!(
+            status.rejected.unwrap(),
+            format!("snapshot generation {} is newer than the database ({})", db_generation + 1, db_generation)
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::{web, HttpResponse};
+use deadpool_postgres::Pool;
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::api_error::ApiError;
+use crate::routes;
+use crate::AppState;
+
+// Routes whose patterns don't match, e.g. a scanner probing paths; one series rather than one per path
+const UNMATCHED_ROUTE: &str = "unmatched";
+// The statuses /search answers most; others go through the labelled lookup like any other route
+const SEARCH_STATUSES: [u16; 4] = [200, 400, 422, 429];
+
+// Series for one search path and status, resolved up front so counting a search is a couple of
+// atomic adds rather than a label lookup behind the metric family's lock
+struct SearchSeries {
+    path: String,
+    status: u16,
+    requests: IntCounter,
+    duration: Histogram,
+}
+
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_duration: HistogramVec,
+    search: Vec<SearchSeries>,
+    upload_duration: Histogram,
+    upload_files: IntCounterVec,
+    upload_rows: IntCounterVec,
+    // Set when scraped, from the table, the refresh status and the pools
+    table_records: IntGauge,
+    last_refresh: IntGauge,
+    pool_connections: IntGaugeVec,
+    pool_waiting: IntGaugeVec,
+}
+
+impl std::fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Metrics").finish_non_exhaustive()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    // The names and labels are fixed, so registering them can only fail on a typo here
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route pattern, method and status"),
+            &["route", "method", "status"],
+        )
+        .unwrap();
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "Time to answer HTTP requests"),
+            &["route", "method", "status"],
+        )
+        .unwrap();
+        // Seconds to minutes: a workbook takes far longer than a request
+        let upload_duration = Histogram::with_opts(
+            HistogramOpts::new("upload_file_duration_seconds", "Time to parse and apply one uploaded workbook")
+                .buckets(exponential_buckets(0.25, 2.0, 12).unwrap()),
+        )
+        .unwrap();
+        let upload_files = IntCounterVec::new(
+            Opts::new("upload_files_total", "Uploaded workbooks by outcome"),
+            &["outcome"],
+        )
+        .unwrap();
+        let upload_rows = IntCounterVec::new(
+            Opts::new("upload_rows_total", "Rows applied or rejected by uploads"),
+            &["kind", "outcome"],
+        )
+        .unwrap();
+        let table_records = IntGauge::new("virtual_table_records", "Records in the served table").unwrap();
+        let last_refresh = IntGauge::new(
+            "virtual_table_last_refresh_timestamp_seconds",
+            "Unix time of the last successful full refresh; 0 before the first",
+        )
+        .unwrap();
+        let pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Database pool connections: max, open and idle"),
+            &["pool", "state"],
+        )
+        .unwrap();
+        let pool_waiting = IntGaugeVec::new(
+            Opts::new("db_pool_waiting", "Requests waiting for a database connection"),
+            &["pool"],
+        )
+        .unwrap();
+
+        let mut search = Vec::new();
+        for route in ["/search", "/search.csv"] {
+            for path in [format!("{}{}", routes::V1_PREFIX, route), route.to_string()] {
+                for status in SEARCH_STATUSES {
+                    let labels = [path.as_str(), Method::GET.as_str(), &status.to_string()];
+                    search.push(SearchSeries {
+                        requests: http_requests.with_label_values(&labels),
+                        duration: http_duration.with_label_values(&labels),
+                        path: path.clone(),
+                        status,
+                    });
+                }
+            }
+        }
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_duration.clone())).unwrap();
+        registry.register(Box::new(upload_duration.clone())).unwrap();
+        registry.register(Box::new(upload_files.clone())).unwrap();
+        registry.register(Box::new(upload_rows.clone())).unwrap();
+        registry.register(Box::new(table_records.clone())).unwrap();
+        registry.register(Box::new(last_refresh.clone())).unwrap();
+        registry.register(Box::new(pool_connections.clone())).unwrap();
+        registry.register(Box::new(pool_waiting.clone())).unwrap();
+
+        Metrics {
+            registry,
+            http_requests,
+            http_duration,
+            search,
+            upload_duration,
+            upload_files,
+            upload_rows,
+            table_records,
+            last_refresh,
+            pool_connections,
+            pool_waiting,
+        }
+    }
+
+    fn observe_request<B>(&self, response: &ServiceResponse<B>, elapsed: Duration) {
+        let request = response.request();
+        let status = response.status().as_u16();
+        let seconds = elapsed.as_secs_f64();
+
+        if request.method() == Method::GET {
+            let path = request.path();
+            if let Some(series) = self.search.iter().find(|series| series.status == status && series.path == path) {
+                series.requests.inc();
+                series.duration.observe(seconds);
+                return;
+            }
+        }
+
+        // The pattern rather than the path, so /funds/1 and /funds/2 are one series
+        let route = request.match_pattern().unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+        let labels = [route.as_str(), request.method().as_str(), &status.to_string()];
+        self.http_requests.with_label_values(&labels).inc();
+        self.http_duration.with_label_values(&labels).observe(seconds);
+    }
+
+    // One workbook of a fund upload: committed, rolled_back or error
+    pub fn observe_upload_file(&self, outcome: &str, elapsed: Duration) {
+        self.upload_files.with_label_values(&[outcome]).inc();
+        self.upload_duration.observe(elapsed.as_secs_f64());
+    }
+
+    // `kind` is funds or rates. Rows of a rolled-back upload count as failed only.
+    pub fn add_upload_rows(&self, kind: &str, inserted: usize, updated: usize, failed: usize) {
+        self.upload_rows.with_label_values(&[kind, "inserted"]).inc_by(inserted as u64);
+        self.upload_rows.with_label_values(&[kind, "updated"]).inc_by(updated as u64);
+        self.upload_rows.with_label_values(&[kind, "failed"]).inc_by(failed as u64);
+    }
+
+    fn set_pool(&self, name: &str, pool: &Pool) {
+        let status = pool.status();
+        self.pool_connections.with_label_values(&[name, "max"]).set(status.max_size as i64);
+        self.pool_connections.with_label_values(&[name, "open"]).set(status.size as i64);
+        self.pool_connections.with_label_values(&[name, "idle"]).set(status.available as i64);
+        self.pool_waiting.with_label_values(&[name]).set(status.waiting as i64);
+    }
+
+    // The gauges are read from the state here, so nothing on the request path keeps them current
+    fn render(&self, state: &AppState) -> Result<Vec<u8>, prometheus::Error> {
+        self.table_records.set(state.virtual_table.load().len() as i64);
+        let last_refresh = state.refresh_status.lock().unwrap().last_success_at;
+        self.last_refresh.set(last_refresh.map_or(0, |at| at.timestamp()));
+        self.set_pool("primary", state.pools.primary());
+        self.set_pool("read", state.pools.replica());
+
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+// App-wide middleware, inside the request id so it times everything else, credentials and rate
+// limits included
+pub fn record_request<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = Result<ServiceResponse<B>, actix_web::Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let started = Instant::now();
+    let metrics = req.app_data::<web::Data<AppState>>().map(|state| Arc::clone(&state.metrics));
+    let response = srv.call(req);
+    async move {
+        let response = response.await?;
+        if let Some(metrics) = metrics {
+            metrics.observe_request(&response, started.elapsed());
+        }
+        Ok(response)
+    }
+}
+
+// Prometheus text format, outside /api/v1 and its credentials like /swagger; keep it off the
+// public listener if the numbers are sensitive
+pub async fn serve(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    match state.metrics.render(&state) {
+        Ok(body) => Ok(HttpResponse::Ok().content_type(TextEncoder::new().format_type()).body(body)),
+        Err(e) => Err(ApiError::internal(format!("Failed to encode metrics: {}", e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use crate::config::RuntimeConfig;
+    use crate::{test_support, AppState, CombinedSchemeData};
+
+    async fn scrape(state: &AppState) -> String {
+        let response = test_support::call(state, TestRequest::get().uri("/metrics")).await;
+        assert_eq!(response.status(), 200);
+        String::from_utf8(actix_web::test::read_body(response).await.to_vec()).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn requests_are_counted_by_route_pattern_and_status_with_the_table_size() {
+        let records = vec![
+            CombinedSchemeData::test_fund(1, "Parag Parikh Flexi Cap Fund"),
+            CombinedSchemeData::test_fund(2, "Quant Small Cap Fund"),
+        ];
+        let state = test_support::state(RuntimeConfig::default(), records);
+        let searches = ["/api/v1/search?q=flexi", "/api/v1/search?q=quant", "/api/v1/search?limit=many"];
+        for uri in searches.into_iter().chain(["/api/v1/funds/7", "/nope"]) {
+            test_support::call(&state, TestRequest::get().uri(uri)).await;
+        }
+
+        let body = scrape(&state).await;
+        for line in [
+            r#"http_requests_total{method="GET",route="/api/v1/search",status="200"} 2"#,
+            r#"http_request_duration_seconds_count{method="GET",route="/api/v1/search",status="200"} 2"#,
+            r#"http_requests_total{method="GET",route="/api/v1/search",status="400"} 1"#,
+            // Searches that haven't happened are still exported, at zero
+            r#"http_requests_total{method="GET",route="/api/v1/search.csv",status="200"} 0"#,
+            r#"http_requests_total{method="GET",route="unmatched",status="404"} 1"#,
+            "virtual_table_records 2",
+            "virtual_table_last_refresh_timestamp_seconds 0",
+        ] {
+            assert!(body.lines().any(|l| l == line), "no `{}` in\n{}", line, body);
+        }
+        // One series per pattern, not per fund id
+        assert!(body.contains(r#"route="/api/v1/funds/{id}""#), "{}", body);
+        assert!(!body.contains("/api/v1/funds/7"), "{}", body);
+    }
+
+    #[actix_web::test]
+    async fn uploads_count_their_files_and_rows() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let first: &[u8] =
+            b"Scheme Name,Launch Date\nParag Parikh Flexi Cap Fund,2013-05-24\nQuant Flexi Cap Fund,2008-10-17\n";
+        let second: &[u8] = b"Scheme Name,Launch Date\nQuant Flexi Cap Fund,2008-10-17\n";
+        for csv in [first, second] {
+            test_support::upload(&db.state, &[("Flexi Cap.csv", csv)]).await;
+        }
+
+        let body = scrape(&db.state).await;
+        for line in [
+            r#"upload_files_total{outcome="committed"} 2"#,
+            "upload_file_duration_seconds_count 2",
+            r#"upload_rows_total{kind="funds",outcome="inserted"} 2"#,
+            r#"upload_rows_total{kind="funds",outcome="updated"} 1"#,
+            r#"upload_rows_total{kind="funds",outcome="failed"} 0"#,
+            r#"http_requests_total{method="POST",route="/api/v1/upload",status="202"} 2"#,
+        ] {
+            assert!(body.lines().any(|l| l == line), "no `{}` in\n{}", line, body);
+        }
+    }
+}
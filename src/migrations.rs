@@ -0,0 +1,527 @@
+use log::{info, warn};
+use std::collections::HashSet;
+use tokio_postgres::Client;
+
+struct Migration {
+    version: i32,
+    description: &'static str,
+    sql: &'static str,
+}
+
+// Embedded schema migrations, applied in order and recorded in schema_migrations.
+// Never edit an applied migration; add a new one instead.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create funds and scheme_rates",
+        sql: "
+            CREATE TABLE IF NOT EXISTS funds (
+                id SERIAL PRIMARY KEY,
+                category TEXT NOT NULL,
+                scheme_name TEXT NOT NULL,
+                launch_date TEXT,
+                fund_size_apr25 REAL,
+                fund_size_may25 REAL,
+                latest_nav REAL,
+                month_1 REAL,
+                months_3 REAL,
+                months_6 REAL,
+                ytd REAL,
+                year_1 REAL,
+                years_2 REAL,
+                years_3 REAL,
+                years_5 REAL,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                CONSTRAINT unique_scheme_name UNIQUE (scheme_name)
+            );
+
+            CREATE TABLE IF NOT EXISTS scheme_rates (
+                id SERIAL PRIMARY KEY,
+                arn TEXT NOT NULL,
+                company TEXT NOT NULL,
+                scheme_name TEXT NOT NULL,
+                scheme_category TEXT NOT NULL,
+                brokerage_type TEXT NOT NULL,
+                start_date DATE NOT NULL,
+                end_date DATE NOT NULL,
+                source_file TEXT NOT NULL,
+                is_approved BOOLEAN DEFAULT true,
+                base_year_1 REAL,
+                base_year_2 REAL,
+                base_year_3 REAL,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_funds_scheme_name
+                ON funds USING gin(to_tsvector('english', scheme_name));
+            CREATE INDEX IF NOT EXISTS idx_scheme_rates_scheme_name
+                ON scheme_rates USING gin(to_tsvector('english', scheme_name));
+        ",
+    },
+    Migration {
+        version: 2,
+        description: "create scheme_aliases",
+        sql: "
+            CREATE TABLE IF NOT EXISTS scheme_aliases (
+                id SERIAL PRIMARY KEY,
+                alias_name TEXT NOT NULL,
+                fund_id INTEGER NOT NULL REFERENCES funds(id) ON DELETE CASCADE,
+                source TEXT NOT NULL,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                CONSTRAINT unique_alias_name UNIQUE (alias_name)
+            );
+        ",
+    },
+    Migration {
+        version: 3,
+        description: "create category_vocabulary and category_reassignments",
+        sql: "
+            CREATE TABLE IF NOT EXISTS category_vocabulary (
+                name TEXT PRIMARY KEY,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS category_reassignments (
+                id SERIAL PRIMARY KEY,
+                from_category TEXT NOT NULL,
+                to_category TEXT NOT NULL,
+                funds_affected BIGINT NOT NULL,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+        ",
+    },
+    Migration {
+        version: 4,
+        description: "create category_preferences",
+        sql: "
+            CREATE TABLE IF NOT EXISTS category_preferences (
+                category TEXT PRIMARY KEY,
+                default_sort TEXT,
+                default_order TEXT,
+                default_fields TEXT[],
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+        ",
+    },
+    Migration {
+        version: 5,
+        description: "add version columns to funds and scheme_rates",
+        sql: "
+            ALTER TABLE funds ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
+            ALTER TABLE scheme_rates ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
+        ",
+    },
+    Migration {
+        version: 6,
+        description: "track data generation for snapshot validation",
+        sql: "
+            CREATE SEQUENCE IF NOT EXISTS data_generation_seq;
+
+            CREATE OR REPLACE FUNCTION bump_data_generation() RETURNS trigger AS $$
+            BEGIN
+                PERFORM nextval('data_generation_seq');
+                RETURN NULL;
+            END;
+            $$ LANGUAGE plpgsql;
+
+            CREATE TRIGGER funds_data_generation
+                AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON funds
+                FOR EACH STATEMENT EXECUTE FUNCTION bump_data_generation();
+            CREATE TRIGGER scheme_rates_data_generation
+                AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON scheme_rates
+                FOR EACH STATEMENT EXECUTE FUNCTION bump_data_generation();
+            CREATE TRIGGER scheme_aliases_data_generation
+                AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON scheme_aliases
+                FOR EACH STATEMENT EXECUTE FUNCTION bump_data_generation();
+        ",
+    },
+    Migration {
+        version: 7,
+        description: "record uploads and the fund values each wrote",
+        // Only writes made while an upload has set perftracker.upload_id are recorded, see
+        // history::record_writes. No foreign key to funds: the history outlives a fund's deletion.
+        sql: "
+            CREATE TABLE IF NOT EXISTS uploads (
+                id SERIAL PRIMARY KEY,
+                uploaded_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS fund_history (
+                id BIGSERIAL PRIMARY KEY,
+                fund_id INTEGER NOT NULL,
+                upload_id INTEGER NOT NULL REFERENCES uploads(id),
+                category TEXT NOT NULL,
+                scheme_name TEXT NOT NULL,
+                launch_date TEXT,
+                fund_size_apr25 REAL,
+                fund_size_may25 REAL,
+                latest_nav REAL,
+                month_1 REAL,
+                months_3 REAL,
+                months_6 REAL,
+                ytd REAL,
+                year_1 REAL,
+                years_2 REAL,
+                years_3 REAL,
+                years_5 REAL
+            );
+            CREATE INDEX IF NOT EXISTS idx_fund_history_fund_upload ON fund_history (fund_id, upload_id);
+
+            CREATE OR REPLACE FUNCTION record_fund_history() RETURNS trigger AS $$
+            DECLARE
+                current_upload TEXT := current_setting('perftracker.upload_id', true);
+            BEGIN
+                IF current_upload <> '' THEN
+                    INSERT INTO fund_history (
+                        fund_id, upload_id, category, scheme_name, launch_date, fund_size_apr25, fund_size_may25,
+                        latest_nav, month_1, months_3, months_6, ytd, year_1, years_2, years_3, years_5
+                    ) VALUES (
+                        NEW.id, current_upload::INTEGER, NEW.category, NEW.scheme_name, NEW.launch_date,
+                        NEW.fund_size_apr25, NEW.fund_size_may25, NEW.latest_nav, NEW.month_1, NEW.months_3,
+                        NEW.months_6, NEW.ytd, NEW.year_1, NEW.years_2, NEW.years_3, NEW.years_5
+                    );
+                END IF;
+                RETURN NULL;
+            END;
+            $$ LANGUAGE plpgsql;
+
+            CREATE TRIGGER funds_history
+                AFTER INSERT OR UPDATE ON funds
+                FOR EACH ROW EXECUTE FUNCTION record_fund_history();
+        ",
+    },
+    Migration {
+        version: 8,
+        description: "record fund uploads with their column mappings",
+        // Uploads numbered before this version have no provider and are grouped as 'unknown'
+        sql: "
+            ALTER TABLE uploads ADD COLUMN IF NOT EXISTS provider TEXT NOT NULL DEFAULT 'unknown';
+            ALTER TABLE uploads ADD COLUMN IF NOT EXISTS file_name TEXT;
+            ALTER TABLE uploads ADD COLUMN IF NOT EXISTS column_mappings JSONB NOT NULL DEFAULT '{}';
+            ALTER TABLE uploads ADD COLUMN IF NOT EXISTS processed INTEGER NOT NULL DEFAULT 0;
+
+            CREATE INDEX IF NOT EXISTS idx_uploads_provider ON uploads (provider, id);
+        ",
+    },
+    Migration {
+        version: 9,
+        description: "store launch_date as DATE in funds and fund_history",
+        // Converts the text formats earlier uploads stored; anything else (or an impossible date)
+        // becomes NULL rather than failing the migration
+        sql: "
+            CREATE OR REPLACE FUNCTION try_to_date(value TEXT, format TEXT) RETURNS DATE AS $$
+            BEGIN
+                RETURN to_date(value, format);
+            EXCEPTION WHEN OTHERS THEN
+                RETURN NULL;
+            END;
+            $$ LANGUAGE plpgsql IMMUTABLE;
+
+            CREATE OR REPLACE FUNCTION launch_date_from_text(launch_date TEXT) RETURNS DATE AS $$
+                SELECT CASE
+                    WHEN launch_date ~ '^\\d{4}-\\d{2}-\\d{2}' THEN try_to_date(substr(launch_date, 1, 10), 'YYYY-MM-DD')
+                    WHEN launch_date ~ '^\\d{1,2}-[A-Za-z]{3}-\\d{4}$' THEN try_to_date(launch_date, 'DD-Mon-YYYY')
+                    WHEN launch_date ~ '^\\d{1,2} [A-Za-z]{3} \\d{4}$' THEN try_to_date(launch_date, 'DD Mon YYYY')
+                    WHEN launch_date ~ '^\\d{1,2}/\\d{1,2}/\\d{4}$' THEN try_to_date(launch_date, 'DD/MM/YYYY')
+                    WHEN launch_date ~ '^\\d{1,2}-\\d{1,2}-\\d{4}$' THEN try_to_date(launch_date, 'DD-MM-YYYY')
+                    WHEN launch_date ~ '^\\d{1,2}\\.\\d{1,2}\\.\\d{4}$' THEN try_to_date(launch_date, 'DD.MM.YYYY')
+                    -- Excel serial days, as calamine rendered date cells
+                    WHEN launch_date ~ '^\\d{4,6}(\\.\\d+)?$' THEN DATE '1899-12-30' + floor(launch_date::NUMERIC)::INTEGER
+                    ELSE NULL
+                END
+            $$ LANGUAGE sql IMMUTABLE;
+
+            ALTER TABLE funds ALTER COLUMN launch_date TYPE DATE USING launch_date_from_text(launch_date);
+            ALTER TABLE fund_history ALTER COLUMN launch_date TYPE DATE USING launch_date_from_text(launch_date);
+
+            DROP FUNCTION launch_date_from_text(TEXT);
+            DROP FUNCTION try_to_date(TEXT, TEXT);
+        ",
+    },
+    Migration {
+        version: 10,
+        description: "guarantee the unique scheme_name constraint fund upserts conflict on",
+        // Databases created before version 1 managed the schema may lack it; duplicate names
+        // already stored make this fail loudly instead of every upload failing later
+        sql: "
+            DO $$
+            BEGIN
+                IF NOT EXISTS (
+                    SELECT 1 FROM pg_constraint
+                    WHERE conrelid = 'funds'::regclass AND conname = 'unique_scheme_name'
+                ) THEN
+                    ALTER TABLE funds ADD CONSTRAINT unique_scheme_name UNIQUE (scheme_name);
+                END IF;
+            END
+            $$;
+        ",
+    },
+    Migration {
+        version: 11,
+        description: "create category_mappings",
+        sql: "
+            CREATE TABLE IF NOT EXISTS category_mappings (
+                sheet_key TEXT PRIMARY KEY,
+                sheet_name TEXT NOT NULL,
+                category TEXT NOT NULL,
+                updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+        ",
+    },
+    Migration {
+        version: 12,
+        description: "upload history: checksums, row counts, status and funds.upload_id",
+        // Uploads recorded before this version all committed
+        sql: "
+            ALTER TABLE uploads ALTER COLUMN processed SET DEFAULT 0;
+            ALTER TABLE uploads ADD COLUMN IF NOT EXISTS sha256 TEXT;
+            ALTER TABLE uploads ADD COLUMN IF NOT EXISTS inserted INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE uploads ADD COLUMN IF NOT EXISTS updated INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE uploads ADD COLUMN IF NOT EXISTS failed INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE uploads ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'committed';
+
+            ALTER TABLE funds ADD COLUMN IF NOT EXISTS upload_id INTEGER REFERENCES uploads(id) ON DELETE SET NULL;
+            CREATE INDEX IF NOT EXISTS idx_funds_upload_id ON funds (upload_id);
+        ",
+    },
+    Migration {
+        version: 13,
+        description: "create name_aliases and expand_name_aliases for the fund/rate name join",
+        // expand_name_aliases mirrors normalize_scheme_name followed by VirtualTable's token
+        // expansion, so the join and in-memory search agree on which names are equal
+        sql: "
+            CREATE TABLE IF NOT EXISTS name_aliases (
+                id SERIAL PRIMARY KEY,
+                alias TEXT NOT NULL UNIQUE,
+                canonical TEXT NOT NULL,
+                created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TRIGGER name_aliases_data_generation
+                AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON name_aliases
+                FOR EACH STATEMENT EXECUTE FUNCTION bump_data_generation();
+
+            CREATE OR REPLACE FUNCTION expand_name_aliases(name TEXT) RETURNS TEXT AS $$
+                SELECT COALESCE(string_agg(COALESCE(na.canonical, t.token), ' ' ORDER BY t.position), '')
+                FROM regexp_split_to_table(
+                    LOWER(REGEXP_REPLACE(name, '[^a-zA-Z0-9\\s]', '', 'g')), '\\s+'
+                ) WITH ORDINALITY AS t(token, position)
+                LEFT JOIN name_aliases na ON na.alias = t.token
+                WHERE t.token <> ''
+            $$ LANGUAGE sql STABLE;
+        ",
+    },
+    Migration {
+        version: 14,
+        description: "rate approval: rejection_reason on scheme_rates",
+        // Pending = is_approved false with no rejection_reason; rejected = false with a reason
+        sql: "
+            ALTER TABLE scheme_rates ADD COLUMN IF NOT EXISTS rejection_reason TEXT;
+            CREATE INDEX IF NOT EXISTS idx_scheme_rates_pending
+                ON scheme_rates (id) WHERE is_approved = false AND rejection_reason IS NULL;
+        ",
+    },
+    Migration {
+        version: 15,
+        description: "updated_at on funds and scheme_rates",
+        // Existing rows start from created_at. Writes bump version in many places (uploads, edits,
+        // reassignments), so the timestamp is kept by a trigger rather than by each statement.
+        sql: "
+            ALTER TABLE funds ADD COLUMN IF NOT EXISTS updated_at TIMESTAMP;
+            ALTER TABLE scheme_rates ADD COLUMN IF NOT EXISTS updated_at TIMESTAMP;
+            UPDATE funds SET updated_at = COALESCE(created_at, CURRENT_TIMESTAMP);
+            UPDATE scheme_rates SET updated_at = COALESCE(created_at, CURRENT_TIMESTAMP);
+            ALTER TABLE funds ALTER COLUMN updated_at SET DEFAULT CURRENT_TIMESTAMP;
+            ALTER TABLE funds ALTER COLUMN updated_at SET NOT NULL;
+            ALTER TABLE scheme_rates ALTER COLUMN updated_at SET DEFAULT CURRENT_TIMESTAMP;
+            ALTER TABLE scheme_rates ALTER COLUMN updated_at SET NOT NULL;
+            CREATE INDEX IF NOT EXISTS idx_funds_updated_at ON funds (updated_at);
+            CREATE INDEX IF NOT EXISTS idx_scheme_rates_updated_at ON scheme_rates (updated_at);
+
+            CREATE OR REPLACE FUNCTION touch_updated_at() RETURNS trigger AS $$
+            BEGIN
+                NEW.updated_at := CURRENT_TIMESTAMP;
+                RETURN NEW;
+            END;
+            $$ LANGUAGE plpgsql;
+
+            CREATE TRIGGER funds_touch_updated_at
+                BEFORE UPDATE ON funds
+                FOR EACH ROW EXECUTE FUNCTION touch_updated_at();
+            CREATE TRIGGER scheme_rates_touch_updated_at
+                BEFORE UPDATE ON scheme_rates
+                FOR EACH ROW EXECUTE FUNCTION touch_updated_at();
+        ",
+    },
+    Migration {
+        version: 16,
+        description: "create audit_log",
+        // Not part of the served data, so no data_generation trigger
+        sql: "
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id BIGSERIAL PRIMARY KEY,
+                at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                actor TEXT NOT NULL,
+                actor_type TEXT NOT NULL,
+                actor_role TEXT,
+                action TEXT NOT NULL,
+                target_type TEXT NOT NULL,
+                target_id TEXT,
+                summary JSONB NOT NULL DEFAULT '{}',
+                request_id TEXT
+            );
+            CREATE INDEX IF NOT EXISTS idx_audit_log_at ON audit_log (at);
+            CREATE INDEX IF NOT EXISTS idx_audit_log_action ON audit_log (action, id);
+        ",
+    },
+    Migration {
+        version: 17,
+        description: "uploads.request_id",
+        // Earlier uploads predate request ids and keep NULL
+        sql: "
+            ALTER TABLE uploads ADD COLUMN IF NOT EXISTS request_id TEXT;
+        ",
+    },
+];
+
+// Destructive: only reachable through --reset-db / RESET_DB=1
+pub async fn reset_database(client: &Client) -> Result<(), tokio_postgres::Error> {
+    warn!("Resetting database: dropping all application tables");
+    client
+        .batch_execute(
+            "DROP FUNCTION IF EXISTS expand_name_aliases(TEXT);
+             DROP TABLE IF EXISTS name_aliases CASCADE;
+             DROP TABLE IF EXISTS fund_history CASCADE;
+             DROP TABLE IF EXISTS audit_log CASCADE;
+             DROP TABLE IF EXISTS scheme_aliases CASCADE;
+             DROP TABLE IF EXISTS category_vocabulary CASCADE;
+             DROP TABLE IF EXISTS category_reassignments CASCADE;
+             DROP TABLE IF EXISTS category_preferences CASCADE;
+             DROP TABLE IF EXISTS category_mappings CASCADE;
+             DROP TABLE IF EXISTS uploads CASCADE;
+             DROP TABLE IF EXISTS funds CASCADE;
+             DROP TABLE IF EXISTS scheme_rates CASCADE;
+             DROP TABLE IF EXISTS schema_migrations CASCADE;
+             DROP SEQUENCE IF EXISTS data_generation_seq;
+             DROP FUNCTION IF EXISTS bump_data_generation() CASCADE;
+             DROP FUNCTION IF EXISTS record_fund_history() CASCADE;
+             DROP FUNCTION IF EXISTS touch_updated_at() CASCADE;",
+        )
+        .await
+}
+
+// Apply every migration not yet recorded, each in its own transaction
+pub async fn run_migrations(client: &mut Client) -> Result<usize, tokio_postgres::Error> {
+    client
+        .batch_execute(
+            "CREATE TABLE IF NOT EXISTS schema_migrations (
+                version INTEGER PRIMARY KEY,
+                description TEXT NOT NULL,
+                applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await?;
+
+    let applied: HashSet<i32> = client
+        .query("SELECT version FROM schema_migrations", &[])
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    let mut count = 0;
+    for migration in MIGRATIONS {
+        if applied.contains(&migration.version) {
+            continue;
+        }
+
+        let transaction = client.transaction().await?;
+        transaction.batch_execute(migration.sql).await?;
+        transaction
+            .execute(
+                "INSERT INTO schema_migrations (version, description) VALUES ($1, $2)",
+                &[&migration.version, &migration.description],
+            )
+            .await?;
+        transaction.commit().await?;
+
+        info!("Applied migration {}: {}", migration.version, migration.description);
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+pub fn reset_requested() -> bool {
+    std::env::args().any(|arg| arg == "--reset-db")
+        || std::env::var("RESET_DB").map(|v| v == "1" || v.eq_ignore_ascii_case("true")).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support;
+
+    const CONSTRAINT_EXISTS: &str =
+        "SELECT 1 FROM pg_constraint WHERE conrelid = 'funds'::regclass AND conname = 'unique_scheme_name'";
+
+    #[test]
+    fn versions_are_unique_and_in_order() {
+        let versions: Vec<i32> = super::MIGRATIONS.iter().map(|migration| migration.version).collect();
+        assert!(versions.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", versions);
+    }
+
+    // A restart runs the migrations again; the data stays, unlike the table drops this replaced
+    #[actix_web::test]
+    async fn a_migrated_database_keeps_its_data_on_the_next_start() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute("INSERT INTO funds (category, scheme_name) VALUES ('Flexi Cap', 'Quant Flexi Cap Fund')").await;
+
+        let mut client = db.state.pools.primary().get().await.unwrap();
+        assert_eq!(super::run_migrations(&mut client).await.unwrap(), 0);
+
+        let recorded: Vec<i32> =
+            db.query("SELECT version FROM schema_migrations ORDER BY version").await.iter().map(|row| row.get(0)).collect();
+        let embedded: Vec<i32> = super::MIGRATIONS.iter().map(|migration| migration.version).collect();
+        assert_eq!(recorded, embedded);
+        assert_eq!(db.query("SELECT 1 FROM funds").await.len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn a_missing_unique_scheme_name_constraint_is_restored() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute("ALTER TABLE funds DROP CONSTRAINT unique_scheme_name; DELETE FROM schema_migrations WHERE version = 10")
+            .await;
+        assert!(db.query(CONSTRAINT_EXISTS).await.is_empty());
+
+        let mut client = db.state.pools.primary().get().await.unwrap();
+        assert_eq!(super::run_migrations(&mut client).await.unwrap(), 1);
+        assert_eq!(db.query(CONSTRAINT_EXISTS).await.len(), 1);
+        // Already applied: nothing to do
+        assert_eq!(super::run_migrations(&mut client).await.unwrap(), 0);
+    }
+
+    #[actix_web::test]
+    async fn duplicate_scheme_names_fail_the_migration_loudly() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(
+            "ALTER TABLE funds DROP CONSTRAINT unique_scheme_name; DELETE FROM schema_migrations WHERE version = 10;
+             INSERT INTO funds (category, scheme_name) VALUES ('Flexi Cap', 'Quant Flexi Cap Fund'), ('Flexi Cap', 'Quant Flexi Cap Fund')",
+        )
+        .await;
+
+        let mut client = db.state.pools.primary().get().await.unwrap();
+        let error = super::run_migrations(&mut client).await.unwrap_err();
+        assert!(error.as_db_error().unwrap().message().contains("unique_scheme_name"), "{}", error);
+        // Rolled back, so the next start tries again
+        assert!(db.query("SELECT 1 FROM schema_migrations WHERE version = 10").await.is_empty());
+    }
+}
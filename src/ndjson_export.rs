@@ -0,0 +1,96 @@
+use actix_web::web::Bytes;
+use futures_util::stream::{self, Stream};
+use log::info;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::table::VirtualTable;
+use crate::CombinedSchemeData;
+
+// Records serialized per body chunk
+const CHUNK_RECORDS: usize = 500;
+
+// Which records an export holds
+#[derive(Debug, Default)]
+pub struct Selection {
+    // Canonical fund category
+    pub category: Option<String>,
+    // Ids of the funds and rates written since updated_since; None exports regardless of age
+    pub changed: Option<(HashSet<i32>, HashSet<i32>)>,
+}
+
+impl Selection {
+    fn matches(&self, record: &CombinedSchemeData) -> bool {
+        if let Some(category) = &self.category {
+            if record.fund_category.as_deref() != Some(category.as_str()) {
+                return false;
+            }
+        }
+        match &self.changed {
+            Some((funds, rates)) => {
+                record.fund_id.is_some_and(|id| funds.contains(&id))
+                    || record.rate_id.is_some_and(|id| rates.contains(&id))
+            }
+            None => true,
+        }
+    }
+}
+
+// Where the export is in the table, and how far it got
+struct Cursor {
+    table: Arc<VirtualTable>,
+    selection: Selection,
+    next: usize,
+    sent: usize,
+    total: usize,
+}
+
+impl Drop for Cursor {
+    fn drop(&mut self) {
+        if self.sent < self.total {
+            info!("NDJSON export stopped after {} of {} records", self.sent, self.total);
+        }
+    }
+}
+
+// The matching record count, then a body that serializes CHUNK_RECORDS records per poll straight
+// from the table it was given. Nothing is serialized ahead of what the socket takes, so a client
+// that disconnects drops the body and the rest of the table is never touched.
+pub fn stream(
+    table: Arc<VirtualTable>,
+    selection: Selection,
+) -> (usize, impl Stream<Item = Result<Bytes, serde_json::Error>>) {
+    let total = table.iter().filter(|record| selection.matches(record)).count();
+    let cursor = Cursor {
+        table,
+        selection,
+        next: 0,
+        sent: 0,
+        total,
+    };
+
+    let body = stream::unfold(cursor, |mut cursor| async move {
+        let records = cursor.table.records();
+        if cursor.next >= records.len() {
+            return None;
+        }
+
+        let mut chunk = Vec::new();
+        let mut count = 0;
+        while cursor.next < records.len() && count < CHUNK_RECORDS {
+            let record = &records[cursor.next];
+            cursor.next += 1;
+            if !cursor.selection.matches(record) {
+                continue;
+            }
+            if let Err(e) = serde_json::to_writer(&mut chunk, record) {
+                return Some((Err(e), cursor));
+            }
+            chunk.push(b'\n');
+            count += 1;
+        }
+        cursor.sent += count;
+        Some((Ok(Bytes::from(chunk)), cursor))
+    });
+    (total, body)
+}
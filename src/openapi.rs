@@ -0,0 +1,366 @@
+use serde::{Deserialize, Serialize};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{IntoParams, Modify, OpenApi, ToSchema};
+
+use crate::api_error::ErrorCode;
+use crate::edits::FieldError;
+use crate::CombinedSchemeData;
+
+// The API contract served at /api-docs/openapi.json and browsable at /swagger. Every route in
+// routes::v1::configure belongs in `paths`, relative to the /api/v1 server; handlers build most
+// bodies with json!, so the types below describe those bodies rather than being used to produce them.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "perftracker",
+        description = "Mutual fund performance and broker rate search over the combined funds/scheme_rates table"
+    ),
+    servers(
+        (url = "/api/v1"),
+        (url = "/", description = "Unprefixed routes, deprecated in favour of /api/v1")
+    ),
+    paths(
+        crate::routes::v1::uploads::upload_excel,
+        crate::routes::v1::uploads::upload_rates,
+        crate::routes::v1::uploads::get_job,
+        crate::routes::v1::uploads::list_uploads,
+        crate::routes::v1::uploads::get_upload,
+        crate::routes::v1::uploads::purge_upload,
+        crate::routes::v1::search::search_schemes,
+        crate::routes::v1::search::search_schemes_csv,
+        crate::routes::v1::search::suggest_names,
+        crate::routes::v1::admin::status,
+        crate::routes::v1::admin::stats,
+        crate::routes::v1::search::facets,
+        crate::routes::v1::admin::refresh_virtual_table_endpoint,
+        crate::routes::v1::funds::head_fund_by_name,
+        crate::routes::v1::funds::get_fund_by_name,
+        crate::routes::v1::funds::batch_funds_by_name,
+        crate::routes::v1::funds::list_funds,
+        crate::routes::v1::funds::get_fund,
+        crate::routes::v1::funds::get_fund_rates,
+        crate::routes::v1::funds::put_fund,
+        crate::routes::v1::funds::patch_fund,
+        crate::routes::v1::funds::delete_fund,
+        crate::routes::v1::rates::list_pending_rates,
+        crate::routes::v1::rates::get_rate,
+        crate::routes::v1::rates::create_rate,
+        crate::routes::v1::rates::delete_rates_by_source,
+        crate::routes::v1::rates::approve_rate,
+        crate::routes::v1::rates::reject_rate,
+        crate::routes::v1::rates::put_rate,
+        crate::routes::v1::rates::patch_rate,
+        crate::routes::v1::rates::delete_rate,
+        crate::routes::v1::uploads::get_provider_mappings,
+        crate::routes::v1::uploads::get_provider_mapping_diff,
+        crate::routes::v1::admin::reload_config_endpoint,
+        crate::routes::v1::exports::export_unmatched_rates,
+        crate::routes::v1::exports::export_xlsx,
+        crate::routes::v1::exports::export_ndjson,
+        crate::routes::v1::exports::export_manifest,
+        crate::routes::v1::exports::download_artifact,
+        crate::routes::v1::rates::import_rate_matches,
+        crate::routes::v1::categories::list_categories,
+        crate::routes::v1::categories::list_companies,
+        crate::routes::v1::categories::list_arns,
+        crate::routes::v1::categories::get_category_mappings,
+        crate::routes::v1::categories::put_category_mappings,
+        crate::routes::v1::categories::get_category_vocabulary,
+        crate::routes::v1::categories::extend_category_vocabulary,
+        crate::routes::v1::categories::reassign_category,
+        crate::routes::v1::categories::get_category_preferences,
+        crate::routes::v1::categories::put_category_preference,
+        crate::routes::v1::categories::delete_category_preference,
+        crate::routes::v1::admin::list_name_aliases,
+        crate::routes::v1::admin::create_name_alias,
+        crate::routes::v1::admin::delete_name_alias,
+        crate::routes::v1::admin::list_audit_log,
+    ),
+    components(schemas(
+        ErrorResponse,
+        ErrorCode,
+        SearchResponse,
+        UploadForm,
+        RateFileForm,
+        UploadAccepted,
+        CombinedSchemeData,
+        FieldError,
+        crate::UploadReport,
+        crate::SheetError,
+        crate::FundRowError,
+        crate::MergedScheme,
+        crate::routes::v1::funds::FundByNameBatch,
+        crate::routes::v1::categories::VocabularyExtension,
+        crate::routes::v1::categories::CategoryReassignment,
+        crate::routes::v1::rates::RateRejection,
+        crate::aliases::NameAlias,
+        crate::aliases::NewNameAlias,
+        crate::audit::AuditEntry,
+        crate::categories::CategoryIssue,
+        crate::categories::CategoryMapping,
+        crate::edits::FundRecord,
+        crate::edits::FundEdit,
+        crate::edits::FundPatch,
+        crate::edits::RateRecord,
+        crate::edits::RateEdit,
+        crate::edits::RatePatch,
+        crate::edits::NewRate,
+        crate::jobs::JobState,
+        crate::jobs::JobStatus,
+        crate::preferences::CategoryPreference,
+        crate::preferences::SortOrder,
+        crate::rate_upload::RateUploadReport,
+        crate::rate_upload::RateRowError,
+        crate::sheets::SheetDecision,
+    )),
+    tags(
+        (name = "search", description = "Searching the served (virtual) table"),
+        (name = "uploads", description = "Fund workbooks and rate sheets"),
+        (name = "funds", description = "Single funds, read from and written to Postgres"),
+        (name = "rates", description = "scheme_rates rows and their approval"),
+        (name = "exports", description = "CSV/xlsx downloads and published artifacts"),
+        (name = "categories", description = "Categories, companies, ARNs and their settings"),
+        (name = "admin", description = "Status, refresh, configuration and aliases")
+    ),
+    modifiers(&Credentials)
+)]
+pub struct ApiDoc;
+
+// The X-Api-Key and JWT bearer schemes named by every write's `security`; a write's 403 says which
+// role it needs. Reads need credentials too when require_api_key_for_reads is set, which the spec
+// can't express per deployment.
+struct Credentials;
+
+impl Modify for Credentials {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Api-Key"))),
+            );
+            components.add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).bearer_format("JWT").build()),
+            );
+        }
+    }
+}
+
+// The envelope of every error, as rendered by ApiError. Every 503 comes with Retry-After.
+#[allow(dead_code)]
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ErrorResponse {
+    /// missing_parameter, invalid_parameter, invalid_body and invalid_multipart are 400; unauthorized, invalid_token and token_expired 401; forbidden 403; not_found 404; conflict 409; payload_too_large 413; unknown_value and unprocessable 422; precondition_required 428; rate_limited 429 with Retry-After; internal 500; database_unavailable and unavailable 503
+    pub code: ErrorCode,
+    pub message: String,
+    // unknown_value: {"parameter", "closest"}; invalid_body for a rate: {"errors": [FieldError]};
+    // conflict on an edit: {"current"}; a failed upload: the per-file entries under "files"
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<serde_json::Value>,
+    // Also sent as the X-Request-Id header
+    pub request_id: String,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, ToSchema)]
+pub struct SearchResponse {
+    #[schema(example = "success")]
+    pub status: String,
+    pub query: String,
+    pub count: usize,
+    pub total_matches: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub grouped: bool,
+    // "flat" or "nested"
+    pub shape: String,
+    // No query and no filters: the whole table, page by page
+    pub browse: bool,
+    // Only the first 12 query tokens were used, or the worst ranked partial matches past
+    // max_search_candidates were dropped; total_matches then undercounts
+    pub truncated: bool,
+    pub sort: Option<String>,
+    // Records projected to the requested fields, each with match_type and score. Nested results
+    // carry their rate fields in a "rates" array instead.
+    pub data: Vec<CombinedSchemeData>,
+    // Closest fund names when nothing matched
+    #[schema(value_type = Vec<Object>)]
+    pub did_you_mean: Vec<serde_json::Value>,
+    // Display settings, applied filters, exclusion counts and filter warnings
+    #[schema(value_type = Object)]
+    pub meta: serde_json::Value,
+    // Only with debug=true
+    #[schema(value_type = Option<Object>)]
+    pub diagnostics: Option<serde_json::Value>,
+}
+
+// POST /upload form: one or more workbooks (xlsx, xlsm, xlsb, xls, ods or csv)
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct UploadForm {
+    #[schema(value_type = Vec<String>, format = Binary)]
+    pub excel_file: Vec<Vec<u8>>,
+    // Data provider, keys the column-mapping history
+    pub provider: Option<String>,
+}
+
+// POST /upload/rates and POST /import/rate-matches form: a single file in any field
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct RateFileForm {
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+}
+
+#[allow(dead_code)]
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct UploadAccepted {
+    #[schema(example = "accepted")]
+    pub status: String,
+    pub message: String,
+    pub job_id: u64,
+    // Poll for the job's UploadReports
+    pub job_url: String,
+}
+
+// The query parameters of /search, which the handler reads from the raw query map. Every numeric
+// field listed by filters::NumericField also takes min_<field>/max_<field>.
+#[allow(dead_code)]
+#[derive(IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchParams {
+    // Scheme name, partial name or ARN, at most 200 characters; empty lists the table
+    pub q: Option<String>,
+    // Defaults to search_limit, or to max_export_rows for CSV
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    // A field name, '-' prefixed for descending
+    pub sort: Option<String>,
+    // "asc" or "desc"
+    pub order: Option<String>,
+    // Comma-separated fields to keep in each result
+    pub fields: Option<String>,
+    // "flat" (default) or "nested"
+    pub shape: Option<String>,
+    // Older spelling of shape=nested
+    pub group: Option<bool>,
+    // "json" or "csv"; otherwise decided by the Accept header
+    pub format: Option<String>,
+    pub debug: Option<bool>,
+    // Repeatable; any of them matches
+    #[param(style = Form, explode)]
+    pub category: Option<Vec<String>>,
+    pub company: Option<String>,
+    pub brokerage_type: Option<String>,
+    pub arn: Option<String>,
+    pub has_rates: Option<bool>,
+    pub include_incomplete: Option<bool>,
+    pub include_expired: Option<bool>,
+    // YYYY-MM-DD; judge rate activeness on this date instead of the build date
+    pub as_of: Option<chrono::NaiveDate>,
+    // CSV only: fund values as recorded right after this upload; the matches are today's
+    pub as_of_upload_id: Option<i32>,
+    // CSV only: fund values as recorded at the end of this YYYY-MM-DD day
+    pub as_of_date: Option<chrono::NaiveDate>,
+    pub min_fund_size: Option<f32>,
+    pub max_fund_size: Option<f32>,
+    pub min_fund_size_apr25: Option<f32>,
+    pub max_fund_size_apr25: Option<f32>,
+    pub min_fund_size_may25: Option<f32>,
+    pub max_fund_size_may25: Option<f32>,
+    pub min_latest_nav: Option<f32>,
+    pub max_latest_nav: Option<f32>,
+    pub min_month_1: Option<f32>,
+    pub max_month_1: Option<f32>,
+    pub min_months_3: Option<f32>,
+    pub max_months_3: Option<f32>,
+    pub min_months_6: Option<f32>,
+    pub max_months_6: Option<f32>,
+    pub min_ytd: Option<f32>,
+    pub max_ytd: Option<f32>,
+    pub min_year_1: Option<f32>,
+    pub max_year_1: Option<f32>,
+    pub min_years_2: Option<f32>,
+    pub max_years_2: Option<f32>,
+    pub min_years_3: Option<f32>,
+    pub max_years_3: Option<f32>,
+    pub min_years_5: Option<f32>,
+    pub max_years_5: Option<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::Method;
+    use actix_web::test::TestRequest;
+    use regex::Regex;
+    use serde_json::Value;
+    use std::collections::BTreeSet;
+
+    use crate::test_support;
+
+    // (method, path) of every `.route(...)` call in a source file
+    fn routes_in(source: &str) -> BTreeSet<(String, String)> {
+        let route = Regex::new(r#"\.route\("([^"]+)", web::(\w+)\(\)"#).unwrap();
+        route
+            .captures_iter(source)
+            .map(|captures| (captures[2].to_uppercase(), captures[1].to_string()))
+            .collect()
+    }
+
+    fn documented() -> BTreeSet<(String, String)> {
+        let spec: Value = serde_json::from_str(&ApiDoc::openapi().to_json().unwrap()).expect("the spec is JSON");
+        let methods = ["get", "head", "post", "put", "patch", "delete"];
+        let mut operations = BTreeSet::new();
+        for (path, item) in spec["paths"].as_object().expect("the spec has paths") {
+            for method in item.as_object().unwrap().keys().filter(|key| methods.contains(&key.as_str())) {
+                operations.insert((method.to_uppercase(), path.clone()));
+            }
+        }
+        operations
+    }
+
+    #[test]
+    fn the_spec_documents_exactly_the_v1_routes() {
+        let registered = routes_in(include_str!("routes/v1/mod.rs"));
+        let documented = documented();
+        assert_eq!(
+            registered.difference(&documented).collect::<Vec<_>>(),
+            Vec::<&(String, String)>::new(),
+            "registered but not in the spec"
+        );
+        assert_eq!(
+            documented.difference(&registered).collect::<Vec<_>>(),
+            Vec::<&(String, String)>::new(),
+            "in the spec but never registered"
+        );
+    }
+
+    // Outside /api/v1, and so outside the spec: the upload page, Swagger itself and the Prometheus
+    // scrape target. Another route here would be an API route nobody documented.
+    #[test]
+    fn only_the_page_swagger_and_metrics_sit_outside_v1() {
+        let outside: Vec<String> = routes_in(include_str!("lib.rs")).into_iter().map(|(_, path)| path).collect();
+        assert_eq!(outside, ["/", "/metrics", "/swagger"]);
+    }
+
+    #[actix_web::test]
+    async fn every_documented_operation_is_routed_under_v1() {
+        let placeholder = Regex::new(r"\{[^}]+\}").unwrap();
+        for (method, path) in documented() {
+            // A fresh state each time, so the rate limiter never answers in the route's place
+            let state = test_support::state(test_support::runtime_config(), vec![]);
+            let uri = format!("/api/v1{}", placeholder.replace_all(&path, "1"));
+            let request = TestRequest::default().method(Method::from_bytes(method.as_bytes()).unwrap()).uri(&uri);
+            let response = test_support::call(&state, test_support::as_admin(request)).await;
+            assert_eq!(
+                response.request().match_pattern(),
+                Some(format!("/api/v1{}", path)),
+                "{} {} answered {}",
+                method,
+                uri,
+                response.status()
+            );
+        }
+    }
+}
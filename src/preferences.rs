@@ -0,0 +1,425 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use tokio_postgres::Client;
+use utoipa::ToSchema;
+
+use crate::filters::NumericField;
+use crate::{normalize_scheme_name, CombinedSchemeData};
+
+// Every field of CombinedSchemeData a caller can ask for in `fields`
+pub const FIELD_NAMES: [&str; 29] = [
+    "fund_id",
+    "fund_category",
+    "launch_date",
+    "fund_size_apr25",
+    "fund_size_may25",
+    "latest_nav",
+    "month_1",
+    "months_3",
+    "months_6",
+    "ytd",
+    "year_1",
+    "years_2",
+    "years_3",
+    "years_5",
+    "rate_id",
+    "arn",
+    "company",
+    "scheme_category",
+    "brokerage_type",
+    "start_date",
+    "end_date",
+    "base_year_1",
+    "base_year_2",
+    "base_year_3",
+    "scheme_name",
+    "normalized_name",
+    "data_completeness",
+    "incomplete",
+    "is_rate_active",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "asc" => Some(SortOrder::Asc),
+            "desc" => Some(SortOrder::Desc),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+}
+
+// Global defaults: relevance order, every field
+const GLOBAL_ORDER: SortOrder = SortOrder::Desc;
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct CategoryPreference {
+    pub category: String,
+    pub default_sort: Option<String>,
+    pub default_order: Option<SortOrder>,
+    pub default_fields: Option<Vec<String>>,
+}
+
+impl CategoryPreference {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.category.trim().is_empty() {
+            return Err("'category' must not be empty".to_string());
+        }
+        if let Some(sort) = &self.default_sort {
+            validate_sort(sort)?;
+        }
+        if let Some(fields) = &self.default_fields {
+            validate_fields(fields)?;
+        }
+        Ok(())
+    }
+}
+
+// Preferences keyed by normalized category name
+pub type PreferenceMap = HashMap<String, CategoryPreference>;
+
+pub async fn load_preferences(client: &Client) -> Result<PreferenceMap, tokio_postgres::Error> {
+    let rows = client
+        .query(
+            "SELECT category, default_sort, default_order, default_fields FROM category_preferences",
+            &[],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let category: String = row.get("category");
+            let default_order: Option<String> = row.get("default_order");
+            let preference = CategoryPreference {
+                category: category.clone(),
+                default_sort: row.get("default_sort"),
+                default_order: default_order.as_deref().and_then(SortOrder::parse),
+                default_fields: row.get("default_fields"),
+            };
+            (normalize_scheme_name(&category), preference)
+        })
+        .collect())
+}
+
+pub async fn upsert_preference(client: &Client, preference: &CategoryPreference) -> Result<(), tokio_postgres::Error> {
+    let order = preference.default_order.map(SortOrder::as_str);
+    client
+        .execute(
+            "INSERT INTO category_preferences (category, default_sort, default_order, default_fields)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (category) DO UPDATE SET
+                default_sort = EXCLUDED.default_sort,
+                default_order = EXCLUDED.default_order,
+                default_fields = EXCLUDED.default_fields,
+                updated_at = CURRENT_TIMESTAMP",
+            &[
+                &preference.category.trim(),
+                &preference.default_sort,
+                &order,
+                &preference.default_fields,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+// Returns the number of preferences removed (0 or 1)
+pub async fn delete_preference(client: &Client, category: &str) -> Result<u64, tokio_postgres::Error> {
+    client
+        .execute("DELETE FROM category_preferences WHERE category = $1", &[&category])
+        .await
+}
+
+fn validate_sort(sort: &str) -> Result<(), String> {
+    if sort == "scheme_name" || NumericField::from_name(sort).is_some() {
+        Ok(())
+    } else {
+        Err(format!("'{}' is not a sortable field", sort))
+    }
+}
+
+fn validate_fields(fields: &[String]) -> Result<(), String> {
+    if fields.is_empty() {
+        return Err("'fields' must list at least one field".to_string());
+    }
+    match fields.iter().find(|field| !FIELD_NAMES.contains(&field.as_str())) {
+        Some(unknown) => Err(format!("'{}' is not a known field", unknown)),
+        None => Ok(()),
+    }
+}
+
+// sort/order/fields exactly as given on the request, before any defaults
+#[derive(Debug, Clone, Default)]
+pub struct DisplayRequest {
+    pub sort: Option<String>,
+    pub order: Option<SortOrder>,
+    pub fields: Option<Vec<String>>,
+}
+
+impl DisplayRequest {
+    // `sort=-year_1` is shorthand for `sort=year_1&order=desc`, and a bare `sort=year_1` without
+    // `order` sorts ascending. A sign that contradicts `order` is rejected.
+    pub fn from_query(params: &HashMap<String, String>) -> Result<Self, String> {
+        let signed = params.get("sort").map(|sort| {
+            let sort = sort.trim();
+            match sort.strip_prefix('-') {
+                Some(field) => (field.trim().to_string(), SortOrder::Desc),
+                None => (sort.to_string(), SortOrder::Asc),
+            }
+        });
+        if let Some((sort, _)) = &signed {
+            validate_sort(sort)?;
+        }
+
+        let requested_order = match params.get("order") {
+            Some(order) => Some(
+                SortOrder::parse(order)
+                    .ok_or_else(|| format!("Parameter 'order' must be asc or desc, got '{}'", order))?,
+            ),
+            None => None,
+        };
+        let order = match (&signed, requested_order) {
+            (Some((sort, SortOrder::Desc)), Some(SortOrder::Asc)) => {
+                return Err(format!("Parameter 'sort=-{}' contradicts 'order=asc'", sort))
+            }
+            (_, Some(order)) => Some(order),
+            (Some((_, sign)), None) => Some(*sign),
+            (None, None) => None,
+        };
+        let sort = signed.map(|(sort, _)| sort);
+
+        let fields = params.get("fields").map(|fields| {
+            fields
+                .split(',')
+                .map(|field| field.trim().to_string())
+                .filter(|field| !field.is_empty())
+                .collect::<Vec<_>>()
+        });
+        if let Some(fields) = &fields {
+            validate_fields(fields)?;
+        }
+
+        Ok(Self { sort, order, fields })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DisplaySources {
+    pub sort: &'static str,
+    pub order: &'static str,
+    pub fields: &'static str,
+}
+
+// The sort/order/fields actually applied, and where each came from ("request", "category", "global")
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveDisplay {
+    pub sort: Option<String>,
+    pub order: SortOrder,
+    pub fields: Option<Vec<String>>,
+    pub sources: DisplaySources,
+}
+
+// Explicit request parameters win, then the category's defaults, then the global defaults.
+// Each setting is resolved independently, so `sort` from the request can combine with the
+// category's `fields`.
+pub fn resolve(request: &DisplayRequest, preference: Option<&CategoryPreference>) -> EffectiveDisplay {
+    let (sort, sort_source) = match (&request.sort, preference.and_then(|p| p.default_sort.as_ref())) {
+        (Some(sort), _) => (Some(sort.clone()), "request"),
+        (None, Some(sort)) => (Some(sort.clone()), "category"),
+        (None, None) => (None, "global"),
+    };
+    let (order, order_source) = match (request.order, preference.and_then(|p| p.default_order)) {
+        (Some(order), _) => (order, "request"),
+        (None, Some(order)) => (order, "category"),
+        (None, None) => (GLOBAL_ORDER, "global"),
+    };
+    let (fields, fields_source) = match (&request.fields, preference.and_then(|p| p.default_fields.as_ref())) {
+        (Some(fields), _) => (Some(fields.clone()), "request"),
+        (None, Some(fields)) => (Some(fields.clone()), "category"),
+        (None, None) => (None, "global"),
+    };
+
+    EffectiveDisplay {
+        sort,
+        order,
+        fields,
+        sources: DisplaySources {
+            sort: sort_source,
+            order: order_source,
+            fields: fields_source,
+        },
+    }
+}
+
+impl EffectiveDisplay {
+    // The applied sort in request syntax, e.g. "-year_1"; None when results keep relevance order
+    pub fn sort_param(&self) -> Option<String> {
+        self.sort.as_ref().map(|sort| match self.order {
+            SortOrder::Asc => sort.clone(),
+            SortOrder::Desc => format!("-{}", sort),
+        })
+    }
+
+    // Sort in place by the record each item carries; records missing the sort value always go
+    // last regardless of order. Without a sort the items keep their order.
+    pub fn sort<T>(&self, items: &mut [T], record: impl Fn(&T) -> &CombinedSchemeData) {
+        let sort = match &self.sort {
+            Some(sort) => sort,
+            None => return,
+        };
+
+        if sort == "scheme_name" {
+            items.sort_by(|a, b| self.directed(record(a).scheme_name.cmp(&record(b).scheme_name)));
+        } else if let Some(field) = NumericField::from_name(sort) {
+            items.sort_by(|a, b| match (field.value(record(a)), field.value(record(b))) {
+                (Some(x), Some(y)) => self.directed(x.partial_cmp(&y).unwrap_or(Ordering::Equal)),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            });
+        }
+    }
+
+    fn directed(&self, ordering: Ordering) -> Ordering {
+        match self.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    }
+
+    // Serialize a record keeping only the selected fields (all of them when none are selected)
+    pub fn project(&self, record: &CombinedSchemeData) -> Result<Value, serde_json::Error> {
+        let mut value = serde_json::to_value(record)?;
+        if let (Some(fields), Value::Object(map)) = (&self.fields, &mut value) {
+            map.retain(|key, _| fields.iter().any(|field| field == key));
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(query: &[(&str, &str)]) -> DisplayRequest {
+        let params = query.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        DisplayRequest::from_query(&params).unwrap()
+    }
+
+    fn debt_desk() -> CategoryPreference {
+        CategoryPreference {
+            category: "Corporate Bond".to_string(),
+            default_sort: Some("year_1".to_string()),
+            default_order: Some(SortOrder::Desc),
+            default_fields: Some(vec!["scheme_name".to_string(), "year_1".to_string()]),
+        }
+    }
+
+    fn summary(display: &EffectiveDisplay) -> (Option<String>, Option<usize>, [&str; 3]) {
+        (
+            display.sort_param(),
+            display.fields.as_ref().map(Vec::len),
+            [display.sources.sort, display.sources.order, display.sources.fields],
+        )
+    }
+
+    // Query parameters, whether the category has defaults, and the expected sort, field count and
+    // where sort, order and fields came from
+    type PrecedenceCase = (&'static [(&'static str, &'static str)], bool, (Option<&'static str>, Option<usize>, [&'static str; 3]));
+
+    // Every setting is resolved on its own: request over category over global
+    #[test]
+    fn precedence_matrix() {
+        let cases: [PrecedenceCase; 8] = [
+            (&[], false, (None, None, ["global", "global", "global"])),
+            (&[], true, (Some("-year_1"), Some(2), ["category", "category", "category"])),
+            (&[("sort", "years_3")], false, (Some("years_3"), None, ["request", "request", "global"])),
+            (&[("sort", "years_3")], true, (Some("years_3"), Some(2), ["request", "request", "category"])),
+            (&[("order", "asc")], false, (None, None, ["global", "request", "global"])),
+            (&[("order", "asc")], true, (Some("year_1"), Some(2), ["category", "request", "category"])),
+            (&[("fields", "scheme_name")], true, (Some("-year_1"), Some(1), ["category", "category", "request"])),
+            (
+                &[("sort", "-years_5"), ("fields", "scheme_name,years_5,years_3")],
+                true,
+                (Some("-years_5"), Some(3), ["request", "request", "request"]),
+            ),
+        ];
+
+        let preference = debt_desk();
+        for (query, with_preference, (sort, fields, sources)) in cases {
+            let display = resolve(&request(query), with_preference.then_some(&preference));
+            assert_eq!(
+                summary(&display),
+                (sort.map(str::to_string), fields, sources),
+                "{:?} with category defaults: {}",
+                query,
+                with_preference
+            );
+        }
+    }
+
+    #[test]
+    fn a_partial_preference_leaves_the_rest_global() {
+        let preference = CategoryPreference {
+            default_order: None,
+            default_fields: None,
+            ..debt_desk()
+        };
+        let display = resolve(&request(&[]), Some(&preference));
+        assert_eq!(summary(&display), (Some("-year_1".to_string()), None, ["category", "global", "global"]));
+    }
+
+    #[test]
+    fn signed_sort_shorthand_and_contradictions() {
+        let params = |query: &[(&str, &str)]| -> HashMap<String, String> {
+            query.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+
+        assert_eq!(request(&[("sort", "-year_1")]).order, Some(SortOrder::Desc));
+        assert_eq!(request(&[("sort", "year_1")]).order, Some(SortOrder::Asc));
+        assert_eq!(request(&[("sort", "year_1"), ("order", "DESC")]).order, Some(SortOrder::Desc));
+        assert_eq!(
+            DisplayRequest::from_query(&params(&[("sort", "-year_1"), ("order", "asc")])).unwrap_err(),
+            "Parameter 'sort=-year_1' contradicts 'order=asc'"
+        );
+        assert!(DisplayRequest::from_query(&params(&[("sort", "launch_date")])).is_err());
+        assert!(DisplayRequest::from_query(&params(&[("fields", "scheme_name,colour")])).is_err());
+    }
+
+    #[test]
+    fn records_missing_the_sort_value_go_last_either_way() {
+        let mut records: Vec<CombinedSchemeData> = [Some(5.0), None, Some(9.0)]
+            .iter()
+            .enumerate()
+            .map(|(n, year_1)| {
+                let mut record = CombinedSchemeData::test_fund(n as i32 + 1, &format!("Fund {}", n + 1));
+                record.year_1 = *year_1;
+                record
+            })
+            .collect();
+
+        for (query, expected) in [("year_1", [1, 3, 2]), ("-year_1", [3, 1, 2])] {
+            let display = resolve(&request(&[("sort", query)]), None);
+            display.sort(&mut records, |record| record);
+            let ids: Vec<i32> = records.iter().filter_map(|record| record.fund_id).collect();
+            assert_eq!(ids, expected, "sort={}", query);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use tokio_postgres::{Client, Row};
+
+use crate::columns::ColumnMapping;
+
+// Sheet (category) -> field -> header, as stored in uploads.column_mappings
+pub type SheetMappings = BTreeMap<String, ColumnMapping>;
+
+const MONTHS: [&str; 24] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec", "january", "february",
+    "march", "april", "june", "july", "august", "september", "sept", "october", "november", "december",
+];
+// Words that mark a re-issue of the same file rather than a different provider
+const NOISE: [&str; 6] = ["final", "latest", "copy", "updated", "revised", "new"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadMappings {
+    pub upload_id: i32,
+    pub file_name: Option<String>,
+    pub uploaded_at: Option<NaiveDateTime>,
+    pub column_mappings: SheetMappings,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderMappings {
+    pub provider: String,
+    pub upload_count: i64,
+    pub latest: UploadMappings,
+    pub previous_upload_id: Option<i32>,
+    // False when there is no previous upload to compare against
+    pub mapping_changed: bool,
+    #[serde(skip)]
+    pub previous: Option<UploadMappings>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MappingChange {
+    pub sheet: String,
+    pub field: String,
+    // None when the field wasn't mapped on that side
+    pub previous: Option<String>,
+    pub current: Option<String>,
+}
+
+// An explicit `provider` wins; otherwise group by the file name with dates, versions and
+// re-issue markers removed, so "HDFC_Factsheet_Mar2025_v2.xlsx" and "hdfc factsheet april 2025.xlsx"
+// both become "hdfc-factsheet"
+pub fn provider_key(explicit: Option<&str>, file_name: Option<&str>) -> String {
+    let source = match explicit {
+        Some(provider) => provider.to_string(),
+        None => file_name
+            .and_then(|name| Path::new(name).file_stem())
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    };
+
+    let tokens: Vec<String> = source
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .filter(|token| explicit.is_some() || !is_noise_token(token))
+        .map(str::to_string)
+        .collect();
+
+    if tokens.is_empty() {
+        "unknown".to_string()
+    } else {
+        tokens.join("-")
+    }
+}
+
+fn is_noise_token(token: &str) -> bool {
+    token.chars().any(|c| c.is_ascii_digit()) || MONTHS.contains(&token) || NOISE.contains(&token)
+}
+
+fn upload_from_row(row: &Row) -> UploadMappings {
+    let mappings: serde_json::Value = row.get("column_mappings");
+    UploadMappings {
+        upload_id: row.get("id"),
+        file_name: row.get("file_name"),
+        uploaded_at: row.get("uploaded_at"),
+        // Written by uploads::begin, so only a hand-edited row fails to decode
+        column_mappings: serde_json::from_value(mappings).unwrap_or_default(),
+    }
+}
+
+// Latest upload of every provider (optionally just one), compared with the one before it
+pub async fn load_provider_mappings(
+    client: &Client,
+    provider: Option<&str>,
+) -> Result<Vec<ProviderMappings>, tokio_postgres::Error> {
+    let rows = client
+        .query(
+            "SELECT id, provider, file_name, column_mappings, uploaded_at, upload_count FROM (
+                SELECT *,
+                       ROW_NUMBER() OVER (PARTITION BY provider ORDER BY id DESC) AS recency,
+                       COUNT(*) OVER (PARTITION BY provider) AS upload_count
+                FROM uploads
+                WHERE ($1::TEXT IS NULL OR provider = $1) AND status = 'committed'
+             ) ranked
+             WHERE recency <= 2
+             ORDER BY provider, id DESC",
+            &[&provider],
+        )
+        .await?;
+
+    let mut providers: Vec<ProviderMappings> = Vec::new();
+    for row in &rows {
+        let name: String = row.get("provider");
+        let upload = upload_from_row(row);
+        match providers.last_mut() {
+            Some(current) if current.provider == name => {
+                current.mapping_changed = current.latest.column_mappings != upload.column_mappings;
+                current.previous_upload_id = Some(upload.upload_id);
+                current.previous = Some(upload);
+            }
+            _ => providers.push(ProviderMappings {
+                provider: name,
+                upload_count: row.get("upload_count"),
+                latest: upload,
+                previous_upload_id: None,
+                mapping_changed: false,
+                previous: None,
+            }),
+        }
+    }
+    Ok(providers)
+}
+
+// Field-level differences between two uploads, sheet by sheet
+pub fn diff(previous: &SheetMappings, current: &SheetMappings) -> Vec<MappingChange> {
+    let empty = ColumnMapping::new();
+    let sheets: BTreeSet<&String> = previous.keys().chain(current.keys()).collect();
+
+    let mut changes = Vec::new();
+    for sheet in sheets {
+        let before = previous.get(sheet).unwrap_or(&empty);
+        let after = current.get(sheet).unwrap_or(&empty);
+        let fields: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+        for field in fields {
+            let (old, new) = (before.get(field), after.get(field));
+            if old != new {
+                changes.push(MappingChange {
+                    sheet: sheet.clone(),
+                    field: field.clone(),
+                    previous: old.cloned(),
+                    current: new.cloned(),
+                });
+            }
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_support;
+
+    fn mappings(sheet: &str, pairs: &[(&str, &str)]) -> SheetMappings {
+        let mapping: ColumnMapping = pairs.iter().map(|(field, header)| (field.to_string(), header.to_string())).collect();
+        SheetMappings::from([(sheet.to_string(), mapping)])
+    }
+
+    #[test]
+    fn file_names_of_one_provider_group_together() {
+        for file_name in [
+            "HDFC_Factsheet_Mar2025_v2.xlsx",
+            "hdfc factsheet april 2025.xlsx",
+            "HDFC-Factsheet (final) copy.xls",
+            "hdfc_factsheet_2025-06-30_latest.ods",
+        ] {
+            assert_eq!(provider_key(None, Some(file_name)), "hdfc-factsheet", "{}", file_name);
+        }
+        assert_eq!(provider_key(None, Some("SBI Factsheet May 2025.xlsx")), "sbi-factsheet");
+        assert_eq!(provider_key(None, Some("2025-05.xlsx")), "unknown");
+        assert_eq!(provider_key(None, None), "unknown");
+    }
+
+    #[test]
+    fn an_explicit_provider_wins_and_keeps_its_digits() {
+        assert_eq!(provider_key(Some("AMFI 2025 Feed"), Some("HDFC_Factsheet.xlsx")), "amfi-2025-feed");
+    }
+
+    #[test]
+    fn diff_lists_changed_added_and_removed_fields() {
+        let previous = mappings("Flexi Cap", &[("latest_nav", "NAV"), ("year_1", "1Y"), ("ytd", "YTD")]);
+        let mut current = mappings("Flexi Cap", &[("latest_nav", "Latest NAV"), ("year_1", "1Y"), ("years_3", "3Y")]);
+        current.extend(mappings("Small Cap", &[("latest_nav", "NAV")]));
+
+        let changes: Vec<(String, String, Option<String>, Option<String>)> = diff(&previous, &current)
+            .into_iter()
+            .map(|change| (change.sheet, change.field, change.previous, change.current))
+            .collect();
+        let change = |sheet: &str, field: &str, previous: Option<&str>, current: Option<&str>| {
+            (sheet.to_string(), field.to_string(), previous.map(str::to_string), current.map(str::to_string))
+        };
+        assert_eq!(
+            changes,
+            vec![
+                change("Flexi Cap", "latest_nav", Some("NAV"), Some("Latest NAV")),
+                change("Flexi Cap", "years_3", None, Some("3Y")),
+                change("Flexi Cap", "ytd", Some("YTD"), None),
+                change("Small Cap", "latest_nav", None, Some("NAV")),
+            ]
+        );
+        assert!(diff(&previous, &previous).is_empty());
+    }
+
+    #[actix_web::test]
+    async fn mapping_changed_compares_the_latest_two_committed_uploads() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(
+            "INSERT INTO uploads (provider, file_name, column_mappings, processed, status) VALUES
+                ('hdfc-factsheet', 'hdfc mar.xlsx', '{\"Flexi Cap\": {\"latest_nav\": \"NAV\"}}', 1, 'committed'),
+                ('hdfc-factsheet', 'hdfc apr.xlsx', '{\"Flexi Cap\": {\"latest_nav\": \"Latest NAV\"}}', 1, 'committed'),
+                ('hdfc-factsheet', 'hdfc may.xlsx', '{\"Flexi Cap\": {\"latest_nav\": \"NAV\"}}', 1, 'rolled_back'),
+                ('sbi-factsheet', 'sbi mar.xlsx', '{\"Flexi Cap\": {\"latest_nav\": \"NAV\"}}', 1, 'committed'),
+                ('sbi-factsheet', 'sbi apr.xlsx', '{\"Flexi Cap\": {\"latest_nav\": \"NAV\"}}', 1, 'committed'),
+                ('axis-factsheet', 'axis mar.xlsx', '{}', 1, 'committed')",
+        )
+        .await;
+        let client = db.state.pools.primary().get().await.unwrap();
+
+        let providers = load_provider_mappings(&client, None).await.unwrap();
+        let summary: Vec<(&str, i64, Option<&str>, bool)> = providers
+            .iter()
+            .map(|provider| {
+                (
+                    provider.provider.as_str(),
+                    provider.upload_count,
+                    provider.latest.file_name.as_deref(),
+                    provider.mapping_changed,
+                )
+            })
+            .collect();
+        // The rolled-back upload is neither the latest nor counted
+        assert_eq!(
+            summary,
+            vec![
+                ("axis-factsheet", 1, Some("axis mar.xlsx"), false),
+                ("hdfc-factsheet", 2, Some("hdfc apr.xlsx"), true),
+                ("sbi-factsheet", 2, Some("sbi apr.xlsx"), false),
+            ]
+        );
+        assert_eq!(providers[0].previous_upload_id, None);
+
+        let hdfc = load_provider_mappings(&client, Some("hdfc-factsheet")).await.unwrap();
+        assert_eq!(hdfc.len(), 1);
+        assert_eq!(hdfc[0].previous.as_ref().unwrap().file_name.as_deref(), Some("hdfc mar.xlsx"));
+    }
+}
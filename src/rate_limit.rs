@@ -0,0 +1,307 @@
+use actix_web::body::BoxBody;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use actix_web::http::Method;
+use actix_web::{web, HttpMessage, ResponseError};
+use dashmap::DashMap;
+use log::{info, warn};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::api_error::{ApiError, ErrorCode};
+use crate::auth::{Credential, RequestUser};
+use crate::routes;
+use crate::AppState;
+
+// Buckets untouched for this long are full again and dropped; a returning client starts afresh
+const IDLE_BUCKET_TTL: Duration = Duration::from_secs(600);
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+// Sustained requests per minute and how many can come at once; 0 per_minute turns the limit off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limit {
+    pub per_minute: u32,
+    pub burst: u32,
+}
+
+impl Limit {
+    // Tokens a full bucket holds
+    fn capacity(self) -> u32 {
+        self.burst.max(1)
+    }
+
+    fn tokens_per_sec(self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+}
+
+// Uploads, refreshes and exports draw on their own, smaller bucket, so a client hammering /search
+// can't take the capacity they need
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LimitClass {
+    Read,
+    Expensive,
+}
+
+fn classify(method: &Method, path: &str) -> LimitClass {
+    let path = path.strip_prefix(routes::V1_PREFIX).unwrap_or(path);
+    let expensive = match *method {
+        Method::POST => matches!(path, "/upload" | "/upload/rates" | "/refresh"),
+        Method::GET | Method::HEAD => path == "/search.csv" || path.starts_with("/export/"),
+        _ => false,
+    };
+    if expensive {
+        LimitClass::Expensive
+    } else {
+        LimitClass::Read
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// What a request left of its bucket, reported in the X-RateLimit-* headers. Both count tokens:
+// the limit is the bucket's capacity (the burst), not the per-minute refill rate.
+struct Quota {
+    limit: u32,
+    remaining: u32,
+}
+
+impl Quota {
+    fn apply(&self, headers: &mut HeaderMap) {
+        headers.insert(HeaderName::from_static("x-ratelimit-limit"), HeaderValue::from(self.limit));
+        headers.insert(HeaderName::from_static("x-ratelimit-remaining"), HeaderValue::from(self.remaining));
+    }
+}
+
+// Token buckets per client and class; in memory, as we run a single instance
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: DashMap<(String, LimitClass), Bucket>,
+}
+
+impl RateLimiter {
+    // Takes a token, or says how many seconds until one is available
+    fn take(&self, client: String, class: LimitClass, limit: Limit) -> Result<Quota, u64> {
+        let now = Instant::now();
+        let mut bucket = self.buckets.entry((client, class)).or_insert_with(|| Bucket {
+            tokens: limit.capacity() as f64,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.tokens_per_sec()).min(limit.capacity() as f64);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(Quota {
+                limit: limit.capacity(),
+                remaining: bucket.tokens as u32,
+            })
+        } else {
+            Err(((1.0 - bucket.tokens) / limit.tokens_per_sec()).ceil().max(1.0) as u64)
+        }
+    }
+
+    fn remove_idle(&self) -> usize {
+        let before = self.buckets.len();
+        self.buckets.retain(|_, bucket| bucket.updated.elapsed() < IDLE_BUCKET_TTL);
+        before - self.buckets.len()
+    }
+}
+
+pub fn spawn_cleanup(limiter: Arc<RateLimiter>) {
+    actix_web::rt::spawn(async move {
+        let mut ticks = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            ticks.tick().await;
+            let removed = limiter.remove_idle();
+            if removed > 0 {
+                info!("Dropped {} idle rate limit buckets", removed);
+            }
+        }
+    });
+}
+
+// Authenticated clients are limited by key id or token subject, everyone else by address. The
+// peer address rather than X-Forwarded-For, which any client can set.
+fn client_key(req: &ServiceRequest) -> String {
+    if let Some(user) = req.extensions().get::<RequestUser>() {
+        match user.credential {
+            Credential::ApiKey => return format!("key:{}", user.subject),
+            Credential::BearerToken => return format!("user:{}", user.subject),
+            Credential::Anonymous => {}
+        }
+    }
+    match req.peer_addr() {
+        Some(addr) => format!("ip:{}", addr.ip()),
+        None => "ip:unknown".to_string(),
+    }
+}
+
+fn check(req: &ServiceRequest) -> Result<Option<Quota>, ApiError> {
+    let state = match req.app_data::<web::Data<AppState>>() {
+        Some(state) => state,
+        None => return Ok(None),
+    };
+    let class = classify(req.method(), req.path());
+    let limit = {
+        let config = state.runtime_config.load();
+        match class {
+            LimitClass::Read => config.read_rate_limit,
+            LimitClass::Expensive => config.expensive_rate_limit,
+        }
+    };
+    if limit.per_minute == 0 {
+        return Ok(None);
+    }
+
+    let client = client_key(req);
+    match state.rate_limiter.take(client.clone(), class, limit) {
+        Ok(quota) => Ok(Some(quota)),
+        Err(retry_after) => {
+            warn!("Rate limited {} on {} {} ({:?})", client, req.method(), req.path(), class);
+            Err(ApiError::new(
+                ErrorCode::RateLimited,
+                format!("Too many requests; limit is {} per minute, retry in {}s", limit.per_minute, retry_after),
+            )
+            .with_header(header::RETRY_AFTER, retry_after.to_string())
+            .with_header(HeaderName::from_static("x-ratelimit-limit"), limit.capacity().to_string())
+            .with_header(HeaderName::from_static("x-ratelimit-remaining"), "0".to_string()))
+        }
+    }
+}
+
+// Middleware for the API scopes, registered inside require_credentials so it sees who is calling
+pub fn limit_requests<S>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = Result<ServiceResponse<BoxBody>, actix_web::Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<BoxBody>, Error = actix_web::Error>,
+{
+    let admitted = match check(&req) {
+        Ok(quota) => Ok((srv.call(req), quota)),
+        Err(e) => Err(req.into_response(e.error_response())),
+    };
+    async move {
+        match admitted {
+            Ok((response, quota)) => {
+                let mut response = response.await?;
+                if let Some(quota) = quota {
+                    quota.apply(response.headers_mut());
+                }
+                Ok(response)
+            }
+            Err(rejected) => Ok(rejected),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+
+    use crate::config::RuntimeConfig;
+    use crate::test_support;
+
+    const LIMIT: Limit = Limit {
+        per_minute: 60,
+        burst: 3,
+    };
+
+    #[test]
+    fn uploads_refreshes_and_exports_are_expensive() {
+        for (method, path, class) in [
+            (Method::GET, "/api/v1/search", LimitClass::Read),
+            (Method::GET, "/search", LimitClass::Read),
+            (Method::POST, "/api/v1/funds/by-name", LimitClass::Read),
+            (Method::POST, "/api/v1/upload", LimitClass::Expensive),
+            (Method::POST, "/upload/rates", LimitClass::Expensive),
+            (Method::POST, "/refresh", LimitClass::Expensive),
+            (Method::GET, "/api/v1/search.csv", LimitClass::Expensive),
+            (Method::GET, "/api/v1/export/funds.xlsx", LimitClass::Expensive),
+        ] {
+            assert_eq!(classify(&method, path), class, "{} {}", method, path);
+        }
+    }
+
+    #[test]
+    fn a_bucket_allows_its_burst_then_says_when_to_retry() {
+        let limiter = RateLimiter::default();
+        let take = |client: &str, class| limiter.take(client.to_string(), class, LIMIT);
+
+        let remaining: Vec<(u32, u32)> = (0..3)
+            .map(|_| take("key:ops", LimitClass::Read).map(|quota| (quota.limit, quota.remaining)).unwrap())
+            .collect();
+        assert_eq!(remaining, vec![(3, 2), (3, 1), (3, 0)]);
+        // One token a second comes back
+        assert_eq!(take("key:ops", LimitClass::Read).err(), Some(1));
+
+        // Other clients and the other class have buckets of their own
+        assert!(take("key:etl", LimitClass::Read).is_ok());
+        assert!(take("key:ops", LimitClass::Expensive).is_ok());
+    }
+
+    #[test]
+    fn a_bucket_refills_at_the_per_minute_rate() {
+        let limiter = RateLimiter::default();
+        for _ in 0..3 {
+            limiter.take("key:ops".to_string(), LimitClass::Read, LIMIT).unwrap();
+        }
+        let mut bucket = limiter.buckets.get_mut(&("key:ops".to_string(), LimitClass::Read)).unwrap();
+        bucket.updated -= Duration::from_secs(2);
+        drop(bucket);
+
+        let quota = limiter.take("key:ops".to_string(), LimitClass::Read, LIMIT).unwrap();
+        assert_eq!(quota.remaining, 1);
+    }
+
+    #[test]
+    fn idle_buckets_are_dropped() {
+        let limiter = RateLimiter::default();
+        limiter.take("ip:10.0.0.1".to_string(), LimitClass::Read, LIMIT).unwrap();
+        limiter.take("ip:10.0.0.2".to_string(), LimitClass::Read, LIMIT).unwrap();
+        limiter.buckets.get_mut(&("ip:10.0.0.1".to_string(), LimitClass::Read)).unwrap().updated -= IDLE_BUCKET_TTL;
+
+        assert_eq!(limiter.remove_idle(), 1);
+        assert_eq!(limiter.buckets.len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn headers_report_tokens_left_of_the_burst_and_429_says_when_to_retry() {
+        let config = RuntimeConfig {
+            read_rate_limit: LIMIT,
+            ..test_support::runtime_config()
+        };
+        let state = test_support::state(config, vec![]);
+        let header = |response: &actix_web::dev::ServiceResponse, name: &str| {
+            response.headers().get(name).map(|value| value.to_str().unwrap().to_string())
+        };
+
+        for remaining in ["2", "1", "0"] {
+            let response = test_support::call(&state, TestRequest::get().uri("/api/v1/search")).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(header(&response, "x-ratelimit-limit").as_deref(), Some("3"));
+            assert_eq!(header(&response, "x-ratelimit-remaining").as_deref(), Some(remaining));
+        }
+
+        let response = test_support::call(&state, TestRequest::get().uri("/search")).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(header(&response, "retry-after").as_deref(), Some("1"));
+        assert_eq!(header(&response, "x-ratelimit-limit").as_deref(), Some("3"));
+        assert_eq!(header(&response, "x-ratelimit-remaining").as_deref(), Some("0"));
+        let body: serde_json::Value = serde_json::from_slice(&actix_web::test::read_body(response).await).unwrap();
+        assert_eq!(body["code"], "rate_limited");
+
+        // An authenticated client has its own bucket
+        let with_key = test_support::as_admin(TestRequest::get().uri("/api/v1/search"));
+        assert_eq!(test_support::call(&state, with_key).await.status(), StatusCode::OK);
+    }
+}
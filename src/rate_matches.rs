@@ -0,0 +1,197 @@
+use calamine::{Data, Reader};
+use chrono::NaiveDate;
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const SUGGESTIONS_PER_RATE: usize = 3;
+
+const RATE_ID_HEADER: &str = "Rate ID";
+const SCHEME_NAME_HEADER: &str = "Scheme Name";
+const CONFIRMED_HEADER: &str = "Confirmed Fund Name";
+
+// A scheme_rates row that did not join to any fund in the latest build
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnmatchedRate {
+    pub rate_id: i32,
+    pub arn: String,
+    pub company: String,
+    pub scheme_name: String,
+    pub scheme_category: String,
+    pub brokerage_type: String,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub base_year_1: Option<f32>,
+    pub base_year_2: Option<f32>,
+    pub base_year_3: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FundSuggestion {
+    pub scheme_name: String,
+    pub score: f64,
+}
+
+// A filled-in row from a re-uploaded worksheet
+#[derive(Debug, Clone)]
+pub struct ConfirmedMatch {
+    pub row: usize,
+    pub rate_id: Option<i32>,
+    pub rate_scheme_name: String,
+    pub confirmed_fund_name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RowError {
+    pub row: usize,
+    pub rate_id: Option<i32>,
+    pub value: String,
+    pub reason: String,
+}
+
+pub fn build_worksheet(rows: &[(UnmatchedRate, Vec<FundSuggestion>)]) -> Result<Vec<u8>, XlsxError> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name("Unmatched Rates")?;
+
+    let bold = Format::new().set_bold();
+    let mut headers = vec![
+        RATE_ID_HEADER.to_string(),
+        "ARN".to_string(),
+        "Company".to_string(),
+        SCHEME_NAME_HEADER.to_string(),
+        "Scheme Category".to_string(),
+        "Brokerage Type".to_string(),
+        "Start Date".to_string(),
+        "End Date".to_string(),
+        "Base Year 1".to_string(),
+        "Base Year 2".to_string(),
+        "Base Year 3".to_string(),
+    ];
+    for n in 1..=SUGGESTIONS_PER_RATE {
+        headers.push(format!("Suggestion {}", n));
+        headers.push(format!("Score {}", n));
+    }
+    headers.push(CONFIRMED_HEADER.to_string());
+
+    for (col, header) in headers.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, header, &bold)?;
+    }
+    worksheet.set_freeze_panes(1, 0)?;
+
+    for (i, (rate, suggestions)) in rows.iter().enumerate() {
+        let row = (i + 1) as u32;
+
+        worksheet.write_number(row, 0, rate.rate_id)?;
+        worksheet.write_string(row, 1, &rate.arn)?;
+        worksheet.write_string(row, 2, &rate.company)?;
+        worksheet.write_string(row, 3, &rate.scheme_name)?;
+        worksheet.write_string(row, 4, &rate.scheme_category)?;
+        worksheet.write_string(row, 5, &rate.brokerage_type)?;
+        write_optional_date(worksheet, row, 6, rate.start_date)?;
+        write_optional_date(worksheet, row, 7, rate.end_date)?;
+        write_optional_number(worksheet, row, 8, rate.base_year_1)?;
+        write_optional_number(worksheet, row, 9, rate.base_year_2)?;
+        write_optional_number(worksheet, row, 10, rate.base_year_3)?;
+
+        for (n, suggestion) in suggestions.iter().take(SUGGESTIONS_PER_RATE).enumerate() {
+            let col = (11 + n * 2) as u16;
+            worksheet.write_string(row, col, &suggestion.scheme_name)?;
+            worksheet.write_number(row, col + 1, (suggestion.score * 1000.0).round() / 1000.0)?;
+        }
+    }
+
+    workbook.save_to_buffer()
+}
+
+fn write_optional_date(
+    worksheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: Option<NaiveDate>,
+) -> Result<(), XlsxError> {
+    if let Some(date) = value {
+        worksheet.write_string(row, col, date.format("%Y-%m-%d").to_string())?;
+    }
+    Ok(())
+}
+
+fn write_optional_number(
+    worksheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: Option<f32>,
+) -> Result<(), XlsxError> {
+    if let Some(number) = value {
+        worksheet.write_number(row, col, number)?;
+    }
+    Ok(())
+}
+
+// Read back the worksheet produced by build_worksheet; rows without a confirmed name are ignored
+pub fn parse_confirmed_matches(
+    path: &Path,
+) -> Result<(Vec<ConfirmedMatch>, Vec<RowError>), Box<dyn std::error::Error>> {
+    let mut workbook = crate::workbook::open(path)?;
+    let sheet_name = workbook
+        .sheet_names()
+        .first()
+        .cloned()
+        .ok_or("Workbook contains no sheets")?;
+    let range = workbook.worksheet_range(&sheet_name)?;
+
+    let header_col = |name: &str| -> Option<usize> {
+        (0..range.width()).find(|&col| {
+            range
+                .get((0, col))
+                .map(|cell| cell.to_string().trim().eq_ignore_ascii_case(name))
+                .unwrap_or(false)
+        })
+    };
+
+    let rate_id_col = header_col(RATE_ID_HEADER).ok_or("Missing 'Rate ID' column")?;
+    let scheme_col = header_col(SCHEME_NAME_HEADER).ok_or("Missing 'Scheme Name' column")?;
+    let confirmed_col = header_col(CONFIRMED_HEADER).ok_or("Missing 'Confirmed Fund Name' column")?;
+
+    let mut matches = Vec::new();
+    let mut errors = Vec::new();
+
+    for row_idx in 1..range.height() {
+        let confirmed = cell_text(range.get((row_idx, confirmed_col)));
+        if confirmed.is_empty() {
+            continue;
+        }
+
+        // Report spreadsheet row numbers (1-based, header is row 1)
+        let row = row_idx + 1;
+        let rate_id = match range.get((row_idx, rate_id_col)) {
+            Some(Data::Float(f)) => Some(*f as i32),
+            Some(Data::Int(i)) => Some(*i as i32),
+            other => cell_text(other).parse().ok(),
+        };
+        let rate_scheme_name = cell_text(range.get((row_idx, scheme_col)));
+
+        if rate_scheme_name.is_empty() {
+            errors.push(RowError {
+                row,
+                rate_id,
+                value: confirmed,
+                reason: "Scheme Name is empty".to_string(),
+            });
+            continue;
+        }
+
+        matches.push(ConfirmedMatch {
+            row,
+            rate_id,
+            rate_scheme_name,
+            confirmed_fund_name: confirmed,
+        });
+    }
+
+    Ok((matches, errors))
+}
+
+fn cell_text(cell: Option<&Data>) -> String {
+    cell.map(|c| c.to_string().trim().to_string()).unwrap_or_default()
+}
@@ -0,0 +1,261 @@
+use calamine::{Data, Range, Reader};
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use tokio_postgres::Client;
+use utoipa::ToSchema;
+
+use crate::columns::header_key;
+use crate::dates::parse_date;
+use crate::parse_float_option;
+
+// Columns of the broker rate sheet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum RateColumn {
+    Arn,
+    Company,
+    SchemeName,
+    SchemeCategory,
+    BrokerageType,
+    StartDate,
+    EndDate,
+    BaseYear1,
+    BaseYear2,
+    BaseYear3,
+}
+
+// Known spellings per column, in header_key form
+const HEADERS: &[(RateColumn, &[&str])] = &[
+    (RateColumn::Arn, &["arn", "arnno", "arncode", "arnnumber"]),
+    (RateColumn::Company, &["company", "amc", "amcname", "companyname"]),
+    (RateColumn::SchemeName, &["schemename", "scheme"]),
+    (RateColumn::SchemeCategory, &["schemecategory", "category"]),
+    (RateColumn::BrokerageType, &["brokeragetype", "brokerage", "type"]),
+    (RateColumn::StartDate, &["startdate", "fromdate", "validfrom", "effectivefrom"]),
+    (RateColumn::EndDate, &["enddate", "todate", "validto", "validtill", "effectiveto"]),
+    (RateColumn::BaseYear1, &["baseyear1", "trailyear1", "year1", "1styear"]),
+    (RateColumn::BaseYear2, &["baseyear2", "trailyear2", "year2", "2ndyear"]),
+    (RateColumn::BaseYear3, &["baseyear3", "trailyear3", "year3", "3rdyear"]),
+];
+
+const REQUIRED: [(RateColumn, &str); 7] = [
+    (RateColumn::Arn, "ARN"),
+    (RateColumn::Company, "Company"),
+    (RateColumn::SchemeName, "Scheme Name"),
+    (RateColumn::SchemeCategory, "Scheme Category"),
+    (RateColumn::BrokerageType, "Brokerage Type"),
+    (RateColumn::StartDate, "Start Date"),
+    (RateColumn::EndDate, "End Date"),
+];
+
+#[derive(Debug, Clone)]
+pub struct RateRow {
+    pub arn: String,
+    pub company: String,
+    pub scheme_name: String,
+    pub scheme_category: String,
+    pub brokerage_type: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub base_year_1: Option<f32>,
+    pub base_year_2: Option<f32>,
+    pub base_year_3: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RateRowError {
+    pub sheet: String,
+    // 1-based, as shown in Excel
+    pub row: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct RateUploadReport {
+    pub inserted: usize,
+    // Rows identical to a rate already stored
+    pub skipped: usize,
+    pub error_count: usize,
+    pub errors: Vec<RateRowError>,
+}
+
+// Parse every sheet that has a recognisable rate header; rows failing validation are reported, not fatal
+pub fn parse_workbook(path: &Path) -> Result<(Vec<RateRow>, Vec<RateRowError>), Box<dyn std::error::Error>> {
+    let mut workbook = crate::workbook::open(path)?;
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    let mut found_sheet = false;
+
+    for sheet_name in workbook.sheet_names().clone() {
+        let range = match workbook.worksheet_range(&sheet_name) {
+            Ok(range) => range,
+            Err(_) => continue,
+        };
+        let header_row = match find_header_row(&range) {
+            Some(header_row) => header_row,
+            None => continue,
+        };
+        found_sheet = true;
+
+        let columns = match map_columns(&range, header_row) {
+            Ok(columns) => columns,
+            Err(reason) => {
+                errors.push(RateRowError {
+                    sheet: sheet_name.clone(),
+                    row: header_row + 1,
+                    reason,
+                });
+                continue;
+            }
+        };
+
+        for row_idx in (header_row + 1)..range.height() {
+            if is_blank_row(&range, row_idx) {
+                continue;
+            }
+            match parse_row(&range, &columns, row_idx) {
+                Ok(row) => rows.push(row),
+                Err(reason) => errors.push(RateRowError {
+                    sheet: sheet_name.clone(),
+                    row: row_idx + 1,
+                    reason,
+                }),
+            }
+        }
+    }
+
+    if !found_sheet {
+        return Err("No sheet with an 'ARN' header row found".into());
+    }
+    Ok((rows, errors))
+}
+
+fn find_header_row(range: &Range<Data>) -> Option<usize> {
+    (0..range.height().min(15)).find(|&row| {
+        (0..range.width()).any(|col| {
+            range
+                .get((row, col))
+                .is_some_and(|cell| match_header(&header_key(&cell.to_string())) == Some(RateColumn::Arn))
+        })
+    })
+}
+
+fn match_header(key: &str) -> Option<RateColumn> {
+    HEADERS
+        .iter()
+        .find(|(_, spellings)| spellings.contains(&key))
+        .map(|(column, _)| *column)
+}
+
+fn map_columns(range: &Range<Data>, header_row: usize) -> Result<HashMap<RateColumn, usize>, String> {
+    let mut columns = HashMap::new();
+    for col in 0..range.width() {
+        if let Some(cell) = range.get((header_row, col)) {
+            if let Some(column) = match_header(&header_key(&cell.to_string())) {
+                columns.entry(column).or_insert(col);
+            }
+        }
+    }
+
+    let missing: Vec<&str> = REQUIRED
+        .iter()
+        .filter(|(column, _)| !columns.contains_key(column))
+        .map(|(_, label)| *label)
+        .collect();
+    if missing.is_empty() {
+        Ok(columns)
+    } else {
+        Err(format!("missing required column(s): {}", missing.join(", ")))
+    }
+}
+
+fn is_blank_row(range: &Range<Data>, row: usize) -> bool {
+    (0..range.width()).all(|col| range.get((row, col)).is_none_or(|cell| cell.to_string().trim().is_empty()))
+}
+
+fn parse_row(range: &Range<Data>, columns: &HashMap<RateColumn, usize>, row: usize) -> Result<RateRow, String> {
+    let cell = |column: RateColumn| columns.get(&column).and_then(|&col| range.get((row, col)));
+    let text = |column: RateColumn, label: &str| {
+        let value = cell(column).map(|c| c.to_string().trim().to_string()).unwrap_or_default();
+        if value.is_empty() {
+            Err(format!("{} is empty", label))
+        } else {
+            Ok(value)
+        }
+    };
+
+    let start_date = parse_date(cell(RateColumn::StartDate)).map_err(|e| format!("Start Date {}", e))?;
+    let end_date = parse_date(cell(RateColumn::EndDate)).map_err(|e| format!("End Date {}", e))?;
+    if end_date < start_date {
+        return Err(format!("End Date {} is before Start Date {}", end_date, start_date));
+    }
+
+    Ok(RateRow {
+        arn: text(RateColumn::Arn, "ARN")?,
+        company: text(RateColumn::Company, "Company")?,
+        scheme_name: text(RateColumn::SchemeName, "Scheme Name")?,
+        scheme_category: text(RateColumn::SchemeCategory, "Scheme Category")?,
+        brokerage_type: text(RateColumn::BrokerageType, "Brokerage Type")?,
+        start_date,
+        end_date,
+        base_year_1: parse_float_option(cell(RateColumn::BaseYear1)),
+        base_year_2: parse_float_option(cell(RateColumn::BaseYear2)),
+        base_year_3: parse_float_option(cell(RateColumn::BaseYear3)),
+    })
+}
+
+// Insert all rows in one transaction; a row identical to a stored rate is skipped, not duplicated.
+// `is_approved` false inserts them pending review.
+pub async fn insert_rates(
+    client: &mut Client,
+    rows: &[RateRow],
+    source_file: &str,
+    is_approved: bool,
+) -> Result<(usize, usize), tokio_postgres::Error> {
+    let transaction = client.transaction().await?;
+    let mut inserted = 0;
+    let mut skipped = 0;
+
+    for row in rows {
+        let count = transaction
+            .execute(
+                "INSERT INTO scheme_rates (
+                    arn, company, scheme_name, scheme_category, brokerage_type, start_date, end_date,
+                    source_file, base_year_1, base_year_2, base_year_3, is_approved
+                )
+                SELECT $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM scheme_rates
+                    WHERE arn = $1 AND company = $2 AND scheme_name = $3 AND brokerage_type = $5
+                      AND start_date = $6 AND end_date = $7
+                      AND base_year_1 IS NOT DISTINCT FROM $9
+                      AND base_year_2 IS NOT DISTINCT FROM $10
+                      AND base_year_3 IS NOT DISTINCT FROM $11
+                )",
+                &[
+                    &row.arn,
+                    &row.company,
+                    &row.scheme_name,
+                    &row.scheme_category,
+                    &row.brokerage_type,
+                    &row.start_date,
+                    &row.end_date,
+                    &source_file,
+                    &row.base_year_1,
+                    &row.base_year_2,
+                    &row.base_year_3,
+                    &is_approved,
+                ],
+            )
+            .await?;
+        if count > 0 {
+            inserted += 1;
+        } else {
+            skipped += 1;
+        }
+    }
+
+    transaction.commit().await?;
+    Ok((inserted, skipped))
+}
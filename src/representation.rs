@@ -0,0 +1,165 @@
+use actix_web::{http::header, HttpRequest};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::preferences::EffectiveDisplay;
+use crate::table::MatchKind;
+use crate::CombinedSchemeData;
+
+// CombinedSchemeData fields that come from scheme_rates, nested under "rates" in the nested shape
+const RATE_FIELDS: [&str; 11] = [
+    "rate_id",
+    "arn",
+    "company",
+    "scheme_category",
+    "brokerage_type",
+    "start_date",
+    "end_date",
+    "base_year_1",
+    "base_year_2",
+    "base_year_3",
+    "is_rate_active",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+impl Format {
+    // An explicit `format` (or the /search.csv path) wins over the Accept header. Accept is read
+    // best q first; anything other than text/csv, including types we don't serve, means JSON.
+    pub fn negotiate(req: &HttpRequest, query: &HashMap<String, String>) -> Result<Self, String> {
+        match query.get("format").map(String::as_str) {
+            Some("json") => return Ok(Format::Json),
+            Some("csv") => return Ok(Format::Csv),
+            Some(other) => return Err(format!("Unknown format '{}', expected json or csv", other)),
+            None => {}
+        }
+        if req.path().ends_with(".csv") {
+            return Ok(Format::Csv);
+        }
+
+        let accept = match req.headers().get(header::ACCEPT).and_then(|value| value.to_str().ok()) {
+            Some(accept) => accept,
+            None => return Ok(Format::Json),
+        };
+        let mut ranges: Vec<(&str, f32)> = accept
+            .split(',')
+            .map(|range| {
+                let mut parts = range.split(';').map(str::trim);
+                let media_type = parts.next().unwrap_or("");
+                let quality = parts
+                    .find_map(|param| param.strip_prefix("q="))
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (media_type, quality)
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            .collect();
+        // Stable, so equally weighted types keep the client's order
+        ranges.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        Ok(match ranges.first() {
+            Some((media_type, _)) if media_type.eq_ignore_ascii_case("text/csv") => Format::Csv,
+            _ => Format::Json,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    // One entry per fund/rate record, as CombinedSchemeData
+    Flat,
+    // One entry per fund with its rates in a "rates" array
+    Nested,
+}
+
+impl Shape {
+    // `group=true` is the older spelling of `shape=nested`; the two must not disagree
+    pub fn from_query(query: &HashMap<String, String>) -> Result<Self, String> {
+        let shape = match query.get("shape").map(String::as_str) {
+            None => None,
+            Some("flat") => Some(Shape::Flat),
+            Some("nested") => Some(Shape::Nested),
+            Some(other) => return Err(format!("Unknown shape '{}', expected flat or nested", other)),
+        };
+        let group = match query.get("group").map(|value| value.parse::<bool>()) {
+            None => None,
+            Some(Ok(group)) => Some(group),
+            Some(Err(_)) => return Err("Parameter 'group' must be true or false".to_string()),
+        };
+
+        match (shape, group) {
+            (Some(Shape::Flat), Some(true)) | (Some(Shape::Nested), Some(false)) => {
+                Err("Parameters 'shape' and 'group' disagree".to_string())
+            }
+            (Some(shape), _) => Ok(shape),
+            (None, Some(true)) => Ok(Shape::Nested),
+            (None, _) => Ok(Shape::Flat),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Shape::Flat => "flat",
+            Shape::Nested => "nested",
+        }
+    }
+}
+
+// One search result, before it is projected into a representation
+pub struct SearchResult {
+    pub record: CombinedSchemeData,
+    pub match_type: MatchKind,
+    pub score: f64,
+    // Nested shape only: every rate of the record's fund that passes the search filters
+    pub rates: Option<Vec<CombinedSchemeData>>,
+}
+
+// JSON entries with the display's field selection; how each result matched is kept even when
+// fields are projected away
+pub fn to_json(results: &[SearchResult], display: &EffectiveDisplay) -> Result<Vec<Value>, serde_json::Error> {
+    results
+        .iter()
+        .map(|result| {
+            let mut value = display.project(&result.record)?;
+            if let Value::Object(map) = &mut value {
+                map.insert("match_type".to_string(), json!(result.match_type));
+                map.insert("score".to_string(), json!(result.score));
+                // Nested: rate fields move from the fund object into its rates array
+                if let Some(records) = &result.rates {
+                    map.retain(|key, _| !RATE_FIELDS.contains(&key.as_str()));
+                    let rates = records
+                        .iter()
+                        .filter(|record| record.rate_id.is_some())
+                        .map(rate_json)
+                        .collect::<Result<Vec<_>, _>>()?;
+                    map.insert("rates".to_string(), json!(rates));
+                }
+            }
+            Ok(value)
+        })
+        .collect()
+}
+
+fn rate_json(record: &CombinedSchemeData) -> Result<Value, serde_json::Error> {
+    let mut rate = serde_json::to_value(record)?;
+    if let Value::Object(map) = &mut rate {
+        map.retain(|key, _| RATE_FIELDS.contains(&key.as_str()));
+    }
+    Ok(rate)
+}
+
+// CSV has no nesting: a nested result contributes one row per rate (its own record when it
+// has none), so both shapes export the same columns
+pub fn to_csv_records(results: Vec<SearchResult>) -> Vec<CombinedSchemeData> {
+    results
+        .into_iter()
+        .flat_map(|result| match result.rates {
+            Some(rates) if !rates.is_empty() => rates,
+            _ => vec![result.record],
+        })
+        .collect()
+}
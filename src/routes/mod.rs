@@ -0,0 +1,67 @@
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::{HttpRequest, HttpResponse};
+
+use crate::api_error::ApiError;
+
+pub mod v1;
+
+pub const V1_PREFIX: &str = "/api/v1";
+
+// An unprefixed route still answers, but says it is deprecated and where its v1 successor is.
+// Paths that match no route at all have no successor either.
+pub fn mark_deprecated<B>(response: &mut ServiceResponse<B>, path: &str) {
+    if response.request().match_pattern().is_none() {
+        return;
+    }
+    let headers = response.headers_mut();
+    headers.insert(HeaderName::from_static("deprecation"), HeaderValue::from_static("true"));
+    let successor = format!("<{}{}>; rel=\"successor-version\"", V1_PREFIX, path);
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.insert(header::LINK, link);
+    }
+}
+
+pub async fn unknown_route(req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    Err(ApiError::not_found(format!("No route for {} {}", req.method(), req.path())))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::{self, TestRequest};
+
+    use crate::config::RuntimeConfig;
+    use crate::{test_support, CombinedSchemeData};
+
+    #[actix_web::test]
+    async fn both_prefixes_serve_the_same_routes_and_only_the_legacy_one_is_deprecated() {
+        let records = vec![CombinedSchemeData::test_fund(1, "Quant Small Cap Fund")];
+        let state = test_support::state(RuntimeConfig::default(), records);
+
+        for path in ["/search?q=quant", "/suggest?q=qua", "/facets", "/categories"] {
+            let v1 = test_support::call(&state, TestRequest::get().uri(&format!("/api/v1{}", path))).await;
+            assert_eq!(v1.status(), 200, "{}", path);
+            assert!(v1.headers().get("deprecation").is_none(), "{}", path);
+            let v1_body = test::read_body(v1).await;
+
+            let legacy = test_support::call(&state, TestRequest::get().uri(path)).await;
+            assert_eq!(legacy.status(), 200, "{}", path);
+            assert_eq!(legacy.headers().get("deprecation").unwrap(), "true");
+            let route = path.split('?').next().unwrap();
+            let successor = format!("</api/v1{}>; rel=\"successor-version\"", route);
+            assert_eq!(legacy.headers().get("link").unwrap().to_str().unwrap(), successor);
+            // Search bodies carry a per-request id in meta, so compare the data only
+            let legacy_body: serde_json::Value = serde_json::from_slice(&test::read_body(legacy).await).unwrap();
+            let v1_body: serde_json::Value = serde_json::from_slice(&v1_body).unwrap();
+            let data = |body: &serde_json::Value| body.get("data").cloned().unwrap_or_else(|| body.clone());
+            assert_eq!(data(&legacy_body), data(&v1_body), "{}", path);
+        }
+
+        // No route, so no successor to point at
+        let missing = test_support::call(&state, TestRequest::get().uri("/no-such-route")).await;
+        assert_eq!(missing.status(), 404);
+        assert!(missing.headers().get("deprecation").is_none());
+        let missing = test_support::call(&state, TestRequest::get().uri("/api/v1/no-such-route")).await;
+        assert_eq!(missing.status(), 404);
+    }
+}
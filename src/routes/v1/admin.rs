@@ -0,0 +1,489 @@
+use actix_web::{web, HttpResponse};
+use log::{error, info, warn};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::atomic::Ordering;
+use utoipa::IntoParams;
+
+use crate::api_error::{ApiError, ErrorCode};
+use crate::{
+    aliases, audit, auth, cached_db_counts, config, dates, get_postgres_client, pool_status,
+    reload_runtime_config, run_refresh, AppState,
+};
+
+#[utoipa::path(
+    post,
+    path = "/admin/reload-config",
+    tag = "admin",
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, description = "Runtime settings reloaded; lists what changed"),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 422, description = "Configuration invalid; nothing reloaded", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn reload_config_endpoint(
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+    info!("Config reload requested by {}", user);
+    match reload_runtime_config(&state) {
+        Ok(changes) => {
+            let audit_id = audit::record(&state, &user, "config.reload", "config", None, json!({"changes": changes})).await;
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "success",
+                "changes": changes,
+                "audit_id": audit_id
+            })))
+        }
+        Err(errors) => {
+            warn!("Config reload rejected: {:?}", errors);
+            Err(ApiError::new(ErrorCode::Unprocessable, "Configuration is invalid; nothing was reloaded")
+                .with_details(json!({"errors": errors})))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/refresh",
+    tag = "admin",
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, description = "Table rebuilt"),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 409, description = "A refresh is running and concurrent_refresh is reject", body = openapi::ErrorResponse),
+        (status = 500, description = "Rebuild failed", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn refresh_virtual_table_endpoint(
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+    info!("Refresh requested by {}", user);
+    let concurrent_refresh = state.runtime_config.load().concurrent_refresh;
+    let refreshed = match state.refresh_lock.try_lock() {
+        Ok(_refreshing) => run_refresh(&state).await,
+        Err(_) if concurrent_refresh == config::ConcurrentRefresh::Reject => {
+            return Err(ApiError::new(ErrorCode::Conflict, "Refresh already in progress"))
+        }
+        // A full refresh already running started before this request and rebuilds the same table,
+        // so its result stands in for a second rebuild. If the lock was held by an in-place
+        // update instead, nothing was rebuilt and this request still runs its own.
+        Err(_) => {
+            let finished_before = state.refresh_status.lock().unwrap().finished;
+            let _refreshing = state.refresh_lock.lock().await;
+            let joined = {
+                let refresh_status = state.refresh_status.lock().unwrap();
+                (refresh_status.finished != finished_before).then(|| refresh_status.last_error.clone())
+            };
+            match joined {
+                Some(None) => Ok(()),
+                Some(Some(e)) => Err(e.into()),
+                None => run_refresh(&state).await,
+            }
+        }
+    };
+
+    match refreshed {
+        Ok(_) => {
+            let (count, incomplete) = {
+                let virtual_table = state.virtual_table.load();
+                (virtual_table.len(), virtual_table.incomplete_count())
+            };
+            let audit_id = audit::record(
+                &state,
+                &user,
+                "table.refresh",
+                "virtual_table",
+                None,
+                json!({"records": count, "incomplete_records": incomplete}),
+            )
+            .await;
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "success",
+                "message": format!("Virtual table refreshed with {} records", count),
+                "incomplete_records": incomplete,
+                "audit_id": audit_id
+            })))
+        }
+        Err(e) => {
+            error!("Failed to refresh virtual table: {}", e);
+            Err(ApiError::database(format!("Failed to refresh: {}", e), e.as_ref()))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/status",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Table size, index, snapshot and refresh status")
+    )
+)]
+pub async fn status(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let virtual_table = state.virtual_table.load();
+    let (tokens, postings, token_index_bytes) = virtual_table.token_index_stats();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "records": virtual_table.len(),
+        "incomplete_records": virtual_table.incomplete_count(),
+        "unmatched_rates": virtual_table.unmatched_rates.len(),
+        "shared_strings": virtual_table.shared_strings(),
+        "token_index": {
+            "tokens": tokens,
+            "postings": postings,
+            "approx_bytes": token_index_bytes
+        },
+        "fuzzy_budget_exhaustions": state.budget_exhaustions.load(Ordering::Relaxed),
+        "snapshot": *state.snapshot_status.lock().unwrap(),
+        "refresh": *state.refresh_status.lock().unwrap(),
+        "pools": {
+            "primary": pool_status(state.pools.primary()),
+            "read": pool_status(state.pools.replica())
+        }
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Table and database counts, last refresh and uptime"),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn stats(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let table = state.virtual_table.load().stats();
+    let refresh = state.refresh_status.lock().unwrap().clone();
+    let db_counts = match cached_db_counts(&state).await {
+        Ok(counts) => counts,
+        Err(e) => return Err(ApiError::database(format!("Failed to count database rows: {}", e), e.as_ref())),
+    };
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "virtual_table": table,
+        "last_refresh": {
+            "at": refresh.last_success_at,
+            "duration_ms": refresh.last_duration_ms,
+            "record_count": refresh.last_record_count
+        },
+        "database": db_counts,
+        "uptime_secs": state.started_at.elapsed().as_secs()
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/aliases",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Name aliases"),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn list_name_aliases(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let result = async {
+        let client = state.pools.read_client().await?;
+        Ok::<_, Box<dyn std::error::Error>>(aliases::list_aliases(&client).await?)
+    }
+    .await;
+
+    match result {
+        Ok(aliases) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "aliases": aliases
+        }))),
+        Err(e) => Err(ApiError::database(format!("Failed to load name aliases: {}", e), e.as_ref())),
+    }
+}
+
+// Aliases change which funds and rates join, so the table is rebuilt after every edit
+#[utoipa::path(
+    post,
+    path = "/aliases",
+    tag = "admin",
+    security(("api_key" = []), ("bearer" = [])),
+    request_body(content = crate::aliases::NewNameAlias),
+    responses(
+        (status = 201, description = "Alias created and the table rebuilt"),
+        (status = 400, description = "Invalid alias", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 409, description = "Alias already defined", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn create_name_alias(
+    body: web::Json<aliases::NewNameAlias>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+    let (alias, canonical) = match body.normalized() {
+        Ok(normalized) => normalized,
+        Err(message) => return Err(ApiError::new(ErrorCode::InvalidBody, message)),
+    };
+
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        let mutation = state.begin_mutation().await;
+        let created = aliases::insert_alias(&client, &alias, &canonical).await?;
+        Ok::<_, Box<dyn std::error::Error>>(created.map(|created| (mutation, created)))
+    }
+    .await;
+
+    match result {
+        Ok(Some((mutation, created))) => {
+            info!("Added name alias '{}' -> '{}' for {}", created.alias, created.canonical, user);
+            if let Err(e) = mutation.refresh().await {
+                warn!("Failed to refresh virtual table after adding a name alias: {}", e);
+            }
+            let audit_id = audit::record(
+                &state,
+                &user,
+                "alias.create",
+                "name_alias",
+                Some(created.id.to_string()),
+                json!({"alias": created.alias, "canonical": created.canonical}),
+            )
+            .await;
+            Ok(HttpResponse::Created().json(json!({
+                "status": "success",
+                "alias": created,
+                "audit_id": audit_id
+            })))
+        }
+        Ok(None) => Err(ApiError::new(ErrorCode::Conflict, format!("Alias '{}' is already defined", alias))),
+        Err(e) => Err(ApiError::database(format!("Failed to save name alias: {}", e), e.as_ref())),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/aliases/{id}",
+    tag = "admin",
+    security(("api_key" = []), ("bearer" = [])),
+    params(("id" = i32, Path, description = "Alias id")),
+    responses(
+        (status = 200, description = "Alias removed and the table rebuilt"),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 404, description = "No such alias", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn delete_name_alias(
+    path: web::Path<i32>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+    let id = path.into_inner();
+
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        let mutation = state.begin_mutation().await;
+        let deleted = aliases::delete_alias(&client, id).await?;
+        Ok::<_, Box<dyn std::error::Error>>((mutation, deleted))
+    }
+    .await;
+
+    match result {
+        Ok((_, 0)) => Err(ApiError::not_found(format!("No name alias with id {}", id))),
+        Ok((mutation, _)) => {
+            info!("Removed name alias {} for {}", id, user);
+            if let Err(e) = mutation.refresh().await {
+                warn!("Failed to refresh virtual table after removing a name alias: {}", e);
+            }
+            let audit_id = audit::record(&state, &user, "alias.delete", "name_alias", Some(id.to_string()), json!({})).await;
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "success",
+                "message": format!("Name alias {} removed", id),
+                "audit_id": audit_id
+            })))
+        }
+        Err(e) => Err(ApiError::database(format!("Failed to delete name alias: {}", e), e.as_ref())),
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    // Entries to return, newest first; default 100, at most 1000
+    limit: Option<i64>,
+    // An action such as rate.approve, or a prefix such as rate for every rate.* action
+    action: Option<String>,
+    // YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS
+    since: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/audit",
+    tag = "admin",
+    security(("api_key" = []), ("bearer" = [])),
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Audit entries, newest first", body = [crate::audit::AuditEntry]),
+        (status = 400, description = "A bad limit or since", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn list_audit_log(
+    query: web::Query<AuditQuery>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+
+    let limit = match query.limit {
+        None => 100,
+        Some(limit) if limit > 0 => limit.min(1000),
+        Some(limit) => return Err(ApiError::invalid_parameter(format!("Parameter 'limit' must be positive, got {}", limit))),
+    };
+    let action = query.action.as_deref().map(str::trim).filter(|value| !value.is_empty());
+    let since = match query.since.as_deref().map(str::trim).filter(|value| !value.is_empty()) {
+        None => None,
+        Some(value) => match dates::parse_since(value) {
+            Some(since) => Some(since),
+            None => {
+                return Err(ApiError::invalid_parameter(format!(
+                    "Parameter 'since' must be a date (YYYY-MM-DD) or timestamp (YYYY-MM-DDTHH:MM:SS), got '{}'",
+                    value
+                )))
+            }
+        },
+    };
+
+    let result = async {
+        let client = state.pools.read_client().await?;
+        Ok::<_, Box<dyn std::error::Error>>(audit::list(&client, limit, action, since).await?)
+    }
+    .await;
+
+    match result {
+        Ok(entries) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "entries": entries
+        }))),
+        Err(e) => Err(ApiError::database(format!("Failed to load audit log: {}", e), e.as_ref())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use crate::config::RuntimeConfig;
+    use crate::test_support;
+
+    #[actix_web::test]
+    async fn a_refresh_during_another_waits_for_its_result_or_is_rejected() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute("INSERT INTO funds (category, scheme_name, latest_nav) VALUES ('Flexi Cap', 'Direct Fund', 10)")
+            .await;
+        let refresh = || test_support::as_admin(TestRequest::post().uri("/api/v1/refresh"));
+        let finished = || db.state.refresh_status.lock().unwrap().finished;
+
+        // Waiting on a full refresh reports its result rather than rebuilding again
+        let running = db.state.refresh_lock.lock().await;
+        let before = finished();
+        let other_refresh = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            db.state.refresh_status.lock().unwrap().record_success(Instant::now(), 0);
+            drop(running);
+        };
+        let ((status, body), ()) = tokio::join!(test_support::call_json(&db.state, refresh()), other_refresh);
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(finished(), before + 1);
+        assert_eq!(db.state.virtual_table.load().len(), 0);
+
+        // Nothing finished while it waited (an in-place update held the lock), so it rebuilds itself
+        let running = db.state.refresh_lock.lock().await;
+        let update = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            drop(running);
+        };
+        let ((status, body), ()) = tokio::join!(test_support::call_json(&db.state, refresh()), update);
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(finished(), before + 2);
+        assert_eq!(db.state.virtual_table.load().len(), 1);
+
+        db.state.runtime_config.store(Arc::new(RuntimeConfig {
+            concurrent_refresh: config::ConcurrentRefresh::Reject,
+            ..test_support::runtime_config()
+        }));
+        let running = db.state.refresh_lock.lock().await;
+        let (status, body) = test_support::call_json(&db.state, refresh()).await;
+        assert_eq!(status, 409, "{}", body);
+        assert_eq!(body["message"], "Refresh already in progress");
+        drop(running);
+        let (status, _) = test_support::call_json(&db.state, refresh()).await;
+        assert_eq!(status, 200);
+        assert_eq!(finished(), before + 3);
+    }
+
+    #[actix_web::test]
+    async fn stats_count_the_served_table_and_the_stored_rows() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(
+            "INSERT INTO funds (category, scheme_name, latest_nav) VALUES
+                ('Flexi Cap', 'Parag Parikh Flexi Cap Fund', 84), ('Flexi Cap', 'Quant Flexi Cap Fund', 90);
+             INSERT INTO scheme_rates (arn, company, scheme_name, scheme_category, brokerage_type, start_date, end_date,
+                                       source_file) VALUES
+                ('ARN-1', 'PPFAS', 'Parag Parikh Flexi Cap Fund', 'Equity', 'Trail', '2025-04-01', '2099-03-31', 'a'),
+                ('ARN-2', 'PPFAS', 'Parag Parikh Flexi Cap Fund', 'Equity', 'Upfront', '2025-04-01', '2099-03-31', 'a'),
+                ('ARN-3', 'Nippon', 'Nippon India Small Cap Fund', 'Equity', 'Trail', '2025-04-01', '2099-03-31', 'a')",
+        )
+        .await;
+        let (status, body) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/stats")).await;
+        assert_eq!((status, &body["last_refresh"]["at"]), (actix_web::http::StatusCode::OK, &json!(null)), "{}", body);
+        crate::refresh_virtual_table(&db.state).await.unwrap();
+
+        let (status, body) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/stats")).await;
+        assert_eq!(status, 200, "{}", body);
+        // One record per joined rate, and one for the fund without any
+        assert_eq!(
+            body["virtual_table"],
+            json!({
+                "records": 3,
+                "distinct_funds": 2,
+                "distinct_names": 2,
+                "records_with_rates": 2,
+                "records_without_rates": 1,
+                "unmatched_rates": 1,
+                "generation": db.state.virtual_table.load().generation
+            })
+        );
+        assert_eq!(body["last_refresh"]["record_count"], 3);
+        assert!(body["last_refresh"]["at"].is_string(), "{}", body);
+        assert!(body["uptime_secs"].is_u64());
+        // The database counts are cached for a while, so a later insert isn't counted yet
+        assert_eq!((&body["database"]["funds"], &body["database"]["scheme_rates"]), (&json!(2), &json!(3)));
+        db.execute("INSERT INTO funds (category, scheme_name) VALUES ('Small Cap', 'Nippon India Small Cap Fund')")
+            .await;
+        let (_, body) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/stats")).await;
+        assert_eq!(body["database"]["funds"], 2);
+    }
+}
@@ -0,0 +1,635 @@
+use actix_web::{web, HttpResponse};
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::json;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api_error::{ApiError, ErrorCode};
+use crate::preferences::CategoryPreference;
+use crate::{
+    audit, auth, categories, get_postgres_client, preferences, reload_category_preferences,
+    AppState,
+};
+
+// Category dropdown data, straight from the served table
+#[utoipa::path(
+    get,
+    path = "/categories",
+    tag = "categories",
+    responses(
+        (status = 200, description = "Categories with record counts and 1-year return ranges")
+    )
+)]
+pub async fn list_categories(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let categories = state.virtual_table.load().category_summaries();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "categories": categories
+    })))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListingQuery {
+    // Case-insensitive substring filter, for autocomplete
+    q: Option<String>,
+}
+
+impl ListingQuery {
+    fn filter(&self) -> Option<String> {
+        self.q.as_deref().map(str::trim).filter(|q| !q.is_empty()).map(str::to_lowercase)
+    }
+}
+
+// Rate companies for the rates upload UI, from the served table
+#[utoipa::path(
+    get,
+    path = "/companies",
+    tag = "categories",
+    params(ListingQuery),
+    responses(
+        (status = 200, description = "Rate companies with scheme counts and date spans")
+    )
+)]
+pub async fn list_companies(query: web::Query<ListingQuery>, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let companies = state.virtual_table.load().company_summaries(query.filter().as_deref());
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "companies": companies
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/arns",
+    tag = "categories",
+    params(ListingQuery),
+    responses(
+        (status = 200, description = "ARNs with their companies")
+    )
+)]
+pub async fn list_arns(query: web::Query<ListingQuery>, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let arns = state.virtual_table.load().arn_summaries(query.filter().as_deref());
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "arns": arns
+    })))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct VocabularyExtension {
+    names: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CategoryReassignment {
+    from: String,
+    to: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/categories/vocabulary",
+    tag = "categories",
+    responses(
+        (status = 200, description = "Canonical category names"),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn get_category_vocabulary(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let result = async {
+        let client = state.pools.read_client().await?;
+        Ok::<_, Box<dyn std::error::Error>>(categories::load_vocabulary(&client).await?)
+    }
+    .await;
+
+    match result {
+        Ok(vocabulary) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "mode": state.runtime_config.load().category_validation,
+            "categories": vocabulary
+        }))),
+        Err(e) => Err(ApiError::database(format!("Failed to load vocabulary: {}", e), e.as_ref())),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/categories/vocabulary",
+    tag = "categories",
+    security(("api_key" = []), ("bearer" = [])),
+    request_body(content = VocabularyExtension),
+    responses(
+        (status = 200, description = "How many names were new"),
+        (status = 400, description = "No names", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn extend_category_vocabulary(
+    body: web::Json<VocabularyExtension>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+    let names: Vec<String> = body
+        .names
+        .iter()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect();
+    if names.is_empty() {
+        return Err(ApiError::new(ErrorCode::InvalidBody, "At least one category name is required"));
+    }
+
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        Ok::<_, Box<dyn std::error::Error>>(categories::extend_vocabulary(&client, &names).await?)
+    }
+    .await;
+
+    match result {
+        Ok(added) => {
+            let audit_id = audit::record(
+                &state,
+                &user,
+                "category_vocabulary.extend",
+                "category_vocabulary",
+                None,
+                json!({"names": names, "added": added}),
+            )
+            .await;
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "success",
+                "added": added,
+                "audit_id": audit_id
+            })))
+        }
+        Err(e) => Err(ApiError::database(format!("Failed to extend vocabulary: {}", e), e.as_ref())),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/categories/mappings",
+    tag = "categories",
+    responses(
+        (status = 200, description = "Sheet name to category mappings"),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn get_category_mappings(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        Ok::<_, Box<dyn std::error::Error>>(categories::load_mappings(&client).await?)
+    }
+    .await;
+
+    match result {
+        Ok(mappings) => {
+            let mut mappings: Vec<categories::CategoryMapping> = mappings.into_values().collect();
+            mappings.sort_by(|a, b| a.sheet_name.cmp(&b.sheet_name));
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "success",
+                "mappings": mappings
+            })))
+        }
+        Err(e) => Err(ApiError::database(format!("Failed to load category mappings: {}", e), e.as_ref())),
+    }
+}
+
+// Replaces the whole sheet -> category mapping; applies from the next upload
+#[utoipa::path(
+    put,
+    path = "/categories/mappings",
+    tag = "categories",
+    security(("api_key" = []), ("bearer" = [])),
+    request_body(content = Vec<crate::categories::CategoryMapping>),
+    responses(
+        (status = 200, description = "Mappings replaced"),
+        (status = 400, description = "Invalid mappings", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn put_category_mappings(
+    body: web::Json<Vec<categories::CategoryMapping>>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+    let mappings = body.into_inner();
+    if let Err(message) = categories::validate_mappings(&mappings) {
+        return Err(ApiError::new(ErrorCode::InvalidBody, message));
+    }
+
+    let result = async {
+        let mut client = get_postgres_client(state.pools.primary()).await?;
+        categories::replace_mappings(&mut client, &mappings).await?;
+        Ok::<_, Box<dyn std::error::Error>>(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            info!("Replaced category mappings ({} entries) for {}", mappings.len(), user);
+            let audit_id = audit::record(
+                &state,
+                &user,
+                "category_mappings.replace",
+                "category_mappings",
+                None,
+                json!({"entries": mappings.len()}),
+            )
+            .await;
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "success",
+                "mappings": mappings,
+                "audit_id": audit_id
+            })))
+        }
+        Err(e) => Err(ApiError::database(format!("Failed to save category mappings: {}", e), e.as_ref())),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/categories/reassign",
+    tag = "categories",
+    security(("api_key" = []), ("bearer" = [])),
+    request_body(content = CategoryReassignment),
+    responses(
+        (status = 200, description = "Funds moved to the new category"),
+        (status = 400, description = "Same or empty categories", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 422, description = "Target not in the vocabulary", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn reassign_category(
+    body: web::Json<CategoryReassignment>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+    let from = body.from.trim();
+    let to = body.to.trim();
+    if from.is_empty() || to.is_empty() || from == to {
+        return Err(ApiError::new(ErrorCode::InvalidBody, "'from' and 'to' must be different, non-empty categories"));
+    }
+
+    let result = async {
+        let mut client = get_postgres_client(state.pools.primary()).await?;
+        let vocabulary = categories::load_vocabulary(&client).await?;
+
+        // When a vocabulary exists the target must be one of its canonical names
+        let target = if vocabulary.is_empty() {
+            to.to_string()
+        } else {
+            match categories::canonicalize(to, &vocabulary) {
+                Some(canonical) => canonical.to_string(),
+                None => return Ok(None),
+            }
+        };
+
+        let mutation = state.begin_mutation().await;
+        let moved = categories::reassign(&mut client, from, &target).await?;
+        Ok::<_, Box<dyn std::error::Error>>(Some((mutation, target, moved)))
+    }
+    .await;
+
+    match result {
+        Ok(Some((mutation, target, moved))) => {
+            info!("Reassigned {} funds from category '{}' to '{}' for {}", moved, from, target, user);
+            if let Err(e) = mutation.refresh().await {
+                warn!("Failed to refresh virtual table after category reassignment: {}", e);
+            }
+            let audit_id = audit::record(
+                &state,
+                &user,
+                "category.reassign",
+                "category",
+                Some(from.to_string()),
+                json!({"from": from, "to": target, "funds_moved": moved}),
+            )
+            .await;
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "success",
+                "from": from,
+                "to": target,
+                "funds_moved": moved,
+                "audit_id": audit_id
+            })))
+        }
+        Ok(None) => Err(ApiError::new(ErrorCode::Unprocessable, format!("'{}' is not in the category vocabulary", to))),
+        Err(e) => Err(ApiError::database(format!("Failed to reassign category: {}", e), e.as_ref())),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/categories/preferences",
+    tag = "categories",
+    responses(
+        (status = 200, description = "Per-category display defaults")
+    )
+)]
+pub async fn get_category_preferences(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let mut preferences: Vec<CategoryPreference> = state.category_preferences.load().values().cloned().collect();
+    preferences.sort_by(|a, b| a.category.cmp(&b.category));
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "preferences": preferences
+    })))
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/categories/preferences",
+    tag = "categories",
+    security(("api_key" = []), ("bearer" = [])),
+    request_body(content = crate::preferences::CategoryPreference),
+    responses(
+        (status = 200, description = "Preference stored"),
+        (status = 400, description = "Invalid sort or fields", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn put_category_preference(
+    body: web::Json<CategoryPreference>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+    let preference = body.into_inner();
+    if let Err(message) = preference.validate() {
+        return Err(ApiError::new(ErrorCode::InvalidBody, message));
+    }
+
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        preferences::upsert_preference(&client, &preference).await?;
+        reload_category_preferences(&state, &client).await?;
+        Ok::<_, Box<dyn std::error::Error>>(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            info!("Updated display preferences for category '{}' for {}", preference.category.trim(), user);
+            let audit_id = audit::record(
+                &state,
+                &user,
+                "category_preference.put",
+                "category_preference",
+                Some(preference.category.trim().to_string()),
+                json!({"preference": preference}),
+            )
+            .await;
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "success",
+                "preference": preference,
+                "audit_id": audit_id
+            })))
+        }
+        Err(e) => Err(ApiError::database(format!("Failed to save category preference: {}", e), e.as_ref())),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/categories/preferences/{category}",
+    tag = "categories",
+    security(("api_key" = []), ("bearer" = [])),
+    params(("category" = String, Path, description = "Category name")),
+    responses(
+        (status = 200, description = "Preference removed"),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 404, description = "None stored", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn delete_category_preference(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+    let category = path.into_inner();
+
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        let removed = preferences::delete_preference(&client, category.trim()).await?;
+        reload_category_preferences(&state, &client).await?;
+        Ok::<_, Box<dyn std::error::Error>>(removed)
+    }
+    .await;
+
+    match result {
+        Ok(0) => Err(ApiError::not_found(format!("No preferences stored for category '{}'", category))),
+        Ok(_) => {
+            info!("Removed display preferences for category '{}' for {}", category.trim(), user);
+            let audit_id = audit::record(
+                &state,
+                &user,
+                "category_preference.delete",
+                "category_preference",
+                Some(category.trim().to_string()),
+                json!({}),
+            )
+            .await;
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "success",
+                "message": format!("Preferences for category '{}' removed", category),
+                "audit_id": audit_id
+            })))
+        }
+        Err(e) => Err(ApiError::database(format!("Failed to delete category preference: {}", e), e.as_ref())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    use crate::{refresh_virtual_table, test_support};
+
+    fn reassign(from: &str, to: &str) -> TestRequest {
+        TestRequest::post()
+            .uri("/api/v1/admin/categories/reassign")
+            .set_json(json!({"from": from, "to": to}))
+    }
+
+    #[actix_web::test]
+    async fn categories_are_counted_largest_first_with_their_1_year_range() {
+        let funds = [
+            (Some("Flexi Cap"), Some(12.0)),
+            (Some("Small Cap"), Some(40.0)),
+            (Some("Flexi Cap"), None),
+            (Some("Large Cap"), Some(8.0)),
+            (Some("Flexi Cap"), Some(-3.5)),
+            (None, Some(99.0)),
+        ];
+        let records = funds
+            .into_iter()
+            .enumerate()
+            .map(|(n, (category, year_1))| {
+                let mut record = crate::CombinedSchemeData::test_fund(n as i32 + 1, &format!("Fund {}", n + 1));
+                record.fund_category = category.map(std::sync::Arc::from);
+                record.year_1 = year_1;
+                record
+            })
+            .collect();
+        let state = test_support::state(crate::config::RuntimeConfig::default(), records);
+
+        let (status, body) = test_support::call_json(&state, TestRequest::get().uri("/api/v1/categories")).await;
+        assert_eq!(status, 200, "{}", body);
+        // Ties go by name; a record without a category isn't counted anywhere
+        assert_eq!(
+            body["categories"],
+            json!([
+                {"category": "Flexi Cap", "records": 3, "min_year_1": -3.5, "max_year_1": 12.0},
+                {"category": "Large Cap", "records": 1, "min_year_1": 8.0, "max_year_1": 8.0},
+                {"category": "Small Cap", "records": 1, "min_year_1": 40.0, "max_year_1": 40.0}
+            ])
+        );
+    }
+
+    #[actix_web::test]
+    async fn bulk_reassignment_moves_facets_and_category_ranks_on_the_next_refresh() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(
+            "INSERT INTO category_vocabulary (name) VALUES ('Large Cap'), ('Flexi Cap');
+             INSERT INTO funds (category, scheme_name) VALUES
+                 ('Large Cap', 'Axis Bluechip Fund'),
+                 ('Flexi Cap', 'Parag Parikh Flexi Cap Fund'), ('Flexi Cap', 'HDFC Flexi Cap Fund'),
+                 ('Largecap Funds', 'HDFC Top 100 Fund'), ('Largecap Funds', 'ICICI Bluechip Fund'),
+                 ('Largecap Funds', 'SBI Bluechip Fund');",
+        )
+        .await;
+        refresh_virtual_table(&db.state).await.unwrap();
+
+        let (_, before) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/categories")).await;
+        assert_eq!(before["categories"][0]["category"], "Largecap Funds");
+
+        let request = test_support::as_admin(reassign("Largecap Funds", "LARGE CAP"));
+        let (status, body) = test_support::call_json(&db.state, request).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["to"], "Large Cap");
+        assert_eq!(body["funds_moved"], 3);
+
+        let (_, facets) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/facets")).await;
+        assert_eq!(facets["categories"], json!({"Flexi Cap": 2, "Large Cap": 4}));
+        let (_, after) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/categories")).await;
+        let ranked: Vec<&str> = after["categories"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|summary| summary["category"].as_str().unwrap())
+            .collect();
+        assert_eq!(ranked, vec!["Large Cap", "Flexi Cap"]);
+
+        let history = db
+            .query("SELECT from_category, to_category, funds_affected FROM category_reassignments")
+            .await;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].get::<_, String>(0), "Largecap Funds");
+        assert_eq!(history[0].get::<_, String>(1), "Large Cap");
+        assert_eq!(history[0].get::<_, i64>(2), 3);
+        let audit = db.query("SELECT target_id FROM audit_log WHERE action = 'category.reassign'").await;
+        assert_eq!(audit.len(), 1);
+        assert_eq!(audit[0].get::<_, Option<String>>(0).as_deref(), Some("Largecap Funds"));
+    }
+
+    #[actix_web::test]
+    async fn reassignment_to_a_category_outside_the_vocabulary_is_refused() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(
+            "INSERT INTO category_vocabulary (name) VALUES ('Large Cap');
+             INSERT INTO funds (category, scheme_name) VALUES ('Largecap Funds', 'HDFC Top 100 Fund');",
+        )
+        .await;
+
+        let request = test_support::as_admin(reassign("Largecap Funds", "Mega Cap"));
+        let (status, body) = test_support::call_json(&db.state, request).await;
+        assert_eq!(status, 422, "{}", body);
+
+        let unchanged = db.query("SELECT category FROM funds").await;
+        assert_eq!(unchanged[0].get::<_, String>(0), "Largecap Funds");
+        assert!(db.query("SELECT 1 FROM category_reassignments").await.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn mapped_sheets_upload_into_their_category_and_new_sheets_are_flagged() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let put = |mappings: serde_json::Value| {
+            test_support::as_admin(TestRequest::put().uri("/api/v1/categories/mappings").set_json(mappings))
+        };
+
+        let clash = json!([
+            {"sheet_name": "Large Cap Funds", "category": "Large Cap"},
+            {"sheet_name": "large cap funds.", "category": "Large Cap"}
+        ]);
+        let (status, body) = test_support::call_json(&db.state, put(clash)).await;
+        assert_eq!(status, 400, "{}", body);
+        let mappings = json!([
+            {"sheet_name": "Large Cap Funds", "category": "Large Cap"},
+            {"sheet_name": "Eq- Large Cap (2)", "category": "Large Cap"}
+        ]);
+        let (status, body) = test_support::call_json(&db.state, put(mappings)).await;
+        assert_eq!(status, 200, "{}", body);
+        let (_, listed) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/categories/mappings")).await;
+        let sheets: Vec<&str> = listed["mappings"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|mapping| mapping["sheet_name"].as_str().unwrap())
+            .collect();
+        assert_eq!(sheets, ["Eq- Large Cap (2)", "Large Cap Funds"]);
+
+        // Matched after normalization, so case and punctuation don't matter
+        let csv = b"Scheme Name,Launch Date\nAxis Bluechip Fund,2010-01-05\n";
+        let job = test_support::upload(&db.state, &[("EQ LARGE CAP 2.csv", csv)]).await;
+        assert_eq!(job["summary"]["files"][0]["report"]["unmapped_sheets"], json!([]), "{}", job);
+        let csv = b"Scheme Name,Launch Date\nKotak Emerging Equity,2007-03-30\n";
+        let job = test_support::upload(&db.state, &[("Mid Cap.csv", csv)]).await;
+        assert_eq!(job["summary"]["files"][0]["report"]["unmapped_sheets"], json!(["Mid Cap"]), "{}", job);
+
+        let rows = db.query("SELECT scheme_name, category FROM funds ORDER BY scheme_name").await;
+        let funds: Vec<(String, String)> = rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+        assert_eq!(
+            funds,
+            [
+                ("Axis Bluechip Fund".to_string(), "Large Cap".to_string()),
+                ("Kotak Emerging Equity".to_string(), "Mid Cap".to_string())
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn reassignment_needs_the_admin_role() {
+        let state = test_support::state(test_support::runtime_config(), Vec::new());
+        let (status, _) = test_support::call_json(&state, reassign("Largecap Funds", "Large Cap")).await;
+        assert_eq!(status, 401);
+    }
+}
@@ -0,0 +1,430 @@
+use actix_web::{web, HttpResponse};
+use log::info;
+use serde::Deserialize;
+use serde_json::json;
+use utoipa::IntoParams;
+
+use crate::api_error::{ApiError, ErrorCode};
+use crate::{
+    categories, dates, edits, ndjson_export, rate_matches, routes, unmatched_rate_rows, xlsx_export,
+    AppState, CombinedSchemeData,
+};
+
+#[utoipa::path(
+    get,
+    path = "/export/manifest",
+    tag = "exports",
+    responses(
+        (status = 200, description = "Published artifacts"),
+        (status = 404, description = "No export_dir configured", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn export_manifest(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let store = match &state.artifacts {
+        Some(store) => store,
+        None => return Err(ApiError::not_found("Artifact publishing is not configured")),
+    };
+
+    let manifest = store.manifest().map_err(|e| {
+        ApiError::internal(format!("Failed to read manifest: {}", e))
+    })?;
+
+    let artifacts: Vec<serde_json::Value> = manifest
+        .artifacts
+        .iter()
+        .map(|entry| {
+            json!({
+                "dataset": entry.dataset,
+                "generation": entry.generation,
+                "timestamp": entry.timestamp,
+                "file_name": entry.file_name,
+                "bytes": entry.bytes,
+                "sha256": entry.sha256,
+                "url": format!("{}/export/artifacts/{}", routes::V1_PREFIX, entry.file_name)
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(json!({ "artifacts": artifacts })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/artifacts/{file_name}",
+    tag = "exports",
+    params(("file_name" = String, Path, description = "A file listed in the manifest")),
+    responses(
+        (status = 200, description = "The artifact", content_type = ["application/json", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"], body = String),
+        (status = 404, description = "Unknown artifact, or no export_dir configured", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn download_artifact(path: web::Path<String>, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let store = match &state.artifacts {
+        Some(store) => store,
+        None => return Err(ApiError::not_found("Artifact publishing is not configured")),
+    };
+
+    let file_name = path.into_inner();
+    let artifact_path = match store.artifact_path(&file_name) {
+        Ok(Some(artifact_path)) => artifact_path,
+        Ok(None) => return Err(ApiError::not_found("Unknown artifact")),
+        Err(e) => {
+            return Err(ApiError::internal(format!("Failed to read manifest: {}", e)))
+        }
+    };
+
+    let contents = web::block(move || std::fs::read(artifact_path)).await?.map_err(|e| {
+        ApiError::internal(format!("Failed to read artifact: {}", e))
+    })?;
+    let content_type = if file_name.ends_with(".xlsx") {
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+    } else {
+        "application/json"
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", file_name)))
+        .body(contents))
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/unmatched-rates.xlsx",
+    tag = "exports",
+    responses(
+        (status = 200, description = "Rates that joined no fund, with suggested names", content_type = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", body = String)
+    )
+)]
+pub async fn export_unmatched_rates(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let rows = unmatched_rate_rows(&state);
+
+    let buffer = rate_matches::build_worksheet(&rows).map_err(|e| {
+        ApiError::internal(format!("Failed to build worksheet: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet")
+        .insert_header(("Content-Disposition", "attachment; filename=\"unmatched-rates.xlsx\""))
+        .body(buffer))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct XlsxExportQuery {
+    category: Option<String>,
+    company: Option<String>,
+}
+
+// The served table as a formatted workbook, optionally narrowed to one category and/or company
+#[utoipa::path(
+    get,
+    path = "/export/xlsx",
+    tag = "exports",
+    params(XlsxExportQuery),
+    responses(
+        (status = 200, description = "One sheet per fund category", content_type = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", body = String),
+        (status = 422, description = "Unknown category or company, or too many rows", body = openapi::ErrorResponse),
+        (status = 503, description = "Another export is being generated", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn export_xlsx(query: web::Query<XlsxExportQuery>, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    // Owned, so the guard travels into the blocking build and outlives a client that hangs up
+    let generating = match state.xlsx_export.clone().try_lock_owned() {
+        Ok(guard) => guard,
+        Err(_) => return Err(ApiError::new(ErrorCode::Unavailable, "An export is already being generated")),
+    };
+
+    let config = state.runtime_config.load();
+    let virtual_table = state.virtual_table.load_full();
+
+    // Same resolution as the search filters: canonical spelling, or a 422 with the closest values
+    let mut wanted = Vec::new();
+    for (name, value, known) in [
+        ("category", &query.category, virtual_table.category_counts()),
+        ("company", &query.company, virtual_table.company_counts()),
+    ] {
+        let value = match value.as_deref().map(str::trim).filter(|value| !value.is_empty()) {
+            Some(value) => value,
+            None => {
+                wanted.push(None);
+                continue;
+            }
+        };
+        match categories::resolve_known(value, known.keys(), &config) {
+            Ok(canonical) => wanted.push(Some(canonical)),
+            Err(closest) => return Err(ApiError::unknown_value(name, value, closest)),
+        }
+    }
+    let (category, company) = (wanted[0].as_deref(), wanted[1].as_deref());
+
+    let records: Vec<CombinedSchemeData> = virtual_table
+        .iter()
+        .filter(|record| category.is_none_or(|category| record.fund_category.as_deref() == Some(category)))
+        .filter(|record| company.is_none_or(|company| record.company.as_deref() == Some(company)))
+        .cloned()
+        .collect();
+    if records.len() > xlsx_export::MAX_ROWS {
+        return Err(ApiError::new(ErrorCode::Unprocessable, format!(
+                "Export would hold {} rows, more than the {} allowed; narrow it by category or company",
+                records.len(),
+                xlsx_export::MAX_ROWS
+            )));
+    }
+
+    let row_count = records.len();
+    let started = std::time::Instant::now();
+    let buffer = web::block(move || {
+        let _generating = generating;
+        xlsx_export::build_workbook(&records)
+    })
+    .await?
+    .map_err(|e| ApiError::internal(format!("Failed to build workbook: {}", e)))?;
+    info!(
+        "Built xlsx export of {} rows ({} bytes) in {}ms",
+        row_count,
+        buffer.len(),
+        started.elapsed().as_millis()
+    );
+
+    let file_name = format!("perftracker-{}.xlsx", chrono::Local::now().date_naive().format("%Y-%m-%d"));
+    Ok(HttpResponse::Ok()
+        .content_type("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet")
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", file_name)))
+        .body(buffer))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct NdjsonExportQuery {
+    category: Option<String>,
+    // YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS, compared with funds/scheme_rates updated_at
+    updated_since: Option<String>,
+}
+
+// The served table for the data warehouse, streamed a chunk at a time. updated_since keeps the
+// records of funds and rates written since then, as far as the served table has picked them up.
+#[utoipa::path(
+    get,
+    path = "/export/ndjson",
+    tag = "exports",
+    params(NdjsonExportQuery),
+    responses(
+        (status = 200, description = "One CombinedSchemeData object per line; X-Total-Count has the line count", content_type = "application/x-ndjson", body = CombinedSchemeData),
+        (status = 400, description = "Bad updated_since", body = openapi::ErrorResponse),
+        (status = 422, description = "Unknown category, with the closest known values", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn export_ndjson(query: web::Query<NdjsonExportQuery>, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let updated_since = match query.updated_since.as_deref().map(str::trim).filter(|value| !value.is_empty()) {
+        None => None,
+        Some(value) => match dates::parse_since(value) {
+            Some(since) => Some(since),
+            None => {
+                return Err(ApiError::invalid_parameter(format!(
+                        "Parameter 'updated_since' must be a date (YYYY-MM-DD) or timestamp (YYYY-MM-DDTHH:MM:SS), got '{}'",
+                        value
+                    )))
+            }
+        },
+    };
+
+    let virtual_table = state.virtual_table.load_full();
+    let category = match query.category.as_deref().map(str::trim).filter(|value| !value.is_empty()) {
+        None => None,
+        Some(value) => {
+            match categories::resolve_known(value, virtual_table.category_counts().keys(), &state.runtime_config.load()) {
+                Ok(canonical) => Some(canonical),
+                Err(closest) => return Err(ApiError::unknown_value("category", value, closest)),
+            }
+        }
+    };
+
+    let changed = match updated_since {
+        None => None,
+        Some(since) => {
+            let result = async {
+                let client = state.pools.read_client().await?;
+                Ok::<_, Box<dyn std::error::Error>>(edits::changed_since(&client, since).await?)
+            }
+            .await;
+            match result {
+                Ok(changed) => Some(changed),
+                Err(e) => {
+                    return Err(ApiError::database(format!("Failed to read changed rows: {}", e), e.as_ref()))
+                }
+            }
+        }
+    };
+
+    let (total, body) = ndjson_export::stream(virtual_table, ndjson_export::Selection { category, changed });
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .insert_header(("X-Total-Count", total.to_string()))
+        .streaming(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use calamine::{Data, Reader, Xlsx};
+    use futures_util::StreamExt;
+    use sha2::{Digest, Sha256};
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use crate::{artifacts, ndjson_export, test_support, CombinedSchemeData};
+
+    #[actix_web::test]
+    async fn manifest_urls_download_files_matching_their_listed_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = artifacts::ArtifactStore::new(dir.path().to_path_buf(), 1).unwrap();
+        store.publish("funds", "json", b"[\"old\"]").unwrap();
+        store.publish("funds", "json", b"[\"new\"]").unwrap();
+        let mut state = test_support::state(test_support::runtime_config(), Vec::new());
+        state.artifacts = Some(Arc::new(store));
+
+        let (status, manifest) = test_support::call_json(&state, TestRequest::get().uri("/api/v1/export/manifest")).await;
+        assert_eq!(status, 200);
+        let listed = manifest["artifacts"].as_array().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0]["generation"], 2);
+
+        let url = listed[0]["url"].as_str().unwrap();
+        let response = test_support::call(&state, TestRequest::get().uri(url)).await;
+        assert_eq!(response.status(), 200);
+        let body = actix_web::test::read_body(response).await;
+        assert_eq!(&body[..], b"[\"new\"]");
+        assert_eq!(listed[0]["sha256"], format!("{:x}", Sha256::digest(&body)));
+    }
+
+    #[actix_web::test]
+    async fn the_manifest_is_not_found_without_an_export_dir() {
+        let state = test_support::state(test_support::runtime_config(), Vec::new());
+        let (status, _) = test_support::call_json(&state, TestRequest::get().uri("/api/v1/export/manifest")).await;
+        assert_eq!(status, 404);
+    }
+
+    #[actix_web::test]
+    async fn the_workbook_has_a_sheet_per_category_with_returns_as_fractions() {
+        let funds = [
+            ("Quant Flexi Cap Fund", Some("Flexi Cap"), "Quant", 20.0),
+            ("Axis Flexi Cap Fund", Some("Flexi Cap"), "Axis", 12.5),
+            ("HDFC Short Term Debt Fund", Some("Debt: Short/Medium Duration"), "HDFC", 7.0),
+            ("Unsorted Fund", None, "Axis", 1.0),
+        ];
+        let records = funds
+            .into_iter()
+            .enumerate()
+            .map(|(n, (name, category, company, year_1))| {
+                let mut record = CombinedSchemeData::test_fund(n as i32 + 1, name);
+                record.fund_category = category.map(Arc::from);
+                record.company = Some(Arc::from(company));
+                record.year_1 = Some(year_1);
+                record
+            })
+            .collect();
+        let state = test_support::state(test_support::runtime_config(), records);
+        let export = |query: &str| TestRequest::get().uri(&format!("/api/v1/export/xlsx{}", query));
+
+        let response = test_support::call(&state, export("")).await;
+        assert_eq!(response.status(), 200);
+        let body = actix_web::test::read_body(response).await;
+        let mut workbook = Xlsx::new(Cursor::new(body.to_vec())).unwrap();
+        // Sheet names lose the characters Excel refuses
+        assert_eq!(workbook.sheet_names(), ["Debt Short Medium Duration", "Flexi Cap", "Uncategorized"]);
+        let sheet = workbook.worksheet_range("Flexi Cap").unwrap();
+        let column = |header: &str| {
+            (0..sheet.width()).find(|&col| sheet.get((0, col)) == Some(&Data::String(header.to_string())))
+        };
+        let (name, year_1) = (column("Scheme Name").unwrap(), column("1 Year").unwrap());
+        let rows: Vec<(String, Option<Data>)> = (1..sheet.height())
+            .map(|row| (sheet.get((row, name)).unwrap().to_string(), sheet.get((row, year_1)).cloned()))
+            .collect();
+        let expected = [("Axis Flexi Cap Fund", 0.125), ("Quant Flexi Cap Fund", 0.2)];
+        assert_eq!(rows, expected.map(|(name, year_1)| (name.to_string(), Some(Data::Float(year_1)))));
+
+        let response = test_support::call(&state, export("?category=flexi+cap&company=axis")).await;
+        let body = actix_web::test::read_body(response).await;
+        let mut workbook = Xlsx::new(Cursor::new(body.to_vec())).unwrap();
+        assert_eq!(workbook.sheet_names(), ["Flexi Cap"]);
+        assert_eq!(workbook.worksheet_range("Flexi Cap").unwrap().height(), 2);
+
+        let (status, body) = test_support::call_json(&state, export("?company=nobody")).await;
+        assert_eq!(status, 422, "{}", body);
+        // One export at a time
+        let _generating = state.xlsx_export.clone().try_lock_owned().unwrap();
+        let (status, _) = test_support::call_json(&state, export("")).await;
+        assert_eq!(status, 503);
+    }
+
+    fn ndjson_lines(body: &[u8]) -> Vec<serde_json::Value> {
+        body.split(|&byte| byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn ndjson_streams_a_line_per_record_a_chunk_at_a_time() {
+        let records: Vec<CombinedSchemeData> = (0..1200)
+            .map(|n| {
+                let mut record = CombinedSchemeData::test_fund(n + 1, &format!("Scheme {:04} Fund", n));
+                record.fund_category = Some(Arc::from(if n % 3 == 0 { "Small Cap" } else { "Flexi Cap" }));
+                record
+            })
+            .collect();
+        let state = test_support::state(test_support::runtime_config(), records);
+        let export = |query: &str| TestRequest::get().uri(&format!("/api/v1/export/ndjson?{}", query));
+
+        let response = test_support::call(&state, export("category=small+cap")).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers().get("Content-Type").unwrap(), "application/x-ndjson");
+        assert_eq!(response.headers().get("X-Total-Count").unwrap(), "400");
+        let lines = ndjson_lines(&actix_web::test::read_body(response).await);
+        assert_eq!(lines.len(), 400);
+        assert!(lines.iter().all(|record| record["fund_category"] == "Small Cap"));
+
+        // Serialized as the body is polled, so a client that hangs up stops the export
+        let (total, body) = ndjson_export::stream(state.virtual_table.load_full(), ndjson_export::Selection::default());
+        futures_util::pin_mut!(body);
+        let first = body.next().await.unwrap().unwrap();
+        assert_eq!((total, ndjson_lines(&first).len()), (1200, 500));
+
+        assert_eq!(test_support::call_json(&state, export("category=midcap")).await.0, 422);
+        assert_eq!(test_support::call_json(&state, export("updated_since=May")).await.0, 400);
+    }
+
+    #[actix_web::test]
+    async fn ndjson_updated_since_keeps_records_of_funds_and_rates_written_since() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(
+            "INSERT INTO funds (category, scheme_name, latest_nav, updated_at) VALUES
+                 ('Flexi Cap', 'Quant Flexi Cap Fund', 90, '2024-01-10'),
+                 ('Flexi Cap', 'Axis Flexi Cap Fund', 20, '2025-06-01'),
+                 ('Small Cap', 'Quant Small Cap Fund', 200, '2024-01-10');
+             INSERT INTO scheme_rates (arn, company, scheme_name, scheme_category, brokerage_type, start_date, end_date,
+                                       source_file, updated_at)
+             VALUES ('ARN-1', 'Quant', 'Quant Small Cap Fund', 'Equity', 'Trail', '2025-04-01', '2099-03-31', 'a.xlsx',
+                     '2025-06-02T09:30:00');",
+        )
+        .await;
+        crate::refresh_virtual_table(&db.state).await.unwrap();
+        let export = |since: &str| TestRequest::get().uri(&format!("/api/v1/export/ndjson?updated_since={}", since));
+
+        // The small cap fund is older, but its rate was written since
+        let cases = [("2024-01-01", vec![1, 2, 3]), ("2025-01-01", vec![2, 3]), ("2025-06-02T10:00:00", vec![])];
+        for (since, expected) in cases {
+            let response = test_support::call(&db.state, export(since)).await;
+            assert_eq!(response.status(), 200, "{}", since);
+            let lines = ndjson_lines(&actix_web::test::read_body(response).await);
+            let mut ids: Vec<i64> = lines.iter().map(|record| record["fund_id"].as_i64().unwrap()).collect();
+            ids.sort();
+            assert_eq!(ids, expected, "{}", since);
+        }
+    }
+}
@@ -0,0 +1,899 @@
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api_error::{ApiError, ErrorCode};
+use crate::config::RuntimeConfig;
+use crate::edits::{EditOutcome, FundEdit};
+use crate::filters::NumericField;
+use crate::table::VirtualTable;
+use crate::{
+    audit, auth, edit_outcome_response, edits, expected_version, get_postgres_client, history,
+    normalize_scheme_name, table, AppState, CombinedSchemeData,
+};
+
+const MAX_BATCH_LOOKUP_NAMES: usize = 1000;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FundByNameQuery {
+    name: String,
+    #[serde(default)]
+    fuzzy: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FundByNameBatch {
+    names: Vec<String>,
+    #[serde(default)]
+    fuzzy: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct FundNameLookup<'a> {
+    exists: bool,
+    fund_id: Option<i32>,
+    matched_normalized_name: Option<&'a str>,
+    match_type: Option<table::MatchKind>,
+    // Present whenever the fund exists, so "found but dataless" is distinguishable from "not found"
+    data_completeness: Option<f32>,
+    incomplete: Option<bool>,
+    missing_fields: Option<Vec<&'static str>>,
+}
+
+impl<'a> FundNameLookup<'a> {
+    fn from_match(found: Option<(&'a CombinedSchemeData, table::MatchKind)>) -> Self {
+        match found {
+            Some((record, match_type)) => Self {
+                exists: true,
+                fund_id: record.fund_id,
+                matched_normalized_name: Some(&*record.normalized_name),
+                match_type: Some(match_type),
+                data_completeness: Some(record.data_completeness),
+                incomplete: Some(record.incomplete),
+                missing_fields: Some(NumericField::missing(record)),
+            },
+            None => Self {
+                exists: false,
+                fund_id: None,
+                matched_normalized_name: None,
+                match_type: None,
+                data_completeness: None,
+                incomplete: None,
+                missing_fields: None,
+            },
+        }
+    }
+}
+
+// Config aliases are applied by the caller, as /search does; the table applies its name_aliases
+// and, with `fuzzy`, the search tiers under the configured threshold, budget and candidate cap
+fn lookup_fund_name<'a>(
+    virtual_table: &'a VirtualTable,
+    config: &RuntimeConfig,
+    name: &str,
+    fuzzy: bool,
+) -> Option<(&'a CombinedSchemeData, table::MatchKind)> {
+    virtual_table.lookup_name(
+        name,
+        fuzzy,
+        config.fuzzy_search_threshold,
+        Some(config.fuzzy_budget()),
+        config.max_search_candidates,
+    )
+}
+
+#[utoipa::path(
+    head,
+    path = "/funds/by-name",
+    tag = "funds",
+    params(FundByNameQuery),
+    responses(
+        (status = 200, description = "A fund has this name; X-Match-Type and X-Fund-Id say how and which"),
+        (status = 404, description = "No fund has this name")
+    )
+)]
+pub async fn head_fund_by_name(
+    query: web::Query<FundByNameQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let config = state.runtime_config.load();
+    let name = config.expand_aliases(&normalize_scheme_name(&query.name));
+    let virtual_table = state.virtual_table.load();
+
+    match lookup_fund_name(&virtual_table, &config, &name, query.fuzzy) {
+        Some((record, match_type)) => {
+            let mut response = HttpResponse::Ok();
+            response.insert_header(("X-Match-Type", match_type.as_str()));
+            if let Some(fund_id) = record.fund_id {
+                response.insert_header(("X-Fund-Id", fund_id.to_string()));
+            }
+            Ok(response.finish())
+        }
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/funds/by-name",
+    tag = "funds",
+    params(FundByNameQuery),
+    responses(
+        (status = 200, description = "The fund's records"),
+        (status = 404, description = "No fund has this name; details holds the lookup", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn get_fund_by_name(
+    query: web::Query<FundByNameQuery>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let config = state.runtime_config.load();
+    let name = config.expand_aliases(&normalize_scheme_name(&query.name));
+    let virtual_table = state.virtual_table.load();
+    let lookup = FundNameLookup::from_match(lookup_fund_name(&virtual_table, &config, &name, query.fuzzy));
+
+    if lookup.exists {
+        Ok(HttpResponse::Ok().json(lookup))
+    } else {
+        Err(ApiError::not_found(format!("No fund named '{}'", query.name)).with_details(json!(lookup)))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/funds/by-name",
+    tag = "funds",
+    request_body(content = FundByNameBatch),
+    responses(
+        (status = 200, description = "One lookup per name, in order"),
+        (status = 400, description = "More than 1000 names", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn batch_funds_by_name(
+    body: web::Json<FundByNameBatch>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    if body.names.len() > MAX_BATCH_LOOKUP_NAMES {
+        return Err(ApiError::invalid_parameter(format!("At most {} names may be looked up per request", MAX_BATCH_LOOKUP_NAMES)));
+    }
+
+    let config = state.runtime_config.load();
+    let names: Vec<String> = body
+        .names
+        .iter()
+        .map(|name| config.expand_aliases(&normalize_scheme_name(name)))
+        .collect();
+
+    let virtual_table = state.virtual_table.load();
+    let results: Vec<FundNameLookup> = names
+        .iter()
+        .map(|name| FundNameLookup::from_match(lookup_fund_name(&virtual_table, &config, name, body.fuzzy)))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "count": results.len(),
+        "results": results
+    })))
+}
+
+const DEFAULT_FUND_LIST_LIMIT: i64 = 50;
+const MAX_FUND_LIST_LIMIT: i64 = 500;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FundListQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    category: Option<String>,
+    // A column of edits::FUND_ORDER_COLUMNS, '-' prefixed for descending; id by default
+    order_by: Option<String>,
+}
+
+// The funds table as stored, for checking what a refresh should have picked up; never the
+// virtual table
+#[utoipa::path(
+    get,
+    path = "/funds",
+    tag = "funds",
+    params(FundListQuery),
+    responses(
+        (status = 200, description = "Funds as stored, with the total count"),
+        (status = 400, description = "Bad offset or order_by", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn list_funds(query: web::Query<FundListQuery>, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_FUND_LIST_LIMIT).clamp(1, MAX_FUND_LIST_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+    if offset < 0 {
+        return Err(ApiError::invalid_parameter("Parameter 'offset' must not be negative"));
+    }
+    let order_by = query.order_by.as_deref().map(str::trim).filter(|o| !o.is_empty()).unwrap_or("id");
+    let (column, descending) = match order_by.strip_prefix('-') {
+        Some(column) => (column, true),
+        None => (order_by, false),
+    };
+    if !edits::FUND_ORDER_COLUMNS.contains(&column) {
+        return Err(ApiError::invalid_parameter(format!(
+                "Cannot order by '{}'; expected one of: {}",
+                column,
+                edits::FUND_ORDER_COLUMNS.join(", ")
+            )));
+    }
+    let category = query.category.as_deref().map(str::trim).filter(|c| !c.is_empty());
+
+    // The primary, so rows a lagging replica hasn't seen yet are listed too
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        Ok::<_, Box<dyn std::error::Error>>(
+            edits::list_funds(&client, category, column, descending, limit, offset).await?,
+        )
+    }
+    .await;
+
+    match result {
+        Ok((funds, total)) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "total": total,
+            "limit": limit,
+            "offset": offset,
+            "order_by": if descending { format!("-{}", column) } else { column.to_string() },
+            "funds": funds
+        }))),
+        Err(e) => Err(ApiError::database(format!("Failed to list funds: {}", e), e.as_ref())),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/funds/{id}",
+    tag = "funds",
+    params(
+        ("id" = i32, Path, description = "Fund id"),
+        ("as_of_upload_id" = Option<i32>, Query, description = "The fund's values right after this upload, from fund_history"),
+        ("as_of_date" = Option<String>, Query, description = "The fund's values at the end of this YYYY-MM-DD day, from fund_history")
+    ),
+    responses(
+        (status = 200, description = "The fund with its approved rates; ETag is its version. As of an upload or date, the recorded values with meta.reconstructed"),
+        (status = 400, description = "Malformed as_of_upload_id/as_of_date, or both given", body = openapi::ErrorResponse),
+        (status = 404, description = "No such fund, or no recorded values at that point", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn get_fund(
+    path: web::Path<i32>,
+    query: web::Query<HashMap<String, String>>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    match history::AsOf::from_query(&query) {
+        Ok(Some(as_of)) => return get_fund_as_of(id, as_of, &state).await,
+        Ok(None) => {}
+        Err(message) => return Err(ApiError::invalid_parameter(message)),
+    }
+    // Edits are checked against the primary, so read versions from it too
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        match edits::load_fund(&client, id).await? {
+            Some(fund) => Ok::<_, Box<dyn std::error::Error>>(Some((fund, edits::load_fund_rates(&client, id).await?))),
+            None => Ok(None),
+        }
+    }
+    .await;
+
+    match result {
+        // normalized_name is the key search results carry as matched_normalized_name
+        Ok(Some((fund, rates))) => Ok(HttpResponse::Ok()
+            .insert_header((header::ETAG, edits::etag(fund.version)))
+            .json(json!({
+                "status": "success",
+                "fund": fund,
+                "normalized_name": normalize_scheme_name(&fund.scheme_name),
+                "rates": rates
+            }))),
+        Ok(None) => edit_outcome_response("fund", id, EditOutcome::<edits::FundRecord>::NotFound, None, |f| f.version),
+        Err(e) => Err(ApiError::database(format!("Failed to load fund: {}", e), e.as_ref())),
+    }
+}
+
+// The fund as uploads had left it; never current values, which would answer an audit question wrongly
+async fn get_fund_as_of(id: i32, as_of: history::AsOf, state: &AppState) -> Result<HttpResponse, ApiError> {
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        Ok::<_, Box<dyn std::error::Error>>(history::funds_as_of(&**client, &[id], as_of).await?)
+    }
+    .await;
+
+    match result {
+        Ok(mut funds) => match funds.remove(&id) {
+            Some(fund) => {
+                let (point, value) = as_of.meta();
+                Ok(HttpResponse::Ok().json(json!({
+                    "status": "success",
+                    "fund": fund,
+                    "normalized_name": normalize_scheme_name(&fund.scheme_name),
+                    "meta": {
+                        "reconstructed": true,
+                        point: value,
+                        "upload_id": fund.upload_id
+                    }
+                })))
+            }
+            None => Err(ApiError::not_found(format!("Fund {} has no recorded values as of that point", id))),
+        },
+        Err(e) => Err(ApiError::database(format!("Failed to load fund history: {}", e), e.as_ref())),
+    }
+}
+
+// Every rate the join maps to the fund, read from Postgres so rows a refresh hasn't picked up yet
+// show too, each flagged with whether the served table has it
+#[utoipa::path(
+    get,
+    path = "/funds/{id}/rates",
+    tag = "funds",
+    params(("id" = i32, Path, description = "Fund id")),
+    responses(
+        (status = 200, description = "Every rate joined to the fund, in any approval state"),
+        (status = 404, description = "No such fund", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn get_fund_rates(path: web::Path<i32>, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        match edits::load_fund(&client, id).await? {
+            Some(fund) => Ok::<_, Box<dyn std::error::Error>>(Some((fund, edits::load_all_fund_rates(&client, id).await?))),
+            None => Ok(None),
+        }
+    }
+    .await;
+
+    match result {
+        Ok(Some((fund, rates))) => {
+            let served: std::collections::HashSet<i32> = state
+                .virtual_table
+                .load()
+                .get_by_fund_id(id)
+                .iter()
+                .filter_map(|record| record.rate_id)
+                .collect();
+            let today = chrono::Local::now().date_naive();
+            let rates: Vec<serde_json::Value> = rates
+                .iter()
+                .map(|rate| {
+                    json!({
+                        "rate": rate,
+                        "approved": rate.approved(),
+                        "active": rate.active_on(today),
+                        "in_virtual_table": served.contains(&rate.id)
+                    })
+                })
+                .collect();
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "success",
+                "fund_id": id,
+                "scheme_name": fund.scheme_name,
+                "normalized_name": normalize_scheme_name(&fund.scheme_name),
+                "rates": rates
+            })))
+        }
+        Ok(None) => Err(ApiError::not_found(format!("No fund {}", id))),
+        Err(e) => Err(ApiError::database(format!("Failed to load fund rates: {}", e), e.as_ref())),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/funds/{id}",
+    tag = "funds",
+    security(("api_key" = []), ("bearer" = [])),
+    params(("id" = i32, Path, description = "Fund id"), ("If-Match" = Option<String>, Header, description = "Version the edit is based on; or send version in the body")),
+    request_body(content = crate::edits::FundEdit),
+    responses(
+        (status = 200, description = "Updated fund; ETag is its new version"),
+        (status = 400, description = "Invalid fund", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the uploader role", body = openapi::ErrorResponse),
+        (status = 409, description = "Changed by another write; carries the current fund", body = openapi::ErrorResponse),
+        (status = 404, description = "No such fund", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn put_fund(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    body: web::Json<FundEdit>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Uploader)?;
+    let id = path.into_inner();
+    let expected = expected_version(&req, body.version)?;
+
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        let mutation = state.begin_mutation().await;
+        let outcome = edits::update_fund(&client, id, expected, &body).await?;
+        Ok::<_, Box<dyn std::error::Error>>((mutation, outcome))
+    }
+    .await;
+
+    match result {
+        Ok((mutation, outcome)) => {
+            let audit_id = if let EditOutcome::Updated(fund) = &outcome {
+                info!("Fund {} edited by {} (was version {})", id, user, expected);
+                if let Err(e) = mutation.refresh().await {
+                    warn!("Failed to refresh virtual table after fund edit: {}", e);
+                }
+                let summary = json!({"previous_version": expected, "fund": fund});
+                audit::record(&state, &user, "fund.update", "fund", Some(id.to_string()), summary).await
+            } else {
+                None
+            };
+            edit_outcome_response("fund", id, outcome, audit_id, |f| f.version)
+        }
+        Err(e) => Err(ApiError::database(format!("Failed to update fund: {}", e), e.as_ref())),
+    }
+}
+
+// Correct a few figures without re-uploading the workbook. The name doesn't change, so only this
+// fund's records are re-read into the served table.
+#[utoipa::path(
+    patch,
+    path = "/funds/{id}",
+    tag = "funds",
+    security(("api_key" = []), ("bearer" = [])),
+    params(("id" = i32, Path, description = "Fund id"), ("If-Match" = Option<String>, Header, description = "Version the edit is based on; or send version in the body")),
+    request_body(content = crate::edits::FundPatch),
+    responses(
+        (status = 200, description = "Updated fund; ETag is its new version"),
+        (status = 400, description = "Invalid field, or scheme_name (rename through PUT)", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the uploader role", body = openapi::ErrorResponse),
+        (status = 409, description = "Changed by another write; carries the current fund", body = openapi::ErrorResponse),
+        (status = 404, description = "No such fund", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn patch_fund(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    body: web::Json<edits::FundPatch>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Uploader)?;
+    let id = path.into_inner();
+    if let Err(message) = body.validate() {
+        return Err(ApiError::new(ErrorCode::InvalidBody, message));
+    }
+    let expected = expected_version(&req, body.version)?;
+
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        let mutation = state.begin_mutation().await;
+        let outcome = edits::patch_fund(&client, id, expected, &body).await?;
+        Ok::<_, Box<dyn std::error::Error>>((mutation, outcome))
+    }
+    .await;
+
+    match result {
+        Ok((mutation, outcome)) => {
+            let audit_id = if let EditOutcome::Updated(fund) = &outcome {
+                info!("Fund {} patched by {} (was version {})", id, user, expected);
+                if let Err(e) = mutation.refresh_for(std::slice::from_ref(&fund.scheme_name)).await {
+                    warn!("Failed to refresh virtual table after fund patch: {}", e);
+                }
+                let summary = json!({"previous_version": expected, "fund": fund});
+                audit::record(&state, &user, "fund.patch", "fund", Some(id.to_string()), summary).await
+            } else {
+                None
+            };
+            edit_outcome_response("fund", id, outcome, audit_id, |f| f.version)
+        }
+        Err(e) => Err(ApiError::database(format!("Failed to patch fund: {}", e), e.as_ref())),
+    }
+}
+
+// For junk rows (a disclaimer parsed as a scheme name and the like). The fund's records leave the
+// served table in place rather than through a rebuild.
+#[utoipa::path(
+    delete,
+    path = "/funds/{id}",
+    tag = "funds",
+    security(("api_key" = []), ("bearer" = [])),
+    params(("id" = i32, Path, description = "Fund id")),
+    responses(
+        (status = 200, description = "Fund deleted and its records dropped from the served table"),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 404, description = "No such fund", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn delete_fund(
+    path: web::Path<i32>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+    let id = path.into_inner();
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        let mutation = state.begin_mutation().await;
+        let deleted = edits::delete_fund(&client, id).await?;
+        Ok::<_, Box<dyn std::error::Error>>(deleted.map(|fund| (mutation, fund)))
+    }
+    .await;
+
+    match result {
+        Ok(Some((mutation, fund))) => {
+            info!("Fund {} ('{}') deleted by {}", id, fund.scheme_name, user);
+            let records_removed = mutation.remove_fund(id).await;
+            let audit_id = audit::record(&state, &user, "fund.delete", "fund", Some(id.to_string()), json!({"fund": fund})).await;
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "success",
+                "message": format!("Fund {} deleted", id),
+                "fund": fund,
+                "records_removed": records_removed,
+                "audit_id": audit_id
+            })))
+        }
+        Ok(None) => Err(ApiError::not_found(format!("No fund {}", id))),
+        Err(e) => Err(ApiError::database(format!("Failed to delete fund: {}", e), e.as_ref())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    use crate::test_support;
+
+    const FUND: &str = "INSERT INTO funds (category, scheme_name, latest_nav) VALUES ('Flexi Cap', 'Parag Parikh Flexi Cap Fund', 80)";
+
+    fn edit(latest_nav: f32) -> serde_json::Value {
+        json!({"category": "Flexi Cap", "scheme_name": "Parag Parikh Flexi Cap Fund", "latest_nav": latest_nav})
+    }
+
+    fn put(if_match: Option<&str>, body: serde_json::Value) -> TestRequest {
+        let request = TestRequest::put().uri("/api/v1/funds/1").set_json(body);
+        let request = match if_match {
+            Some(version) => request.insert_header((header::IF_MATCH, version)),
+            None => request,
+        };
+        test_support::as_admin(request)
+    }
+
+    async fn etag_of_fund(state: &AppState) -> String {
+        let response = test_support::call(state, TestRequest::get().uri("/api/v1/funds/1")).await;
+        assert_eq!(response.status(), 200);
+        response.headers().get(header::ETAG).unwrap().to_str().unwrap().to_string()
+    }
+
+    #[actix_web::test]
+    async fn an_edit_based_on_the_current_version_applies_and_bumps_it() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(FUND).await;
+        assert_eq!(etag_of_fund(&db.state).await, "\"1\"");
+
+        let response = test_support::call(&db.state, put(Some("\"1\""), edit(81.5))).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers().get(header::ETAG).unwrap(), "\"2\"");
+
+        // The body's version works as well as If-Match
+        let mut body = edit(82.0);
+        body["version"] = json!(2);
+        let (status, body) = test_support::call_json(&db.state, put(None, body)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["fund"]["version"], 3);
+        assert_eq!(body["fund"]["latest_nav"], 82.0);
+    }
+
+    #[actix_web::test]
+    async fn a_stale_edit_is_refused_with_the_current_fund() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(FUND).await;
+        let seen = etag_of_fund(&db.state).await;
+        let (status, _) = test_support::call_json(&db.state, put(Some(&seen), edit(81.5))).await;
+        assert_eq!(status, 200);
+
+        let response = test_support::call(&db.state, put(Some(&seen), edit(79.0))).await;
+        assert_eq!(response.status(), 409);
+        assert_eq!(response.headers().get(header::ETAG).unwrap(), "\"2\"");
+        let body: serde_json::Value = serde_json::from_slice(&actix_web::test::read_body(response).await).unwrap();
+        assert_eq!(body["details"]["current"]["version"], 2);
+        assert_eq!(body["details"]["current"]["latest_nav"], 81.5);
+
+        let (status, _) = test_support::call_json(&db.state, put(None, edit(79.0))).await;
+        assert_eq!(status, 428);
+        let nav = db.query("SELECT latest_nav FROM funds").await;
+        assert_eq!(nav[0].get::<_, Option<f32>>(0), Some(81.5));
+    }
+
+    #[actix_web::test]
+    async fn an_upload_between_reading_and_editing_a_fund_makes_the_edit_stale() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(FUND).await;
+        let seen = etag_of_fund(&db.state).await;
+
+        let csv = b"Scheme Name,Launch Date,Latest NAV\nParag Parikh Flexi Cap Fund,2013-05-24,84.25\n";
+        let job = test_support::upload(&db.state, &[("Flexi Cap.csv", csv)]).await;
+        assert_eq!(job["state"], "done", "{}", job);
+        assert_eq!(etag_of_fund(&db.state).await, "\"2\"");
+
+        let (status, body) = test_support::call_json(&db.state, put(Some(&seen), edit(81.5))).await;
+        assert_eq!(status, 409, "{}", body);
+        assert_eq!(body["details"]["current"]["latest_nav"], 84.25);
+    }
+
+    // Latest NAV and 1Y return by fund id from a CSV export, values parsed
+    fn csv_values(csv: &str) -> HashMap<i32, (Option<f32>, Option<f32>)> {
+        let mut lines = csv.lines();
+        let header: Vec<&str> = lines.next().unwrap().split(',').collect();
+        let column = |name: &str| header.iter().position(|field| *field == name).unwrap();
+        let (fund_id, latest_nav, year_1) = (column("fund_id"), column("latest_nav"), column("year_1"));
+        lines
+            .map(|line| {
+                let fields: Vec<&str> = line.split(',').collect();
+                (
+                    fields[fund_id].parse().unwrap(),
+                    (fields[latest_nav].parse().ok(), fields[year_1].parse().ok()),
+                )
+            })
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn fund_values_are_reconstructed_as_of_each_upload() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let uploads: [&[u8]; 3] = [
+            b"Scheme Name,Launch Date,Latest NAV\nParag Parikh Flexi Cap Fund,2013-05-24,80\n",
+            b"Scheme Name,Launch Date,Latest NAV,1Y\nParag Parikh Flexi Cap Fund,2013-05-24,82,12.5\nQuant Flexi Cap Fund,2008-10-17,90,20\n",
+            b"Scheme Name,Launch Date,Latest NAV,1Y\nParag Parikh Flexi Cap Fund,2013-05-24,85,14\n",
+        ];
+        for csv in uploads {
+            let job = test_support::upload(&db.state, &[("Flexi Cap.csv", csv)]).await;
+            assert_eq!(job["state"], "done", "{}", job);
+        }
+        let ids = |rows: Vec<tokio_postgres::Row>| rows.iter().map(|row| row.get(0)).collect::<Vec<i32>>();
+        let upload_ids = ids(db.query("SELECT id FROM uploads ORDER BY id").await);
+        let fund_ids = ids(db.query("SELECT id FROM funds ORDER BY scheme_name").await);
+        let (parag, quant) = (fund_ids[0], fund_ids[1]);
+
+        let as_of = |query: String| TestRequest::get().uri(&format!("/api/v1/funds/{}?{}", parag, query));
+        let expected = [
+            (upload_ids[0], json!(80.0), json!(null)),
+            (upload_ids[1], json!(82.0), json!(12.5)),
+            (upload_ids[2], json!(85.0), json!(14.0)),
+        ];
+        for (upload_id, latest_nav, year_1) in expected {
+            let (status, body) = test_support::call_json(&db.state, as_of(format!("as_of_upload_id={}", upload_id))).await;
+            assert_eq!(status, 200, "{}", body);
+            assert_eq!(body["fund"]["latest_nav"], latest_nav);
+            assert_eq!(body["fund"]["year_1"], year_1);
+            let meta = json!({"reconstructed": true, "as_of_upload_id": upload_id, "upload_id": upload_id});
+            assert_eq!(body["meta"], meta);
+        }
+
+        // By date: everything uploaded today counts
+        let today = chrono::Local::now().date_naive();
+        let (status, body) = test_support::call_json(&db.state, as_of(format!("as_of_date={}", today))).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["fund"]["latest_nav"], 85.0);
+        assert_eq!(body["meta"]["upload_id"], upload_ids[2]);
+        let (status, _) = test_support::call_json(&db.state, as_of("as_of_date=2000-01-01".to_string())).await;
+        assert_eq!(status, 404);
+
+        // A fund the first upload didn't have, and a bad combination of parameters
+        let uri = format!("/api/v1/funds/{}?as_of_upload_id={}", quant, upload_ids[0]);
+        let (status, _) = test_support::call_json(&db.state, TestRequest::get().uri(&uri)).await;
+        assert_eq!(status, 404);
+        let both = as_of(format!("as_of_upload_id=1&as_of_date={}", today));
+        let (status, body) = test_support::call_json(&db.state, both).await;
+        assert_eq!(status, 400);
+        assert_eq!(body["code"], "invalid_parameter");
+
+        // The CSV export reconstructs its matches; the quant fund's values didn't exist yet
+        let uri = format!("/api/v1/search.csv?include_incomplete=true&as_of_upload_id={}", upload_ids[0]);
+        let response = test_support::call(&db.state, TestRequest::get().uri(&uri)).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers().get("X-Reconstructed").unwrap(), "true");
+        let csv = String::from_utf8(actix_web::test::read_body(response).await.to_vec()).unwrap();
+        let values = csv_values(&csv);
+        assert_eq!(values[&parag], (Some(80.0), None));
+        assert_eq!(values[&quant], (None, None));
+
+        let uri = format!("/api/v1/search.csv?include_incomplete=true&as_of_upload_id={}", upload_ids[1]);
+        let response = test_support::call(&db.state, TestRequest::get().uri(&uri)).await;
+        let csv = String::from_utf8(actix_web::test::read_body(response).await.to_vec()).unwrap();
+        assert_eq!(csv_values(&csv)[&parag], (Some(82.0), Some(12.5)));
+
+        // JSON search results stay live
+        let uri = format!("/api/v1/search?as_of_upload_id={}", upload_ids[0]);
+        let (status, _) = test_support::call_json(&db.state, TestRequest::get().uri(&uri)).await;
+        assert_eq!(status, 400);
+    }
+
+    #[actix_web::test]
+    async fn a_fund_by_id_carries_its_approved_rates_normalized_name_and_created_at() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(FUND).await;
+        // Joined by normalized name; the pending rate and the other fund's rate stay out
+        db.execute(
+            "INSERT INTO scheme_rates (arn, company, scheme_name, scheme_category, brokerage_type, start_date, end_date,
+                                       source_file, is_approved, base_year_1)
+             VALUES ('ARN-1', 'PPFAS', 'Parag Parikh Flexi Cap Fund.', 'Flexi Cap', 'Trail', '2025-04-01', '2099-03-31',
+                     'rates.xlsx', true, 0.8),
+                    ('ARN-2', 'PPFAS', 'Parag Parikh Flexi Cap Fund', 'Flexi Cap', 'Upfront', '2025-04-01', '2099-03-31',
+                     'rates.xlsx', false, 0.5),
+                    ('ARN-3', 'Quant', 'Quant Flexi Cap Fund', 'Flexi Cap', 'Trail', '2025-04-01', '2099-03-31',
+                     'rates.xlsx', true, 0.9);",
+        )
+        .await;
+
+        let (status, body) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/funds/1")).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["fund"]["scheme_name"], "Parag Parikh Flexi Cap Fund");
+        assert_eq!(body["normalized_name"], normalize_scheme_name("Parag Parikh Flexi Cap Fund"));
+        assert!(body["fund"]["created_at"].is_string(), "{}", body);
+        let rates = body["rates"].as_array().unwrap();
+        assert_eq!(rates.len(), 1, "{}", body);
+        assert_eq!(rates[0]["arn"], "ARN-1");
+
+        let (status, body) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/funds/99")).await;
+        assert_eq!(status, 404);
+        assert_eq!(body["code"], "not_found");
+    }
+
+    #[actix_web::test]
+    async fn a_funds_rates_include_every_state_each_flagged_and_marked_if_served() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(FUND).await;
+        let rates = "INSERT INTO scheme_rates (arn, company, scheme_name, scheme_category, brokerage_type, start_date,
+                                               end_date, source_file, is_approved) VALUES";
+        db.execute(&format!(
+            "{} ('ARN-1', 'PPFAS', 'Parag Parikh Flexi Cap Fund', 'Equity', 'Trail', '2025-04-01', '2099-03-31', 'a.xlsx', true),
+                ('ARN-1', 'PPFAS', 'Parag Parikh Flexi Cap Fund', 'Equity', 'Trail', '2023-04-01', '2024-03-31', 'a.xlsx', true),
+                ('ARN-2', 'Axis', 'PARAG PARIKH FLEXI CAP FUND', 'Equity', 'Upfront', '2025-04-01', '2099-03-31', 'b.xlsx', false),
+                ('ARN-3', 'Quant', 'Quant Flexi Cap Fund', 'Equity', 'Trail', '2025-04-01', '2099-03-31', 'b.xlsx', true);",
+            rates
+        ))
+        .await;
+        crate::refresh_virtual_table(&db.state).await.unwrap();
+        // Not picked up by a refresh yet
+        db.execute(&format!(
+            "{} ('ARN-4', 'PPFAS', 'Parag Parikh Flexi Cap Fund', 'Equity', 'Upfront', '2024-04-01', '2099-03-31', 'c.xlsx', true);",
+            rates
+        ))
+        .await;
+
+        let (status, body) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/funds/1/rates")).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["normalized_name"], normalize_scheme_name("Parag Parikh Flexi Cap Fund"));
+        // By company, then newest first; expired rates are served too, for include_expired
+        let flags: Vec<(&str, &str, bool, bool, bool)> = body["rates"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|rate| {
+                (
+                    rate["rate"]["arn"].as_str().unwrap(),
+                    rate["rate"]["start_date"].as_str().unwrap(),
+                    rate["approved"].as_bool().unwrap(),
+                    rate["active"].as_bool().unwrap(),
+                    rate["in_virtual_table"].as_bool().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            flags,
+            vec![
+                ("ARN-2", "2025-04-01", false, true, false),
+                ("ARN-1", "2025-04-01", true, true, true),
+                ("ARN-4", "2024-04-01", true, true, false),
+                ("ARN-1", "2023-04-01", true, false, true),
+            ]
+        );
+
+        let (status, _) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/funds/99/rates")).await;
+        assert_eq!(status, 404);
+    }
+
+    #[actix_web::test]
+    async fn the_fund_listing_pages_the_stored_rows_with_their_total() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(
+            "INSERT INTO funds (category, scheme_name, latest_nav) VALUES
+                 ('Flexi Cap', 'Quant Flexi Cap Fund', 90), ('Flexi Cap', 'Axis Flexi Cap Fund', 20),
+                 ('Small Cap', 'Quant Small Cap Fund', 200), ('Flexi Cap', 'HDFC Flexi Cap Fund', 1500);",
+        )
+        .await;
+        // Stored rows are listed whether or not a refresh has picked them up
+        assert_eq!(db.state.virtual_table.load().len(), 0);
+        let list = |query: &str| TestRequest::get().uri(&format!("/api/v1/funds?{}", query));
+        let ids = |body: &serde_json::Value| -> Vec<i64> {
+            body["funds"].as_array().unwrap().iter().map(|fund| fund["id"].as_i64().unwrap()).collect()
+        };
+
+        let (status, body) = test_support::call_json(&db.state, list("")).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!((ids(&body), body["total"].as_i64()), (vec![1, 2, 3, 4], Some(4)));
+        let (_, body) = test_support::call_json(&db.state, list("limit=2&offset=1")).await;
+        assert_eq!((ids(&body), body["total"].as_i64()), (vec![2, 3], Some(4)));
+
+        // The filter narrows the total too
+        let (_, body) = test_support::call_json(&db.state, list("category=Flexi+Cap&order_by=-latest_nav")).await;
+        assert_eq!((ids(&body), body["total"].as_i64()), (vec![4, 1, 2], Some(3)));
+        assert_eq!(body["order_by"], "-latest_nav");
+        let (_, body) = test_support::call_json(&db.state, list("order_by=scheme_name&limit=1")).await;
+        assert_eq!(ids(&body), vec![2]);
+
+        for bad in ["order_by=version;drop", "order_by=-nonsense", "offset=-1"] {
+            let (status, body) = test_support::call_json(&db.state, list(bad)).await;
+            assert_eq!((status.as_u16(), body["code"].as_str()), (400, Some("invalid_parameter")), "{}", bad);
+        }
+    }
+
+    #[actix_web::test]
+    async fn deleting_a_fund_drops_it_from_the_database_and_the_served_table() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(
+            "INSERT INTO funds (category, scheme_name, latest_nav) VALUES
+                 ('Flexi Cap', 'Quant Flexi Cap Fund', 90), ('Flexi Cap', 'Disclaimer: past returns', 1);",
+        )
+        .await;
+        let refresh = test_support::as_admin(TestRequest::post().uri("/api/v1/refresh"));
+        assert_eq!(test_support::call_json(&db.state, refresh).await.0, 200);
+        let junk = 2;
+        let delete = |id: i32| test_support::as_admin(TestRequest::delete().uri(&format!("/api/v1/funds/{}", id)));
+
+        let anonymous = TestRequest::delete().uri(&format!("/api/v1/funds/{}", junk));
+        assert_eq!(test_support::call_json(&db.state, anonymous).await.0, 401);
+        let (status, body) = test_support::call_json(&db.state, delete(junk)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["fund"]["scheme_name"], "Disclaimer: past returns");
+        assert_eq!(body["records_removed"], 1);
+
+        assert!(db.query("SELECT id FROM funds WHERE scheme_name LIKE 'Disclaimer%'").await.is_empty());
+        let table = db.state.virtual_table.load();
+        assert_eq!(table.len(), 1);
+        assert!(table.get_by_fund_id(junk).is_empty());
+        assert!(table.lookup_name("Disclaimer: past returns", false, 0.85, None, 1000).is_none());
+        drop(table);
+
+        let (status, body) = test_support::call_json(&db.state, delete(junk)).await;
+        assert_eq!(status, 404, "{}", body);
+    }
+}
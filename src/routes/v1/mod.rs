@@ -0,0 +1,70 @@
+pub mod admin;
+pub mod categories;
+pub mod exports;
+pub mod funds;
+pub mod rates;
+pub mod search;
+pub mod uploads;
+
+use actix_web::web;
+
+// Every API route, relative to where it is mounted: under /api/v1, and again unprefixed for the
+// clients that predate the prefix. A change to a route's shape belongs in a v2 module, never here.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg
+        .route("/upload", web::post().to(uploads::upload_excel))
+        .route("/upload/rates", web::post().to(uploads::upload_rates))
+        .route("/jobs/{id}", web::get().to(uploads::get_job))
+        .route("/uploads", web::get().to(uploads::list_uploads))
+        .route("/uploads/{id}", web::get().to(uploads::get_upload))
+        .route("/uploads/{id}", web::delete().to(uploads::purge_upload))
+        .route("/search", web::get().to(search::search_schemes))
+        .route("/search.csv", web::get().to(search::search_schemes_csv))
+        .route("/suggest", web::get().to(search::suggest_names))
+        .route("/status", web::get().to(admin::status))
+        .route("/stats", web::get().to(admin::stats))
+        .route("/facets", web::get().to(search::facets))
+        .route("/refresh", web::post().to(admin::refresh_virtual_table_endpoint))
+        .route("/funds/by-name", web::head().to(funds::head_fund_by_name))
+        .route("/funds/by-name", web::get().to(funds::get_fund_by_name))
+        .route("/funds/by-name", web::post().to(funds::batch_funds_by_name))
+        .route("/funds", web::get().to(funds::list_funds))
+        .route("/funds/{id}", web::get().to(funds::get_fund))
+        .route("/funds/{id}/rates", web::get().to(funds::get_fund_rates))
+        .route("/funds/{id}", web::put().to(funds::put_fund))
+        .route("/funds/{id}", web::patch().to(funds::patch_fund))
+        .route("/funds/{id}", web::delete().to(funds::delete_fund))
+        .route("/rates/pending", web::get().to(rates::list_pending_rates))
+        .route("/rates/{id}", web::get().to(rates::get_rate))
+        .route("/rates", web::post().to(rates::create_rate))
+        .route("/rates", web::delete().to(rates::delete_rates_by_source))
+        .route("/rates/{id}/approve", web::post().to(rates::approve_rate))
+        .route("/rates/{id}/reject", web::post().to(rates::reject_rate))
+        .route("/rates/{id}", web::put().to(rates::put_rate))
+        .route("/rates/{id}", web::patch().to(rates::patch_rate))
+        .route("/rates/{id}", web::delete().to(rates::delete_rate))
+        .route("/providers/mappings", web::get().to(uploads::get_provider_mappings))
+        .route("/providers/{provider}/mappings/diff", web::get().to(uploads::get_provider_mapping_diff))
+        .route("/admin/reload-config", web::post().to(admin::reload_config_endpoint))
+        .route("/export/unmatched-rates.xlsx", web::get().to(exports::export_unmatched_rates))
+        .route("/export/xlsx", web::get().to(exports::export_xlsx))
+        .route("/export/ndjson", web::get().to(exports::export_ndjson))
+        .route("/export/manifest", web::get().to(exports::export_manifest))
+        .route("/export/artifacts/{file_name}", web::get().to(exports::download_artifact))
+        .route("/import/rate-matches", web::post().to(rates::import_rate_matches))
+        .route("/categories", web::get().to(categories::list_categories))
+        .route("/companies", web::get().to(categories::list_companies))
+        .route("/arns", web::get().to(categories::list_arns))
+        .route("/categories/mappings", web::get().to(categories::get_category_mappings))
+        .route("/categories/mappings", web::put().to(categories::put_category_mappings))
+        .route("/admin/categories/vocabulary", web::get().to(categories::get_category_vocabulary))
+        .route("/admin/categories/vocabulary", web::post().to(categories::extend_category_vocabulary))
+        .route("/admin/categories/reassign", web::post().to(categories::reassign_category))
+        .route("/admin/categories/preferences", web::get().to(categories::get_category_preferences))
+        .route("/admin/categories/preferences", web::put().to(categories::put_category_preference))
+        .route("/admin/categories/preferences/{category}", web::delete().to(categories::delete_category_preference))
+        .route("/aliases", web::get().to(admin::list_name_aliases))
+        .route("/aliases", web::post().to(admin::create_name_alias))
+        .route("/aliases/{id}", web::delete().to(admin::delete_name_alias))
+        .route("/audit", web::get().to(admin::list_audit_log));
+}
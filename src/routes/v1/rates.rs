@@ -0,0 +1,800 @@
+use actix_multipart::Multipart;
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures_util::TryStreamExt;
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::Write;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api_error::{ApiError, ErrorCode};
+use crate::edits::{EditOutcome, RateEdit};
+use crate::{
+    apply_rate_matches, audit, auth, edit_outcome_response, edits, expected_version,
+    get_postgres_client, invalid_rate, require_confirmation, routes, AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/rates/{id}",
+    tag = "rates",
+    params(("id" = i32, Path, description = "Rate id")),
+    responses(
+        (status = 200, description = "The rate; ETag is its version"),
+        (status = 404, description = "No such rate", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn get_rate(path: web::Path<i32>, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        Ok::<_, Box<dyn std::error::Error>>(edits::load_rate(&client, id).await?)
+    }
+    .await;
+
+    match result {
+        Ok(Some(rate)) => Ok(HttpResponse::Ok()
+            .insert_header((header::ETAG, edits::etag(rate.version)))
+            .json(json!({"status": "success", "rate": rate}))),
+        Ok(None) => edit_outcome_response("rate", id, EditOutcome::<edits::RateRecord>::NotFound, None, |r| r.version),
+        Err(e) => Err(ApiError::database(format!("Failed to load rate: {}", e), e.as_ref())),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/rates/{id}",
+    tag = "rates",
+    security(("api_key" = []), ("bearer" = [])),
+    params(("id" = i32, Path, description = "Rate id"), ("If-Match" = Option<String>, Header, description = "Version the edit is based on; or send version in the body")),
+    request_body(content = crate::edits::RateEdit),
+    responses(
+        (status = 200, description = "Updated rate; ETag is its new version"),
+        (status = 400, description = "Invalid rate", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the uploader role", body = openapi::ErrorResponse),
+        (status = 409, description = "Changed by another write; carries the current rate", body = openapi::ErrorResponse),
+        (status = 404, description = "No such rate", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn put_rate(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    body: web::Json<RateEdit>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Uploader)?;
+    let id = path.into_inner();
+    let expected = expected_version(&req, body.version)?;
+
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        let mutation = state.begin_mutation().await;
+        let outcome = edits::update_rate(&client, id, expected, &body).await?;
+        Ok::<_, Box<dyn std::error::Error>>((mutation, outcome))
+    }
+    .await;
+
+    match result {
+        Ok((mutation, outcome)) => {
+            let audit_id = if let EditOutcome::Updated(rate) = &outcome {
+                info!("Rate {} edited by {} (was version {})", id, user, expected);
+                if let Err(e) = mutation.refresh().await {
+                    warn!("Failed to refresh virtual table after rate edit: {}", e);
+                }
+                let summary = json!({"previous_version": expected, "rate": rate});
+                audit::record(&state, &user, "rate.update", "rate", Some(id.to_string()), summary).await
+            } else {
+                None
+            };
+            edit_outcome_response("rate", id, outcome, audit_id, |r| r.version)
+        }
+        Err(e) => Err(ApiError::database(format!("Failed to update rate: {}", e), e.as_ref())),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/rates",
+    tag = "rates",
+    security(("api_key" = []), ("bearer" = [])),
+    request_body(content = crate::edits::NewRate),
+    responses(
+        (status = 201, description = "Rate created; Location points at it", body = crate::edits::RateRecord),
+        (status = 400, description = "Invalid fields, listed in errors", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the uploader role", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn create_rate(
+    body: web::Json<edits::NewRate>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Uploader)?;
+    let rate = match body.validate() {
+        Ok(rate) => rate,
+        Err(errors) => return Err(invalid_rate(errors)),
+    };
+
+    let pending_approval = state.runtime_config.load().require_rate_approval;
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        let mutation = state.begin_mutation().await;
+        let created = edits::insert_rate(&client, &rate, !pending_approval).await?;
+        Ok::<_, Box<dyn std::error::Error>>((mutation, created))
+    }
+    .await;
+
+    match result {
+        Ok((mutation, created)) => {
+            info!("Rate {} created for '{}' by {}", created.id, created.scheme_name, user);
+            if !pending_approval {
+                if let Err(e) = mutation.refresh_for_rates(&[created.scheme_name.as_str()]).await {
+                    warn!("Failed to refresh virtual table after creating a rate: {}", e);
+                }
+            }
+            let audit_id = audit::record(
+                &state,
+                &user,
+                "rate.create",
+                "rate",
+                Some(created.id.to_string()),
+                json!({"rate": created, "pending_approval": pending_approval}),
+            )
+            .await;
+            Ok(HttpResponse::Created()
+                .insert_header((header::LOCATION, format!("{}/rates/{}", routes::V1_PREFIX, created.id)))
+                .insert_header((header::ETAG, edits::etag(created.version)))
+                .json(json!({"status": "success", "rate": created, "audit_id": audit_id})))
+        }
+        Err(e) => Err(ApiError::database(format!("Failed to create rate: {}", e), e.as_ref())),
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/rates/{id}",
+    tag = "rates",
+    security(("api_key" = []), ("bearer" = [])),
+    params(("id" = i32, Path, description = "Rate id"), ("If-Match" = Option<String>, Header, description = "Version the edit is based on; or send version in the body")),
+    request_body(content = crate::edits::RatePatch),
+    responses(
+        (status = 200, description = "Updated rate; ETag is its new version"),
+        (status = 400, description = "Invalid fields, listed in errors", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the uploader role", body = openapi::ErrorResponse),
+        (status = 409, description = "Changed by another write; carries the current rate", body = openapi::ErrorResponse),
+        (status = 404, description = "No such rate", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn patch_rate(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    body: web::Json<edits::RatePatch>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Uploader)?;
+    let id = path.into_inner();
+    let mut patch = body.into_inner();
+    patch.trim();
+    let expected = expected_version(&req, patch.version)?;
+
+    // Dates are validated against the row the patch applies to, so a stale version is reported
+    // as a conflict before anything is checked against it
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        let mutation = state.begin_mutation().await;
+        let current = match edits::load_rate(&client, id).await? {
+            Some(current) => current,
+            None => return Ok::<_, Box<dyn std::error::Error>>(Ok((mutation, EditOutcome::NotFound, None))),
+        };
+        if current.version != expected {
+            return Ok(Ok((mutation, EditOutcome::Conflict(current), None)));
+        }
+        if let Err(errors) = patch.validate(&current) {
+            return Ok(Err(errors));
+        }
+        let outcome = edits::patch_rate(&client, id, expected, &patch).await?;
+        Ok(Ok((mutation, outcome, Some(current.scheme_name))))
+    }
+    .await;
+
+    match result {
+        Ok(Ok((mutation, outcome, previous_name))) => {
+            let audit_id = if let EditOutcome::Updated(rate) = &outcome {
+                info!("Rate {} patched by {} (was version {})", id, user, expected);
+                let mut names = vec![rate.scheme_name.as_str()];
+                if let Some(previous_name) = previous_name.as_deref().filter(|name| *name != rate.scheme_name) {
+                    names.push(previous_name);
+                }
+                if let Err(e) = mutation.refresh_for_rates(&names).await {
+                    warn!("Failed to refresh virtual table after rate patch: {}", e);
+                }
+                let summary = json!({"previous_version": expected, "rate": rate});
+                audit::record(&state, &user, "rate.patch", "rate", Some(id.to_string()), summary).await
+            } else {
+                None
+            };
+            edit_outcome_response("rate", id, outcome, audit_id, |r| r.version)
+        }
+        Ok(Err(errors)) => Err(invalid_rate(errors)),
+        Err(e) => Err(ApiError::database(format!("Failed to patch rate: {}", e), e.as_ref())),
+    }
+}
+
+const DEFAULT_PENDING_RATES_LIMIT: i64 = 50;
+const MAX_PENDING_RATES_LIMIT: i64 = 500;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PendingRatesQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+// Rates waiting for review, see require_rate_approval
+#[utoipa::path(
+    get,
+    path = "/rates/pending",
+    tag = "rates",
+    params(PendingRatesQuery),
+    responses(
+        (status = 200, description = "Rates waiting for approval"),
+        (status = 400, description = "Bad offset", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn list_pending_rates(query: web::Query<PendingRatesQuery>, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_PENDING_RATES_LIMIT).clamp(1, MAX_PENDING_RATES_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+    if offset < 0 {
+        return Err(ApiError::invalid_parameter("Parameter 'offset' must not be negative"));
+    }
+
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        Ok::<_, Box<dyn std::error::Error>>(edits::list_pending_rates(&client, limit, offset).await?)
+    }
+    .await;
+
+    match result {
+        Ok((rates, total)) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "total": total,
+            "limit": limit,
+            "offset": offset,
+            "rates": rates
+        }))),
+        Err(e) => Err(ApiError::database(format!("Failed to list pending rates: {}", e), e.as_ref())),
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RateRejection {
+    reason: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/rates/{id}/approve",
+    tag = "rates",
+    security(("api_key" = []), ("bearer" = [])),
+    params(("id" = i32, Path, description = "Rate id")),
+    responses(
+        (status = 200, description = "Rate approved and joined into the served table"),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 404, description = "No such rate", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn approve_rate(
+    path: web::Path<i32>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+    review_rate(path.into_inner(), None, &state, &user).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/rates/{id}/reject",
+    tag = "rates",
+    security(("api_key" = []), ("bearer" = [])),
+    params(("id" = i32, Path, description = "Rate id")),
+    request_body(content = RateRejection),
+    responses(
+        (status = 200, description = "Rate rejected with the reason"),
+        (status = 400, description = "Empty reason", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 404, description = "No such rate", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn reject_rate(
+    path: web::Path<i32>,
+    body: web::Json<RateRejection>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+    let reason = body.reason.trim();
+    if reason.is_empty() {
+        return Err(invalid_rate(vec![edits::FieldError {
+            field: "reason",
+            message: "is required".to_string(),
+        }]));
+    }
+    review_rate(path.into_inner(), Some(reason), &state, &user).await
+}
+
+// Approval and rejection both change what the join sees, so the rate's funds are re-read right away
+async fn review_rate(
+    id: i32,
+    rejection_reason: Option<&str>,
+    state: &AppState,
+    user: &auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        let mutation = state.begin_mutation().await;
+        let reviewed = edits::review_rate(&client, id, rejection_reason).await?;
+        Ok::<_, Box<dyn std::error::Error>>(reviewed.map(|rate| (mutation, rate)))
+    }
+    .await;
+
+    let verb = if rejection_reason.is_some() { "rejected" } else { "approved" };
+    match result {
+        Ok(Some((mutation, rate))) => {
+            info!("Rate {} for '{}' {} by {}", id, rate.scheme_name, verb, user);
+            if let Err(e) = mutation.refresh_for_rates(&[rate.scheme_name.as_str()]).await {
+                warn!("Failed to refresh virtual table after a rate was {}: {}", verb, e);
+            }
+            let (action, summary) = match rejection_reason {
+                Some(reason) => ("rate.reject", json!({"scheme_name": rate.scheme_name, "reason": reason})),
+                None => ("rate.approve", json!({"scheme_name": rate.scheme_name})),
+            };
+            let audit_id = audit::record(state, user, action, "rate", Some(id.to_string()), summary).await;
+            Ok(HttpResponse::Ok()
+                .insert_header((header::ETAG, edits::etag(rate.version)))
+                .json(json!({
+                    "status": "success",
+                    "message": format!("Rate {} {}", id, verb),
+                    "rate": rate,
+                    "audit_id": audit_id
+                })))
+        }
+        Ok(None) => Err(ApiError::not_found(format!("No rate {}", id))),
+        Err(e) => Err(ApiError::database(format!("Failed to review rate: {}", e), e.as_ref())),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/rates",
+    tag = "rates",
+    security(("api_key" = []), ("bearer" = [])),
+    params(("source_file" = String, Query, description = "Rates uploaded from this file"), ("confirm" = bool, Query, description = "Must be true")),
+    responses(
+        (status = 200, description = "Rates deleted"),
+        (status = 400, description = "source_file or confirm=true missing", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn delete_rates_by_source(
+    query: web::Query<HashMap<String, String>>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+    let source_file = match query.get("source_file").map(|value| value.trim()).filter(|value| !value.is_empty()) {
+        Some(source_file) => source_file.to_string(),
+        None => {
+            return Err(ApiError::missing_parameter("source_file"))
+        }
+    };
+    require_confirmation(&query, "every rate ingested from that file")?;
+
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        let mutation = state.begin_mutation().await;
+        let deleted = edits::delete_rates_by_source(&client, &source_file).await?;
+        Ok::<_, Box<dyn std::error::Error>>((mutation, deleted))
+    }
+    .await;
+
+    match result {
+        Ok((mutation, deleted)) => {
+            info!("Deleted {} rates ingested from '{}' for {}", deleted, source_file, user);
+            if deleted > 0 {
+                if let Err(e) = mutation.refresh().await {
+                    warn!("Failed to refresh virtual table after deleting rates: {}", e);
+                }
+            }
+            let audit_id = audit::record(
+                &state,
+                &user,
+                "rates.delete_by_source",
+                "source_file",
+                Some(source_file.clone()),
+                json!({"deleted": deleted}),
+            )
+            .await;
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "success",
+                "message": format!("Deleted {} rates from '{}'", deleted, source_file),
+                "deleted": deleted,
+                "audit_id": audit_id
+            })))
+        }
+        Err(e) => Err(ApiError::database(format!("Failed to delete rates: {}", e), e.as_ref())),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/rates/{id}",
+    tag = "rates",
+    security(("api_key" = []), ("bearer" = [])),
+    params(("id" = i32, Path, description = "Rate id")),
+    responses(
+        (status = 200, description = "Rate deleted"),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 404, description = "No such rate", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn delete_rate(
+    path: web::Path<i32>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+    let id = path.into_inner();
+    let result = async {
+        let client = get_postgres_client(state.pools.primary()).await?;
+        let mutation = state.begin_mutation().await;
+        let deleted = edits::delete_rate(&client, id).await?;
+        Ok::<_, Box<dyn std::error::Error>>(deleted.map(|rate| (mutation, rate)))
+    }
+    .await;
+
+    match result {
+        Ok(Some((mutation, rate))) => {
+            info!("Rate {} for '{}' deleted by {}", id, rate.scheme_name, user);
+            if let Err(e) = mutation.refresh_for_rates(&[rate.scheme_name.as_str()]).await {
+                warn!("Failed to refresh virtual table after deleting a rate: {}", e);
+            }
+            let audit_id = audit::record(&state, &user, "rate.delete", "rate", Some(id.to_string()), json!({"rate": rate})).await;
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "success",
+                "message": format!("Rate {} deleted", id),
+                "rate": rate,
+                "audit_id": audit_id
+            })))
+        }
+        Ok(None) => Err(ApiError::not_found(format!("No rate {}", id))),
+        Err(e) => Err(ApiError::database(format!("Failed to delete rate: {}", e), e.as_ref())),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/import/rate-matches",
+    tag = "rates",
+    security(("api_key" = []), ("bearer" = [])),
+    request_body(content = openapi::RateFileForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Confirmed names applied; partial when some rows failed"),
+        (status = 400, description = "Unreadable workbook", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the uploader role", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn import_rate_matches(
+    mut payload: Multipart,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Uploader)?;
+    let mut temp_file = tempfile::Builder::new().suffix(".xlsx").tempfile().map_err(|e| {
+        ApiError::internal(format!("Failed to create temp file: {}", e))
+    })?;
+
+    while let Some(mut field) = payload.try_next().await? {
+        while let Some(chunk) = field.try_next().await? {
+            temp_file.write_all(&chunk).map_err(|e| {
+                ApiError::internal(format!("Failed to write chunk: {}", e))
+            })?;
+        }
+    }
+
+    let mutation = state.begin_mutation().await;
+    match apply_rate_matches(temp_file.path(), state.pools.primary()).await {
+        Ok((applied, errors)) => {
+            info!("Rate match import by {}: {} applied, {} rejected", user, applied, errors.len());
+            if applied > 0 {
+                if let Err(e) = mutation.refresh().await {
+                    warn!("Failed to refresh virtual table after rate match import: {}", e);
+                }
+            }
+
+            let audit_id = audit::record(
+                &state,
+                &user,
+                "rate_matches.import",
+                "scheme_rates",
+                None,
+                json!({"applied": applied, "errors": errors.len()}),
+            )
+            .await;
+            let status = if errors.is_empty() { "success" } else { "partial" };
+            Ok(HttpResponse::Ok().json(json!({
+                "status": status,
+                "applied": applied,
+                "error_count": errors.len(),
+                "errors": errors,
+                "audit_id": audit_id
+            })))
+        }
+        Err(e) => Err(ApiError::new(ErrorCode::InvalidBody, format!("Error processing file: {}", e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use calamine::{Reader, Xlsx};
+    use serde_json::json;
+    use std::io::Cursor;
+
+    use crate::config::RuntimeConfig;
+    use crate::{refresh_virtual_table, test_support};
+
+    // The downloaded worksheet as text cells, header row first
+    fn read_worksheet(bytes: &[u8]) -> Vec<Vec<String>> {
+        let mut workbook = Xlsx::new(Cursor::new(bytes.to_vec())).unwrap();
+        let range = workbook.worksheet_range("Unmatched Rates").unwrap();
+        range.rows().map(|row| row.iter().map(|cell| cell.to_string()).collect()).collect()
+    }
+
+    // The rows written back as an ops user would after filling in the last column
+    fn write_worksheet(rows: &[Vec<String>]) -> Vec<u8> {
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let worksheet = workbook.add_worksheet();
+        for (r, row) in rows.iter().enumerate() {
+            for (c, cell) in row.iter().enumerate() {
+                worksheet.write_string(r as u32, c as u16, cell).unwrap();
+            }
+        }
+        workbook.save_to_buffer().unwrap()
+    }
+
+    #[actix_web::test]
+    async fn unmatched_rates_worksheet_round_trips_and_reports_unknown_names() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(
+            "INSERT INTO funds (category, scheme_name) VALUES
+                 ('Flexi Cap', 'Parag Parikh Flexi Cap Fund'), ('Small Cap', 'Quant Small Cap Fund');
+             INSERT INTO scheme_rates (arn, company, scheme_name, scheme_category, brokerage_type, start_date, end_date, source_file)
+             VALUES ('ARN-1', 'PPFAS', 'PPFAS Flexi Cap Regular', 'Equity', 'Trail', '2024-01-01', '2099-12-31', 'rates.xlsx'),
+                    ('ARN-1', 'Quant', 'Quant Smallcap Regular', 'Equity', 'Trail', '2024-01-01', '2099-12-31', 'rates.xlsx');",
+        )
+        .await;
+        refresh_virtual_table(&db.state).await.unwrap();
+        assert_eq!(db.state.virtual_table.load().unmatched_rates.len(), 2);
+
+        let export = test_support::call(&db.state, TestRequest::get().uri("/api/v1/export/unmatched-rates.xlsx")).await;
+        assert_eq!(export.status(), 200);
+        let mut rows = read_worksheet(&actix_web::test::read_body(export).await);
+        assert_eq!(rows.len(), 3);
+        let confirmed_col = rows[0].iter().position(|header| header == "Confirmed Fund Name").unwrap();
+        let scheme_col = rows[0].iter().position(|header| header == "Scheme Name").unwrap();
+        let suggestion_col = rows[0].iter().position(|header| header == "Suggestion 1").unwrap();
+        let mut invalid_row = 0;
+        for (r, row) in rows.iter_mut().enumerate().skip(1) {
+            row.resize(confirmed_col + 1, String::new());
+            if row[scheme_col] == "PPFAS Flexi Cap Regular" {
+                assert_eq!(row[suggestion_col], "Parag Parikh Flexi Cap Fund");
+                row[confirmed_col] = "PARAG PARIKH Flexi Cap Fund ".to_string();
+            } else {
+                row[confirmed_col] = "Quant Tiny Cap Fund".to_string();
+                invalid_row = r + 1;
+            }
+        }
+
+        let workbook = write_worksheet(&rows);
+        let import = test_support::multipart("/api/v1/import/rate-matches", &[("file", Some("matches.xlsx"), &workbook)]);
+        let (status, body) = test_support::call_json(&db.state, test_support::as_admin(import)).await;
+
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["status"], "partial");
+        assert_eq!(body["applied"], 1);
+        assert_eq!(body["error_count"], 1);
+        assert_eq!(body["errors"][0]["row"], invalid_row);
+        assert_eq!(body["errors"][0]["value"], "Quant Tiny Cap Fund");
+        assert_eq!(body["errors"][0]["reason"], "Confirmed fund name does not match any existing fund");
+
+        let aliases = db.query("SELECT alias_name FROM scheme_aliases").await;
+        let aliases: Vec<String> = aliases.iter().map(|row| row.get(0)).collect();
+        assert_eq!(aliases, vec!["PPFAS Flexi Cap Regular"]);
+        let table = db.state.virtual_table.load();
+        assert_eq!(table.unmatched_rates.len(), 1);
+        assert_eq!(table.unmatched_rates[0].scheme_name, "Quant Smallcap Regular");
+    }
+
+    #[actix_web::test]
+    async fn new_rates_wait_for_approval_and_join_the_table_once_approved() {
+        let config = RuntimeConfig { require_rate_approval: true, ..test_support::runtime_config() };
+        let db = match test_support::database(config).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute("INSERT INTO funds (category, scheme_name, latest_nav) VALUES ('Flexi Cap', 'Quant Flexi Cap Fund', 90)")
+            .await;
+        refresh_virtual_table(&db.state).await.unwrap();
+        let served_rates = || -> Vec<Option<i32>> {
+            db.state.virtual_table.load().get_by_fund_id(1).iter().map(|record| record.rate_id).collect()
+        };
+
+        let mut ids = Vec::new();
+        for brokerage_type in ["Trail", "Upfront"] {
+            let rate = json!({
+                "arn": "ARN-1", "company": "Quant", "scheme_name": "Quant Flexi Cap Fund", "scheme_category": "Equity",
+                "brokerage_type": brokerage_type, "start_date": "2025-04-01", "end_date": "2099-03-31", "base_year_1": 0.8
+            });
+            let create = test_support::as_admin(TestRequest::post().uri("/api/v1/rates").set_json(rate));
+            let (status, body) = test_support::call_json(&db.state, create).await;
+            assert_eq!(status, 201, "{}", body);
+            assert_eq!(body["rate"]["is_approved"], false);
+            ids.push(body["rate"]["id"].as_i64().unwrap());
+        }
+        assert_eq!(served_rates(), vec![None]);
+        let pending = || TestRequest::get().uri("/api/v1/rates/pending?limit=1");
+        let (_, body) = test_support::call_json(&db.state, pending()).await;
+        assert_eq!((body["total"].as_i64(), body["rates"].as_array().unwrap().len()), (Some(2), 1));
+
+        // Approval joins the rate into the served table straight away, without a refresh
+        let approve = test_support::as_admin(TestRequest::post().uri(&format!("/api/v1/rates/{}/approve", ids[0])));
+        let (status, body) = test_support::call_json(&db.state, approve).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(served_rates(), vec![Some(ids[0] as i32)]);
+
+        // Rejection keeps the row with its reason, out of the table and off the pending list
+        let reject = |reason: &str| {
+            let uri = format!("/api/v1/rates/{}/reject", ids[1]);
+            test_support::as_admin(TestRequest::post().uri(&uri).set_json(json!({"reason": reason})))
+        };
+        let (status, _) = test_support::call_json(&db.state, reject("  ")).await;
+        assert_eq!(status, 400);
+        let (status, body) = test_support::call_json(&db.state, reject("Superseded by the April sheet")).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["rate"]["rejection_reason"], "Superseded by the April sheet");
+        assert_eq!(served_rates(), vec![Some(ids[0] as i32)]);
+        let (_, body) = test_support::call_json(&db.state, pending()).await;
+        assert_eq!(body["total"], 0);
+
+        let missing = test_support::as_admin(TestRequest::post().uri("/api/v1/rates/999/approve"));
+        assert_eq!(test_support::call_json(&db.state, missing).await.0, 404);
+    }
+
+    #[actix_web::test]
+    async fn rates_from_one_source_file_are_deleted_only_when_confirmed() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute(
+            "INSERT INTO scheme_rates (arn, company, scheme_name, scheme_category, brokerage_type, start_date, end_date, source_file)
+             VALUES ('ARN-1', 'Quant', 'Quant Flexi Cap Fund', 'Equity', 'Trail', '2025-04-01', '2099-03-31', 'april.xlsx'),
+                    ('ARN-1', 'Quant', 'Quant Small Cap Fund', 'Equity', 'Trail', '2025-04-01', '2099-03-31', 'april.xlsx'),
+                    ('ARN-1', 'PPFAS', 'Parag Parikh Flexi Cap Fund', 'Equity', 'Trail', '2025-04-01', '2099-03-31', 'may.xlsx');",
+        )
+        .await;
+        let delete = |query: &str| test_support::as_admin(TestRequest::delete().uri(&format!("/api/v1/rates?{}", query)));
+
+        for (query, parameter) in [("confirm=true", "source_file"), ("source_file=april.xlsx", "confirm")] {
+            let (status, body) = test_support::call_json(&db.state, delete(query)).await;
+            assert_eq!(status, 400, "{}", query);
+            assert_eq!(body["details"]["parameter"], parameter, "{}", body);
+        }
+        let (status, body) = test_support::call_json(&db.state, delete("source_file=april.xlsx&confirm=true")).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["deleted"], 2);
+        let files: Vec<String> = db.query("SELECT source_file FROM scheme_rates").await.iter().map(|row| row.get(0)).collect();
+        assert_eq!(files, ["may.xlsx"]);
+
+        let (_, body) = test_support::call_json(&db.state, delete("source_file=april.xlsx&confirm=true")).await;
+        assert_eq!(body["deleted"], 0);
+    }
+
+    // The fields a rate body was rejected for, in the order they were reported
+    fn invalid_fields(body: &serde_json::Value) -> Vec<&str> {
+        body["details"]["errors"].as_array().unwrap().iter().map(|error| error["field"].as_str().unwrap()).collect()
+    }
+
+    #[actix_web::test]
+    async fn rate_writes_reject_bad_fields_and_unknown_ids() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let create =
+            |rate: serde_json::Value| test_support::as_admin(TestRequest::post().uri("/api/v1/rates").set_json(rate));
+        let rate = json!({
+            "arn": "ARN-1", "company": "Quant", "scheme_name": "Quant Flexi Cap Fund", "scheme_category": "Equity",
+            "brokerage_type": "Trail", "start_date": "2025-04-01", "end_date": "2025-04-01"
+        });
+
+        let partial = json!({"company": " ", "end_date": "2025-04-01"});
+        let (status, body) = test_support::call_json(&db.state, create(partial)).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("invalid_body")), "{}", body);
+        let missing = ["arn", "company", "scheme_name", "scheme_category", "brokerage_type", "start_date"];
+        assert_eq!(invalid_fields(&body), missing);
+        let mut backwards = rate.clone();
+        backwards["start_date"] = json!("2025-04-02");
+        let (status, body) = test_support::call_json(&db.state, create(backwards)).await;
+        assert_eq!((status, invalid_fields(&body)), (StatusCode::BAD_REQUEST, vec!["end_date"]), "{}", body);
+        // Wrong types and unknown fields fail before validation
+        for (field, value) in [("start_date", json!("01/04/2025")), ("base_year_1", json!("0.8")), ("trail", json!(1))] {
+            let mut bad = rate.clone();
+            bad[field] = value;
+            let (status, body) = test_support::call_json(&db.state, create(bad)).await;
+            assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("invalid_body")), "{}", field);
+        }
+        assert_eq!(db.query("SELECT id FROM scheme_rates").await.len(), 0);
+
+        // A rate may start and end on the same day
+        let (status, body) = test_support::call_json(&db.state, create(rate)).await;
+        assert_eq!(status, 201, "{}", body);
+        let id = body["rate"]["id"].as_i64().unwrap();
+        let patch = |id: i64, patch: serde_json::Value| {
+            let uri = format!("/api/v1/rates/{}", id);
+            test_support::as_admin(TestRequest::patch().uri(&uri).insert_header(("If-Match", "\"1\"")).set_json(patch))
+        };
+
+        // Dates are checked against the stored ones they're paired with
+        for (body, fields) in [
+            (json!({"start_date": "2025-04-02"}), vec!["end_date"]),
+            (json!({"end_date": "2025-03-31"}), vec!["end_date"]),
+            (json!({"arn": "", "company": "  "}), vec!["arn", "company"]),
+            (json!({}), vec!["body"]),
+        ] {
+            let (status, response) = test_support::call_json(&db.state, patch(id, body.clone())).await;
+            assert_eq!((status, invalid_fields(&response)), (StatusCode::BAD_REQUEST, fields), "{}", body);
+        }
+        for body in [json!({"arn": null}), json!({"end_date": 20250401})] {
+            let (status, response) = test_support::call_json(&db.state, patch(id, body.clone())).await;
+            let code = response["code"].as_str();
+            assert_eq!((status, code), (StatusCode::BAD_REQUEST, Some("invalid_body")), "{}", body);
+        }
+        let (status, body) = test_support::call_json(&db.state, patch(id, json!({"end_date": "2026-03-31"}))).await;
+        assert_eq!((status, &body["rate"]["end_date"]), (StatusCode::OK, &json!("2026-03-31")), "{}", body);
+
+        let (status, body) = test_support::call_json(&db.state, patch(id + 1, json!({"arn": "ARN-2"}))).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("not_found")), "{}", body);
+        let delete = |id: i64| test_support::as_admin(TestRequest::delete().uri(&format!("/api/v1/rates/{}", id)));
+        let (status, body) = test_support::call_json(&db.state, delete(id + 1)).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("not_found")), "{}", body);
+        assert_eq!(test_support::call_json(&db.state, delete(id)).await.0, 200);
+        assert_eq!(test_support::call_json(&db.state, delete(id)).await.0, 404);
+    }
+}
@@ -0,0 +1,895 @@
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+use crate::api_error::ApiError;
+use crate::filters::{FilterEvaluator, SearchFilters};
+use crate::preferences::DisplayRequest;
+use crate::representation::{Format, SearchResult, Shape};
+use crate::table::SearchLimits;
+use crate::{
+    categories, config, csv_export, get_postgres_client, history, normalize_scheme_name, openapi,
+    parse_page_param, preferences, representation, AppState, DID_YOU_MEAN_COUNT,
+};
+
+// Longer queries are no scheme name anyone types, and only cost CPU to normalize and match
+const MAX_QUERY_CHARS: usize = 200;
+// Tokens past this are dropped from the query, which reports the results truncated
+const MAX_QUERY_TOKENS: usize = 12;
+
+#[utoipa::path(
+    get,
+    path = "/search",
+    tag = "search",
+    params(openapi::SearchParams),
+    responses(
+        (status = 200, description = "Matches, best first unless sorted; text/csv when negotiated", body = openapi::SearchResponse, content_type = ["application/json", "text/csv"]),
+        (status = 400, description = "Invalid parameter", body = openapi::ErrorResponse),
+        (status = 422, description = "Unknown category or company, with the closest known values", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn search_schemes(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    // The map keeps one value per key; repeatable parameters are read from the pairs
+    let pairs = match web::Query::<Vec<(String, String)>>::from_query(req.query_string()) {
+        Ok(pairs) => pairs.into_inner(),
+        Err(e) => return Err(ApiError::invalid_parameter(format!("Invalid query string: {}", e))),
+    };
+    let mut filters = match SearchFilters::from_query(&query, &pairs) {
+        Ok(filters) => filters,
+        Err(message) => return Err(ApiError::invalid_parameter(message)),
+    };
+
+    // Without a query the filters screen the whole table, or with no filters either the whole
+    // table is listed page by page in name order
+    let search_term = query.get("q").map(String::as_str).unwrap_or("");
+    let query_chars = search_term.chars().count();
+    if query_chars > MAX_QUERY_CHARS {
+        return Err(ApiError::invalid_parameter(format!(
+            "Parameter 'q' must be at most {} characters, got {}",
+            MAX_QUERY_CHARS, query_chars
+        )));
+    }
+
+    let display_request = match DisplayRequest::from_query(&query) {
+        Ok(display_request) => display_request,
+        Err(message) => return Err(ApiError::invalid_parameter(message)),
+    };
+
+    // CSV downloads every column of the same matches: /search.csv, format=csv or Accept: text/csv
+    let export = match Format::negotiate(&req, &query) {
+        Ok(format) => format == Format::Csv,
+        Err(message) => return Err(ApiError::invalid_parameter(message)),
+    };
+    // Reconstructed values are an export for audits; JSON results stay live
+    let as_of = match history::AsOf::from_query(&query) {
+        Ok(Some(_)) if !export => {
+            return Err(ApiError::invalid_parameter(format!(
+                "'{}' and '{}' are only supported for CSV export",
+                history::AS_OF_UPLOAD_ID,
+                history::AS_OF_DATE
+            )))
+        }
+        Ok(as_of) => as_of,
+        Err(message) => return Err(ApiError::invalid_parameter(message)),
+    };
+    // Nested results page by fund rather than by fund/rate record
+    let shape = match Shape::from_query(&query) {
+        Ok(shape) => shape,
+        Err(message) => return Err(ApiError::invalid_parameter(message)),
+    };
+
+    let config = state.runtime_config.load();
+    // Exports default to, and are capped at, max_export_rows rather than a page
+    let (default_limit, max_limit) = if export {
+        (config.max_export_rows, config.max_export_rows)
+    } else {
+        (config.search_limit, config::MAX_SEARCH_LIMIT)
+    };
+    let page = parse_page_param(&query, "limit", default_limit, max_limit)
+        .and_then(|limit| Ok((limit, parse_page_param(&query, "offset", 0, MAX_SEARCH_OFFSET)?)));
+    let (limit, offset) = match page {
+        Ok(page) => page,
+        Err(message) => return Err(ApiError::invalid_parameter(message)),
+    };
+    // What the search tried (per-tier counts, candidates, timing), only when asked for
+    let debug = match query.get("debug").map(|value| value.parse::<bool>()) {
+        None => false,
+        Some(Ok(debug)) => debug,
+        Some(Err(_)) => {
+            return Err(ApiError::invalid_parameter("Parameter 'debug' must be true or false"))
+        }
+    };
+    let normalized = normalize_scheme_name(search_term);
+    // Only punctuation would otherwise list the whole table as if q were left out
+    if normalized.is_empty() && !search_term.trim().is_empty() {
+        return Err(ApiError::invalid_parameter(format!(
+            "Parameter 'q' ('{}') has no letters or digits to search for; leave it out to list every scheme",
+            search_term.trim()
+        )));
+    }
+    let mut expanded = config.expand_aliases(&normalized);
+    let tokens_dropped = expanded.split(' ').count() > MAX_QUERY_TOKENS;
+    if tokens_dropped {
+        expanded = expanded.split(' ').take(MAX_QUERY_TOKENS).collect::<Vec<_>>().join(" ");
+    }
+    let browse = expanded.is_empty() && filters.is_empty();
+    // One table for the whole request: a refresh swapping in a new one neither blocks this search
+    // nor changes the table under it
+    let virtual_table = state.virtual_table.load_full();
+
+    // Resolve category/company filters to their canonical spelling; an unknown value is a 422
+    // with the closest known values rather than an empty result
+    {
+        let text_filters = filters
+            .category
+            .iter_mut()
+            .map(|value| ("category", value, virtual_table.category_counts()))
+            .chain(filters.company.iter_mut().map(|value| ("company", value, virtual_table.company_counts())));
+        for (name, value, known) in text_filters {
+            match categories::resolve_known(value, known.keys(), &config) {
+                Ok(canonical) => *value = canonical,
+                Err(closest) => return Err(ApiError::unknown_value(name, value, closest)),
+            }
+        }
+    }
+    // Two spellings of one category resolve to the same canonical name
+    filters.category.sort();
+    filters.category.dedup();
+
+    // Category defaults only apply when the caller filtered by exactly that one category
+    let category_preferences = state.category_preferences.load();
+    let preference = match filters.category.as_slice() {
+        [category] => category_preferences.get(&normalize_scheme_name(category)),
+        _ => None,
+    };
+    let display = preferences::resolve(&display_request, preference);
+
+    // The whole match set is sorted before slicing out the page, so pages don't overlap or skip
+    // and total_matches counts everything. Whether fuzzy matches join it depends on the query and
+    // the default page size, never on the page asked for.
+    let mut evaluator = FilterEvaluator::new(&filters);
+    let (total_matches, results, search_budget_exhausted, truncated, diagnostics) = {
+        let limits = SearchLimits {
+            wanted: config.search_limit,
+            threshold: config.fuzzy_search_threshold,
+            budget: Some(config.fuzzy_budget()),
+            max_candidates: config.max_search_candidates,
+        };
+        let outcome = virtual_table.search(&expanded, &mut evaluator, &limits);
+        let mut hits = outcome.hits;
+        display.sort(&mut hits, |hit| hit.record);
+        if shape == Shape::Nested {
+            // One result per fund, at its best-placed hit; rate-only records stand alone
+            let mut seen_funds = std::collections::HashSet::new();
+            hits.retain(|hit| hit.record.fund_id.is_none_or(|fund_id| seen_funds.insert(fund_id)));
+        }
+
+        // Nested: every rate of each fund that passes the same filters, not just the one that matched
+        let mut rate_filter = FilterEvaluator::new(&filters);
+        let results: Vec<SearchResult> = hits
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(|hit| SearchResult {
+                record: hit.record.clone(),
+                match_type: hit.kind,
+                score: hit.score,
+                rates: (shape == Shape::Nested).then(|| match hit.record.fund_id {
+                    Some(fund_id) => virtual_table
+                        .fund_records_matching(fund_id, &mut rate_filter)
+                        .into_iter()
+                        .cloned()
+                        .collect(),
+                    None => vec![hit.record.clone()],
+                }),
+            })
+            .collect();
+        (hits.len(), results, outcome.budget_exhausted, outcome.truncated || tokens_dropped, outcome.diagnostics)
+    };
+    if export {
+        if search_budget_exhausted {
+            state.budget_exhaustions.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut records = representation::to_csv_records(results);
+        if let Some(as_of) = as_of {
+            let fund_ids: Vec<i32> = records.iter().filter_map(|record| record.fund_id).collect();
+            let result = async {
+                let client = get_postgres_client(state.pools.primary()).await?;
+                Ok::<_, Box<dyn std::error::Error>>(history::funds_as_of(&**client, &fund_ids, as_of).await?)
+            }
+            .await;
+            match result {
+                Ok(history) => history::reconstruct(&mut records, &history),
+                Err(e) => return Err(ApiError::database(format!("Failed to load fund history: {}", e), e.as_ref())),
+            }
+        }
+        let file_name = csv_export::file_name(search_term, chrono::Local::now().date_naive());
+        return Ok(HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", file_name)))
+            .insert_header(("X-Total-Matches", total_matches.to_string()))
+            .insert_header(("X-Search-Truncated", truncated.to_string()))
+            .insert_header(("X-Reconstructed", as_of.is_some().to_string()))
+            .insert_header((header::VARY, "Accept"))
+            .streaming(csv_export::stream(records)));
+    }
+
+    let excluded_incomplete = evaluator.excluded_incomplete();
+    let excluded_expired = evaluator.excluded_expired();
+    let (filter_diagnostics, filter_warnings) = evaluator.finish(config.sparse_filter_warning_ratio);
+
+    let data = match representation::to_json(&results, &display) {
+        Ok(data) => data,
+        Err(e) => return Err(ApiError::internal(format!("Failed to serialize results: {}", e))),
+    };
+
+    // Offer did-you-mean suggestions only when no tier, fuzzy included, found anything
+    let (did_you_mean, suggest_budget_exhausted) = if total_matches == 0 && !expanded.is_empty() {
+        virtual_table.suggest_funds(&expanded, DID_YOU_MEAN_COUNT, Some(config.fuzzy_budget()))
+    } else {
+        (Vec::new(), false)
+    };
+    let budget_exhausted = search_budget_exhausted || suggest_budget_exhausted;
+    if budget_exhausted {
+        state.budget_exhaustions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let mut body = json!({
+        "status": "success",
+        "query": search_term,
+        "count": data.len(),
+        "total_matches": total_matches,
+        "offset": offset,
+        "limit": limit,
+        "grouped": shape == Shape::Nested,
+        "shape": shape.name(),
+        "browse": browse,
+        "truncated": truncated,
+        "sort": display.sort_param(),
+        "data": data,
+        "did_you_mean": did_you_mean,
+        "meta": {
+            "budget_exhausted": budget_exhausted,
+            "display": display,
+            "applied_filters": filters.applied(),
+            "excluded_incomplete": excluded_incomplete,
+            "excluded_expired": excluded_expired,
+            "filters": filter_diagnostics,
+            "warnings": filter_warnings
+        }
+    });
+    if debug {
+        body["diagnostics"] = json!(diagnostics);
+    }
+    Ok(HttpResponse::Ok().insert_header((header::VARY, "Accept")).json(body))
+}
+
+// The same handler; its own function so the spec can list /search.csv as a route
+#[utoipa::path(
+    get,
+    path = "/search.csv",
+    tag = "search",
+    params(openapi::SearchParams),
+    responses(
+        (status = 200, description = "Every column of the matches as CSV; X-Total-Matches has the uncapped count, X-Search-Truncated whether the search stopped early, X-Reconstructed whether fund values are as of as_of_upload_id/as_of_date", content_type = "text/csv", body = String),
+        (status = 400, description = "Invalid parameter", body = openapi::ErrorResponse),
+        (status = 422, description = "Unknown category or company, with the closest known values", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn search_schemes_csv(
+    req: HttpRequest,
+    query: web::Query<HashMap<String, String>>,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    search_schemes(req, query, state).await
+}
+
+const DEFAULT_SUGGEST_LIMIT: usize = 10;
+const MAX_SUGGEST_LIMIT: usize = 50;
+
+// Type-ahead: scheme names starting with q, alphabetically, with their fund ids
+#[utoipa::path(
+    get,
+    path = "/suggest",
+    tag = "search",
+    params(("q" = String, Query, description = "Name prefix"), ("limit" = Option<usize>, Query, description = "At most 50")),
+    responses(
+        (status = 200, description = "Scheme names starting with q"),
+        (status = 400, description = "Missing q or bad limit", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn suggest_names(query: web::Query<HashMap<String, String>>, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let prefix = match query.get("q") {
+        Some(q) => q,
+        None => return Err(ApiError::missing_parameter("q")),
+    };
+    let limit = match parse_page_param(&query, "limit", DEFAULT_SUGGEST_LIMIT, MAX_SUGGEST_LIMIT) {
+        Ok(limit) => limit,
+        Err(message) => return Err(ApiError::invalid_parameter(message)),
+    };
+
+    let suggestions = state.virtual_table.load().suggest_names(prefix, limit);
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "query": prefix,
+        "suggestions": suggestions
+    })))
+}
+
+// Deep paging past this is a scraping pattern, not a person using the pager
+const MAX_SEARCH_OFFSET: usize = 10_000;
+
+// Canonical category and company spellings with record counts
+#[utoipa::path(
+    get,
+    path = "/facets",
+    tag = "search",
+    responses(
+        (status = 200, description = "Category and company spellings with record counts")
+    )
+)]
+pub async fn facets(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let virtual_table = state.virtual_table.load();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "categories": virtual_table.category_counts(),
+        "companies": virtual_table.company_counts()
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use std::sync::Arc;
+
+    use crate::config::RuntimeConfig;
+    use crate::preferences::PreferenceMap;
+    use crate::{test_support, CombinedSchemeData};
+
+    fn numbered_schemes(count: i32) -> Vec<CombinedSchemeData> {
+        (0..count)
+            .map(|n| CombinedSchemeData::test_fund(n + 1, &format!("Scheme {:04} Growth Fund", n)))
+            .collect()
+    }
+
+    fn fund_ids(body: &serde_json::Value) -> Vec<i64> {
+        body["data"].as_array().unwrap().iter().map(|record| record["fund_id"].as_i64().unwrap()).collect()
+    }
+
+    #[actix_web::test]
+    async fn category_defaults_apply_only_to_a_single_category_filter_and_yield_to_the_request() {
+        let mut records = Vec::new();
+        for (n, (name, category, year_1, years_3)) in [
+            ("HDFC Corporate Bond Fund", "Corporate Bond", 7.5, 6.0),
+            ("ICICI Corporate Bond Fund", "Corporate Bond", 8.0, 5.5),
+            ("Axis Bluechip Fund", "Large Cap", 20.0, 14.0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mut record = CombinedSchemeData::test_fund(n as i32 + 1, name);
+            record.fund_category = Some(Arc::from(category));
+            record.year_1 = Some(year_1);
+            record.years_3 = Some(years_3);
+            records.push(record);
+        }
+        let state = test_support::state(RuntimeConfig::default(), records);
+        let preference = preferences::CategoryPreference {
+            category: "Corporate Bond".to_string(),
+            default_sort: Some("year_1".to_string()),
+            default_order: Some(preferences::SortOrder::Desc),
+            default_fields: Some(vec!["fund_id".to_string(), "year_1".to_string()]),
+        };
+        state
+            .category_preferences
+            .store(Arc::new(PreferenceMap::from([(normalize_scheme_name(&preference.category), preference)])));
+
+        let search = |query: &str| TestRequest::get().uri(&format!("/api/v1/search?{}", query));
+        let (status, body) = test_support::call_json(&state, search("category=corporate+bond")).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["sort"], "-year_1");
+        assert_eq!(body["meta"]["display"]["sources"], json!({"sort": "category", "order": "category", "fields": "category"}));
+        assert_eq!(fund_ids(&body), vec![2, 1]);
+        assert_eq!(body["data"][0]["year_1"], 8.0);
+        assert!(body["data"][0].get("scheme_name").is_none());
+
+        let (_, body) = test_support::call_json(&state, search("category=corporate+bond&sort=years_3")).await;
+        assert_eq!(body["sort"], "years_3");
+        assert_eq!(body["meta"]["display"]["sources"]["fields"], "category");
+        assert_eq!(fund_ids(&body), vec![2, 1]);
+
+        let (_, body) = test_support::call_json(&state, search("category=corporate+bond&category=large+cap")).await;
+        assert_eq!(body["sort"], json!(null));
+        assert_eq!(body["meta"]["display"]["sources"], json!({"sort": "global", "order": "global", "fields": "global"}));
+    }
+
+    #[actix_web::test]
+    async fn category_and_company_filters_resolve_to_canonical_spellings_or_422() {
+        let mut records = Vec::new();
+        for (n, (name, category, company)) in [
+            ("Parag Parikh Flexi Cap Fund", "Flexi Cap", "PPFAS Mutual Fund"),
+            ("Quant Small Cap Fund", "Small Cap", "Quant Mutual Fund"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mut record = CombinedSchemeData::test_fund(n as i32 + 1, name);
+            record.fund_category = Some(Arc::from(category));
+            record.company = Some(Arc::from(company));
+            record.rate_id = Some(n as i32 + 1);
+            records.push(record);
+        }
+        let state = test_support::state(RuntimeConfig::default(), records);
+        let search = |query: &str| TestRequest::get().uri(&format!("/api/v1/search?{}", query));
+
+        for query in ["category=flexicap", "category=Flexi-Cap", "category=FLEXI+CAP+FUND", "company=ppfas+mutual+fund"] {
+            let (status, body) = test_support::call_json(&state, search(query)).await;
+            assert_eq!(status, 200, "{}: {}", query, body);
+            assert_eq!(fund_ids(&body), vec![1], "{}", query);
+        }
+        let (_, body) = test_support::call_json(&state, search("category=flexicap")).await;
+        assert_eq!(body["meta"]["applied_filters"]["category"], json!(["Flexi Cap"]));
+
+        let (status, body) = test_support::call_json(&state, search("category=mega+cap")).await;
+        assert_eq!(status, 422);
+        assert_eq!(body["code"], "unknown_value");
+        assert_eq!(body["details"]["parameter"], "category");
+        assert_eq!(body["details"]["closest"], json!(["Small Cap", "Flexi Cap"]));
+        let (status, _) = test_support::call_json(&state, search("company=nippon")).await;
+        assert_eq!(status, 422);
+
+        let (_, facets) = test_support::call_json(&state, TestRequest::get().uri("/api/v1/facets")).await;
+        assert_eq!(facets["categories"], json!({"Flexi Cap": 1, "Small Cap": 1}));
+        assert_eq!(facets["companies"], json!({"PPFAS Mutual Fund": 1, "Quant Mutual Fund": 1}));
+    }
+
+    #[actix_web::test]
+    async fn every_page_counts_the_same_matches_fuzzy_ones_included() {
+        let records = ["Axis Bluechip Fund", "SBI Bluechip Fund", "ICICI Bluechp Fund", "Kotak Bluchip Fund"]
+            .into_iter()
+            .enumerate()
+            .map(|(n, name)| CombinedSchemeData::test_fund(n as i32 + 1, name))
+            .collect();
+        let state = test_support::state(RuntimeConfig::default(), records);
+
+        // Two token matches fill the first page; the misspelled two only the fuzzy tier finds
+        let mut paged = Vec::new();
+        for offset in 0..5 {
+            let uri = format!("/api/v1/search?q=bluechip&limit=1&offset={}", offset);
+            let (status, body) = test_support::call_json(&state, TestRequest::get().uri(&uri)).await;
+            assert_eq!(status, 200, "{}", body);
+            assert_eq!(body["total_matches"], 4, "offset {}: {}", offset, body);
+            paged.extend(fund_ids(&body));
+        }
+        let (_, body) = test_support::call_json(&state, TestRequest::get().uri("/api/v1/search?q=bluechip")).await;
+        assert_eq!(paged, fund_ids(&body));
+        assert_eq!(body["data"][3]["match_type"], "fuzzy");
+    }
+
+    #[actix_web::test]
+    async fn suggest_lists_names_starting_with_the_prefix_alphabetically() {
+        let records = ["Axis Small Cap Fund", "Axis Bluechip Fund", "HDFC Top 100 Fund", "Axis Midcap Fund"]
+            .into_iter()
+            .enumerate()
+            .map(|(n, name)| CombinedSchemeData::test_fund(n as i32 + 1, name))
+            .collect();
+        let state = test_support::state(RuntimeConfig::default(), records);
+        let suggest = |query: &str| TestRequest::get().uri(&format!("/api/v1/suggest?{}", query));
+
+        let (status, body) = test_support::call_json(&state, suggest("q=AXIS")).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(
+            body["suggestions"],
+            json!([
+                {"scheme_name": "Axis Bluechip Fund", "fund_id": 2},
+                {"scheme_name": "Axis Midcap Fund", "fund_id": 4},
+                {"scheme_name": "Axis Small Cap Fund", "fund_id": 1}
+            ])
+        );
+        let (_, body) = test_support::call_json(&state, suggest("q=axis+m&limit=1")).await;
+        assert_eq!(body["suggestions"], json!([{"scheme_name": "Axis Midcap Fund", "fund_id": 4}]));
+        let (_, body) = test_support::call_json(&state, suggest("q=quant")).await;
+        assert_eq!(body["suggestions"], json!([]));
+
+        for (query, status) in [("limit=5", 400), ("q=axis&limit=51", 400), ("q=axis&limit=none", 400)] {
+            let (actual, body) = test_support::call_json(&state, suggest(query)).await;
+            assert_eq!(actual, status, "{}: {}", query, body);
+        }
+    }
+
+    #[actix_web::test]
+    async fn category_filters_apply_before_the_limit_and_any_of_several_matches() {
+        let mut records = Vec::new();
+        for (n, (name, category)) in [
+            ("Bluechip Fund", "Mid Cap"),
+            ("Bluechip Equity Fund", "Mid Cap"),
+            ("Canara Robeco Bluechip Equity Fund", "Large Cap"),
+            ("Mirae Asset Bluechip Flexi Fund", "Flexi Cap"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mut record = CombinedSchemeData::test_fund(n as i32 + 1, name);
+            record.fund_category = Some(Arc::from(category));
+            records.push(record);
+        }
+        let state = test_support::state(RuntimeConfig::default(), records);
+        let search = |query: &str| TestRequest::get().uri(&format!("/api/v1/search?q=bluechip&{}", query));
+
+        // The Mid Cap funds rank first but are filtered out, so they take no place on the page
+        let (status, body) = test_support::call_json(&state, search("category=large+cap&limit=1")).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!((fund_ids(&body), body["total_matches"].as_u64()), (vec![3], Some(1)));
+
+        let (_, body) = test_support::call_json(&state, search("category=LARGE+CAP&category=flexi+cap")).await;
+        let mut ids = fund_ids(&body);
+        ids.sort();
+        assert_eq!(ids, vec![3, 4]);
+        assert_eq!(body["meta"]["applied_filters"]["category"], json!(["Flexi Cap", "Large Cap"]));
+    }
+
+    #[actix_web::test]
+    async fn company_and_brokerage_type_filters_compose_with_the_query() {
+        let mut records = Vec::new();
+        for (n, (name, rate)) in [
+            ("HDFC Flexi Cap Fund", Some(("NJ India Invest", "Trail"))),
+            ("HDFC Flexi Cap Fund Direct", Some(("NJ India Invest", "Upfront"))),
+            ("Parag Parikh Flexi Cap Fund", Some(("Prudent Corporate", "Trail"))),
+            ("Quant Flexi Cap Fund", None),
+            ("NJ Balanced Advantage Fund", Some(("NJ India Invest", "Trail"))),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mut record = CombinedSchemeData::test_fund(n as i32 + 1, name);
+            if let Some((company, brokerage_type)) = rate {
+                record.rate_id = Some(n as i32 + 1);
+                record.company = Some(Arc::from(company));
+                record.brokerage_type = Some(Arc::from(brokerage_type));
+            }
+            records.push(record);
+        }
+        let state = test_support::state(RuntimeConfig::default(), records);
+        let search = |query: &str| TestRequest::get().uri(&format!("/api/v1/search?{}", query));
+
+        // A fund without a rate has no company, so the company filter drops it
+        let (status, body) = test_support::call_json(&state, search("q=flexi+cap&company=nj+india+invest")).await;
+        assert_eq!(status, 200, "{}", body);
+        let mut ids = fund_ids(&body);
+        ids.sort();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(body["total_matches"], 2);
+
+        let both = search("q=flexi+cap&company=NJ+India+Invest&brokerage_type=TRAIL");
+        let (_, body) = test_support::call_json(&state, both).await;
+        assert_eq!((fund_ids(&body), body["total_matches"].as_u64()), (vec![1], Some(1)));
+        assert_eq!(body["meta"]["applied_filters"]["brokerage_type"], "TRAIL");
+        let (_, body) = test_support::call_json(&state, search("brokerage_type=trail")).await;
+        assert_eq!(body["total_matches"], 3);
+    }
+
+    #[actix_web::test]
+    async fn range_filters_screen_without_a_query_and_drop_missing_values() {
+        let mut records = Vec::new();
+        let values = [(Some(25.0), Some(5000.0)), (Some(22.0), Some(800.0)), (None, Some(9000.0)), (Some(12.0), None)];
+        for (n, (year_1, fund_size)) in values.into_iter().enumerate() {
+            let mut record = CombinedSchemeData::test_fund(n as i32 + 1, &format!("Fund {}", n + 1));
+            record.year_1 = year_1;
+            record.fund_size_may25 = fund_size;
+            records.push(record);
+        }
+        let state = test_support::state(RuntimeConfig::default(), records);
+        let search = |query: &str| TestRequest::get().uri(&format!("/api/v1/search?{}", query));
+
+        // No q: a screen over every record, in name order
+        let (status, body) = test_support::call_json(&state, search("min_year_1=20")).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(fund_ids(&body), vec![1, 2]);
+        assert_eq!(body["data"][0]["match_type"], "screen");
+        let (_, body) = test_support::call_json(&state, search("min_year_1=20&min_fund_size=1000")).await;
+        assert_eq!(fund_ids(&body), vec![1]);
+        let fund_size = &body["meta"]["applied_filters"]["ranges"]["fund_size_may25"];
+        assert_eq!(*fund_size, json!({"min": 1000.0, "max": null}));
+        let (_, body) = test_support::call_json(&state, search("max_year_1=15")).await;
+        assert_eq!(fund_ids(&body), vec![4]);
+
+        for (query, parameter) in [("min_year_1=high", "min_year_1"), ("max_fund_size=NaN", "max_fund_size")] {
+            let (status, body) = test_support::call_json(&state, search(query)).await;
+            assert_eq!(status, 400, "{}", query);
+            assert!(body["message"].as_str().unwrap().contains(&format!("'{}'", parameter)), "{}", body);
+        }
+        let (status, _) = test_support::call_json(&state, search("min_fund_size=1&max_fund_size_may25=2")).await;
+        assert_eq!(status, 400);
+    }
+
+    #[actix_web::test]
+    async fn no_query_lists_the_table_by_name_a_page_at_a_time() {
+        let mut records: Vec<CombinedSchemeData> = ["Quant Small Cap Fund", "Axis Bluechip Fund", "HDFC Top 100 Fund"]
+            .iter()
+            .enumerate()
+            .map(|(n, name)| CombinedSchemeData::test_fund(n as i32 + 1, name))
+            .collect();
+        records[0].fund_category = Some(Arc::from("Small Cap"));
+        let state = test_support::state(RuntimeConfig::default(), records);
+        let search = |query: &str| TestRequest::get().uri(&format!("/api/v1/search{}", query));
+
+        for query in ["", "?q=", "?q=+&limit=2"] {
+            let (status, body) = test_support::call_json(&state, search(query)).await;
+            assert_eq!(status, 200, "{}: {}", query, body);
+            assert_eq!(body["browse"], true, "{}", query);
+            assert_eq!(body["total_matches"], 3, "{}", query);
+            assert_eq!(fund_ids(&body)[..2], [2, 3], "{}", query);
+        }
+        let (_, body) = test_support::call_json(&state, search("?limit=2&offset=2")).await;
+        assert_eq!((fund_ids(&body), body["offset"].as_u64()), (vec![1], Some(2)));
+
+        // Filters still apply; it is a screen then, not a browse
+        let (_, body) = test_support::call_json(&state, search("?category=small+cap")).await;
+        assert_eq!((fund_ids(&body), body["browse"].as_bool()), (vec![1], Some(false)));
+    }
+
+    #[actix_web::test]
+    async fn sort_orders_results_puts_missing_values_last_and_refuses_unknown_fields() {
+        let mut records = Vec::new();
+        for (n, (name, year_1)) in [
+            ("Alpha Growth Fund", Some(10.0)),
+            ("Beta Growth Fund", None),
+            ("Gamma Growth Fund", Some(30.0)),
+            ("Delta Growth Fund", Some(20.0)),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mut record = CombinedSchemeData::test_fund(n as i32 + 1, name);
+            record.year_1 = year_1;
+            records.push(record);
+        }
+        let state = test_support::state(RuntimeConfig::default(), records);
+        let search = |query: &str| TestRequest::get().uri(&format!("/api/v1/search?q=growth&{}", query));
+
+        // A missing value goes last whichever way the sort runs
+        for (query, sort, expected) in [
+            ("sort=year_1", "year_1", vec![1, 4, 3, 2]),
+            ("sort=-year_1", "-year_1", vec![3, 4, 1, 2]),
+            ("sort=year_1&order=desc", "-year_1", vec![3, 4, 1, 2]),
+            ("sort=scheme_name", "scheme_name", vec![1, 2, 4, 3]),
+        ] {
+            let (status, body) = test_support::call_json(&state, search(query)).await;
+            assert_eq!(status, 200, "{}: {}", query, body);
+            assert_eq!(body["sort"], sort, "{}", query);
+            assert_eq!(fund_ids(&body), expected, "{}", query);
+        }
+        // The sort runs over every match before the page is cut
+        let (_, body) = test_support::call_json(&state, search("sort=-year_1&limit=1&offset=1")).await;
+        assert_eq!(fund_ids(&body), vec![4]);
+
+        for bad in ["sort=bogus", "sort=-year_1&order=asc", "sort=year_1&order=sideways"] {
+            let (status, body) = test_support::call_json(&state, search(bad)).await;
+            assert_eq!(status, 400, "{}: {}", bad, body);
+        }
+    }
+
+    #[actix_web::test]
+    async fn accept_picks_json_or_csv_and_shape_nests_each_funds_rates() {
+        let mut records = Vec::new();
+        for (rate_id, company) in [(11, "Quant"), (12, "NJ Wealth")] {
+            let mut record = CombinedSchemeData::test_fund(1, "Quant Flexi Cap Growth Fund");
+            record.rate_id = Some(rate_id);
+            record.company = Some(Arc::from(company));
+            records.push(record);
+        }
+        records.push(CombinedSchemeData::test_fund(2, "Axis Bluechip Growth Fund"));
+        let state = test_support::state(RuntimeConfig::default(), records);
+        let search = |query: &str| TestRequest::get().uri(&format!("/api/v1/search?q=growth{}", query));
+
+        let (status, body) = test_support::call_json(&state, search("")).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["shape"], "flat");
+        let flat_count = body["count"].as_u64().unwrap();
+        assert!(body["data"][0].get("company").is_some(), "{}", body);
+
+        for query in ["&shape=nested", "&group=true"] {
+            let (status, body) = test_support::call_json(&state, search(query)).await;
+            assert_eq!(status, 200, "{}: {}", query, body);
+            assert_eq!(body["shape"], "nested");
+            let mut ids = fund_ids(&body);
+            ids.sort();
+            assert_eq!(ids, vec![1, 2], "{}", query);
+            let fund = |id: i64| body["data"].as_array().unwrap().iter().find(|fund| fund["fund_id"] == id).unwrap();
+            let (fund, axis) = (fund(1), fund(2));
+            assert!(fund.get("company").is_none(), "{}", fund);
+            let rates: Vec<(i64, &str)> = fund["rates"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|rate| (rate["rate_id"].as_i64().unwrap(), rate["company"].as_str().unwrap()))
+                .collect();
+            assert_eq!(rates, [(11, "Quant"), (12, "NJ Wealth")]);
+            assert_eq!(axis["rates"], json!([]));
+        }
+        for bad in ["&shape=tree", "&shape=flat&group=true", "&format=xml"] {
+            let (status, _) = test_support::call_json(&state, search(bad)).await;
+            assert_eq!(status, 400, "{}", bad);
+        }
+
+        // The best-weighted type wins; types we don't serve fall back to JSON
+        for (accept, csv) in [
+            ("text/csv", true),
+            ("application/json;q=0.5, text/csv", true),
+            ("text/csv;q=0.5, application/json", false),
+            ("application/xml", false),
+        ] {
+            let response = test_support::call(&state, search("").insert_header((header::ACCEPT, accept))).await;
+            assert_eq!(response.status(), 200, "{}", accept);
+            let content_type = response.headers().get(header::CONTENT_TYPE).unwrap().to_str().unwrap();
+            assert_eq!(content_type.starts_with("text/csv"), csv, "{}: {}", accept, content_type);
+        }
+        // CSV can't nest, so either shape exports one row per record
+        let (_, flat) = csv_export(&state, "/api/v1/search.csv?q=growth").await;
+        let (_, nested) = csv_export(&state, "/api/v1/search.csv?q=growth&shape=nested").await;
+        assert_eq!(nested.lines().count(), 1 + 3);
+        assert_eq!(flat.lines().count() as u64, 1 + flat_count);
+    }
+
+    async fn csv_export(state: &AppState, uri: &str) -> (header::HeaderMap, String) {
+        let response = test_support::call(state, TestRequest::get().uri(uri)).await;
+        assert_eq!(response.status(), 200);
+        let headers = response.headers().clone();
+        let body = actix_web::test::read_body(response).await;
+        (headers, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[actix_web::test]
+    async fn csv_exports_every_match_past_the_page_limit_with_fixed_precision_and_quoting() {
+        let mut records = numbered_schemes(45);
+        let mut comma = CombinedSchemeData::test_fund(46, "Axis Bluechip, Growth Fund");
+        comma.year_1 = Some(12.34);
+        records.push(comma);
+        let config = RuntimeConfig { max_export_rows: 40, ..RuntimeConfig::default() };
+        let state = test_support::state(config, records);
+
+        let (headers, csv) = csv_export(&state, "/api/v1/search.csv?q=growth").await;
+        assert_eq!(headers.get(header::CONTENT_TYPE).unwrap(), "text/csv; charset=utf-8");
+        let file_name = format!("search-growth-{}.csv", chrono::Local::now().date_naive());
+        let disposition = headers.get("Content-Disposition").unwrap().to_str().unwrap();
+        assert_eq!(disposition, format!("attachment; filename=\"{}\"", file_name));
+        // Capped at max_export_rows, not the search page size; the header still counts every match
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], preferences::FIELD_NAMES.join(","));
+        assert_eq!(lines.len(), 1 + 40);
+        assert_eq!(headers.get("X-Total-Matches").unwrap(), "46");
+        let over_the_cap = TestRequest::get().uri("/api/v1/search.csv?q=growth&limit=41");
+        let (status, _) = test_support::call_json(&state, over_the_cap).await;
+        assert_eq!(status, 400);
+
+        let (_, csv) = csv_export(&state, "/api/v1/search.csv?q=bluechip").await;
+        let row = csv.lines().nth(1).unwrap();
+        assert!(row.contains("\"Axis Bluechip, Growth Fund\""), "{}", row);
+        assert!(row.contains(",12.3400,"), "{}", row);
+        // format=csv is the same export
+        let (headers, same) = csv_export(&state, "/api/v1/search?q=bluechip&format=csv").await;
+        assert_eq!((same, headers.get("Vary").unwrap().to_str().unwrap()), (csv, "Accept"));
+    }
+
+    #[actix_web::test]
+    async fn pathological_queries_are_refused_or_cut_short() {
+        let state = test_support::state(RuntimeConfig::default(), numbered_schemes(3));
+        let search = |q: &str| TestRequest::get().uri(&format!("/api/v1/search?q={}", q));
+
+        let (status, body) = test_support::call_json(&state, search(&"a".repeat(MAX_QUERY_CHARS + 1))).await;
+        assert_eq!(status, 400);
+        assert_eq!(body["code"], "invalid_parameter");
+        let (status, _) = test_support::call_json(&state, search(&"a".repeat(MAX_QUERY_CHARS))).await;
+        assert_eq!(status, 200);
+
+        // Punctuation alone is refused rather than listing everything; blanks still browse
+        let (status, body) = test_support::call_json(&state, search("%21%3F-%26%26")).await;
+        assert_eq!(status, 400, "{}", body);
+        let (status, body) = test_support::call_json(&state, search("+++")).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["browse"], true);
+
+        let (status, body) = test_support::call_json(&state, search("scheme+0001")).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["truncated"], false);
+        // Tokens past MAX_QUERY_TOKENS are dropped, so the leading ones still find the fund
+        let long_query = format!("scheme+0001{}+nonsense", "+growth".repeat(MAX_QUERY_TOKENS - 2));
+        let (status, body) = test_support::call_json(&state, search(&long_query)).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["truncated"], true);
+        assert_eq!(fund_ids(&body)[0], 2);
+    }
+
+    #[actix_web::test]
+    async fn pages_tile_the_whole_match_set_and_report_its_total() {
+        let state = test_support::state(RuntimeConfig::default(), numbered_schemes(45));
+        let page = |query: &str| TestRequest::get().uri(&format!("/api/v1/search?q=growth&{}", query));
+
+        let (status, body) = test_support::call_json(&state, page("")).await;
+        assert_eq!(status, 200, "{}", body);
+        let page_fields = (body["count"].as_u64(), body["limit"].as_u64(), body["offset"].as_u64());
+        assert_eq!(page_fields, (Some(20), Some(20), Some(0)));
+        assert_eq!(body["total_matches"], 45);
+
+        let mut paged = Vec::new();
+        for offset in [0, 20, 40] {
+            let (_, body) = test_support::call_json(&state, page(&format!("limit=20&offset={}", offset))).await;
+            assert_eq!(body["total_matches"], 45);
+            paged.extend(fund_ids(&body));
+        }
+        let (_, all) = test_support::call_json(&state, page("limit=100")).await;
+        assert_eq!(paged, fund_ids(&all));
+        assert_eq!(paged.len(), 45);
+
+        // Past the end, or no page at all, still counts the matches
+        for query in ["offset=50", "limit=0"] {
+            let (_, body) = test_support::call_json(&state, page(query)).await;
+            assert_eq!((body["count"].as_u64(), body["total_matches"].as_u64()), (Some(0), Some(45)), "{}", query);
+        }
+        for bad in ["limit=1001", "offset=-1", "offset=10001", "limit=ten"] {
+            let (status, body) = test_support::call_json(&state, page(bad)).await;
+            assert_eq!((status.as_u16(), body["code"].as_str()), (400, Some("invalid_parameter")), "{}", bad);
+        }
+    }
+
+    #[actix_web::test]
+    async fn results_carry_their_score_and_match_type_best_first() {
+        let records = ["HDFC Corporate Bond Fund", "HDFC Top 100 Fund", "Top HDFC Equity Fund"]
+            .iter()
+            .enumerate()
+            .map(|(n, name)| CombinedSchemeData::test_fund(n as i32 + 1, name))
+            .collect();
+        let state = test_support::state(RuntimeConfig::default(), records);
+
+        let request = TestRequest::get().uri("/api/v1/search?q=hdfc+top");
+        let (status, body) = test_support::call_json(&state, request).await;
+
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["data"][0]["scheme_name"], "HDFC Top 100 Fund");
+        assert_eq!(body["data"][0]["match_type"], "prefix");
+        assert_eq!(body["data"][1]["match_type"], "tokens");
+        assert!(body["data"][0]["score"].as_f64().unwrap() > body["data"][1]["score"].as_f64().unwrap());
+    }
+
+    #[actix_web::test]
+    async fn a_search_cut_short_by_its_budget_says_so_in_valid_ranked_json() {
+        let config = RuntimeConfig {
+            fuzzy_budget_ms: 0,
+            ..RuntimeConfig::default()
+        };
+        let state = test_support::state(config, numbered_schemes(1000));
+
+        let request = TestRequest::get().uri("/api/v1/search?q=scheme+growth+fnd&limit=50");
+        let (status, body) = test_support::call_json(&state, request).await;
+
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["meta"]["budget_exhausted"], true);
+        assert_eq!(body["count"], 50);
+        assert_eq!(body["total_matches"], 256);
+        let scores: Vec<f64> = body["data"].as_array().unwrap().iter().map(|r| r["score"].as_f64().unwrap()).collect();
+        assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert_eq!(body["data"][0]["scheme_name"], "Scheme 0000 Growth Fund");
+        assert_eq!(state.budget_exhaustions.load(Ordering::Relaxed), 1);
+    }
+
+    #[actix_web::test]
+    async fn normal_searches_never_trip_the_default_budget() {
+        let state = test_support::state(RuntimeConfig::default(), numbered_schemes(1000));
+
+        for q in ["scheme+growth+fnd", "scheme+0500", "schme+0500+growht", "0500"] {
+            let request = TestRequest::get().uri(&format!("/api/v1/search?q={}", q));
+            let (status, body) = test_support::call_json(&state, request).await;
+            assert_eq!(status, 200, "{}", body);
+            assert_eq!(body["meta"]["budget_exhausted"], false, "{}", q);
+        }
+        assert_eq!(state.budget_exhaustions.load(Ordering::Relaxed), 0);
+    }
+}
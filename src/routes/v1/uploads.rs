@@ -0,0 +1,780 @@
+use actix_multipart::Multipart;
+use actix_web::http::header;
+use actix_web::{web, HttpResponse};
+use futures_util::TryStreamExt;
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::Write;
+use utoipa::IntoParams;
+
+use crate::api_error::{ApiError, ErrorCode};
+use crate::{
+    api_error, audit, auth, get_postgres_client, multi_upload_summary, next_upload_chunk, providers,
+    rate_upload, require_confirmation, routes, run_upload, sniff,
+    upload_failure, upload_too_large, uploads, validate_excel_file, workbook, AppState,
+    UPLOAD_FILE_FIELD, UploadedFile,
+};
+
+#[utoipa::path(
+    post,
+    path = "/upload",
+    tag = "uploads",
+    security(("api_key" = []), ("bearer" = [])),
+    params(("dry_run" = Option<bool>, Query, description = "Validate the files and report what would change without writing")),
+    request_body(content = openapi::UploadForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 202, description = "Upload queued; poll job_url for the per-file UploadReports", body = openapi::UploadAccepted),
+        (status = 200, description = "Dry run: per-file validation reports"),
+        (status = 400, description = "No file, an unexpected form field or a bad dry_run", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the uploader role", body = openapi::ErrorResponse),
+        (status = 413, description = "More than max_upload_bytes", body = openapi::ErrorResponse),
+        (status = 422, description = "A file that is not a spreadsheet", body = openapi::ErrorResponse),
+        (status = 503, description = "Shutting down", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn upload_excel(
+    mut payload: Multipart,
+    query: web::Query<HashMap<String, String>>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+    context: api_error::RequestContext,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Uploader)?;
+    let dry_run = match query.get("dry_run").map(|value| value.trim().to_ascii_lowercase()) {
+        None => false,
+        Some(value) if value == "true" || value == "1" => true,
+        Some(value) if value == "false" || value == "0" => false,
+        Some(value) => {
+            return Err(ApiError::invalid_parameter(format!(
+                "Parameter 'dry_run' must be true or false, got '{}'",
+                value
+            )))
+        }
+    };
+
+    // Everything written so far lives in NamedTempFiles, so any early return (limit exceeded,
+    // unexpected field, client disconnect surfacing as a payload error) deletes it on drop
+    let max_bytes = state.runtime_config.load().max_upload_bytes;
+    let mut received: u64 = 0;
+    let mut files: Vec<UploadedFile> = Vec::new();
+    let mut provider = None;
+    while let Some(mut field) = payload.try_next().await? {
+        let field_name = field.name().to_string();
+        match field_name.as_str() {
+            UPLOAD_FILE_FIELD => {}
+            // Optional text field naming the data provider
+            "provider" => {
+                let mut value = Vec::new();
+                while let Some(chunk) = next_upload_chunk(&mut field).await? {
+                    received += chunk.len() as u64;
+                    if received > max_bytes {
+                        return Err(upload_too_large(max_bytes));
+                    }
+                    value.extend_from_slice(&chunk);
+                }
+                provider = Some(String::from_utf8_lossy(&value).trim().to_string()).filter(|p| !p.is_empty());
+                continue;
+            }
+            other => {
+                return Err(ApiError::new(
+                    ErrorCode::InvalidMultipart,
+                    format!(
+                        "Unexpected form field '{}'; send files as '{}' and optionally 'provider'",
+                        other, UPLOAD_FILE_FIELD
+                    ),
+                ))
+            }
+        }
+
+        let file_name = field.content_disposition().get_filename().map(str::to_string);
+        let mut temp_file = workbook::temp_file_for(file_name.as_deref())
+            .map_err(|e| ApiError::internal(format!("Failed to create temp file: {}", e)))?;
+        let mut bytes = 0;
+        let mut head = Vec::with_capacity(sniff::SNIFF_BYTES);
+        while let Some(chunk) = next_upload_chunk(&mut field).await? {
+            bytes += chunk.len();
+            received += chunk.len() as u64;
+            let wanted = sniff::SNIFF_BYTES.saturating_sub(head.len()).min(chunk.len());
+            head.extend_from_slice(&chunk[..wanted]);
+            // Checked per chunk so an oversized upload never lands on disk in full
+            if received > max_bytes {
+                return Err(upload_too_large(max_bytes));
+            }
+            temp_file
+                .write_all(&chunk)
+                .map_err(|e| ApiError::internal(format!("Failed to write chunk: {}", e)))?;
+        }
+        // Browsers send an empty, unnamed part for a file input left blank
+        if bytes == 0 && file_name.is_none() {
+            continue;
+        }
+        // Catch renamed images and the like here rather than as an opaque reader error later
+        if let Err(message) = sniff::check_upload(file_name.as_deref(), &head) {
+            return Err(ApiError::new(ErrorCode::Unprocessable, message).with_details(json!({"file_name": file_name})));
+        }
+        files.push(UploadedFile { file_name, temp_file, bytes });
+    }
+
+    if files.is_empty() {
+        return Err(ApiError::new(ErrorCode::InvalidMultipart, "No file was uploaded"));
+    }
+
+    // Read-only, so it neither counts as an in-flight upload nor waits for the mutation gate
+    if dry_run {
+        let config = state.runtime_config.load_full();
+        let mut results = Vec::new();
+        let mut failures = Vec::new();
+        for file in &files {
+            match validate_excel_file(file.temp_file.path(), file.file_name.as_deref(), &config, state.pools.primary()).await {
+                Ok(report) => results.push(json!({
+                    "file_name": file.file_name,
+                    "status": "success",
+                    "message": format!(
+                        "Dry run: {} new and {} updated schemes; nothing was written",
+                        report.new_schemes, report.updated_schemes
+                    ),
+                    "report": report
+                })),
+                Err(e) => {
+                    let failure = upload_failure(file, "Error validating file", e.as_ref());
+                    results.push(failure.1.clone());
+                    failures.push(failure);
+                }
+            }
+        }
+        let summary = multi_upload_summary(results, failures, |succeeded, total| {
+            json!({
+                "dry_run": true,
+                "message": format!("Dry run of {} of {} files succeeded; nothing was written", succeeded, total)
+            })
+        })?;
+        return Ok(HttpResponse::Ok().json(summary));
+    }
+
+    let total_bytes: usize = files.iter().map(|file| file.bytes).sum();
+    let names: Vec<&str> = files.iter().map(|file| file.file_name.as_deref().unwrap_or("unnamed")).collect();
+    let description = format!("'{}' ({} bytes)", names.join("', '"), total_bytes);
+    let upload = match state.uploads.begin(description) {
+        Some(upload) => upload,
+        None => {
+            return Err(ApiError::new(
+                ErrorCode::Unavailable,
+                "Server is shutting down; retry the upload shortly",
+            ))
+        }
+    };
+
+    // Parsing and inserting a large workbook outlasts browser timeouts, so the work runs as a job
+    // the client polls at /jobs/{id}; the guard travels with it so shutdown still waits for it
+    let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+    let (job_id, rows_processed) = state.jobs.create(names.clone());
+    info!("Upload job {} queued for {}", job_id, user);
+    // Recorded when the job is queued: the job's own outcome is at /jobs/{id} and in /uploads
+    let audit_id = audit::record(
+        &state,
+        &user,
+        "upload.fund_workbook",
+        "job",
+        Some(job_id.to_string()),
+        json!({"files": names, "bytes": total_bytes, "provider": provider}),
+    )
+    .await;
+    let job_state = state.clone();
+    // A failed job's summary is the error envelope, and its log lines come after the response,
+    // so both need this request's id
+    actix_web::rt::spawn(api_error::with_request_id(context.request_id.clone(), async move {
+        job_state.jobs.start(job_id);
+        let result = run_upload(&job_state, &context, &files, provider.as_deref(), &rows_processed).await;
+        upload.finish();
+        let succeeded = result.is_ok();
+        job_state.jobs.finish(job_id, succeeded, result.unwrap_or_else(|e| e.body()));
+        info!("Upload job {} finished", job_id);
+    }));
+
+    Ok(HttpResponse::Accepted()
+        .insert_header((header::LOCATION, format!("{}/jobs/{}", routes::V1_PREFIX, job_id)))
+        .json(json!({
+            "status": "accepted",
+            "message": format!("Upload queued as job {}", job_id),
+            "job_id": job_id,
+            "job_url": format!("{}/jobs/{}", routes::V1_PREFIX, job_id),
+            "audit_id": audit_id
+        })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}",
+    tag = "uploads",
+    params(("id" = u64, Path, description = "Job id from the upload response")),
+    responses(
+        (status = 200, description = "Job progress; summary is set once it ends", body = crate::jobs::JobStatus),
+        (status = 404, description = "Unknown or expired job", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn get_job(path: web::Path<u64>, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    match state.jobs.status(id) {
+        Some(job) => Ok(HttpResponse::Ok().json(job)),
+        None => Err(ApiError::not_found(format!("No job {} (finished jobs are kept for an hour)", id))),
+    }
+}
+
+// Broker rate sheets go straight into scheme_rates, tagged with the uploaded file name
+#[utoipa::path(
+    post,
+    path = "/upload/rates",
+    tag = "uploads",
+    security(("api_key" = []), ("bearer" = [])),
+    request_body(content = openapi::RateFileForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Rates inserted (pending when require_rate_approval is on)", body = crate::rate_upload::RateUploadReport),
+        (status = 400, description = "Unreadable rate sheet", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the uploader role", body = openapi::ErrorResponse),
+        (status = 422, description = "Not a spreadsheet", body = openapi::ErrorResponse),
+        (status = 503, description = "Shutting down", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn upload_rates(
+    mut payload: Multipart,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Uploader)?;
+    let mut file_name = None;
+    let mut contents = Vec::new();
+    while let Some(mut field) = payload.try_next().await? {
+        if file_name.is_none() {
+            file_name = field.content_disposition().get_filename().map(str::to_string);
+        }
+        while let Some(chunk) = field.try_next().await? {
+            contents.extend_from_slice(&chunk);
+        }
+    }
+
+    let head = &contents[..contents.len().min(sniff::SNIFF_BYTES)];
+    if let Err(message) = sniff::check_upload(file_name.as_deref(), head) {
+        return Err(ApiError::new(ErrorCode::Unprocessable, message));
+    }
+
+    let source_file = file_name.unwrap_or_else(|| "unnamed".to_string());
+    let mut temp_file = workbook::temp_file_for(Some(&source_file))
+        .map_err(|e| ApiError::internal(format!("Failed to create temp file: {}", e)))?;
+    temp_file
+        .write_all(&contents)
+        .map_err(|e| ApiError::internal(format!("Failed to write upload: {}", e)))?;
+
+    let (rows, errors) = match rate_upload::parse_workbook(temp_file.path()) {
+        Ok(parsed) => parsed,
+        Err(e) => return Err(ApiError::new(ErrorCode::InvalidBody, format!("Error processing file: {}", e))),
+    };
+
+    let upload = match state.uploads.begin(format!("rates '{}' ({} bytes)", source_file, contents.len())) {
+        Some(upload) => upload,
+        None => {
+            return Err(ApiError::new(
+                ErrorCode::Unavailable,
+                "Server is shutting down; retry the upload shortly",
+            ))
+        }
+    };
+
+    let pending_approval = state.runtime_config.load().require_rate_approval;
+    let result = async {
+        let mut client = get_postgres_client(state.pools.primary()).await?;
+        let mutation = state.begin_mutation().await;
+        let (inserted, skipped) = rate_upload::insert_rates(&mut client, &rows, &source_file, !pending_approval).await?;
+        Ok::<_, Box<dyn std::error::Error>>((mutation, inserted, skipped))
+    }
+    .await;
+    upload.finish();
+
+    match result {
+        Ok((mutation, inserted, skipped)) => {
+            info!(
+                "Rate upload '{}' by {}: {} inserted, {} skipped, {} rejected",
+                source_file,
+                user,
+                inserted,
+                skipped,
+                errors.len()
+            );
+            // Pending rates stay out of the table until approved, so there is nothing to refresh
+            if inserted > 0 && !pending_approval {
+                if let Err(e) = mutation.refresh().await {
+                    warn!("Failed to refresh virtual table after rate upload: {}", e);
+                }
+            }
+
+            let report = rate_upload::RateUploadReport {
+                inserted,
+                skipped,
+                error_count: errors.len(),
+                errors,
+            };
+            state.metrics.add_upload_rows("rates", report.inserted, 0, report.error_count);
+            let audit_id = audit::record(
+                &state,
+                &user,
+                "upload.rates",
+                "source_file",
+                Some(source_file.clone()),
+                json!({
+                    "inserted": report.inserted,
+                    "skipped": report.skipped,
+                    "rejected": report.error_count,
+                    "pending_approval": pending_approval
+                }),
+            )
+            .await;
+            let status = if report.errors.is_empty() { "success" } else { "partial" };
+            Ok(HttpResponse::Ok().json(json!({
+                "status": status,
+                "source_file": source_file,
+                "pending_approval": pending_approval,
+                "report": report,
+                "audit_id": audit_id
+            })))
+        }
+        Err(e) => Err(ApiError::database(format!("Failed to store rates: {}", e), e.as_ref())),
+    }
+}
+
+// Column mapping of each provider's latest upload, flagged when it differs from the upload before
+#[utoipa::path(
+    get,
+    path = "/providers/mappings",
+    tag = "uploads",
+    responses(
+        (status = 200, description = "Each provider's latest column mapping, flagged when it changed"),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn get_provider_mappings(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let result = async {
+        let client = state.pools.read_client().await?;
+        Ok::<_, Box<dyn std::error::Error>>(providers::load_provider_mappings(&client, None).await?)
+    }
+    .await;
+
+    match result {
+        Ok(providers) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "providers": providers
+        }))),
+        Err(e) => Err(ApiError::database(format!("Failed to load provider mappings: {}", e), e.as_ref())),
+    }
+}
+
+// Field-by-field diff between a provider's latest and previous upload
+#[utoipa::path(
+    get,
+    path = "/providers/{provider}/mappings/diff",
+    tag = "uploads",
+    params(("provider" = String, Path, description = "Provider named at upload")),
+    responses(
+        (status = 200, description = "Field-by-field diff against the previous upload"),
+        (status = 404, description = "No uploads from this provider", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn get_provider_mapping_diff(path: web::Path<String>, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let provider = path.into_inner();
+    let result = async {
+        let client = state.pools.read_client().await?;
+        Ok::<_, Box<dyn std::error::Error>>(providers::load_provider_mappings(&client, Some(&provider)).await?)
+    }
+    .await;
+
+    let found = match result {
+        Ok(mut providers) => providers.pop(),
+        Err(e) => return Err(ApiError::database(format!("Failed to load provider mappings: {}", e), e.as_ref())),
+    };
+    let found = match found {
+        Some(found) => found,
+        None => return Err(ApiError::not_found(format!("No uploads recorded for provider '{}'", provider))),
+    };
+
+    let changes = match &found.previous {
+        Some(previous) => providers::diff(&previous.column_mappings, &found.latest.column_mappings),
+        None => Vec::new(),
+    };
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "provider": found.provider,
+        "latest": found.latest,
+        "previous": found.previous,
+        "mapping_changed": found.mapping_changed,
+        "changes": changes
+    })))
+}
+
+const DEFAULT_UPLOAD_HISTORY_LIMIT: i64 = 50;
+const MAX_UPLOAD_HISTORY_LIMIT: i64 = 500;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UploadHistoryQuery {
+    limit: Option<i64>,
+}
+
+// Upload history, newest first, including rolled-back uploads
+#[utoipa::path(
+    get,
+    path = "/uploads",
+    tag = "uploads",
+    params(UploadHistoryQuery),
+    responses(
+        (status = 200, description = "Upload history, newest first"),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn list_uploads(query: web::Query<UploadHistoryQuery>, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_UPLOAD_HISTORY_LIMIT)
+        .clamp(1, MAX_UPLOAD_HISTORY_LIMIT);
+    let result = async {
+        let client = state.pools.read_client().await?;
+        Ok::<_, Box<dyn std::error::Error>>(uploads::list(&client, limit).await?)
+    }
+    .await;
+
+    match result {
+        Ok(entries) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "uploads": entries
+        }))),
+        Err(e) => Err(ApiError::database(format!("Failed to load uploads: {}", e), e.as_ref())),
+    }
+}
+
+// Undo a bad workbook: delete the funds it created, then rebuild the table
+#[utoipa::path(
+    delete,
+    path = "/uploads/{id}",
+    tag = "uploads",
+    security(("api_key" = []), ("bearer" = [])),
+    params(("id" = i32, Path, description = "Upload id"), ("confirm" = bool, Query, description = "Must be true")),
+    responses(
+        (status = 200, description = "Funds created by the upload deleted"),
+        (status = 400, description = "confirm=true missing", body = openapi::ErrorResponse),
+        (status = 401, description = "Missing or invalid credentials", body = openapi::ErrorResponse),
+        (status = 403, description = "Needs the admin role", body = openapi::ErrorResponse),
+        (status = 404, description = "No such upload", body = openapi::ErrorResponse),
+        (status = 409, description = "Upload still processing", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn purge_upload(
+    path: web::Path<i32>,
+    query: web::Query<HashMap<String, String>>,
+    state: web::Data<AppState>,
+    user: auth::RequestUser,
+) -> Result<HttpResponse, ApiError> {
+    user.require(auth::Role::Admin)?;
+    let id = path.into_inner();
+    require_confirmation(&query, "every fund the upload created")?;
+
+    let result = async {
+        let mut client = get_postgres_client(state.pools.primary()).await?;
+        let mutation = state.begin_mutation().await;
+        let purged = uploads::purge(&mut client, id).await?;
+        Ok::<_, Box<dyn std::error::Error>>(purged.map(|purged| purged.map(|report| (mutation, report))))
+    }
+    .await;
+
+    match result {
+        Ok(Some(Ok((mutation, report)))) => {
+            info!(
+                "Upload {} purged by {}: {} funds deleted, {} updated funds kept",
+                id, user, report.deleted, report.kept_updated
+            );
+            if report.deleted > 0 {
+                if let Err(e) = mutation.refresh().await {
+                    warn!("Failed to refresh virtual table after purging an upload: {}", e);
+                }
+            }
+            let audit_id =
+                audit::record(&state, &user, "upload.purge", "upload", Some(id.to_string()), json!({"report": report})).await;
+            Ok(HttpResponse::Ok().json(json!({
+                "status": "success",
+                "message": format!("Deleted {} funds created by upload {}", report.deleted, id),
+                "report": report,
+                "audit_id": audit_id
+            })))
+        }
+        Ok(Some(Err(status))) => Err(ApiError::new(
+            ErrorCode::Conflict,
+            format!("Upload {} is still {}; purge it once it finishes", id, status),
+        )),
+        Ok(None) => Err(ApiError::not_found(format!("No upload {}", id))),
+        Err(e) => Err(ApiError::database(format!("Failed to purge upload: {}", e), e.as_ref())),
+    }
+}
+
+// One upload with the funds whose current values it wrote
+#[utoipa::path(
+    get,
+    path = "/uploads/{id}",
+    tag = "uploads",
+    params(("id" = i32, Path, description = "Upload id")),
+    responses(
+        (status = 200, description = "The upload and the funds it wrote"),
+        (status = 404, description = "No such upload", body = openapi::ErrorResponse),
+        (status = 503, description = "Database busy or unavailable; retry after Retry-After", body = openapi::ErrorResponse)
+    )
+)]
+pub async fn get_upload(path: web::Path<i32>, state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    let result = async {
+        let client = state.pools.read_client().await?;
+        let upload = match uploads::get(&client, id).await? {
+            Some(upload) => upload,
+            None => return Ok(None),
+        };
+        let funds = uploads::funds_for(&client, id).await?;
+        Ok::<_, Box<dyn std::error::Error>>(Some((upload, funds)))
+    }
+    .await;
+
+    match result {
+        Ok(Some((upload, funds))) => Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "upload": upload,
+            "funds": funds
+        }))),
+        Ok(None) => Err(ApiError::not_found(format!("No upload {}", id))),
+        Err(e) => Err(ApiError::database(format!("Failed to load upload: {}", e), e.as_ref())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use serde_json::json;
+    use sha2::{Digest, Sha256};
+
+    use crate::config::RuntimeConfig;
+    use crate::test_support;
+
+    #[actix_web::test]
+    async fn the_size_limit_covers_every_file_and_only_known_fields_are_accepted() {
+        let config = RuntimeConfig {
+            max_upload_bytes: 100,
+            ..test_support::runtime_config()
+        };
+        let state = test_support::state(config, vec![]);
+        let upload = |parts: &[(&str, Option<&str>, &[u8])]| {
+            test_support::as_admin(test_support::multipart("/api/v1/upload?dry_run=true", parts))
+        };
+        let file = [b'a'; 60];
+
+        // Each file fits; together they don't
+        let two_files = upload(&[("excel_file", Some("a.csv"), &file), ("excel_file", Some("b.csv"), &file)]);
+        let (status, body) = test_support::call_json(&state, two_files).await;
+        assert_eq!((status, body["details"]["max_upload_bytes"].as_u64()), (StatusCode::PAYLOAD_TOO_LARGE, Some(100)));
+
+        let misnamed = upload(&[("excel_file", Some("a.csv"), &file), ("file", Some("b.csv"), b"x")]);
+        let (status, body) = test_support::call_json(&state, misnamed).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["message"], "Unexpected form field 'file'; send files as 'excel_file' and optionally 'provider'");
+
+        // A file input left blank arrives as an empty, unnamed part
+        let blank = upload(&[("excel_file", None, b""), ("provider", None, b"amc")]);
+        let (status, body) = test_support::call_json(&state, blank).await;
+        assert_eq!((status, body["message"].as_str()), (StatusCode::BAD_REQUEST, Some("No file was uploaded")));
+    }
+
+    #[actix_web::test]
+    async fn uploads_are_recorded_with_their_checksum_counts_and_funds() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let first: &[u8] =
+            b"Scheme Name,Launch Date\nParag Parikh Flexi Cap Fund,2013-05-24\nQuant Flexi Cap Fund,2008-10-17\n";
+        let second: &[u8] = b"Scheme Name,Launch Date\nQuant Flexi Cap Fund,2008-10-17\n";
+        let mut upload_ids = Vec::new();
+        for csv in [first, second] {
+            let job = test_support::upload(&db.state, &[("Flexi Cap.csv", csv)]).await;
+            upload_ids.push(job["summary"]["files"][0]["report"]["upload_id"].as_i64().expect("an upload id"));
+        }
+
+        let (status, body) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/uploads")).await;
+        assert_eq!(status, 200, "{}", body);
+        let history = body["uploads"].as_array().unwrap();
+        let ids: Vec<i64> = history.iter().map(|upload| upload["id"].as_i64().unwrap()).collect();
+        assert_eq!(ids, [upload_ids[1], upload_ids[0]]);
+        let oldest = &history[1];
+        assert_eq!(oldest["sha256"], format!("{:x}", Sha256::digest(first)));
+        assert_eq!(oldest["file_name"], "Flexi Cap.csv");
+        assert_eq!((&oldest["inserted"], &oldest["updated"]), (&json!(2), &json!(0)));
+        assert_eq!(oldest["status"], "committed");
+        assert_eq!((&history[0]["inserted"], &history[0]["updated"]), (&json!(0), &json!(1)));
+
+        // The second upload rewrote one fund, so the first only accounts for the other
+        let request = TestRequest::get().uri(&format!("/api/v1/uploads/{}", upload_ids[0]));
+        let (_, body) = test_support::call_json(&db.state, request).await;
+        let funds: Vec<&str> = body["funds"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|fund| fund["scheme_name"].as_str().unwrap())
+            .collect();
+        assert_eq!(funds, ["Parag Parikh Flexi Cap Fund"]);
+        let (status, _) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/uploads/999")).await;
+        assert_eq!(status, 404);
+    }
+
+    #[actix_web::test]
+    async fn a_callers_request_id_is_echoed_and_kept_with_its_upload() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let csv: &[u8] = b"Scheme Name,Launch Date\nParag Parikh Flexi Cap Fund,2013-05-24\n";
+        let request = test_support::multipart("/api/v1/upload", &[("excel_file", Some("Flexi Cap.csv"), csv)])
+            .insert_header(("X-Request-Id", "support-ticket-581"));
+        let response = test_support::call(&db.state, test_support::as_admin(request)).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(response.headers().get("x-request-id").unwrap(), "support-ticket-581");
+        let accepted: serde_json::Value = actix_web::test::read_body_json(response).await;
+
+        // The import runs after the response, still under the caller's id
+        let job_url = accepted["job_url"].as_str().unwrap().to_string();
+        let mut job = json!(null);
+        for _ in 0..500 {
+            job = test_support::call_json(&db.state, TestRequest::get().uri(&job_url)).await.1;
+            if job["state"] == "done" || job["state"] == "failed" {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(job["state"], "done", "{}", job);
+        let rows = db.query("SELECT request_id FROM uploads").await;
+        let ids: Vec<Option<String>> = rows.iter().map(|row| row.get(0)).collect();
+        assert_eq!(ids, [Some("support-ticket-581".to_string())]);
+
+        let (_, body) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/uploads")).await;
+        assert_eq!(body["uploads"][0]["request_id"], "support-ticket-581");
+    }
+
+    #[actix_web::test]
+    async fn purging_an_upload_deletes_only_the_funds_it_created() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let good: &[u8] = b"Scheme Name,Launch Date\nQuant Flexi Cap Fund,2008-10-17\n";
+        let bad: &[u8] = b"Scheme Name,Launch Date\nQuant Flexi Cap Fund,2008-10-17\nPast returns may not be sustained,2024-01-01\n";
+        let mut upload_ids = Vec::new();
+        for csv in [good, bad] {
+            let job = test_support::upload(&db.state, &[("Flexi Cap.csv", csv)]).await;
+            upload_ids.push(job["summary"]["files"][0]["report"]["upload_id"].as_i64().expect("an upload id"));
+        }
+        assert_eq!(db.state.virtual_table.load().len(), 2);
+        let purge = |query: &str| {
+            test_support::as_admin(TestRequest::delete().uri(&format!("/api/v1/uploads/{}{}", upload_ids[1], query)))
+        };
+
+        for unconfirmed in ["", "?confirm=yes"] {
+            let (status, body) = test_support::call_json(&db.state, purge(unconfirmed)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", unconfirmed);
+            assert_eq!(body["details"]["parameter"], "confirm");
+        }
+        let (status, body) = test_support::call_json(&db.state, purge("?confirm=true")).await;
+        assert_eq!(status, 200, "{}", body);
+        // The fund the earlier upload created survives, with the values the purged one wrote
+        let report = &body["report"];
+        assert_eq!((&report["deleted"], &report["kept_updated"]), (&json!(1), &json!(1)));
+        assert_eq!(report["deleted_schemes"], json!(["Past returns may not be sustained"]));
+        let names: Vec<String> = db.query("SELECT scheme_name FROM funds").await.iter().map(|row| row.get(0)).collect();
+        assert_eq!(names, ["Quant Flexi Cap Fund"]);
+        assert_eq!(db.state.virtual_table.load().len(), 1);
+
+        let request = TestRequest::get().uri(&format!("/api/v1/uploads/{}", upload_ids[1]));
+        let (_, body) = test_support::call_json(&db.state, request).await;
+        assert_eq!(body["upload"]["status"], "purged");
+        let missing = test_support::as_admin(TestRequest::delete().uri("/api/v1/uploads/999?confirm=true"));
+        assert_eq!(test_support::call_json(&db.state, missing).await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn each_file_of_an_upload_is_reported_and_a_bad_one_spares_the_rest() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let files: [(&str, &[u8]); 3] = [
+            ("Flexi Cap.csv", b"Scheme Name,Launch Date\nParag Parikh Flexi Cap Fund,2013-05-24\n"),
+            // Passes the signature check, then fails to open as a workbook
+            ("Mid Cap.xlsx", b"PK\x03\x04 truncated in transit"),
+            ("Small Cap.csv", b"Scheme Name,Launch Date\nQuant Small Cap Fund,2013-01-01\n"),
+        ];
+
+        let job = test_support::upload(&db.state, &files).await;
+
+        assert_eq!(job["state"], "done", "{}", job);
+        assert_eq!(job["summary"]["status"], "partial", "{}", job);
+        let outcomes: Vec<(&str, &str)> = job["summary"]["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| (file["file_name"].as_str().unwrap(), file["status"].as_str().unwrap()))
+            .collect();
+        assert_eq!(outcomes, [("Flexi Cap.csv", "success"), ("Mid Cap.xlsx", "error"), ("Small Cap.csv", "success")]);
+
+        let table = db.state.virtual_table.load();
+        let mut names: Vec<&str> = table.records().iter().map(|record| &*record.scheme_name).collect();
+        names.sort();
+        assert_eq!(names, ["Parag Parikh Flexi Cap Fund", "Quant Small Cap Fund"]);
+    }
+
+    #[actix_web::test]
+    async fn a_dry_run_writes_nothing_and_reports_what_the_upload_then_does() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        db.execute("INSERT INTO funds (category, scheme_name, latest_nav) VALUES ('Flexi Cap', 'Quant Flexi Cap Fund', 90)")
+            .await;
+        crate::refresh_virtual_table(&db.state).await.unwrap();
+        let table = db.state.virtual_table.load_full();
+        // One existing fund, one new one and a duplicate row of it
+        let csv: &[u8] = b"Scheme Name,Launch Date\nQuant Flexi Cap Fund,2008-10-17\n\
+                           Parag Parikh Flexi Cap Fund,2013-05-24\nParag Parikh Flexi Cap Fund,2013-05-24\n";
+
+        let parts = [("excel_file", Some("Flexi Cap.csv"), csv)];
+        let request = test_support::multipart("/api/v1/upload?dry_run=true", &parts);
+        let (status, body) = test_support::call_json(&db.state, test_support::as_admin(request)).await;
+        assert_eq!((status, &body["dry_run"]), (StatusCode::OK, &json!(true)), "{}", body);
+        let dry_run = body["files"][0]["report"].clone();
+        assert_eq!((&dry_run["new_schemes"], &dry_run["updated_schemes"]), (&json!(1), &json!(1)), "{}", dry_run);
+
+        let funds: Vec<String> = db.query("SELECT scheme_name FROM funds").await.iter().map(|row| row.get(0)).collect();
+        assert_eq!(funds, ["Quant Flexi Cap Fund"]);
+        assert!(db.query("SELECT id FROM uploads").await.is_empty());
+        // Not even rebuilt: the served table is the same one
+        assert!(std::sync::Arc::ptr_eq(&db.state.virtual_table.load_full(), &table));
+
+        let job = test_support::upload(&db.state, &[("Flexi Cap.csv", csv)]).await;
+        let report = &job["summary"]["files"][0]["report"];
+        assert_eq!(report["committed"], true, "{}", job);
+        assert_eq!((&report["inserted"], &report["updated"]), (&dry_run["new_schemes"], &dry_run["updated_schemes"]));
+        let shared = [
+            "merged_count", "merged_schemes", "row_errors", "sheet_errors", "category_issues", "column_mappings",
+            "sheets", "unmapped_sheets",
+        ];
+        for field in shared {
+            assert_eq!(report[field], dry_run[field], "{}", field);
+        }
+        assert_eq!(db.query("SELECT id FROM funds").await.len(), 2);
+        assert_eq!(db.state.virtual_table.load().len(), 2);
+    }
+}
@@ -0,0 +1,138 @@
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::Serialize;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{try_refresh_virtual_table, AppState};
+
+// Outcome of the latest full rebuilds (scheduled, manual or after a mutation), for /status
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RefreshStatus {
+    pub interval_secs: u64,
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<u64>,
+    pub last_record_count: Option<usize>,
+    pub last_error: Option<String>,
+    // Scheduled ticks dropped because another refresh was still running
+    pub skipped_ticks: u64,
+    // Refreshes finished, successfully or not; lets a waiting /refresh tell whether one ran
+    pub finished: u64,
+}
+
+impl RefreshStatus {
+    pub fn record_success(&mut self, started: Instant, record_count: usize) {
+        self.last_success_at = Some(Utc::now());
+        self.last_duration_ms = Some(started.elapsed().as_millis() as u64);
+        self.last_record_count = Some(record_count);
+        self.last_error = None;
+        self.finished += 1;
+    }
+
+    pub fn record_failure(&mut self, error: String) {
+        self.last_error = Some(error);
+        self.finished += 1;
+    }
+}
+
+// Rebuild the virtual table every `interval` (plus up to 10% jitter, so replicas started together
+// don't hit the database in lockstep). The next wait only starts once a refresh has finished, and a
+// tick that finds a manual or post-upload refresh still running is skipped rather than queued.
+pub fn spawn(state: AppState, interval: Duration) {
+    state.refresh_status.lock().unwrap().interval_secs = interval.as_secs();
+    info!("Scheduled virtual table refresh every {}s", interval.as_secs());
+
+    actix_web::rt::spawn(async move {
+        loop {
+            tokio::time::sleep(interval + jitter(interval)).await;
+            if state.uploads.is_draining() {
+                break;
+            }
+
+            let started = Instant::now();
+            match try_refresh_virtual_table(&state).await {
+                None => {
+                    state.refresh_status.lock().unwrap().skipped_ticks += 1;
+                    info!("Scheduled refresh skipped: a refresh is already running");
+                }
+                Some(Ok(())) => info!(
+                    "Scheduled refresh finished in {}ms with {} records",
+                    started.elapsed().as_millis(),
+                    state.virtual_table.load().len()
+                ),
+                Some(Err(e)) => warn!(
+                    "Scheduled refresh failed after {}ms: {}",
+                    started.elapsed().as_millis(),
+                    e
+                ),
+            }
+        }
+    });
+}
+
+// No rand dependency; the clock's sub-second part is spread enough for this
+fn jitter(interval: Duration) -> Duration {
+    let max_ms = interval.as_millis() as u64 / 10;
+    if max_ms == 0 {
+        return Duration::ZERO;
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos() as u64)
+        .unwrap_or(0);
+    Duration::from_millis(nanos % max_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    use crate::test_support;
+
+    #[test]
+    fn jitter_stays_under_a_tenth_of_the_interval() {
+        for _ in 0..100 {
+            assert!(jitter(Duration::from_secs(60)) < Duration::from_secs(6));
+        }
+        assert_eq!(jitter(Duration::from_millis(5)), Duration::ZERO);
+    }
+
+    #[actix_web::test]
+    async fn scheduled_refreshes_pick_up_direct_writes_and_skip_ticks_while_one_runs() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        // Written behind the app's back, as another service would
+        db.execute("INSERT INTO funds (category, scheme_name, latest_nav) VALUES ('Flexi Cap', 'Direct Fund', 10)")
+            .await;
+        spawn(db.state.clone(), Duration::from_millis(50));
+
+        for _ in 0..200 {
+            if db.state.virtual_table.load().len() == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+        assert_eq!(db.state.virtual_table.load().len(), 1);
+        let (status, stats) = test_support::call_json(&db.state, TestRequest::get().uri("/api/v1/stats")).await;
+        assert_eq!(status, 200, "{}", stats);
+        assert!(stats["last_refresh"]["at"].is_string(), "{}", stats);
+        assert_eq!(stats["last_refresh"]["record_count"], 1);
+
+        // A tick that finds a refresh running is dropped, not queued behind it
+        let running = db.state.refresh_lock.lock().await;
+        let finished = db.state.refresh_status.lock().unwrap().finished;
+        for _ in 0..200 {
+            if db.state.refresh_status.lock().unwrap().skipped_ticks > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+        let status = db.state.refresh_status.lock().unwrap().clone();
+        assert!(status.skipped_ticks > 0);
+        assert_eq!(status.finished, finished);
+        drop(running);
+        db.state.uploads.start_draining();
+    }
+}
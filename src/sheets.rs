@@ -0,0 +1,157 @@
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+// Which workbook sheets an upload imports. Skip entries are case-insensitive globs (`*` any run,
+// `?` one character), so "Main Page" and "index*" both work; when an include pattern is set, only
+// sheets matching that (case-insensitive) regex are imported. Skip entries win over the include.
+#[derive(Debug, Clone)]
+pub struct SheetSelection {
+    skip: Vec<String>,
+    include: Option<Regex>,
+}
+
+impl PartialEq for SheetSelection {
+    fn eq(&self, other: &Self) -> bool {
+        self.skip == other.skip && self.include_pattern() == other.include_pattern()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SheetDecision {
+    pub sheet: String,
+    // "processed" or "skipped: <why>"
+    pub decision: String,
+}
+
+impl SheetSelection {
+    pub fn new(skip: Vec<String>) -> Self {
+        Self { skip, include: None }
+    }
+
+    pub fn with_include(mut self, pattern: Option<&str>) -> Result<Self, String> {
+        self.include = match pattern {
+            Some(pattern) => Some(
+                RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| format!("include_sheets '{}' is not a valid regex: {}", pattern, e))?,
+            ),
+            None => None,
+        };
+        Ok(self)
+    }
+
+    pub fn skip(&self) -> &[String] {
+        &self.skip
+    }
+
+    pub fn include_pattern(&self) -> Option<&str> {
+        self.include.as_ref().map(Regex::as_str)
+    }
+
+    // None when the sheet should be imported, otherwise the reason it is skipped
+    pub fn skip_reason(&self, sheet: &str) -> Option<String> {
+        if let Some(pattern) = self.skip.iter().find(|pattern| glob_matches(pattern, sheet)) {
+            return Some(format!("matched skip pattern '{}'", pattern));
+        }
+        match &self.include {
+            Some(include) if !include.is_match(sheet) => {
+                Some(format!("did not match include pattern '{}'", include.as_str()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl SheetDecision {
+    pub fn new(sheet: &str, skip_reason: Option<&str>) -> Self {
+        Self {
+            sheet: sheet.to_string(),
+            decision: match skip_reason {
+                Some(reason) => format!("skipped: {}", reason),
+                None => "processed".to_string(),
+            },
+        }
+    }
+}
+
+// Case-insensitive, whole-name match; surrounding whitespace is ignored on both sides
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.trim().to_lowercase().chars().collect();
+    let name: Vec<char> = name.trim().to_lowercase().chars().collect();
+
+    // Iterative wildcard match, backtracking to the most recent '*'
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skip_globs_match_whole_names_ignoring_case_and_surrounding_space() {
+        let cases = [
+            ("Main Page", "main page", true),
+            (" INDEX* ", "Index of Funds", true),
+            ("index*", "Fund Index", false),
+            ("*cap*", "Small Cap Funds", true),
+            ("Sheet?", "Sheet1", true),
+            ("Sheet?", "Sheet12", false),
+            ("Sheet?", "Sheet", false),
+            ("*", "", true),
+            ("a*b*c", "aXbYbZc", true),
+            ("a*b*c", "aXbYc d", false),
+            ("Disclaimer", "Disclaimers", false),
+        ];
+
+        for (pattern, sheet, expected) in cases {
+            assert_eq!(glob_matches(pattern, sheet), expected, "{} against {}", pattern, sheet);
+        }
+    }
+
+    #[test]
+    fn skip_patterns_win_over_the_include_regex_and_a_bad_regex_is_refused() {
+        let selection = SheetSelection::new(vec!["index*".to_string(), "Small Cap Archive".to_string()])
+            .with_include(Some("cap$"))
+            .unwrap();
+
+        assert_eq!(selection.skip_reason("Flexi Cap"), None);
+        assert_eq!(selection.skip_reason("LARGE CAP"), None);
+        assert_eq!(selection.skip_reason("Index Cap").as_deref(), Some("matched skip pattern 'index*'"));
+        assert_eq!(selection.skip_reason("Debt").as_deref(), Some("did not match include pattern 'cap$'"));
+        assert_eq!(
+            selection.skip_reason("small cap archive").as_deref(),
+            Some("matched skip pattern 'Small Cap Archive'")
+        );
+        // Without an include every sheet but the skipped ones is imported
+        let skip_only = SheetSelection::new(vec!["index*".to_string()]).with_include(None).unwrap();
+        assert_eq!(skip_only.skip_reason("Debt"), None);
+        assert_eq!(skip_only.include_pattern(), None);
+
+        let error = SheetSelection::new(Vec::new()).with_include(Some("cap(")).unwrap_err();
+        assert!(error.starts_with("include_sheets 'cap(' is not a valid regex: "), "{}", error);
+        assert_eq!(
+            SheetDecision::new("Debt", Some("did not match include pattern 'cap$'")).decision,
+            "skipped: did not match include pattern 'cap$'"
+        );
+        assert_eq!(SheetDecision::new("Flexi Cap", None).decision, "processed");
+    }
+}
@@ -0,0 +1,223 @@
+use log::{info, warn};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const IDLE_POLL: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+struct InFlightUpload {
+    description: String,
+    started: Instant,
+}
+
+// Uploads currently being processed, so a shutdown can report what it is waiting on
+#[derive(Debug, Default)]
+pub struct UploadTracker {
+    next_id: AtomicU64,
+    uploads: Mutex<HashMap<u64, InFlightUpload>>,
+    draining: AtomicBool,
+}
+
+impl UploadTracker {
+    // None once shutdown has started: new uploads are refused rather than cut off mid-way
+    pub fn begin(self: &Arc<Self>, description: String) -> Option<UploadGuard> {
+        if self.is_draining() {
+            return None;
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.uploads.lock().unwrap().insert(
+            id,
+            InFlightUpload {
+                description,
+                started: Instant::now(),
+            },
+        );
+
+        Some(UploadGuard {
+            tracker: Arc::clone(self),
+            id,
+            finished: false,
+        })
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    // Stop admitting uploads and log what is still running
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+
+        let uploads = self.uploads.lock().unwrap();
+        if uploads.is_empty() {
+            info!("Shutdown: no uploads in flight");
+            return;
+        }
+        info!("Shutdown: waiting for {} in-flight upload(s)", uploads.len());
+        for upload in uploads.values() {
+            info!(
+                "Shutdown: upload {} running for {:.1}s",
+                upload.description,
+                upload.started.elapsed().as_secs_f64()
+            );
+        }
+    }
+
+    // Resolves once nothing is in flight or the timeout passes, whichever is first. Background
+    // upload jobs outlive their request, so the server's own connection drain doesn't cover them.
+    pub async fn wait_idle(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        while !self.uploads.lock().unwrap().is_empty() {
+            if Instant::now() >= deadline {
+                warn!("Shutdown: grace period over with uploads still running");
+                return;
+            }
+            tokio::time::sleep(IDLE_POLL).await;
+        }
+    }
+}
+
+// Removes its upload from the tracker when dropped. Dropped without `finish` during a shutdown
+// means the grace period ran out and the handler was cancelled.
+pub struct UploadGuard {
+    tracker: Arc<UploadTracker>,
+    id: u64,
+    finished: bool,
+}
+
+impl UploadGuard {
+    pub fn finish(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for UploadGuard {
+    fn drop(&mut self) {
+        let upload = self.tracker.uploads.lock().unwrap().remove(&self.id);
+        if let Some(upload) = upload {
+            if !self.finished && self.tracker.is_draining() {
+                warn!(
+                    "Shutdown: upload {} cancelled after {:.1}s; its database changes were rolled back",
+                    upload.description,
+                    upload.started.elapsed().as_secs_f64()
+                );
+            }
+        }
+    }
+}
+
+// Refuse new uploads, give running ones up to `grace` to finish, then stop the server. Workers
+// drop their spawned jobs when they stop, so the uploads go first; the caller closes the pools once
+// the server has stopped. `stop_server` is only called then: actix's stop acts when called, not
+// when awaited.
+pub async fn drain<S, F>(uploads: &UploadTracker, grace: Duration, stop_server: S)
+where
+    S: FnOnce() -> F,
+    F: Future<Output = ()>,
+{
+    uploads.start_draining();
+    info!("Waiting up to {}s for in-flight requests", grace.as_secs());
+    uploads.wait_idle(grace).await;
+    stop_server().await;
+}
+
+// Resolves on SIGTERM or Ctrl-C
+pub async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = terminate.recv() => info!("SIGTERM received, shutting down"),
+                    _ = tokio::signal::ctrl_c() => info!("Ctrl-C received, shutting down"),
+                }
+                return;
+            }
+            Err(e) => warn!("Failed to install SIGTERM handler: {}", e),
+        }
+    }
+
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        warn!("Failed to listen for Ctrl-C: {}", e);
+        std::future::pending::<()>().await;
+    }
+    info!("Ctrl-C received, shutting down");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_support;
+
+    #[actix_web::test]
+    async fn a_drain_waits_for_an_upload_to_commit_before_stopping_the_server() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        let uploads = Arc::clone(&db.state.uploads);
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        // An upload mid-transaction when the shutdown starts
+        let guard = uploads.begin("Flexi Cap.xlsx".to_string()).unwrap();
+        let mut client = db.state.pools.primary().get().await.unwrap();
+        let (started, wrote) = tokio::sync::oneshot::channel();
+        let upload_events = Arc::clone(&events);
+        let upload = tokio::spawn(async move {
+            let transaction = client.transaction().await.unwrap();
+            transaction
+                .execute("INSERT INTO funds (category, scheme_name) VALUES ('Flexi Cap', 'Quant Flexi Cap Fund')", &[])
+                .await
+                .unwrap();
+            started.send(()).unwrap();
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            transaction.commit().await.unwrap();
+            upload_events.lock().unwrap().push("upload committed");
+            guard.finish();
+        });
+        wrote.await.unwrap();
+
+        let drained = Instant::now();
+        drain(&uploads, Duration::from_secs(30), || async {
+            events.lock().unwrap().push("server stopped");
+        })
+        .await;
+
+        assert_eq!(*events.lock().unwrap(), ["upload committed", "server stopped"]);
+        assert!(drained.elapsed() < Duration::from_secs(5));
+        upload.await.unwrap();
+        assert!(uploads.begin("Small Cap.xlsx".to_string()).is_none(), "a draining server refused no upload");
+        assert_eq!(db.query("SELECT id FROM funds").await.len(), 1);
+        // Nothing still needs the pools, so they can close
+        db.state.pools.close();
+        assert!(db.state.pools.primary().get().await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn a_drain_stops_the_server_at_the_grace_limit_with_an_upload_still_running() {
+        let uploads = Arc::new(UploadTracker::default());
+        let stuck = uploads.begin("Stuck.xlsx".to_string()).unwrap();
+        let stopped = Mutex::new(None);
+
+        let started = Instant::now();
+        drain(&uploads, Duration::from_millis(300), || async {
+            *stopped.lock().unwrap() = Some(started.elapsed());
+        })
+        .await;
+
+        let stopped = stopped.into_inner().unwrap().expect("the server was stopped");
+        assert!(stopped >= Duration::from_millis(300), "{:?}", stopped);
+        assert!(stopped < Duration::from_millis(300) + IDLE_POLL * 5, "{:?}", stopped);
+        assert_eq!(uploads.uploads.lock().unwrap().len(), 1);
+        // Cancelled with the worker, it leaves the tracker
+        drop(stuck);
+        assert!(uploads.uploads.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,282 @@
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::rate_matches::UnmatchedRate;
+use crate::table::{TableBuilder, VirtualTable};
+use crate::CombinedSchemeData;
+
+// Bump whenever the header layout or the payload types change; older files are then rebuilt
+pub const FORMAT_VERSION: u32 = 4;
+
+const MAGIC: &[u8; 8] = b"PTSNAP\0\0";
+// magic + format version + record count + generation + source timestamp + payload length + sha256
+const HEADER_LEN: usize = 8 + 4 + 8 + 8 + 8 + 8 + 32;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotHeader {
+    pub format_version: u32,
+    pub record_count: u64,
+    // data_generation_seq value the table was built from
+    pub generation: i64,
+    pub source_timestamp: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+struct Payload {
+    data: Vec<CombinedSchemeData>,
+    unmatched_rates: Vec<UnmatchedRate>,
+    name_aliases: HashMap<String, String>,
+}
+
+// Serializes identically to Payload without cloning the table
+#[derive(Serialize)]
+struct PayloadRef<'a> {
+    data: &'a [CombinedSchemeData],
+    unmatched_rates: &'a [UnmatchedRate],
+    name_aliases: &'a HashMap<String, String>,
+}
+
+// How a snapshot built at `header.generation` compares with the database at `db_generation`:
+// Err when it is newer, so it may hold rows the database no longer has (restored or rolled back
+// since) and must not be served; Ok(Some) with why an older one is stale; Ok(None) when current
+pub fn check_generation(header: &SnapshotHeader, db_generation: i64) -> Result<Option<String>, String> {
+    if header.generation > db_generation {
+        Err(format!(
+            "snapshot generation {} is newer than the database ({})",
+            header.generation, db_generation
+        ))
+    } else if header.generation < db_generation {
+        Ok(Some(format!(
+            "snapshot generation {} is older than the database ({})",
+            header.generation, db_generation
+        )))
+    } else {
+        Ok(None)
+    }
+}
+
+// What happened to the snapshot at startup and on the latest write, for /status
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SnapshotStatus {
+    pub loaded: Option<SnapshotHeader>,
+    // Why the loaded snapshot was served only until a background rebuild replaced it
+    pub stale: Option<String>,
+    // Why the snapshot on disk was not served at all
+    pub rejected: Option<String>,
+    pub last_written: Option<SnapshotHeader>,
+    pub last_write_error: Option<String>,
+}
+
+// Binary layout (all integers little-endian):
+//   magic[8] | format_version u32 | record_count u64 | generation i64 | source_timestamp i64 (unix secs)
+//   | payload_len u64 | sha256(payload)[32] | payload (bincode)
+pub fn write(path: &Path, table: &VirtualTable, built_at: DateTime<Utc>) -> Result<SnapshotHeader, String> {
+    let payload = bincode::serialize(&PayloadRef {
+        data: table.records(),
+        unmatched_rates: &table.unmatched_rates,
+        name_aliases: table.name_aliases(),
+    })
+    .map_err(|e| format!("cannot encode snapshot: {}", e))?;
+
+    let header = SnapshotHeader {
+        format_version: FORMAT_VERSION,
+        record_count: table.len() as u64,
+        generation: table.generation,
+        source_timestamp: built_at,
+    };
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&header.format_version.to_le_bytes());
+    bytes.extend_from_slice(&header.record_count.to_le_bytes());
+    bytes.extend_from_slice(&header.generation.to_le_bytes());
+    bytes.extend_from_slice(&header.source_timestamp.timestamp().to_le_bytes());
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&Sha256::digest(&payload));
+    bytes.extend_from_slice(&payload);
+
+    crate::artifacts::write_atomically(path, &bytes)
+        .map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+    Ok(header)
+}
+
+// Load and verify a snapshot. Err is the human-readable reason it was rejected (unreadable,
+// another format version, corrupt, or a payload that no longer decodes); the caller falls back to
+// a database build in every such case. Whether it is current is a separate question, see check_generation.
+pub fn read(path: &Path) -> Result<(VirtualTable, SnapshotHeader), String> {
+    let bytes = fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    if bytes.len() < HEADER_LEN {
+        return Err(format!("truncated header ({} bytes)", bytes.len()));
+    }
+    if &bytes[0..8] != MAGIC {
+        return Err("not a perftracker snapshot".to_string());
+    }
+
+    let u64_at = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+    let format_version = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+    let record_count = u64_at(12);
+    let generation = u64_at(20) as i64;
+    let timestamp = u64_at(28) as i64;
+    let payload_len = u64_at(36) as usize;
+    let checksum = &bytes[44..76];
+    let payload = &bytes[HEADER_LEN..];
+
+    if format_version != FORMAT_VERSION {
+        return Err(format!(
+            "format version {} is not supported (expected {})",
+            format_version, FORMAT_VERSION
+        ));
+    }
+    if payload.len() != payload_len {
+        return Err(format!("payload is {} bytes, header says {}", payload.len(), payload_len));
+    }
+    if Sha256::digest(payload).as_slice() != checksum {
+        return Err("checksum mismatch".to_string());
+    }
+    let payload: Payload = bincode::deserialize(payload).map_err(|e| format!("payload does not decode: {}", e))?;
+    if payload.data.len() as u64 != record_count {
+        return Err(format!(
+            "payload has {} records, header says {}",
+            payload.data.len(),
+            record_count
+        ));
+    }
+
+    let mut table = VirtualTable::new();
+    table.set_name_aliases(payload.name_aliases);
+    let mut builder = TableBuilder::new(table);
+    for record in payload.data {
+        builder.add_record(record);
+    }
+    let mut table = builder.finish();
+    table.unmatched_rates = payload.unmatched_rates;
+    table.generation = generation;
+
+    let header = SnapshotHeader {
+        format_version,
+        record_count,
+        generation,
+        source_timestamp: Utc.timestamp_opt(timestamp, 0).single().unwrap_or_default(),
+    };
+    Ok((table, header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn table() -> VirtualTable {
+        let mut table = VirtualTable::new();
+        let alias = ("ppfas flexi cap regular".to_string(), "parag parikh flexi cap fund".to_string());
+        table.set_name_aliases(HashMap::from([alias]));
+        for (id, name) in [(1, "Parag Parikh Flexi Cap Fund"), (2, "Quant Small Cap Fund"), (3, "HDFC Top 100 Fund")] {
+            let mut record = CombinedSchemeData::test_fund(id, name);
+            record.fund_category = Some("Flexi Cap".into());
+            record.year_1 = Some(id as f32 * 1.5);
+            table.add_record(record);
+        }
+        table.generation = 7;
+        table
+    }
+
+    // A freshly written snapshot of `table()`, and the directory keeping it alive
+    fn written() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("table.snapshot");
+        let built_at = Utc.with_ymd_and_hms(2025, 6, 1, 9, 30, 0).unwrap();
+        write(&path, &table(), built_at).unwrap();
+        (dir, path)
+    }
+
+    fn rewrite(path: &Path, edit: impl FnOnce(&mut Vec<u8>)) {
+        let mut bytes = fs::read(path).unwrap();
+        edit(&mut bytes);
+        fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn a_snapshot_reads_back_as_the_table_it_was_written_from() {
+        let (_dir, path) = written();
+        let (loaded, header) = read(&path).unwrap();
+
+        assert_eq!(header.format_version, FORMAT_VERSION);
+        assert_eq!(header.record_count, 3);
+        assert_eq!(header.generation, 7);
+        assert_eq!(header.source_timestamp, Utc.with_ymd_and_hms(2025, 6, 1, 9, 30, 0).unwrap());
+
+        let original = table();
+        assert_eq!(loaded.generation, original.generation);
+        assert_eq!(loaded.name_aliases(), original.name_aliases());
+        assert_eq!(
+            serde_json::to_value(loaded.records()).unwrap(),
+            serde_json::to_value(original.records()).unwrap()
+        );
+        // The indexes are rebuilt, not just the records
+        let (record, _) = loaded.lookup_name("hdfc top 100 fund", false, 0.85, None, 1000).unwrap();
+        assert_eq!(record.fund_id, Some(3));
+    }
+
+    #[test]
+    fn truncated_files_are_rejected() {
+        let (_dir, path) = written();
+        let full = fs::read(&path).unwrap();
+
+        fs::write(&path, &full[..HEADER_LEN - 1]).unwrap();
+        assert_eq!(read(&path).err().unwrap(), format!("truncated header ({} bytes)", HEADER_LEN - 1));
+
+        fs::write(&path, &full[..full.len() - 10]).unwrap();
+        let reason = read(&path).err().unwrap();
+        assert!(reason.starts_with("payload is"), "{}", reason);
+    }
+
+    #[test]
+    fn a_flipped_payload_byte_fails_the_checksum() {
+        let (_dir, path) = written();
+        rewrite(&path, |bytes| {
+            let middle = HEADER_LEN + (bytes.len() - HEADER_LEN) / 2;
+            bytes[middle] ^= 0x01;
+        });
+        assert_eq!(read(&path).err().unwrap(), "checksum mismatch");
+    }
+
+    #[test]
+    fn another_format_version_or_file_type_is_rejected() {
+        let (_dir, path) = written();
+        rewrite(&path, |bytes| bytes[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes()));
+        assert_eq!(
+            read(&path).err().unwrap(),
+            format!("format version {} is not supported (expected {})", FORMAT_VERSION + 1, FORMAT_VERSION)
+        );
+
+        rewrite(&path, |bytes| bytes[0] = b'X');
+        assert_eq!(read(&path).err().unwrap(), "not a perftracker snapshot");
+    }
+
+    #[test]
+    fn a_record_count_disagreeing_with_the_payload_is_rejected() {
+        let (_dir, path) = written();
+        rewrite(&path, |bytes| bytes[12..20].copy_from_slice(&4u64.to_le_bytes()));
+        assert_eq!(read(&path).err().unwrap(), "payload has 3 records, header says 4");
+    }
+
+    #[test]
+    fn newer_snapshots_are_rejected_and_older_ones_stale() {
+        let (_dir, path) = written();
+        let (_, header) = read(&path).unwrap();
+
+        assert_eq!(check_generation(&header, 7), Ok(None));
+        assert_eq!(
+            check_generation(&header, 6),
+            Err("snapshot generation 7 is newer than the database (6)".to_string())
+        );
+        assert_eq!(
+            check_generation(&header, 9),
+            Ok(Some("snapshot generation 7 is older than the database (9)".to_string()))
+        );
+    }
+}
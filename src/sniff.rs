@@ -0,0 +1,180 @@
+// Bytes of an upload kept for content detection
+pub const SNIFF_BYTES: usize = 4096;
+
+// Containers calamine reads: zip (xlsx/xlsm/xlsb/ods) and OLE2 compound files (xls)
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const OLE_MAGIC: &[u8] = &[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+// Formats people upload by mistake, named in the rejection message
+const KNOWN_OTHERS: [(&[u8], &str); 8] = [
+    (b"\x89PNG", "a PNG image"),
+    (&[0xFF, 0xD8, 0xFF], "a JPEG image"),
+    (b"GIF8", "a GIF image"),
+    (b"%PDF", "a PDF document"),
+    (&[0x1F, 0x8B], "a gzip archive"),
+    (b"Rar!", "a RAR archive"),
+    (b"7z\xBC\xAF", "a 7-Zip archive"),
+    (b"{\\rtf", "an RTF document"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Zip,
+    Ole2,
+    // UTF-8 without control characters other than whitespace
+    Text,
+    Other(&'static str),
+}
+
+impl Format {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Format::Zip => "a zip-based workbook",
+            Format::Ole2 => "a legacy Excel workbook",
+            Format::Text => "plain text",
+            Format::Other(description) => description,
+        }
+    }
+}
+
+pub fn detect(head: &[u8]) -> Format {
+    if head.starts_with(ZIP_MAGIC) {
+        return Format::Zip;
+    }
+    if head.starts_with(OLE_MAGIC) {
+        return Format::Ole2;
+    }
+    if let Some((_, description)) = KNOWN_OTHERS.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return Format::Other(description);
+    }
+    if head.is_empty() {
+        return Format::Other("an empty file");
+    }
+
+    // The cut at SNIFF_BYTES may split a multi-byte character
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default(),
+        Err(_) => return Format::Other("binary data"),
+    };
+    if text.chars().any(|c| c.is_control() && !c.is_whitespace()) {
+        Format::Other("binary data")
+    } else {
+        Format::Text
+    }
+}
+
+// Err with a message naming what the content looks like when it can't be what the name says.
+// Without a recognised extension any supported container (or text, for CSV) is accepted.
+pub fn check_upload(file_name: Option<&str>, head: &[u8]) -> Result<Format, String> {
+    let format = detect(head);
+    let expected = match file_name.and_then(crate::workbook::extension).as_deref() {
+        Some("xlsx") | Some("xlsm") | Some("xlsb") | Some("ods") => Some(Format::Zip),
+        Some("xls") => Some(Format::Ole2),
+        Some("csv") => Some(Format::Text),
+        _ => None,
+    };
+
+    let matches = match expected {
+        Some(expected) => format == expected,
+        None => matches!(format, Format::Zip | Format::Ole2 | Format::Text),
+    };
+    if matches {
+        return Ok(format);
+    }
+
+    let name = file_name.unwrap_or("unnamed");
+    Err(match expected {
+        Some(expected) => format!(
+            "'{}' looks like {}, not {} as its name suggests",
+            name,
+            format.describe(),
+            expected.describe()
+        ),
+        None => format!(
+            "'{}' looks like {}; expected a spreadsheet (.{}) or CSV file",
+            name,
+            format.describe(),
+            crate::workbook::SUPPORTED_EXTENSIONS.join(", .")
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_support;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    const OLE: &[u8] = &[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1, 0, 0];
+
+    #[test]
+    fn leading_bytes_identify_the_container() {
+        let cases: [(&[u8], Format); 14] = [
+            (b"PK\x03\x04\x14\0\x06\0", Format::Zip),
+            (OLE, Format::Ole2),
+            (PNG, Format::Other("a PNG image")),
+            (&[0xFF, 0xD8, 0xFF, 0xE0], Format::Other("a JPEG image")),
+            (b"GIF89a", Format::Other("a GIF image")),
+            (b"%PDF-1.7\n", Format::Other("a PDF document")),
+            (&[0x1F, 0x8B, 0x08], Format::Other("a gzip archive")),
+            (b"Rar!\x1a\x07", Format::Other("a RAR archive")),
+            (b"7z\xBC\xAF\x27\x1C", Format::Other("a 7-Zip archive")),
+            (b"{\\rtf1\\ansi", Format::Other("an RTF document")),
+            (b"", Format::Other("an empty file")),
+            (b"Scheme Name,Launch Date\r\nQuant Flexi Cap Fund,\t2008-10-17\n", Format::Text),
+            (b"Scheme Name\0\x01\x02", Format::Other("binary data")),
+            (&[0xC3, 0x28, 0x41], Format::Other("binary data")),
+        ];
+        for (head, format) in cases {
+            assert_eq!(detect(head), format, "{:?}", head);
+        }
+    }
+
+    #[test]
+    fn a_character_split_by_the_sniff_window_is_still_text() {
+        let mut head = "Scheme Name,Category\nSBI Équité Fund,Flexi Cap\n".as_bytes().to_vec();
+        let split = head.iter().position(|&b| b == 0xC3).unwrap();
+        head.truncate(split + 1);
+        assert_eq!(detect(&head), Format::Text);
+    }
+
+    #[test]
+    fn content_must_match_the_extension_when_there_is_one() {
+        let zip: &[u8] = b"PK\x03\x04";
+        assert_eq!(check_upload(Some("funds.xlsx"), zip), Ok(Format::Zip));
+        assert_eq!(check_upload(Some("funds.ODS"), zip), Ok(Format::Zip));
+        assert_eq!(check_upload(Some("funds.xls"), OLE), Ok(Format::Ole2));
+        assert_eq!(check_upload(Some("funds.csv"), b"Scheme Name\n"), Ok(Format::Text));
+        // No extension, or an unknown one: any readable container
+        assert_eq!(check_upload(None, OLE), Ok(Format::Ole2));
+        assert_eq!(check_upload(Some("funds.dat"), b"Scheme Name\n"), Ok(Format::Text));
+
+        assert_eq!(
+            check_upload(Some("funds.xlsx"), PNG).unwrap_err(),
+            "'funds.xlsx' looks like a PNG image, not a zip-based workbook as its name suggests"
+        );
+        assert_eq!(
+            check_upload(Some("funds.xls"), zip).unwrap_err(),
+            "'funds.xls' looks like a zip-based workbook, not a legacy Excel workbook as its name suggests"
+        );
+        assert_eq!(
+            check_upload(None, b"%PDF-1.4").unwrap_err(),
+            "'unnamed' looks like a PDF document; expected a spreadsheet (.xlsx, .xlsm, .xlsb, .xls, .ods) or CSV file"
+        );
+    }
+
+    #[actix_web::test]
+    async fn a_renamed_image_is_refused_before_any_import() {
+        let state = test_support::state(test_support::runtime_config(), Vec::new());
+        let request = test_support::multipart("/api/v1/upload", &[("excel_file", Some("funds.xlsx"), PNG)]);
+        let (status, body) = test_support::call_json(&state, test_support::as_admin(request)).await;
+        assert_eq!(status, 422, "{}", body);
+        assert_eq!(body["code"], "unprocessable");
+        assert_eq!(body["details"]["file_name"], "funds.xlsx");
+        assert_eq!(
+            body["message"],
+            "'funds.xlsx' looks like a PNG image, not a zip-based workbook as its name suggests"
+        );
+    }
+}
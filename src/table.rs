@@ -0,0 +1,1909 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::filters::{self, FilterEvaluator, Interner, RecordKeys, SearchFilters};
+use crate::rate_matches::{FundSuggestion, UnmatchedRate};
+use crate::{normalize_scheme_name, CombinedSchemeData};
+
+// Number of names scored between time-budget checks in the suggestion and fuzzy tiers
+const SUGGEST_BUDGET_BATCH: usize = 256;
+// Shorter queries are too ambiguous for the fuzzy tier
+const MIN_FUZZY_QUERY_LEN: usize = 4;
+// Query tokens shorter than this must appear in the name exactly
+const MIN_FUZZY_TOKEN_LEN: usize = 3;
+// Words most scheme names share; a query made only of these matches nothing but an exact name,
+// rather than (nearly) the whole table
+const STOP_WORDS: [&str; 8] = ["and", "fund", "funds", "of", "option", "plan", "scheme", "the"];
+
+// How a search result matched the query, best first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchKind {
+    Exact,
+    // The name starts with the query
+    Prefix,
+    // The name has every query token, in any order
+    Tokens,
+    Substring,
+    Fuzzy,
+    // Every query token sounds like (same Soundex code as) a token of the name; only tried when
+    // no other tier found anything
+    Phonetic,
+    // The query is an ARN code and the record's rate is under it
+    Arn,
+    // Empty query: every record passing the filters
+    Screen,
+}
+
+impl MatchKind {
+    // As serialized, for headers
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MatchKind::Exact => "exact",
+            MatchKind::Prefix => "prefix",
+            MatchKind::Tokens => "tokens",
+            MatchKind::Substring => "substring",
+            MatchKind::Fuzzy => "fuzzy",
+            MatchKind::Phonetic => "phonetic",
+            MatchKind::Arn => "arn",
+            MatchKind::Screen => "screen",
+        }
+    }
+}
+
+// Shape of the table, for /stats. Computed from the indexes already held, so it costs one pass
+// over the records and no locking beyond the caller's snapshot of the table.
+#[derive(Debug, Clone, Serialize)]
+pub struct TableStats {
+    pub records: usize,
+    pub distinct_funds: usize,
+    pub distinct_names: usize,
+    pub records_with_rates: usize,
+    pub records_without_rates: usize,
+    pub unmatched_rates: usize,
+    pub generation: i64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SearchHit<'a> {
+    pub record: &'a CombinedSchemeData,
+    pub kind: MatchKind,
+    // Orders results across match kinds, see banded_score; 0.0 when screening
+    pub score: f64,
+}
+
+// What one search did, for debugging why a name is or isn't found. Match counts are of results,
+// after filters; candidates_examined counts records the tiers considered before filtering.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SearchDiagnostics {
+    pub normalized_query: String,
+    pub exact_matches: usize,
+    pub prefix_matches: usize,
+    pub token_matches: usize,
+    pub substring_matches: usize,
+    pub fuzzy_matches: usize,
+    pub phonetic_matches: usize,
+    pub arn_matches: usize,
+    pub candidates_examined: usize,
+    // Names the fuzzy tier scored; 0 when it didn't run
+    pub fuzzy_names_scanned: usize,
+    pub elapsed_us: u64,
+}
+
+// How far one search goes. The fuzzy tier only runs while the other tiers found fewer than
+// `wanted`, a page size that doesn't move with the offset so every page sees one match set; it
+// scores names per token against `threshold` and stops scanning once `budget` runs out. Prefix,
+// token and substring matches are ranked together and only the best `max_candidates` are kept,
+// so a cut never depends on where a name sorts.
+#[derive(Debug, Clone, Copy)]
+pub struct SearchLimits {
+    pub wanted: usize,
+    pub threshold: f64,
+    pub budget: Option<Duration>,
+    pub max_candidates: usize,
+}
+
+pub struct SearchOutcome<'a> {
+    pub hits: Vec<SearchHit<'a>>,
+    // The fuzzy tier stopped at its time budget
+    pub budget_exhausted: bool,
+    // More than max_candidates prefix, token and substring matches; the worst ranked were dropped
+    pub truncated: bool,
+    pub diagnostics: SearchDiagnostics,
+}
+
+// Each match kind gets its own band so one descending sort ranks every tier: exact 1.0, prefix
+// [0.75, 1), tokens [0.5, 0.75), substring [0.25, 0.5), fuzzy [0.05, 0.25), phonetic [0, 0.05).
+// Within a band `fraction`
+// decides: the share of the name the query covers, token_order_fraction for multi-token queries,
+// or the fuzzy similarity.
+fn banded_score(kind: MatchKind, fraction: f64) -> f64 {
+    let (band, width) = match kind {
+        MatchKind::Exact | MatchKind::Arn => return 1.0,
+        MatchKind::Prefix => (0.75, 0.25),
+        MatchKind::Tokens => (0.5, 0.25),
+        MatchKind::Substring => (0.25, 0.25),
+        MatchKind::Fuzzy => (0.05, 0.2),
+        MatchKind::Phonetic => (0.0, 0.05),
+        MatchKind::Screen => return 0.0,
+    };
+    band + width * fraction.clamp(0.0, 0.999)
+}
+
+fn is_stop_word_query(normalized_query: &str) -> bool {
+    normalized_query.split(' ').all(|token| STOP_WORDS.contains(&token))
+}
+
+// Within-band fraction for a name holding every token of a multi-token query: mostly how many
+// neighbouring query tokens are also neighbours, in order, in the name, then how early the first
+// one appears, then how much of the name the query covers. Tokens that only match through an
+// alias expansion count as appearing at the end of the name.
+fn token_order_fraction(query_tokens: &[&str], name: &str) -> f64 {
+    let name_tokens: Vec<&str> = name.split(' ').collect();
+    let positions: Vec<usize> = query_tokens
+        .iter()
+        .map(|token| name_tokens.iter().position(|name_token| name_token == token).unwrap_or(name_tokens.len()))
+        .collect();
+
+    let adjacent = positions.windows(2).filter(|pair| pair[1] == pair[0] + 1).count();
+    let contiguity = adjacent as f64 / (positions.len() - 1) as f64;
+    let first = positions.iter().copied().min().unwrap_or(0);
+    let earliness = 1.0 - first as f64 / name_tokens.len() as f64;
+    let coverage = query_tokens.len() as f64 / name_tokens.len() as f64;
+    0.5 * contiguity + 0.3 * earliness + 0.2 * coverage.min(1.0)
+}
+
+// One type-ahead entry: the display name and, when the name belongs to a fund, its id
+#[derive(Debug, Clone, Serialize)]
+pub struct NameSuggestion {
+    pub scheme_name: String,
+    pub fund_id: Option<i32>,
+}
+
+// One fund category for GET /categories: its records and the range of their 1-year returns
+// (None when no record in it has one)
+#[derive(Debug, Clone, Serialize)]
+pub struct CategorySummary {
+    pub category: String,
+    pub records: usize,
+    pub min_year_1: Option<f32>,
+    pub max_year_1: Option<f32>,
+}
+
+// One brokerage company for GET /companies: the distinct scheme names its rates cover and the span
+// of its active rates (None when none is active)
+#[derive(Debug, Clone, Serialize)]
+pub struct CompanySummary {
+    pub company: String,
+    pub schemes: usize,
+    pub active_from: Option<NaiveDate>,
+    pub active_until: Option<NaiveDate>,
+}
+
+// A company's distinct scheme names and the earliest start and latest end of its active rates
+type CompanyActivity<'a> = (HashSet<&'a str>, Option<NaiveDate>, Option<NaiveDate>);
+
+// One ARN code for GET /arns with the companies whose rates carry it (usually one)
+#[derive(Debug, Clone, Serialize)]
+pub struct ArnSummary {
+    pub arn: String,
+    pub companies: Vec<String>,
+}
+
+// Case-insensitive substring match for the autocomplete listings; no filter matches everything
+fn contains_filter(value: &str, filter: Option<&str>) -> bool {
+    filter.is_none_or(|filter| value.to_lowercase().contains(filter))
+}
+
+// Latest known fund size, the tie-break between equally scored results
+fn fund_size(record: &CombinedSchemeData) -> Option<f32> {
+    record.fund_size_may25.or(record.fund_size_apr25)
+}
+
+// Best similarity of a query token to any token of a name. Tokens whose lengths differ by more
+// than a typo or two are skipped without scoring.
+fn token_similarity(query_token: &str, name_tokens: &[&str]) -> f64 {
+    if query_token.len() < MIN_FUZZY_TOKEN_LEN {
+        return if name_tokens.contains(&query_token) { 1.0 } else { 0.0 };
+    }
+    let slack = 2 + query_token.len() / 4;
+    name_tokens
+        .iter()
+        .filter(|token| token.len().abs_diff(query_token.len()) <= slack)
+        .map(|token| strsim::jaro_winkler(query_token, token))
+        .fold(0.0, f64::max)
+}
+
+// One allocation per distinct string across all records: the company, category and ARN repeat on
+// thousands of records, a fund's name on each of its rates. Never shrinks; strings only removed
+// records used stay until the next full build replaces the table.
+#[derive(Debug, Clone, Default)]
+struct StringPool(HashSet<Arc<str>>);
+
+impl StringPool {
+    fn intern(&mut self, value: &Arc<str>) -> Arc<str> {
+        if let Some(shared) = self.0.get(&**value) {
+            return Arc::clone(shared);
+        }
+        self.0.insert(Arc::clone(value));
+        Arc::clone(value)
+    }
+
+    // Point every string field of the record at the pooled copy
+    fn share(&mut self, record: &mut CombinedSchemeData) {
+        record.scheme_name = self.intern(&record.scheme_name);
+        record.normalized_name = self.intern(&record.normalized_name);
+        for value in [
+            &mut record.fund_category,
+            &mut record.arn,
+            &mut record.company,
+            &mut record.scheme_category,
+            &mut record.brokerage_type,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *value = self.intern(value);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+// In-memory virtual table. Records are only reachable through methods so every index stays in
+// step with `records`; check_invariants spells out what "in step" means.
+#[derive(Debug, Clone)]
+pub struct VirtualTable {
+    records: Vec<CombinedSchemeData>,
+    // Parallel to records: normalized filterable strings, interned
+    keys: Vec<RecordKeys>,
+    interner: Interner,
+    // Shared strings of every record, see StringPool
+    strings: StringPool,
+    // normalized_name -> positions in records. Keys are the records' own pooled normalized_name,
+    // not copies.
+    name_index: HashMap<Arc<str>, Vec<usize>>,
+    // The keys of name_index in sorted order, for prefix lookups by binary search
+    sorted_names: Vec<Arc<str>>,
+    // Each distinct token of a normalized name -> positions in records, kept sorted for
+    // intersection. Costs one usize per (record, distinct token) plus one String key per distinct
+    // token; see token_index_stats.
+    token_index: HashMap<String, Vec<usize>>,
+    // Soundex code of each alphabetic name token (see phonetic_keys) -> sorted positions
+    phonetic_index: HashMap<String, Vec<usize>>,
+    // A fund appears once per matched rate, so this can hold several positions too
+    fund_index: HashMap<i32, Vec<usize>>,
+    // filters::normalize_arn(arn) -> positions of records whose rate is under that ARN
+    arn_index: HashMap<String, Vec<usize>>,
+    // name_aliases table: single alias token -> normalized canonical text. Set before any record
+    // is added; names are indexed under both their own and their expanded tokens.
+    name_aliases: HashMap<String, String>,
+    // Canonical category/company spellings with record counts, for filter resolution and facets
+    category_counts: BTreeMap<String, usize>,
+    company_counts: BTreeMap<String, usize>,
+    // Active rates that joined to no fund; not indexed
+    pub unmatched_rates: Vec<UnmatchedRate>,
+    // data_generation_seq value the table was built from, see snapshot
+    pub generation: i64,
+}
+
+impl Default for VirtualTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualTable {
+    pub fn new() -> Self {
+        Self {
+            records: Vec::new(),
+            keys: Vec::new(),
+            interner: Interner::default(),
+            strings: StringPool::default(),
+            name_index: HashMap::new(),
+            sorted_names: Vec::new(),
+            token_index: HashMap::new(),
+            phonetic_index: HashMap::new(),
+            fund_index: HashMap::new(),
+            arn_index: HashMap::new(),
+            name_aliases: HashMap::new(),
+            category_counts: BTreeMap::new(),
+            company_counts: BTreeMap::new(),
+            unmatched_rates: Vec::new(),
+            generation: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, CombinedSchemeData> {
+        self.records.iter()
+    }
+
+    // All records in table order, e.g. for serialization
+    pub fn records(&self) -> &[CombinedSchemeData] {
+        &self.records
+    }
+
+    pub fn name_aliases(&self) -> &HashMap<String, String> {
+        &self.name_aliases
+    }
+
+    // Only valid on an empty table: records already indexed would miss the new expansions
+    pub fn set_name_aliases(&mut self, aliases: HashMap<String, String>) {
+        debug_assert!(self.records.is_empty());
+        self.name_aliases = aliases;
+    }
+
+    // Replace whole tokens of an already-normalized name with their name_aliases expansion
+    pub fn expand_name_aliases(&self, normalized: &str) -> String {
+        expand_tokens(normalized, &self.name_aliases)
+    }
+
+    pub fn category_counts(&self) -> &BTreeMap<String, usize> {
+        &self.category_counts
+    }
+
+    // Every category with its record count and 1-year return range, largest first, in one pass
+    // over the records
+    pub fn category_summaries(&self) -> Vec<CategorySummary> {
+        let mut summaries: HashMap<&str, CategorySummary> = HashMap::new();
+        for record in &self.records {
+            let category = match record.fund_category.as_deref() {
+                Some(category) => category,
+                None => continue,
+            };
+            let summary = summaries.entry(category).or_insert_with(|| CategorySummary {
+                category: category.to_string(),
+                records: 0,
+                min_year_1: None,
+                max_year_1: None,
+            });
+            summary.records += 1;
+            if let Some(year_1) = record.year_1 {
+                summary.min_year_1 = Some(summary.min_year_1.map_or(year_1, |min| min.min(year_1)));
+                summary.max_year_1 = Some(summary.max_year_1.map_or(year_1, |max| max.max(year_1)));
+            }
+        }
+
+        let mut summaries: Vec<CategorySummary> = summaries.into_values().collect();
+        summaries.sort_by(|a, b| b.records.cmp(&a.records).then_with(|| a.category.cmp(&b.category)));
+        summaries
+    }
+
+    // Companies of the records' rates whose name contains `filter` (already lowercased), by name.
+    // Records without a company are skipped.
+    pub fn company_summaries(&self, filter: Option<&str>) -> Vec<CompanySummary> {
+        let mut companies: BTreeMap<&str, CompanyActivity> = BTreeMap::new();
+        for record in &self.records {
+            let company = match record.company.as_deref() {
+                Some(company) if contains_filter(company, filter) => company,
+                _ => continue,
+            };
+            let (schemes, from, until) = companies.entry(company).or_default();
+            schemes.insert(&*record.normalized_name);
+            if record.is_rate_active {
+                if let Some(start) = record.start_date {
+                    *from = Some(from.map_or(start, |from| from.min(start)));
+                }
+                if let Some(end) = record.end_date {
+                    *until = Some(until.map_or(end, |until| until.max(end)));
+                }
+            }
+        }
+
+        companies
+            .into_iter()
+            .map(|(company, (schemes, active_from, active_until))| CompanySummary {
+                company: company.to_string(),
+                schemes: schemes.len(),
+                active_from,
+                active_until,
+            })
+            .collect()
+    }
+
+    // ARN codes whose code or company contains `filter` (already lowercased), by code. Codes are
+    // grouped as arn_index groups them; the first spelling seen is the one listed.
+    pub fn arn_summaries(&self, filter: Option<&str>) -> Vec<ArnSummary> {
+        let mut arns: BTreeMap<&str, ArnSummary> = BTreeMap::new();
+        for (key, positions) in &self.arn_index {
+            for &idx in positions {
+                let record = &self.records[idx];
+                let (arn, company) = match (record.arn.as_deref(), record.company.as_deref()) {
+                    (Some(arn), Some(company)) => (arn, company),
+                    _ => continue,
+                };
+                if !contains_filter(arn, filter) && !contains_filter(company, filter) {
+                    continue;
+                }
+                let summary = arns.entry(key.as_str()).or_insert_with(|| ArnSummary {
+                    arn: arn.to_string(),
+                    companies: Vec::new(),
+                });
+                if !summary.companies.iter().any(|known| known == company) {
+                    summary.companies.push(company.to_string());
+                }
+            }
+        }
+
+        arns.into_values()
+            .map(|mut summary| {
+                summary.companies.sort();
+                summary
+            })
+            .collect()
+    }
+
+    pub fn company_counts(&self) -> &BTreeMap<String, usize> {
+        &self.company_counts
+    }
+
+    pub fn get_by_fund_id(&self, fund_id: i32) -> Vec<&CombinedSchemeData> {
+        self.fund_index
+            .get(&fund_id)
+            .map(|positions| positions.iter().map(|&idx| &self.records[idx]).collect())
+            .unwrap_or_default()
+    }
+
+    // A fund's records that pass the filters, by rate id
+    pub fn fund_records_matching(&self, fund_id: i32, filters: &mut FilterEvaluator) -> Vec<&CombinedSchemeData> {
+        filters.bind(&self.interner);
+        let mut records: Vec<&CombinedSchemeData> = self
+            .fund_index
+            .get(&fund_id)
+            .map(|positions| {
+                positions
+                    .iter()
+                    .filter(|&&idx| filters.accepts(&self.records[idx], &self.keys[idx]))
+                    .map(|&idx| &self.records[idx])
+                    .collect()
+            })
+            .unwrap_or_default();
+        records.sort_by_key(|record| record.rate_id);
+        records
+    }
+
+    pub fn add_record(&mut self, record: CombinedSchemeData) {
+        self.push_record(record, true);
+    }
+
+    fn push_record(&mut self, mut record: CombinedSchemeData, keep_names_sorted: bool) {
+        self.strings.share(&mut record);
+        let idx = self.records.len();
+        self.keys.push(RecordKeys::new(&record, &mut self.interner));
+        self.records.push(record);
+        self.index(idx, keep_names_sorted);
+    }
+
+    // Replace the record with the same fund and rate ids, or add it; true when one was replaced
+    pub fn upsert(&mut self, record: CombinedSchemeData) -> bool {
+        let replaced = match self.position(record.fund_id, record.rate_id) {
+            Some(idx) => {
+                let mut record = record;
+                self.strings.share(&mut record);
+                self.unindex(idx);
+                self.keys[idx] = RecordKeys::new(&record, &mut self.interner);
+                self.records[idx] = record;
+                self.index(idx, true);
+                true
+            }
+            None => {
+                self.add_record(record);
+                false
+            }
+        };
+        debug_assert_eq!(self.check_invariants(), Ok(()));
+        replaced
+    }
+
+    // Replace every record of the given funds with `records` (that is, the funds' records as a
+    // full build would now produce them), and drop unmatched rates those records now match.
+    // Positions of unrelated records may change; see remove.
+    pub fn upsert_records(&mut self, fund_ids: &HashSet<i32>, records: Vec<CombinedSchemeData>) {
+        for &fund_id in fund_ids {
+            self.remove_fund(fund_id);
+        }
+
+        let matched_rates: HashSet<i32> = records.iter().filter_map(|record| record.rate_id).collect();
+        self.unmatched_rates.retain(|rate| !matched_rates.contains(&rate.rate_id));
+        for record in records {
+            self.add_record(record);
+        }
+    }
+
+    // Remove every record of a fund (one per joined rate), returning them. Rates that were joined
+    // to it are not moved to unmatched_rates; the next full build puts them there.
+    pub fn remove_fund(&mut self, fund_id: i32) -> Vec<CombinedSchemeData> {
+        let rate_ids: Vec<Option<i32>> = self
+            .get_by_fund_id(fund_id)
+            .iter()
+            .map(|record| record.rate_id)
+            .collect();
+        rate_ids
+            .into_iter()
+            .filter_map(|rate_id| self.remove(Some(fund_id), rate_id))
+            .collect()
+    }
+
+    // Remove the record for a fund/rate pair. The last record moves into the freed slot, so
+    // positions are not stable across removals.
+    pub fn remove(&mut self, fund_id: Option<i32>, rate_id: Option<i32>) -> Option<CombinedSchemeData> {
+        let idx = self.position(fund_id, rate_id)?;
+        let last = self.records.len() - 1;
+
+        self.unindex(idx);
+        if idx != last {
+            self.unindex(last);
+        }
+        self.keys.swap_remove(idx);
+        let removed = self.records.swap_remove(idx);
+        if idx != last {
+            self.index(idx, true);
+        }
+
+        debug_assert_eq!(self.check_invariants(), Ok(()));
+        Some(removed)
+    }
+
+    fn position(&self, fund_id: Option<i32>, rate_id: Option<i32>) -> Option<usize> {
+        let matches = |&idx: &usize| self.records[idx].fund_id == fund_id && self.records[idx].rate_id == rate_id;
+        match fund_id {
+            Some(id) => self.fund_index.get(&id)?.iter().copied().find(matches),
+            // Rate-only records aren't indexed by fund
+            None => (0..self.records.len()).find(matches),
+        }
+    }
+
+    // Add the record at idx to every index and count. Every index is keyed off the record's own
+    // normalized_name, computed once when the record was built; nothing here normalizes again.
+    // Without `keep_names_sorted` a new name goes to the end of sorted_names, for TableBuilder to
+    // sort once.
+    fn index(&mut self, idx: usize, keep_names_sorted: bool) {
+        let record = &self.records[idx];
+        debug_assert_eq!(
+            *record.normalized_name,
+            normalize_scheme_name(&record.scheme_name),
+            "record normalized_name is out of step with its scheme_name"
+        );
+        let name = Arc::clone(&record.normalized_name);
+        if !self.name_index.contains_key(&name) {
+            if keep_names_sorted {
+                let at = self.sorted_names.partition_point(|sorted| *sorted < name);
+                self.sorted_names.insert(at, name.clone());
+            } else {
+                self.sorted_names.push(name.clone());
+            }
+        }
+        self.name_index.entry(name).or_default().push(idx);
+        for token in name_tokens(&record.normalized_name, &self.name_aliases) {
+            let positions = self.token_index.entry(token).or_default();
+            if let Err(at) = positions.binary_search(&idx) {
+                positions.insert(at, idx);
+            }
+        }
+        for key in phonetic_keys(&record.normalized_name, &self.name_aliases) {
+            let positions = self.phonetic_index.entry(key).or_default();
+            if let Err(at) = positions.binary_search(&idx) {
+                positions.insert(at, idx);
+            }
+        }
+        if let Some(fund_id) = record.fund_id {
+            self.fund_index.entry(fund_id).or_default().push(idx);
+        }
+        if let Some(arn) = &record.arn {
+            self.arn_index.entry(filters::normalize_arn(arn)).or_default().push(idx);
+        }
+        if let Some(category) = &record.fund_category {
+            *self.category_counts.entry(category.to_string()).or_insert(0) += 1;
+        }
+        if let Some(company) = &record.company {
+            *self.company_counts.entry(company.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    // Exact inverse of index(idx), for the record currently at idx
+    fn unindex(&mut self, idx: usize) {
+        let record = &self.records[idx];
+        let name = Arc::clone(&record.normalized_name);
+        remove_position(&mut self.name_index, Arc::clone(&name), idx);
+        if !self.name_index.contains_key(&name) {
+            if let Ok(at) = self.sorted_names.binary_search(&name) {
+                self.sorted_names.remove(at);
+            }
+        }
+        for token in name_tokens(&record.normalized_name, &self.name_aliases) {
+            remove_position(&mut self.token_index, token, idx);
+        }
+        for key in phonetic_keys(&record.normalized_name, &self.name_aliases) {
+            remove_position(&mut self.phonetic_index, key, idx);
+        }
+        if let Some(fund_id) = record.fund_id {
+            remove_position(&mut self.fund_index, fund_id, idx);
+        }
+        if let Some(arn) = &record.arn {
+            remove_position(&mut self.arn_index, filters::normalize_arn(arn), idx);
+        }
+        if let Some(category) = &record.fund_category {
+            decrement(&mut self.category_counts, category);
+        }
+        if let Some(company) = &record.company {
+            decrement(&mut self.company_counts, company);
+        }
+    }
+
+    // Every index entry points at a live record with the matching key, every record is indexed
+    // exactly once per index, and the counts match the records. Err names the first violation.
+    pub fn check_invariants(&self) -> Result<(), String> {
+        if self.keys.len() != self.records.len() {
+            return Err(format!("{} keys for {} records", self.keys.len(), self.records.len()));
+        }
+
+        let mut name_hits = vec![0usize; self.records.len()];
+        for (key, positions) in &self.name_index {
+            if positions.is_empty() {
+                return Err(format!("name_index has an empty entry for '{}'", key));
+            }
+            for &idx in positions {
+                let record = self
+                    .records
+                    .get(idx)
+                    .ok_or_else(|| format!("name_index '{}' points past the end ({})", key, idx))?;
+                if normalize_scheme_name(&record.scheme_name) != **key || !Arc::ptr_eq(&record.normalized_name, key) {
+                    return Err(format!("name_index '{}' points at '{}'", key, record.scheme_name));
+                }
+                name_hits[idx] += 1;
+            }
+        }
+        if let Some(idx) = name_hits.iter().position(|&hits| hits != 1) {
+            return Err(format!("record {} is in name_index {} times", idx, name_hits[idx]));
+        }
+        if self.sorted_names.len() != self.name_index.len()
+            || !self.sorted_names.windows(2).all(|pair| pair[0] < pair[1])
+            || !self.sorted_names.iter().all(|name| self.name_index.contains_key(name))
+        {
+            return Err("sorted_names out of step with name_index".to_string());
+        }
+
+        let mut token_hits = vec![0usize; self.records.len()];
+        for (token, positions) in &self.token_index {
+            if positions.is_empty() || !positions.windows(2).all(|pair| pair[0] < pair[1]) {
+                return Err(format!("token_index '{}' is empty or unsorted", token));
+            }
+            for &idx in positions {
+                match self.records.get(idx) {
+                    Some(record) if name_tokens(&record.normalized_name, &self.name_aliases).contains(token) => token_hits[idx] += 1,
+                    _ => return Err(format!("token_index '{}' points at the wrong record ({})", token, idx)),
+                }
+            }
+        }
+        for (idx, record) in self.records.iter().enumerate() {
+            let expected = name_tokens(&record.normalized_name, &self.name_aliases).len();
+            if token_hits[idx] != expected {
+                return Err(format!("record {} is in token_index {} times, expected {}", idx, token_hits[idx], expected));
+            }
+        }
+
+        let mut phonetic_hits = vec![0usize; self.records.len()];
+        for (key, positions) in &self.phonetic_index {
+            if positions.is_empty() || !positions.windows(2).all(|pair| pair[0] < pair[1]) {
+                return Err(format!("phonetic_index '{}' is empty or unsorted", key));
+            }
+            for &idx in positions {
+                match self.records.get(idx) {
+                    Some(record) if phonetic_keys(&record.normalized_name, &self.name_aliases).contains(key) => {
+                        phonetic_hits[idx] += 1
+                    }
+                    _ => return Err(format!("phonetic_index '{}' points at the wrong record ({})", key, idx)),
+                }
+            }
+        }
+        for (idx, record) in self.records.iter().enumerate() {
+            let expected = phonetic_keys(&record.normalized_name, &self.name_aliases).len();
+            if phonetic_hits[idx] != expected {
+                return Err(format!("record {} is in phonetic_index {} times, expected {}", idx, phonetic_hits[idx], expected));
+            }
+        }
+
+        let mut fund_hits = vec![0usize; self.records.len()];
+        for (fund_id, positions) in &self.fund_index {
+            for &idx in positions {
+                match self.records.get(idx) {
+                    Some(record) if record.fund_id == Some(*fund_id) => fund_hits[idx] += 1,
+                    _ => return Err(format!("fund_index {} points at the wrong record ({})", fund_id, idx)),
+                }
+            }
+        }
+        for (idx, record) in self.records.iter().enumerate() {
+            let expected = usize::from(record.fund_id.is_some());
+            if fund_hits[idx] != expected {
+                return Err(format!("record {} is in fund_index {} times", idx, fund_hits[idx]));
+            }
+        }
+
+        let mut arn_hits = vec![0usize; self.records.len()];
+        for (arn, positions) in &self.arn_index {
+            for &idx in positions {
+                match self.records.get(idx) {
+                    Some(record) if record.arn.as_deref().map(filters::normalize_arn).as_ref() == Some(arn) => {
+                        arn_hits[idx] += 1
+                    }
+                    _ => return Err(format!("arn_index '{}' points at the wrong record ({})", arn, idx)),
+                }
+            }
+        }
+        for (idx, record) in self.records.iter().enumerate() {
+            if arn_hits[idx] != usize::from(record.arn.is_some()) {
+                return Err(format!("record {} is in arn_index {} times", idx, arn_hits[idx]));
+            }
+        }
+
+        let mut categories = BTreeMap::new();
+        let mut companies = BTreeMap::new();
+        for record in &self.records {
+            if let Some(category) = &record.fund_category {
+                *categories.entry(category.to_string()).or_insert(0) += 1;
+            }
+            if let Some(company) = &record.company {
+                *companies.entry(company.to_string()).or_insert(0) += 1;
+            }
+        }
+        if categories != self.category_counts {
+            return Err("category_counts out of step with records".to_string());
+        }
+        if companies != self.company_counts {
+            return Err("company_counts out of step with records".to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn incomplete_count(&self) -> usize {
+        self.records.iter().filter(|record| record.incomplete).count()
+    }
+
+    // Every match, ranked by score (see banded_score), then larger funds, then name, within
+    // `limits`; the outcome reports where a limit cut the search short
+    pub fn search(&self, query: &str, filters: &mut FilterEvaluator, limits: &SearchLimits) -> SearchOutcome<'_> {
+        let started = Instant::now();
+        let mut diagnostics = SearchDiagnostics {
+            normalized_query: self.expand_name_aliases(&normalize_scheme_name(query)),
+            ..Default::default()
+        };
+        let (hits, budget_exhausted, truncated) = self.search_hits(query, filters, limits, &mut diagnostics);
+
+        for hit in &hits {
+            match hit.kind {
+                MatchKind::Exact => diagnostics.exact_matches += 1,
+                MatchKind::Prefix => diagnostics.prefix_matches += 1,
+                MatchKind::Tokens => diagnostics.token_matches += 1,
+                MatchKind::Substring => diagnostics.substring_matches += 1,
+                MatchKind::Fuzzy => diagnostics.fuzzy_matches += 1,
+                MatchKind::Phonetic => diagnostics.phonetic_matches += 1,
+                MatchKind::Arn => diagnostics.arn_matches += 1,
+                MatchKind::Screen => {}
+            }
+        }
+        diagnostics.elapsed_us = started.elapsed().as_micros() as u64;
+
+        SearchOutcome {
+            hits,
+            budget_exhausted,
+            truncated,
+            diagnostics,
+        }
+    }
+
+    fn search_hits(
+        &self,
+        query: &str,
+        filters: &mut FilterEvaluator,
+        limits: &SearchLimits,
+        diagnostics: &mut SearchDiagnostics,
+    ) -> (Vec<SearchHit<'_>>, bool, bool) {
+        let normalized_query = diagnostics.normalized_query.clone();
+        let mut results = Vec::new();
+        let mut seen = HashSet::new();
+        filters.bind(&self.interner);
+
+        // Screening and browsing: no name to match, so the filters (if any) alone decide, in name
+        // order. sorted_names already gives that order; only records sharing a name need sorting.
+        if normalized_query.is_empty() {
+            diagnostics.candidates_examined = self.records.len();
+            for name in &self.sorted_names {
+                let mut positions = self.name_index[name].clone();
+                positions.sort_by(|&a, &b| self.compare_identity(a, b));
+                for idx in positions {
+                    if filters.accepts(&self.records[idx], &self.keys[idx]) {
+                        results.push(self.hit(idx, MatchKind::Screen, 0.0));
+                    }
+                }
+            }
+            return (results, false, false);
+        }
+
+        // An ARN code lists every record under it; name matching (fuzzy in particular) only runs
+        // when no rate is under that code
+        if filters::looks_like_arn(query) {
+            if let Some(indices) = self.arn_index.get(&filters::normalize_arn(query)) {
+                diagnostics.candidates_examined += indices.len();
+                let mut arn_hits: Vec<(f64, usize)> = indices.iter().map(|&idx| (1.0, idx)).collect();
+                arn_hits.sort_by(|&a, &b| self.rank(a, b));
+                for (score, idx) in arn_hits {
+                    if filters.accepts(&self.records[idx], &self.keys[idx]) {
+                        results.push(self.hit(idx, MatchKind::Arn, score));
+                    }
+                }
+            }
+        }
+        if !results.is_empty() {
+            return (results, false, false);
+        }
+
+        // Exact, prefix, token and substring matches are ranked together
+        let mut candidates: Vec<(MatchKind, f64, usize)> = Vec::new();
+        if let Some(indices) = self.name_index.get(normalized_query.as_str()) {
+            candidates.extend(indices.iter().map(|&idx| (MatchKind::Exact, 1.0, idx)));
+        }
+        let stop_words_only = is_stop_word_query(&normalized_query);
+        let query_tokens: Vec<&str> = normalized_query.split(' ').collect();
+        let token_matches = if stop_words_only { Vec::new() } else { self.token_matches(&normalized_query) };
+        let partial: Vec<(MatchKind, usize)> = if token_matches.is_empty() && !stop_words_only {
+            // Substring matches, for queries ending mid-word ("hdfc flex") that no token lookup
+            // can find
+            self.substring_matches(&normalized_query).into_iter().map(|idx| (MatchKind::Substring, idx)).collect()
+        } else {
+            token_matches.into_iter().map(|idx| (MatchKind::Tokens, idx)).collect()
+        };
+        for (kind, idx) in partial {
+            let name: &str = &self.records[idx].normalized_name;
+            if name == normalized_query {
+                continue;
+            }
+            let kind = if name.starts_with(&normalized_query) { MatchKind::Prefix } else { kind };
+            let fraction = if kind == MatchKind::Tokens && query_tokens.len() > 1 {
+                token_order_fraction(&query_tokens, name)
+            } else {
+                normalized_query.len() as f64 / name.len() as f64
+            };
+            candidates.push((kind, banded_score(kind, fraction), idx));
+        }
+        candidates.sort_by(|a, b| self.rank((a.1, a.2), (b.1, b.2)));
+        diagnostics.candidates_examined += candidates.len();
+        let truncated = candidates.len() > limits.max_candidates;
+        candidates.truncate(limits.max_candidates);
+
+        for (kind, score, idx) in candidates {
+            // Exact matches keep every record of the name, e.g. one per matched rate
+            let accepted = if kind == MatchKind::Exact {
+                let accepted = filters.accepts(&self.records[idx], &self.keys[idx]);
+                if accepted {
+                    seen.insert(&*self.records[idx].normalized_name);
+                }
+                accepted
+            } else {
+                self.accepts_new(&mut seen, idx, filters)
+            };
+            if accepted {
+                results.push(self.hit(idx, kind, score));
+            }
+        }
+
+        let mut budget_exhausted = false;
+        if results.len() < limits.wanted && normalized_query.len() >= MIN_FUZZY_QUERY_LEN && !stop_words_only {
+            let (names, exhausted, scanned) = self.fuzzy_candidates(&normalized_query, limits.threshold, limits.budget);
+            budget_exhausted = exhausted;
+            diagnostics.fuzzy_names_scanned = scanned;
+            let mut fuzzy: Vec<(f64, usize)> = names
+                .into_iter()
+                .flat_map(|(similarity, name)| {
+                    let score = banded_score(MatchKind::Fuzzy, similarity);
+                    self.name_index[name].iter().map(move |&idx| (score, idx))
+                })
+                .collect();
+            fuzzy.sort_by(|&a, &b| self.rank(a, b));
+            diagnostics.candidates_examined += fuzzy.len();
+            for (score, idx) in fuzzy {
+                if self.accepts_new(&mut seen, idx, filters) {
+                    results.push(self.hit(idx, MatchKind::Fuzzy, score));
+                }
+            }
+        }
+
+        // Last resort: misspellings edit distance doesn't reach ("miray" for "mirae")
+        if results.is_empty() && !stop_words_only {
+            let mut phonetic: Vec<(f64, usize)> = self
+                .phonetic_matches(&normalized_query)
+                .into_iter()
+                .map(|idx| {
+                    let name_tokens = self.records[idx].normalized_name.split(' ').count();
+                    let coverage = query_tokens.len() as f64 / name_tokens as f64;
+                    (banded_score(MatchKind::Phonetic, coverage), idx)
+                })
+                .collect();
+            phonetic.sort_by(|&a, &b| self.rank(a, b));
+            diagnostics.candidates_examined += phonetic.len();
+            for (score, idx) in phonetic {
+                if self.accepts_new(&mut seen, idx, filters) {
+                    results.push(self.hit(idx, MatchKind::Phonetic, score));
+                }
+            }
+        }
+
+        (results, budget_exhausted, truncated)
+    }
+
+    // Descending score, then larger fund first (unknown size last), then compare_identity. The
+    // candidates are collected from hash maps in arbitrary order, so this has to be a total order
+    // on records for results (and what a limit cuts off) to be the same on every call.
+    fn rank(&self, (score_a, a): (f64, usize), (score_b, b): (f64, usize)) -> std::cmp::Ordering {
+        let (record_a, record_b) = (&self.records[a], &self.records[b]);
+        score_b
+            .total_cmp(&score_a)
+            .then_with(|| match (fund_size(record_a), fund_size(record_b)) {
+                (Some(x), Some(y)) => y.total_cmp(&x),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => std::cmp::Ordering::Equal,
+            })
+            .then_with(|| self.compare_identity(a, b))
+    }
+
+    // Normalized name, then fund and rate id; position only separates exact duplicates
+    fn compare_identity(&self, a: usize, b: usize) -> std::cmp::Ordering {
+        let (record_a, record_b) = (&self.records[a], &self.records[b]);
+        record_a
+            .normalized_name
+            .cmp(&record_b.normalized_name)
+            .then(record_a.fund_id.cmp(&record_b.fund_id))
+            .then(record_a.rate_id.cmp(&record_b.rate_id))
+            .then(a.cmp(&b))
+    }
+
+    // Positions of records whose name has every query token
+    fn token_matches(&self, normalized_query: &str) -> Vec<usize> {
+        let mut postings = Vec::new();
+        for token in normalized_query.split(' ').filter(|token| !token.is_empty()) {
+            match self.token_index.get(token) {
+                Some(positions) => postings.push(positions),
+                None => return Vec::new(),
+            }
+        }
+        intersect(postings)
+    }
+
+    // Positions of records whose name contains the query. Rather than scanning every name, the
+    // token index narrows them first: a name containing "dfc flexi ca" has a token ending in "dfc",
+    // the token "flexi" and a token starting with "ca"; a one-token query is inside some token.
+    fn substring_matches(&self, normalized_query: &str) -> Vec<usize> {
+        let query_tokens: Vec<&str> = normalized_query.split(' ').collect();
+        let tokens_where = |matches: &dyn Fn(&str) -> bool| {
+            let mut positions: Vec<usize> = self
+                .token_index
+                .iter()
+                .filter(|(token, _)| matches(token))
+                .flat_map(|(_, positions)| positions.iter().copied())
+                .collect();
+            positions.sort_unstable();
+            positions.dedup();
+            positions
+        };
+
+        let mut postings = Vec::new();
+        match query_tokens.as_slice() {
+            [only] => postings.push(tokens_where(&|token| token.contains(only))),
+            [first, middle @ .., last] => {
+                postings.push(tokens_where(&|token| token.ends_with(first)));
+                for token in middle {
+                    match self.token_index.get(*token) {
+                        Some(positions) => postings.push(positions.clone()),
+                        None => return Vec::new(),
+                    }
+                }
+                postings.push(tokens_where(&|token| token.starts_with(last)));
+            }
+            [] => return Vec::new(),
+        }
+        intersect(postings.iter().collect())
+            .into_iter()
+            .filter(|&idx| self.records[idx].normalized_name.contains(normalized_query))
+            .collect()
+    }
+
+    // Positions of records with a token sounding like each query token. Tokens without a Soundex
+    // code (too short, or not purely alphabetic) must appear as they are.
+    fn phonetic_matches(&self, normalized_query: &str) -> Vec<usize> {
+        let mut postings = Vec::new();
+        for token in normalized_query.split(' ').filter(|token| !token.is_empty()) {
+            let positions = match phonetic_key(token) {
+                Some(key) => self.phonetic_index.get(&key),
+                None => self.token_index.get(token),
+            };
+            match positions {
+                Some(positions) => postings.push(positions),
+                None => return Vec::new(),
+            }
+        }
+        intersect(postings)
+    }
+
+    pub fn stats(&self) -> TableStats {
+        let records_with_rates = self.records.iter().filter(|record| record.rate_id.is_some()).count();
+        TableStats {
+            records: self.records.len(),
+            distinct_funds: self.fund_index.len(),
+            distinct_names: self.name_index.len(),
+            records_with_rates,
+            records_without_rates: self.records.len() - records_with_rates,
+            unmatched_rates: self.unmatched_rates.len(),
+            generation: self.generation,
+        }
+    }
+
+    // Distinct strings the records share through the pool
+    pub fn shared_strings(&self) -> usize {
+        self.strings.len()
+    }
+
+    // Distinct tokens, posting entries and an estimate of the heap bytes the token index holds
+    // (keys, posting vectors and hash table slots; allocator overhead not included)
+    pub fn token_index_stats(&self) -> (usize, usize, usize) {
+        let postings: usize = self.token_index.values().map(Vec::len).sum();
+        let bytes = self
+            .token_index
+            .iter()
+            .map(|(token, positions)| {
+                token.capacity()
+                    + positions.capacity() * std::mem::size_of::<usize>()
+                    + std::mem::size_of::<(String, Vec<usize>)>()
+            })
+            .sum::<usize>()
+            + self.token_index.capacity().saturating_sub(self.token_index.len())
+                * std::mem::size_of::<(String, Vec<usize>)>();
+        (self.token_index.len(), postings, bytes)
+    }
+
+    fn hit(&self, idx: usize, kind: MatchKind, score: f64) -> SearchHit<'_> {
+        SearchHit {
+            record: &self.records[idx],
+            kind,
+            score,
+        }
+    }
+
+    // Not already in the results under the same normalized name, and passes the filters
+    fn accepts_new<'a>(&'a self, seen: &mut HashSet<&'a str>, idx: usize, filters: &mut FilterEvaluator) -> bool {
+        let record = &self.records[idx];
+        if seen.contains(&*record.normalized_name) || !filters.accepts(record, &self.keys[idx]) {
+            return false;
+        }
+        seen.insert(&*record.normalized_name);
+        true
+    }
+
+    // Indexed names the exact/substring tiers didn't match, scoring at least `threshold`, best
+    // first, with whether the budget ran out and how many names were looked at
+    fn fuzzy_candidates(
+        &self,
+        normalized_query: &str,
+        threshold: f64,
+        budget: Option<Duration>,
+    ) -> (Vec<(f64, &str)>, bool, usize) {
+        let started = Instant::now();
+        let query_tokens: Vec<&str> = normalized_query.split(' ').collect();
+        let mut budget_exhausted = false;
+        let mut scored = Vec::new();
+        let mut scanned = 0;
+
+        // In name order, so a scan cut short by the budget always covers the same names
+        for (i, name) in self.sorted_names.iter().enumerate() {
+            if i % SUGGEST_BUDGET_BATCH == 0 && i > 0 {
+                if let Some(budget) = budget {
+                    if started.elapsed() >= budget {
+                        budget_exhausted = true;
+                        break;
+                    }
+                }
+            }
+            scanned += 1;
+            if name.contains(normalized_query) {
+                continue;
+            }
+
+            let name_tokens: Vec<&str> = name.split(' ').collect();
+            let mut total = 0.0;
+            for (scored_tokens, query_token) in query_tokens.iter().enumerate() {
+                // Give up on the name once even perfect remaining tokens can't reach the threshold
+                let remaining = (query_tokens.len() - scored_tokens) as f64;
+                if (total + remaining) / (query_tokens.len() as f64) < threshold {
+                    break;
+                }
+                total += token_similarity(query_token, &name_tokens);
+            }
+            let score = total / query_tokens.len() as f64;
+            if score >= threshold {
+                scored.push((score, &**name));
+            }
+        }
+
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        (scored, budget_exhausted, scanned)
+    }
+
+    // Names starting with `prefix` (normalized), alphabetically: a binary search to the first
+    // candidate, then a walk that stops at the first name past the prefix
+    pub fn suggest_names(&self, prefix: &str, limit: usize) -> Vec<NameSuggestion> {
+        let prefix = normalize_scheme_name(prefix);
+        if prefix.is_empty() {
+            return Vec::new();
+        }
+
+        let start = self.sorted_names.partition_point(|name| **name < *prefix);
+        self.sorted_names[start..]
+            .iter()
+            .take_while(|name| name.starts_with(&prefix))
+            .take(limit)
+            .map(|name| {
+                let positions = &self.name_index[name];
+                let fund_id = positions.iter().find_map(|&idx| self.records[idx].fund_id);
+                NameSuggestion {
+                    scheme_name: self.records[positions[0]].scheme_name.to_string(),
+                    fund_id,
+                }
+            })
+            .collect()
+    }
+
+    // Existence check without cloning, with name_aliases expanded as search expands them. Without
+    // `fuzzy` only the exact index is consulted; with it, the best hit of search's tiers, so the
+    // match kind is the one /search would report for the same name.
+    pub fn lookup_name(
+        &self,
+        name: &str,
+        fuzzy: bool,
+        threshold: f64,
+        budget: Option<Duration>,
+        max_candidates: usize,
+    ) -> Option<(&CombinedSchemeData, MatchKind)> {
+        let normalized = self.expand_name_aliases(&normalize_scheme_name(name));
+        if normalized.is_empty() {
+            return None;
+        }
+
+        if !fuzzy {
+            let idx = *self.name_index.get(normalized.as_str())?.first()?;
+            return Some((&self.records[idx], MatchKind::Exact));
+        }
+
+        let no_filters = SearchFilters::default();
+        let mut filters = FilterEvaluator::new(&no_filters);
+        let limits = SearchLimits {
+            wanted: 1,
+            threshold,
+            budget,
+            max_candidates,
+        };
+        let outcome = self.search(name, &mut filters, &limits);
+        outcome.hits.first().map(|hit| (hit.record, hit.kind))
+    }
+
+    // Closest fund names by Jaro-Winkler similarity on normalized names, best first.
+    // The scan checks the time budget every SUGGEST_BUDGET_BATCH names and ranks whatever it
+    // has scored so far once the budget runs out; the bool reports whether that happened.
+    pub fn suggest_funds(&self, name: &str, count: usize, budget: Option<Duration>) -> (Vec<FundSuggestion>, bool) {
+        let normalized = normalize_scheme_name(name);
+        let started = Instant::now();
+        let mut budget_exhausted = false;
+        let mut scored: Vec<(f64, &str)> = Vec::new();
+
+        // In name order, like fuzzy_candidates, so a scan cut short always scores the same names
+        for (i, key) in self.sorted_names.iter().enumerate() {
+            if i % SUGGEST_BUDGET_BATCH == 0 && i > 0 {
+                if let Some(budget) = budget {
+                    if started.elapsed() >= budget {
+                        budget_exhausted = true;
+                        break;
+                    }
+                }
+            }
+
+            if let Some(&idx) = self.name_index[key].first() {
+                scored.push((strsim::jaro_winkler(&normalized, key), &*self.records[idx].scheme_name));
+            }
+        }
+
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        let suggestions = scored
+            .into_iter()
+            .take(count)
+            .map(|(score, scheme_name)| FundSuggestion {
+                scheme_name: scheme_name.to_string(),
+                score,
+            })
+            .collect();
+
+        (suggestions, budget_exhausted)
+    }
+}
+
+// Fills a table with many records at once. add_record keeps sorted_names in order with an insert
+// per new name, which for a full build (rows arrive in fund id order, not name order) moves the
+// names after it every time; the builder appends them and sorts once in finish.
+pub struct TableBuilder {
+    table: VirtualTable,
+}
+
+impl TableBuilder {
+    // Set name_aliases on `table` first: names are indexed under their expansions as they arrive
+    pub fn new(table: VirtualTable) -> Self {
+        Self { table }
+    }
+
+    pub fn add_record(&mut self, record: CombinedSchemeData) {
+        self.table.push_record(record, false);
+    }
+
+    pub fn finish(mut self) -> VirtualTable {
+        self.table.sorted_names.sort_unstable();
+        debug_assert_eq!(self.table.check_invariants(), Ok(()));
+        self.table
+    }
+}
+
+// Positions in every one of the sorted posting lists
+fn intersect(mut postings: Vec<&Vec<usize>>) -> Vec<usize> {
+    postings.sort_by_key(|positions| positions.len());
+    let (shortest, rest) = match postings.split_first() {
+        Some(split) => split,
+        None => return Vec::new(),
+    };
+
+    shortest
+        .iter()
+        .copied()
+        .filter(|idx| rest.iter().all(|positions| positions.binary_search(idx).is_ok()))
+        .collect()
+}
+
+// American Soundex of a normalized token: the first letter, then up to three digits for the
+// consonant groups that follow, so "mirae"/"miray" (m600) and "quant"/"qwant" (q530) agree.
+// None for tokens shorter than MIN_FUZZY_TOKEN_LEN or with anything but ASCII letters.
+fn phonetic_key(token: &str) -> Option<String> {
+    if token.len() < MIN_FUZZY_TOKEN_LEN || !token.bytes().all(|b| b.is_ascii_lowercase()) {
+        return None;
+    }
+    let digit = |c: char| match c {
+        'b' | 'f' | 'p' | 'v' => Some('1'),
+        'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => Some('2'),
+        'd' | 't' => Some('3'),
+        'l' => Some('4'),
+        'm' | 'n' => Some('5'),
+        'r' => Some('6'),
+        _ => None,
+    };
+
+    let mut chars = token.chars();
+    let first = chars.next()?;
+    let mut key = first.to_string();
+    let mut last = digit(first);
+    for c in chars {
+        match digit(c) {
+            Some(d) if last != Some(d) => {
+                key.push(d);
+                if key.len() == 4 {
+                    break;
+                }
+                last = Some(d);
+            }
+            Some(_) => {}
+            // h and w don't separate letters with the same code; vowels do
+            None if c == 'h' || c == 'w' => {}
+            None => last = None,
+        }
+    }
+    while key.len() < 4 {
+        key.push('0');
+    }
+    Some(key)
+}
+
+// Distinct Soundex codes of a name's tokens, alias expansions included
+fn phonetic_keys(normalized: &str, aliases: &HashMap<String, String>) -> Vec<String> {
+    let mut keys: Vec<String> = name_tokens(normalized, aliases)
+        .iter()
+        .filter_map(|token| phonetic_key(token))
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+fn expand_tokens(normalized: &str, aliases: &HashMap<String, String>) -> String {
+    if aliases.is_empty() {
+        return normalized.to_string();
+    }
+
+    normalized
+        .split_whitespace()
+        .map(|token| aliases.get(token).map(String::as_str).unwrap_or(token))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Distinct tokens of the normalized name and of its alias expansion, so "ABSL Frontline" is found
+// by "aditya birla" and the other way round
+fn name_tokens(normalized: &str, aliases: &HashMap<String, String>) -> Vec<String> {
+    let expanded = expand_tokens(normalized, aliases);
+    let mut tokens: Vec<String> = normalized
+        .split(' ')
+        .chain(expanded.split(' '))
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+        .collect();
+    tokens.sort();
+    tokens.dedup();
+    tokens
+}
+
+fn remove_position<K: std::hash::Hash + Eq>(index: &mut HashMap<K, Vec<usize>>, key: K, idx: usize) {
+    if let Some(positions) = index.get_mut(&key) {
+        positions.retain(|&position| position != idx);
+        if positions.is_empty() {
+            index.remove(&key);
+        }
+    }
+}
+
+fn decrement(counts: &mut BTreeMap<String, usize>, key: &str) {
+    if let Some(count) = counts.get_mut(key) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLD: f64 = 0.85;
+    const MAX_CANDIDATES: usize = 1000;
+
+    fn limits(wanted: usize, budget: Option<Duration>) -> SearchLimits {
+        SearchLimits {
+            wanted,
+            threshold: THRESHOLD,
+            budget,
+            max_candidates: MAX_CANDIDATES,
+        }
+    }
+
+    fn table(names: &[&str]) -> VirtualTable {
+        let mut table = VirtualTable::new();
+        for (i, name) in names.iter().enumerate() {
+            table.add_record(CombinedSchemeData::test_fund(i as i32 + 1, name));
+        }
+        table
+    }
+
+    fn lookup(table: &VirtualTable, name: &str, fuzzy: bool) -> Option<(i32, MatchKind)> {
+        table
+            .lookup_name(name, fuzzy, THRESHOLD, None, MAX_CANDIDATES)
+            .map(|(record, kind)| (record.fund_id.unwrap(), kind))
+    }
+
+    fn first_search_hit(table: &VirtualTable, name: &str) -> Option<(i32, MatchKind)> {
+        let no_filters = SearchFilters::default();
+        let mut filters = FilterEvaluator::new(&no_filters);
+        let outcome = table.search(name, &mut filters, &limits(1, None));
+        outcome.hits.first().map(|hit| (hit.record.fund_id.unwrap(), hit.kind))
+    }
+
+    #[test]
+    fn lookup_without_fuzzy_only_finds_exact_names() {
+        let table = table(&["HDFC Flexi Cap Fund", "Axis Bluechip Fund"]);
+
+        assert_eq!(lookup(&table, "  HDFC flexi CAP fund! ", false), Some((1, MatchKind::Exact)));
+        assert_eq!(lookup(&table, "hdfc flexi", false), None);
+        assert_eq!(lookup(&table, "!!!", false), None);
+    }
+
+    #[test]
+    fn fuzzy_lookup_reports_the_tier_search_ranks_first() {
+        let table = table(&["HDFC Flexi Cap Fund", "Axis Bluechip Fund", "Axis Midcap Fund"]);
+
+        for (name, expected) in [
+            ("hdfc flexi", Some((1, MatchKind::Prefix))),
+            ("bluechip axis", Some((2, MatchKind::Tokens))),
+            ("xi cap", Some((1, MatchKind::Substring))),
+            ("axis midcapp fund", Some((3, MatchKind::Fuzzy))),
+            ("nippon india", None),
+        ] {
+            assert_eq!(lookup(&table, name, true), expected, "{}", name);
+            assert_eq!(lookup(&table, name, true), first_search_hit(&table, name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn fuzzy_lookup_uses_the_threshold() {
+        let table = table(&["Axis Midcap Fund"]);
+
+        // Too strict for the fuzzy tier, so only the phonetic fallback finds it
+        assert_eq!(
+            table.lookup_name("axis midcapp fund", true, 0.999, None, MAX_CANDIDATES).map(|(_, kind)| kind),
+            Some(MatchKind::Phonetic)
+        );
+    }
+
+    #[test]
+    fn lookup_expands_name_aliases() {
+        let mut table = VirtualTable::new();
+        table.set_name_aliases(HashMap::from([("absl".to_string(), "aditya birla sun life".to_string())]));
+        table.add_record(CombinedSchemeData::test_fund(1, "Aditya Birla Sun Life Frontline Equity Fund"));
+
+        for fuzzy in [false, true] {
+            assert_eq!(lookup(&table, "ABSL Frontline Equity Fund", fuzzy), Some((1, MatchKind::Exact)));
+        }
+        assert_eq!(lookup(&table, "absl frontline", true), Some((1, MatchKind::Prefix)));
+    }
+
+    #[test]
+    fn typos_match_fuzzily_and_only_when_the_other_tiers_fall_short() {
+        let table = table(&["Axis Bluechip Fund", "ICICI Prudential Bluechip Fund", "Axis Midcap Fund"]);
+        let search = |query: &str, wanted: usize| {
+            let no_filters = SearchFilters::default();
+            let mut filters = FilterEvaluator::new(&no_filters);
+            let outcome = table.search(query, &mut filters, &limits(wanted, None));
+            let hits: Vec<(i32, MatchKind, f64)> =
+                outcome.hits.iter().map(|hit| (hit.record.fund_id.unwrap(), hit.kind, hit.score)).collect();
+            (hits, outcome.diagnostics.fuzzy_names_scanned)
+        };
+
+        for (query, fund_id) in [("axsi bluechip", 1), ("icici prudentail", 2)] {
+            let (hits, scanned) = search(query, 10);
+            assert_eq!((hits[0].0, hits[0].1), (fund_id, MatchKind::Fuzzy), "{}", query);
+            assert!(hits[0].2 > banded_score(MatchKind::Fuzzy, THRESHOLD), "{}", query);
+            assert_eq!(scanned, 3, "{}", query);
+        }
+
+        // Enough prefix matches for the page: no fuzzy scan at all
+        let (hits, scanned) = search("axis", 2);
+        assert!(hits.iter().all(|hit| hit.1 == MatchKind::Prefix));
+        assert_eq!(scanned, 0);
+        // Below the threshold is no match
+        let (hits, _) = search("zxqv bluechip", 10);
+        assert!(hits.iter().all(|hit| hit.1 != MatchKind::Fuzzy), "{:?}", hits);
+    }
+
+    #[test]
+    fn every_query_token_must_appear_in_any_order_and_the_substring_scan_is_the_fallback() {
+        let mut table = table(&["HDFC Flexi Cap Fund", "HDFC Top 100 Fund", "Parag Parikh Flexi Cap Fund"]);
+        let names = |table: &VirtualTable, query: &str| {
+            let mut names: Vec<String> = table
+                .token_matches(query)
+                .into_iter()
+                .map(|idx| table.records[idx].scheme_name.to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names(&table, "flexi hdfc"), ["HDFC Flexi Cap Fund"]);
+        assert_eq!(names(&table, "cap flexi"), ["HDFC Flexi Cap Fund", "Parag Parikh Flexi Cap Fund"]);
+        assert!(names(&table, "flexi zzz").is_empty());
+        // "flex" is no token, so the words-in-order substring scan answers instead
+        assert!(names(&table, "hdfc flex").is_empty());
+        assert_eq!(ranked(&table, "hdfc flex"), [("HDFC Flexi Cap Fund".to_string(), MatchKind::Prefix)]);
+
+        let (tokens, postings, bytes) = table.token_index_stats();
+        assert_eq!((tokens, postings), (8, 13));
+        assert!(bytes > 0);
+        table.remove_fund(1);
+        assert!(names(&table, "flexi hdfc").is_empty());
+        assert_eq!(names(&table, "hdfc"), ["HDFC Top 100 Fund"]);
+        assert_eq!(table.token_index_stats().1, 9);
+    }
+
+    // Names that all score the same against "scheme growth fnd", which only the fuzzy tier matches
+    fn numbered_schemes(count: usize) -> VirtualTable {
+        let names: Vec<String> = (0..count).map(|n| format!("Scheme {:04} Growth Fund", n)).collect();
+        table(&names.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn an_exhausted_fuzzy_budget_returns_the_names_scored_so_far_in_rank_order() {
+        let table = numbered_schemes(1000);
+        let no_filters = SearchFilters::default();
+        let mut filters = FilterEvaluator::new(&no_filters);
+
+        let outcome = table.search("scheme growth fnd", &mut filters, &limits(20, Some(Duration::ZERO)));
+
+        assert!(outcome.budget_exhausted);
+        assert_eq!(outcome.diagnostics.fuzzy_names_scanned, SUGGEST_BUDGET_BATCH);
+        assert_eq!(outcome.hits.len(), SUGGEST_BUDGET_BATCH);
+        assert!(outcome.hits.iter().all(|hit| hit.kind == MatchKind::Fuzzy));
+        let names: Vec<&str> = outcome.hits.iter().map(|hit| &*hit.record.normalized_name).collect();
+        let expected: Vec<String> = (0..SUGGEST_BUDGET_BATCH).map(|n| format!("scheme {:04} growth fund", n)).collect();
+        assert_eq!(names, expected);
+        assert!(outcome.hits.windows(2).all(|pair| pair[0].score >= pair[1].score));
+    }
+
+    #[test]
+    fn normal_queries_stay_within_the_budget() {
+        let table = numbered_schemes(1000);
+        let no_filters = SearchFilters::default();
+        let budget = Some(Duration::from_millis(50));
+
+        for query in ["scheme growth fnd", "scheme 0500 growth fund", "0500", "growth"] {
+            let mut filters = FilterEvaluator::new(&no_filters);
+            let outcome = table.search(query, &mut filters, &limits(20, budget));
+            assert!(!outcome.budget_exhausted, "{}", query);
+            assert!(!outcome.hits.is_empty(), "{}", query);
+        }
+        let (suggestions, exhausted) = table.suggest_funds("schme 0500", 5, budget);
+        assert!(!exhausted);
+        assert_eq!(suggestions[0].scheme_name, "Scheme 0500 Growth Fund");
+    }
+
+    #[test]
+    fn the_budget_never_cuts_the_exact_and_substring_tiers() {
+        let table = numbered_schemes(1000);
+        let no_filters = SearchFilters::default();
+
+        let mut filters = FilterEvaluator::new(&no_filters);
+        let exact = table.search("scheme 0500 growth fund", &mut filters, &limits(1, Some(Duration::ZERO)));
+        assert!(!exact.budget_exhausted);
+        assert_eq!(exact.hits[0].kind, MatchKind::Exact);
+
+        let mut filters = FilterEvaluator::new(&no_filters);
+        let substring = table.search("wth fund", &mut filters, &limits(2000, Some(Duration::ZERO)));
+        assert_eq!(substring.hits.iter().filter(|hit| hit.kind == MatchKind::Substring).count(), 1000);
+    }
+
+    #[test]
+    fn the_candidate_cap_keeps_the_best_ranked_matches_wherever_they_sort() {
+        let table = numbered_schemes(1000);
+        let no_filters = SearchFilters::default();
+        let search = |query: &str, max_candidates: usize| {
+            let mut filters = FilterEvaluator::new(&no_filters);
+            let limits = SearchLimits { max_candidates, ..limits(1, Some(Duration::ZERO)) };
+            let outcome = table.search(query, &mut filters, &limits);
+            let names: Vec<String> = outcome.hits.iter().map(|hit| hit.record.normalized_name.to_string()).collect();
+            (names, outcome.truncated)
+        };
+
+        // Mid-word queries for the last names in name order, whatever the cap
+        assert_eq!(search("0999 grow", 100), (vec!["scheme 0999 growth fund".to_string()], false));
+        assert_eq!(search("e 0998 gro", 1).0, ["scheme 0998 growth fund"]);
+        assert_eq!(search("99 growth fu", 100).0.len(), 10);
+
+        // A cut keeps the head of the full ranking, not the first records or names
+        let (all, truncated) = search("growth", 2000);
+        assert!(!truncated);
+        let (capped, truncated) = search("growth", 100);
+        assert!(truncated);
+        assert_eq!(capped, all[..100]);
+        let (capped, truncated) = search("wth fu", 100);
+        assert!(truncated);
+        assert_eq!(capped, search("wth fu", 2000).0[..100]);
+    }
+
+    #[test]
+    fn an_exhausted_suggestion_budget_ranks_what_it_scored() {
+        let table = numbered_schemes(1000);
+
+        let (suggestions, exhausted) = table.suggest_funds("scheme 0100 growth", 5, Some(Duration::ZERO));
+
+        assert!(exhausted);
+        assert_eq!(suggestions.len(), 5);
+        assert!(suggestions.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        // Only the first batch in name order was scored, so the cut is the same on every call
+        let first_batch = format!("Scheme {:04} Growth Fund", SUGGEST_BUDGET_BATCH);
+        assert!(suggestions.iter().all(|suggestion| suggestion.scheme_name < first_batch));
+        assert_eq!(suggestions[0].scheme_name, "Scheme 0100 Growth Fund");
+    }
+
+    fn ranked(table: &VirtualTable, query: &str) -> Vec<(String, MatchKind)> {
+        let no_filters = SearchFilters::default();
+        let mut filters = FilterEvaluator::new(&no_filters);
+        let outcome = table.search(query, &mut filters, &limits(100, None));
+        outcome.hits.iter().map(|hit| (hit.record.scheme_name.to_string(), hit.kind)).collect()
+    }
+
+    #[test]
+    fn matches_rank_by_band_then_by_coverage_within_it() {
+        let table = table(&[
+            "HDFC Corporate Bond Fund",
+            "HDFC Top 100 Fund",
+            "Top HDFC Equity Fund",
+            "HDFC Mid Cap Opportunities Top Fund",
+        ]);
+
+        let hits = ranked(&table, "hdfc top");
+        assert_eq!(hits[0], ("HDFC Top 100 Fund".to_string(), MatchKind::Prefix));
+        // Sharing only "hdfc" ranks below every name with both tokens, if it matches at all
+        let position = |name: &str| hits.iter().position(|(hit, _)| hit == name).unwrap_or(usize::MAX);
+        assert!(position("HDFC Top 100 Fund") < position("HDFC Corporate Bond Fund"));
+        // Both tokens, but out of order or apart: the tokens band, higher coverage first
+        assert_eq!(hits[1], ("Top HDFC Equity Fund".to_string(), MatchKind::Tokens));
+        assert_eq!(hits[2], ("HDFC Mid Cap Opportunities Top Fund".to_string(), MatchKind::Tokens));
+
+        // A prefix covering more of the name outranks a longer name with the same prefix
+        let table = self::table(&["Axis Bluechip Fund Direct Plan Growth", "Axis Bluechip Fund"]);
+        let names: Vec<String> = ranked(&table, "axis bluechip").into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["Axis Bluechip Fund", "Axis Bluechip Fund Direct Plan Growth"]);
+    }
+
+    #[test]
+    fn tokens_in_any_order_match_and_stop_words_alone_match_nothing() {
+        let table = table(&[
+            "HDFC Mid Cap Opportunities Fund Growth",
+            "Growth Fund of HDFC Midcap",
+            "HDFC Flexi Cap Fund",
+        ]);
+
+        // Only the name with every token is a token match; "mid cap" is no "midcap" token
+        let hits = ranked(&table, "growth hdfc midcap");
+        assert_eq!(hits[0], ("Growth Fund of HDFC Midcap".to_string(), MatchKind::Tokens));
+        assert!(hits[1..].iter().all(|(_, kind)| *kind == MatchKind::Fuzzy), "{:?}", hits);
+        // Contiguous tokens early in the name rank above the same tokens scattered or late
+        let fraction = |query: &[&str], name: &str| token_order_fraction(query, name);
+        assert!(fraction(&["hdfc", "mid"], "hdfc mid cap fund") > fraction(&["hdfc", "mid"], "mid cap hdfc fund"));
+        assert!(fraction(&["mid", "cap"], "hdfc mid cap fund") < fraction(&["mid", "cap"], "mid cap hdfc fund"));
+
+        // One token still matches as before
+        assert_eq!(ranked(&table, "flexi"), [("HDFC Flexi Cap Fund".to_string(), MatchKind::Tokens)]);
+        for query in ["fund", "the fund", "Plan of the Scheme"] {
+            assert!(ranked(&table, query).is_empty(), "{}", query);
+        }
+        assert_eq!(ranked(&table, "hdfc fund").len(), 3);
+    }
+
+    #[test]
+    fn equal_scores_rank_larger_funds_first_then_by_name() {
+        let mut table = VirtualTable::new();
+        for (id, name, size) in [
+            (1, "Gamma Flexi Cap Fund", Some(100.0)),
+            (2, "Alpha Flexi Cap Fund", None),
+            (3, "Delta Flexi Cap Fund", Some(2500.0)),
+            (4, "Beta Flexi Cap Fund", None),
+        ] {
+            let mut record = CombinedSchemeData::test_fund(id, name);
+            record.fund_size_may25 = size;
+            table.add_record(record);
+        }
+
+        let hits = ranked(&table, "flexi cap");
+        let names: Vec<&str> = hits.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Delta Flexi Cap Fund", "Gamma Flexi Cap Fund", "Alpha Flexi Cap Fund", "Beta Flexi Cap Fund"]);
+    }
+
+    #[test]
+    fn the_same_query_returns_the_same_ordered_page_every_time() {
+        let names: Vec<String> = (0..40).map(|n| format!("Flexi Cap Fund Series {:02}", 39 - n)).collect();
+        let search_ids = |reverse: bool| {
+            // A fresh table each time, so the name index hashes with a new seed
+            let mut table = VirtualTable::new();
+            let mut ids: Vec<usize> = (0..names.len()).collect();
+            if reverse {
+                ids.reverse();
+            }
+            for i in ids {
+                table.add_record(CombinedSchemeData::test_fund(i as i32 + 1, &names[i]));
+            }
+            let no_filters = SearchFilters::default();
+            let mut filters = FilterEvaluator::new(&no_filters);
+            let outcome = table.search("flexi cap series", &mut filters, &limits(15, None));
+            // The route pages the ranked hits; the first page must not depend on the seed either
+            outcome.hits.iter().take(15).map(|hit| hit.record.fund_id.unwrap()).collect::<Vec<i32>>()
+        };
+
+        let first = search_ids(false);
+        assert_eq!(first.len(), 15);
+        for run in 0..50 {
+            assert_eq!(search_ids(run % 2 == 1), first, "run {}", run);
+        }
+        // Equal scores fall back to the normalized name, whatever the insertion order
+        assert_eq!(&first[..3], &[40, 39, 38]);
+    }
+
+    #[test]
+    fn soundex_keys_agree_for_the_common_amc_misspellings() {
+        assert_eq!(phonetic_key("mirae"), Some("m600".to_string()));
+        assert_eq!(phonetic_key("miray"), Some("m600".to_string()));
+        assert_eq!(phonetic_key("quant"), Some("q530".to_string()));
+        assert_eq!(phonetic_key("qwant"), Some("q530".to_string()));
+        assert_eq!(phonetic_key("ashcraft"), Some("a261".to_string()));
+        assert_eq!(phonetic_key("of"), None);
+        assert_eq!(phonetic_key("100x"), None);
+    }
+
+    #[test]
+    fn misspelled_amc_names_fall_back_to_phonetic_matches() {
+        let table = table(&["Mirae Asset Large Cap Fund", "Quant Small Cap Fund", "Axis Bluechip Fund"]);
+        let strict = |query: &str| {
+            let no_filters = SearchFilters::default();
+            let mut filters = FilterEvaluator::new(&no_filters);
+            let outcome = table.search(query, &mut filters, &SearchLimits { threshold: 0.99, ..limits(10, None) });
+            outcome.hits.iter().map(|hit| (hit.record.fund_id.unwrap(), hit.kind)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(strict("miray"), vec![(1, MatchKind::Phonetic)]);
+        assert_eq!(strict("qwant"), vec![(2, MatchKind::Phonetic)]);
+        assert_eq!(strict("miray asset"), vec![(1, MatchKind::Phonetic)]);
+        // Only a last resort: any other tier's match keeps phonetic ones out
+        assert_eq!(strict("quant"), vec![(2, MatchKind::Prefix)]);
+        assert_eq!(strict("zzzzz"), vec![]);
+    }
+
+    #[test]
+    fn a_bulk_build_indexes_the_same_as_adding_one_record_at_a_time() {
+        let names = ["Quant Small Cap Fund", "Axis Bluechip Fund", "Quant Small Cap Fund", "HDFC Top 100 Fund"];
+        let incremental = table(&names);
+        let mut builder = TableBuilder::new(VirtualTable::new());
+        for (i, name) in names.iter().enumerate() {
+            builder.add_record(CombinedSchemeData::test_fund(i as i32 + 1, name));
+        }
+        let mut bulk = builder.finish();
+
+        assert_eq!(bulk.check_invariants(), Ok(()));
+        assert_eq!(bulk.sorted_names, incremental.sorted_names);
+        assert_eq!(bulk.name_index, incremental.name_index);
+        // Still in order for the incremental changes after it
+        bulk.upsert(CombinedSchemeData::test_fund(5, "Bandhan Core Equity Fund"));
+        assert_eq!(bulk.sorted_names[..2], [Arc::from("axis bluechip fund"), Arc::from("bandhan core equity fund")]);
+    }
+
+    #[test]
+    fn index_keys_are_the_stored_normalized_names() {
+        let mut table = table(&[
+            "  HDFC Flexi-Cap Fund (G) ",
+            "ICICI Pru. Bluechip Fund",
+            "Nippon India\tSmall   Cap",
+            "SBI Magnum Midcap Fund – Direct",
+        ]);
+        table.upsert(CombinedSchemeData::test_fund(2, "ICICI Prudential Bluechip Fund"));
+
+        assert_eq!(table.check_invariants(), Ok(()));
+        for (key, positions) in &table.name_index {
+            for &idx in positions {
+                let record = &table.records[idx];
+                assert_eq!(*key, record.normalized_name);
+                assert_eq!(**key, normalize_scheme_name(&record.scheme_name));
+            }
+        }
+    }
+
+    // xorshift64*, so the sequence below is the same on every run and a failure names its step
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 33) as usize % n
+        }
+    }
+
+    #[test]
+    fn indexes_stay_in_step_through_a_random_sequence_of_changes() {
+        // Few names, funds and rates, so names are shared and changes hit existing records often
+        const NAMES: [&str; 6] = [
+            "HDFC Flexi Cap Fund",
+            "HDFC Flexi-Cap Fund (G)",
+            "Axis Bluechip Fund",
+            "Mirae Asset Large Cap Fund",
+            "Quant Small Cap Fund",
+            "Nippon India Small Cap",
+        ];
+        const CATEGORIES: [&str; 2] = ["Flexi Cap", "Small Cap"];
+        const COMPANIES: [&str; 2] = ["NJ India Invest", "Prudent Corporate"];
+
+        let mut rng = Rng(0x5eed_1234_abcd_0042);
+        let mut table = VirtualTable::new();
+        // What the table should hold: (fund, rate) -> name
+        let mut expected: HashMap<(Option<i32>, Option<i32>), &str> = HashMap::new();
+
+        for step in 0..1000 {
+            let fund_id = (rng.below(8) > 0).then(|| rng.below(12) as i32 + 1);
+            let rate_id = (rng.below(3) > 0).then(|| rng.below(6) as i32 + 1);
+            let name = NAMES[rng.below(NAMES.len())];
+            let mut record = CombinedSchemeData::test_fund(0, name);
+            record.fund_id = fund_id;
+            record.rate_id = rate_id;
+            if rate_id.is_some() {
+                record.arn = Some(Arc::from(format!("ARN-{}", rng.below(4))));
+                record.company = Some(Arc::from(COMPANIES[rng.below(COMPANIES.len())]));
+            }
+            if rng.below(2) == 0 {
+                record.fund_category = Some(Arc::from(CATEGORIES[rng.below(CATEGORIES.len())]));
+            }
+
+            let operation = rng.below(10);
+            match operation {
+                // Adds the pair, or replaces it (possibly under another name) when it exists
+                0..=4 => {
+                    let replaced = table.upsert(record);
+                    assert_eq!(replaced, expected.insert((fund_id, rate_id), name).is_some(), "step {}", step);
+                }
+                5..=7 => {
+                    let removed = table.remove(fund_id, rate_id).map(|record| record.scheme_name.to_string());
+                    assert_eq!(removed.as_deref(), expected.remove(&(fund_id, rate_id)), "step {}", step);
+                }
+                _ => {
+                    let Some(fund_id) = fund_id else { continue };
+                    let removed = table.remove_fund(fund_id).len();
+                    let before = expected.len();
+                    expected.retain(|(fund, _), _| *fund != Some(fund_id));
+                    assert_eq!(removed, before - expected.len(), "step {}", step);
+                }
+            }
+
+            assert_eq!(table.check_invariants(), Ok(()), "step {} (operation {})", step, operation);
+            assert_eq!(table.len(), expected.len(), "step {}", step);
+            for (key, positions) in &table.name_index {
+                for &idx in positions {
+                    assert_eq!(**key, normalize_scheme_name(&table.records[idx].scheme_name), "step {}", step);
+                }
+            }
+        }
+        for ((fund_id, rate_id), name) in expected {
+            let position = table.position(fund_id, rate_id).expect("every expected record is in the table");
+            assert_eq!(&*table.records[position].scheme_name, name);
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "out of step with its scheme_name")]
+    fn a_record_with_a_foreign_normalized_name_is_refused_in_debug_builds() {
+        let mut record = CombinedSchemeData::test_fund(1, "HDFC Flexi Cap Fund");
+        record.normalized_name = "axis bluechip fund".into();
+        VirtualTable::new().add_record(record);
+    }
+
+    #[test]
+    fn band_scores_never_overlap() {
+        let kinds = [
+            MatchKind::Prefix,
+            MatchKind::Tokens,
+            MatchKind::Substring,
+            MatchKind::Fuzzy,
+            MatchKind::Phonetic,
+        ];
+        for pair in kinds.windows(2) {
+            assert!(banded_score(pair[0], 0.0) > banded_score(pair[1], 1.0), "{:?}", pair);
+        }
+        assert!(banded_score(MatchKind::Exact, 0.0) > banded_score(MatchKind::Prefix, 1.0));
+        assert_eq!(banded_score(MatchKind::Screen, 1.0), 0.0);
+    }
+
+    #[test]
+    fn match_kind_header_value_is_its_serialized_name() {
+        for kind in [
+            MatchKind::Exact,
+            MatchKind::Prefix,
+            MatchKind::Tokens,
+            MatchKind::Substring,
+            MatchKind::Fuzzy,
+            MatchKind::Phonetic,
+            MatchKind::Arn,
+            MatchKind::Screen,
+        ] {
+            assert_eq!(serde_json::to_value(kind).unwrap(), kind.as_str());
+        }
+    }
+}
@@ -0,0 +1,160 @@
+// Fixtures shared by the unit tests. Tests that need PostgreSQL use the database named by
+// TEST_DATABASE_URL, which they wipe, and return early without checking anything when it isn't set:
+//
+//   TEST_DATABASE_URL=postgres://postgres@127.0.0.1/perftracker_test cargo test
+use actix_web::dev::ServiceResponse;
+use actix_web::http::{header, StatusCode};
+use actix_web::test::{self, TestRequest};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, MutexGuard};
+use tokio_postgres::Config;
+
+use crate::auth::{ApiKey, API_KEY_HEADER};
+use crate::config::{self, RuntimeConfig};
+use crate::db::{self, DbPools, TlsMode, TlsSettings};
+use crate::table::{TableBuilder, VirtualTable};
+use crate::{migrations, AppState, CombinedSchemeData};
+
+const TEST_DATABASE_URL_ENV: &str = "TEST_DATABASE_URL";
+// Tests without a database get a pool that fails on checkout, never one that reaches a server
+const UNUSED_DATABASE_URL: &str = "postgres://perftracker@127.0.0.1:1/perftracker";
+
+// One test at a time owns the database
+static DATABASE: Mutex<()> = Mutex::const_new(());
+
+// Secret of the admin key runtime_config sets up
+pub const ADMIN_KEY: &str = "test-admin-key-0123456789";
+const MULTIPART_BOUNDARY: &str = "perftracker-test-boundary";
+
+// The default reloadable configuration with one admin key, ADMIN_KEY
+pub fn runtime_config() -> RuntimeConfig {
+    RuntimeConfig {
+        api_keys: vec![ApiKey::new("test-admin".to_string(), ADMIN_KEY.to_string())],
+        ..RuntimeConfig::default()
+    }
+}
+
+pub fn as_admin(request: TestRequest) -> TestRequest {
+    request.insert_header((API_KEY_HEADER, ADMIN_KEY))
+}
+
+// A multipart/form-data POST with one part per (field name, file name, content); parts without
+// a file name are plain form fields
+pub fn multipart(uri: &str, parts: &[(&str, Option<&str>, &[u8])]) -> TestRequest {
+    let mut body = Vec::new();
+    for (name, file_name, content) in parts {
+        body.extend_from_slice(format!("--{}\r\n", MULTIPART_BOUNDARY).as_bytes());
+        let disposition = match file_name {
+            Some(file_name) => format!("form-data; name=\"{}\"; filename=\"{}\"", name, file_name),
+            None => format!("form-data; name=\"{}\"", name),
+        };
+        body.extend_from_slice(format!("Content-Disposition: {}\r\n\r\n", disposition).as_bytes());
+        body.extend_from_slice(content);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", MULTIPART_BOUNDARY).as_bytes());
+
+    TestRequest::post()
+        .uri(uri)
+        .insert_header((
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY),
+        ))
+        .set_payload(body)
+}
+
+// State with the default static configuration, `runtime_config` and a table of `records`
+pub fn state(runtime_config: RuntimeConfig, records: Vec<CombinedSchemeData>) -> AppState {
+    let state = state_for(UNUSED_DATABASE_URL, runtime_config);
+    let mut builder = TableBuilder::new(VirtualTable::new());
+    for record in records {
+        builder.add_record(record);
+    }
+    state.virtual_table.store(Arc::new(builder.finish()));
+    state
+}
+
+fn state_for(url: &str, runtime_config: RuntimeConfig) -> AppState {
+    let (static_config, _) = config::load(None).expect("default configuration is valid");
+    let tls = TlsSettings {
+        mode: TlsMode::Disable,
+        root_cert: None,
+    };
+    let config: Config = url.parse().expect("TEST_DATABASE_URL is a connection string");
+    let pool = db::create_pool(config, &tls, 4, Duration::from_secs(5)).expect("pool for the test database");
+    AppState::new(static_config, runtime_config, None, DbPools::new(pool, None), None)
+}
+
+// Exclusive use of the test database, migrated from scratch and with an empty table; dropped
+// along with the state
+pub struct TestDatabase {
+    pub state: AppState,
+    _guard: MutexGuard<'static, ()>,
+}
+
+// None when no test database is configured
+pub async fn database(runtime_config: RuntimeConfig) -> Option<TestDatabase> {
+    let url = match std::env::var(TEST_DATABASE_URL_ENV) {
+        Ok(url) => url,
+        Err(_) => {
+            eprintln!("{} is not set; skipping a test that needs PostgreSQL", TEST_DATABASE_URL_ENV);
+            return None;
+        }
+    };
+    let guard = DATABASE.lock().await;
+    let state = state_for(&url, runtime_config);
+
+    let mut client = state.pools.primary().get().await.expect("connect to the test database");
+    migrations::reset_database(&client).await.expect("reset the test database");
+    migrations::run_migrations(&mut client).await.expect("migrate the test database");
+
+    Some(TestDatabase { state, _guard: guard })
+}
+
+impl TestDatabase {
+    pub async fn execute(&self, sql: &str) {
+        let client = self.state.pools.primary().get().await.expect("connect to the test database");
+        client.batch_execute(sql).await.expect("test SQL runs");
+    }
+
+    pub async fn query(&self, sql: &str) -> Vec<tokio_postgres::Row> {
+        let client = self.state.pools.primary().get().await.expect("connect to the test database");
+        client.query(sql, &[]).await.expect("test SQL runs")
+    }
+}
+
+// One request through the whole app, as main serves it over plain HTTP
+pub async fn call(state: &AppState, request: TestRequest) -> ServiceResponse {
+    let app = test::init_service(crate::app(state.clone(), false)).await;
+    test::call_service(&app, request.to_request()).await.map_into_boxed_body()
+}
+
+// Upload `files` (name, content) as admin and wait for the job; the finished job's status
+pub async fn upload(state: &AppState, files: &[(&str, &[u8])]) -> Value {
+    let parts: Vec<(&str, Option<&str>, &[u8])> = files
+        .iter()
+        .map(|(file_name, content)| ("excel_file", Some(*file_name), *content))
+        .collect();
+    let (status, accepted) = call_json(state, as_admin(multipart("/api/v1/upload", &parts))).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", accepted);
+
+    let job_url = accepted["job_url"].as_str().expect("upload response has a job_url").to_string();
+    for _ in 0..500 {
+        let (_, job) = call_json(state, TestRequest::get().uri(&job_url)).await;
+        if job["state"] == "done" || job["state"] == "failed" {
+            return job;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("upload job at {} did not finish", job_url);
+}
+
+// Status and JSON body of one request; Null for a body that isn't JSON
+pub async fn call_json(state: &AppState, request: TestRequest) -> (StatusCode, Value) {
+    let response = call(state, request).await;
+    let status = response.status();
+    let body = test::read_body(response).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
@@ -0,0 +1,128 @@
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::sign::CertifiedKey;
+use rustls::ServerConfig;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// A year, sent only over HTTPS; no includeSubDomains, as the other hosts on the domain aren't ours
+pub const STRICT_TRANSPORT_SECURITY: &str = "max-age=31536000";
+
+// PEM files from tls_cert_path / tls_key_path. The certificate file holds the server certificate
+// first, then any intermediates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsFiles {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+fn open(path: &Path, what: &str) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("Cannot read TLS {} {}: {}", what, path.display(), e))
+}
+
+fn read_chain(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let chain = rustls_pemfile::certs(&mut open(path, "certificate")?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("TLS certificate {} is not valid PEM: {}", path.display(), e))?;
+    if chain.is_empty() {
+        return Err(format!("TLS certificate {} holds no certificates", path.display()));
+    }
+    Ok(chain)
+}
+
+// PKCS#8, PKCS#1 (RSA) or SEC1 (EC); the first key in the file is used
+fn read_key(path: &Path) -> Result<PrivateKeyDer<'static>, String> {
+    match rustls_pemfile::private_key(&mut open(path, "key")?) {
+        Ok(Some(key)) => Ok(key),
+        Ok(None) => Err(format!("TLS key {} holds no private key", path.display())),
+        Err(e) => Err(format!("TLS key {} is not valid PEM: {}", path.display(), e)),
+    }
+}
+
+// Read and check both files so a bad pair stops startup with a clear message instead of every
+// handshake failing later
+pub fn server_config(files: &TlsFiles) -> Result<ServerConfig, String> {
+    let chain = read_chain(&files.cert_path)?;
+    let key = read_key(&files.key_path)?;
+
+    // Ring rather than whichever provider happens to be installed as the process default
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let signing_key = provider
+        .key_provider
+        .load_private_key(key.clone_key())
+        .map_err(|e| format!("TLS key {} is unusable: {}", files.key_path.display(), e))?;
+    CertifiedKey::new(chain.clone(), signing_key).keys_match().map_err(|e| {
+        format!(
+            "TLS key {} does not match certificate {}: {}",
+            files.key_path.display(),
+            files.cert_path.display(),
+            e
+        )
+    })?;
+
+    ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("Failed to set up TLS: {}", e))?
+        .with_no_client_auth()
+        .with_single_cert(chain, key)
+        .map_err(|e| format!("Failed to set up TLS with {}: {}", files.cert_path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{self, TestRequest};
+
+    use crate::config::RuntimeConfig;
+    use crate::test_support;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls").join(name)
+    }
+
+    fn files(cert: &str, key: &str) -> TlsFiles {
+        TlsFiles {
+            cert_path: fixture(cert),
+            key_path: fixture(key),
+        }
+    }
+
+    #[test]
+    fn a_matching_certificate_and_key_set_up_tls() {
+        assert!(server_config(&files("cert.pem", "key.pem")).is_ok());
+        // The server certificate followed by its issuer
+        assert_eq!(read_chain(&fixture("chain.pem")).unwrap().len(), 2);
+        assert!(server_config(&files("chain.pem", "key.pem")).is_ok());
+    }
+
+    #[test]
+    fn a_bad_pair_stops_startup_with_what_is_wrong() {
+        let error = |cert: &str, key: &str| server_config(&files(cert, key)).err().unwrap();
+
+        let mismatched = error("cert.pem", "other_key.pem");
+        assert!(mismatched.contains("other_key.pem does not match certificate"), "{}", mismatched);
+        let missing = error("missing.pem", "key.pem");
+        assert!(missing.starts_with("Cannot read TLS certificate"), "{}", missing);
+        let missing = error("cert.pem", "missing.pem");
+        assert!(missing.starts_with("Cannot read TLS key"), "{}", missing);
+        // Each file holds the other kind of PEM block
+        let no_certificates = error("key.pem", "key.pem");
+        assert!(no_certificates.ends_with("holds no certificates"), "{}", no_certificates);
+        let no_key = error("cert.pem", "cert.pem");
+        assert!(no_key.ends_with("holds no private key"), "{}", no_key);
+    }
+
+    #[actix_web::test]
+    async fn only_https_responses_carry_hsts() {
+        for (https, expected) in [(true, Some(STRICT_TRANSPORT_SECURITY)), (false, None)] {
+            let state = test_support::state(RuntimeConfig::default(), vec![]);
+            let app = test::init_service(crate::app(state, https)).await;
+            let response = test::call_service(&app, TestRequest::get().uri("/api/v1/search").to_request()).await;
+            let hsts = response.headers().get("strict-transport-security").map(|value| value.to_str().unwrap());
+            assert_eq!(hsts, expected);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::path::Path;
+use tokio_postgres::{Client, GenericClient, Row};
+
+use crate::providers::SheetMappings;
+
+pub const STATUS_PROCESSING: &str = "processing";
+pub const STATUS_COMMITTED: &str = "committed";
+pub const STATUS_ROLLED_BACK: &str = "rolled_back";
+// The funds the upload created were deleted by DELETE /uploads/{id}
+pub const STATUS_PURGED: &str = "purged";
+
+// What is known about an upload before its rows are written
+pub struct NewUpload<'a> {
+    pub provider: &'a str,
+    pub file_name: Option<&'a str>,
+    pub sha256: &'a str,
+    pub column_mappings: &'a SheetMappings,
+    // The request that made the upload, to find its log lines
+    pub request_id: &'a str,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UploadCounts {
+    pub processed: usize,
+    pub inserted: usize,
+    pub updated: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadEntry {
+    pub id: i32,
+    pub provider: String,
+    pub file_name: Option<String>,
+    pub sha256: Option<String>,
+    pub uploaded_at: Option<NaiveDateTime>,
+    pub processed: i32,
+    pub inserted: i32,
+    pub updated: i32,
+    pub failed: i32,
+    pub status: String,
+    pub request_id: Option<String>,
+}
+
+// What DELETE /uploads/{id} did. Prior values of the funds the upload overwrote are not kept, so
+// those funds stay, with the upload's numbers, and are only counted.
+#[derive(Debug, Clone, Serialize)]
+pub struct PurgeReport {
+    pub deleted: usize,
+    pub kept_updated: usize,
+    pub deleted_schemes: Vec<String>,
+}
+
+// Funds whose current numbers were written by an upload
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadedFund {
+    pub id: i32,
+    pub scheme_name: String,
+    pub category: String,
+    pub version: i32,
+}
+
+pub fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// Inserted inside the upload's transaction so funds can reference it; finish() fills in the counts
+pub async fn begin(client: &impl GenericClient, upload: &NewUpload<'_>) -> Result<i32, Box<dyn std::error::Error>> {
+    let row = client
+        .query_one(
+            "INSERT INTO uploads (provider, file_name, sha256, column_mappings, status, request_id)
+             VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+            &[
+                &upload.provider,
+                &upload.file_name,
+                &upload.sha256,
+                &serde_json::to_value(upload.column_mappings)?,
+                &STATUS_PROCESSING,
+                &upload.request_id,
+            ],
+        )
+        .await?;
+    Ok(row.get("id"))
+}
+
+pub async fn finish(
+    client: &impl GenericClient,
+    id: i32,
+    counts: UploadCounts,
+    status: &str,
+) -> Result<(), tokio_postgres::Error> {
+    client
+        .execute(
+            "UPDATE uploads SET processed = $2, inserted = $3, updated = $4, failed = $5, status = $6 WHERE id = $1",
+            &[
+                &id,
+                &(counts.processed as i32),
+                &(counts.inserted as i32),
+                &(counts.updated as i32),
+                &(counts.failed as i32),
+                &status,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+// A rolled-back upload loses its row with the transaction; record it again so history shows it
+pub async fn record_rolled_back(
+    client: &Client,
+    upload: &NewUpload<'_>,
+    counts: UploadCounts,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    let id = begin(client, upload).await?;
+    finish(client, id, counts, STATUS_ROLLED_BACK).await?;
+    Ok(id)
+}
+
+fn entry_from_row(row: &Row) -> UploadEntry {
+    UploadEntry {
+        id: row.get("id"),
+        provider: row.get("provider"),
+        file_name: row.get("file_name"),
+        sha256: row.get("sha256"),
+        uploaded_at: row.get("uploaded_at"),
+        processed: row.get("processed"),
+        inserted: row.get("inserted"),
+        updated: row.get("updated"),
+        failed: row.get("failed"),
+        status: row.get("status"),
+        request_id: row.get("request_id"),
+    }
+}
+
+const ENTRY_COLUMNS: &str = "id, provider, file_name, sha256, uploaded_at, processed, inserted, updated, failed, status, request_id";
+
+// Newest first
+pub async fn list(client: &Client, limit: i64) -> Result<Vec<UploadEntry>, tokio_postgres::Error> {
+    let rows = client
+        .query(
+            &format!("SELECT {} FROM uploads ORDER BY id DESC LIMIT $1", ENTRY_COLUMNS),
+            &[&limit],
+        )
+        .await?;
+    Ok(rows.iter().map(entry_from_row).collect())
+}
+
+pub async fn get(client: &Client, id: i32) -> Result<Option<UploadEntry>, tokio_postgres::Error> {
+    let row = client
+        .query_opt(&format!("SELECT {} FROM uploads WHERE id = $1", ENTRY_COLUMNS), &[&id])
+        .await?;
+    Ok(row.as_ref().map(entry_from_row))
+}
+
+// A fund re-uploaded later points at the later upload, so this is what the upload still accounts for
+pub async fn funds_for(client: &Client, id: i32) -> Result<Vec<UploadedFund>, tokio_postgres::Error> {
+    let rows = client
+        .query(
+            "SELECT id, scheme_name, category, version FROM funds WHERE upload_id = $1 ORDER BY scheme_name",
+            &[&id],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| UploadedFund {
+            id: row.get("id"),
+            scheme_name: row.get("scheme_name"),
+            category: row.get("category"),
+            version: row.get("version"),
+        })
+        .collect())
+}
+
+// Delete the funds the upload created. A fund is the upload's own when its upload_id still points
+// at it (no later upload rewrote it) and it was created in the upload's transaction, whose
+// CURRENT_TIMESTAMP is both uploads.uploaded_at and funds.created_at. None when there is no such
+// upload; Err(status) when it is still being processed.
+pub async fn purge(client: &mut Client, id: i32) -> Result<Option<Result<PurgeReport, String>>, tokio_postgres::Error> {
+    let transaction = client.transaction().await?;
+    let status: String = match transaction
+        .query_opt("SELECT status FROM uploads WHERE id = $1 FOR UPDATE", &[&id])
+        .await?
+    {
+        Some(row) => row.get("status"),
+        None => return Ok(None),
+    };
+    if status == STATUS_PROCESSING {
+        return Ok(Some(Err(status)));
+    }
+
+    let deleted = transaction
+        .query(
+            "DELETE FROM funds f USING uploads u
+             WHERE u.id = $1 AND f.upload_id = u.id AND f.created_at >= u.uploaded_at
+             RETURNING f.scheme_name",
+            &[&id],
+        )
+        .await?;
+    let kept_updated: i64 = transaction
+        .query_one("SELECT COUNT(*) FROM funds WHERE upload_id = $1", &[&id])
+        .await?
+        .get(0);
+    transaction
+        .execute("UPDATE uploads SET status = $2 WHERE id = $1", &[&id, &STATUS_PURGED])
+        .await?;
+    transaction.commit().await?;
+
+    let mut deleted_schemes: Vec<String> = deleted.iter().map(|row| row.get("scheme_name")).collect();
+    deleted_schemes.sort();
+    Ok(Some(Ok(PurgeReport {
+        deleted: deleted_schemes.len(),
+        kept_updated: kept_updated as usize,
+        deleted_schemes,
+    })))
+}
@@ -0,0 +1,184 @@
+use calamine::{open_workbook, open_workbook_auto, Ods, Sheets, Xls, Xlsb, Xlsx};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+pub const SUPPORTED_EXTENSIONS: [&str; 5] = ["xlsx", "xlsm", "xlsb", "xls", "ods"];
+
+pub type Workbook = Sheets<BufReader<File>>;
+
+// Lower-cased extension of an uploaded file name, without the dot
+pub fn extension(file_name: &str) -> Option<String> {
+    Path::new(file_name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .filter(|ext| !ext.is_empty())
+}
+
+// Open with the reader matching the path's extension. Uploads are persisted with the original
+// file's extension for this reason; a path without one falls back to calamine's own detection.
+pub fn open(path: &Path) -> Result<Workbook, Box<dyn std::error::Error>> {
+    let extension = match path.file_name().and_then(|name| extension(&name.to_string_lossy())) {
+        Some(extension) => extension,
+        None => return Ok(open_workbook_auto(path)?),
+    };
+
+    let workbook = match extension.as_str() {
+        "xlsx" | "xlsm" => Sheets::Xlsx(open_workbook::<Xlsx<_>, _>(path)?),
+        "xlsb" => Sheets::Xlsb(open_workbook::<Xlsb<_>, _>(path)?),
+        "xls" => Sheets::Xls(open_workbook::<Xls<_>, _>(path)?),
+        "ods" => Sheets::Ods(open_workbook::<Ods<_>, _>(path)?),
+        other => {
+            return Err(format!(
+                "Unsupported workbook format '.{}'; expected one of .{}",
+                other,
+                SUPPORTED_EXTENSIONS.join(", .")
+            )
+            .into())
+        }
+    };
+    Ok(workbook)
+}
+
+// Temp file for an upload, carrying the original extension so open() picks the right reader
+pub fn temp_file_for(file_name: Option<&str>) -> std::io::Result<tempfile::NamedTempFile> {
+    match file_name.and_then(extension) {
+        Some(extension) => tempfile::Builder::new().suffix(&format!(".{}", extension)).tempfile(),
+        None => tempfile::NamedTempFile::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::{Data, Reader};
+
+    use crate::test_support;
+
+    // The same "Flexi Cap" sheet saved in each format
+    const FIXTURES: [&str; 4] = ["funds.xlsx", "funds.xlsb", "funds.xls", "funds.ods"];
+
+    fn fixture(file_name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(file_name)
+    }
+
+    fn cell_text(cell: &Data) -> String {
+        match cell {
+            Data::Float(value) => value.to_string(),
+            Data::Int(value) => value.to_string(),
+            other => other.to_string(),
+        }
+    }
+
+    #[test]
+    fn every_format_opens_with_its_reader_and_reads_the_same_rows() {
+        let expected = vec![
+            vec!["Scheme Name", "Launch Date", "Latest NAV", "1Y"],
+            vec!["Parag Parikh Flexi Cap Fund", "2013-05-24", "80.5", "12.25"],
+            vec!["Quant Flexi Cap Fund", "2008-10-17", "90", "20.5"],
+        ];
+        for file_name in FIXTURES {
+            let mut workbook = open(&fixture(file_name)).unwrap_or_else(|e| panic!("{}: {}", file_name, e));
+            assert_eq!(workbook.sheet_names(), vec!["Flexi Cap"], "{}", file_name);
+            let range = workbook.worksheet_range("Flexi Cap").unwrap();
+            let rows: Vec<Vec<String>> = range.rows().map(|row| row.iter().map(cell_text).collect()).collect();
+            assert_eq!(rows, expected, "{}", file_name);
+        }
+    }
+
+    #[test]
+    fn the_extension_picks_the_reader() {
+        assert_eq!(extension("Funds.XLSB").as_deref(), Some("xlsb"));
+        assert_eq!(extension("funds.tar.ods").as_deref(), Some("ods"));
+        assert_eq!(extension("funds"), None);
+
+        // An .xls named .xlsx is read as a zip and fails rather than being guessed at
+        let dir = tempfile::tempdir().unwrap();
+        let misnamed = dir.path().join("funds.xlsx");
+        std::fs::copy(fixture("funds.xls"), &misnamed).unwrap();
+        assert!(open(&misnamed).is_err());
+
+        // Without an extension calamine detects the format itself
+        let bare = dir.path().join("funds");
+        std::fs::copy(fixture("funds.ods"), &bare).unwrap();
+        assert_eq!(open(&bare).unwrap().sheet_names(), vec!["Flexi Cap"]);
+
+        let numbers = dir.path().join("funds.numbers");
+        std::fs::write(&numbers, "Scheme Name\n").unwrap();
+        let message = open(&numbers).err().unwrap().to_string();
+        assert_eq!(message, "Unsupported workbook format '.numbers'; expected one of .xlsx, .xlsm, .xlsb, .xls, .ods");
+    }
+
+    #[test]
+    fn temp_files_keep_the_upload_extension() {
+        let file = temp_file_for(Some("Funds.ODS")).unwrap();
+        assert_eq!(file.path().extension().unwrap(), "ods");
+        assert!(temp_file_for(None).unwrap().path().extension().is_none());
+    }
+
+    #[actix_web::test]
+    async fn every_format_uploads_the_same_funds() {
+        for file_name in FIXTURES {
+            let db = match test_support::database(test_support::runtime_config()).await {
+                Some(db) => db,
+                None => return,
+            };
+            let content = std::fs::read(fixture(file_name)).unwrap();
+            let job = test_support::upload(&db.state, &[(file_name, &content)]).await;
+            assert_eq!(job["state"], "done", "{}", job);
+            assert_eq!(job["summary"]["files"][0]["report"]["inserted"], 2, "{}: {}", file_name, job);
+
+            let rows = db
+                .query("SELECT category, scheme_name, launch_date::TEXT, latest_nav, year_1 FROM funds ORDER BY scheme_name")
+                .await;
+            let funds: Vec<(String, String, String, f32, f32)> = rows
+                .iter()
+                .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3), row.get(4)))
+                .collect();
+            assert_eq!(
+                funds,
+                vec![
+                    ("Flexi Cap".into(), "Parag Parikh Flexi Cap Fund".into(), "2013-05-24".into(), 80.5, 12.25),
+                    ("Flexi Cap".into(), "Quant Flexi Cap Fund".into(), "2008-10-17".into(), 90.0, 20.5),
+                ],
+                "{}",
+                file_name
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn a_merged_two_row_header_resolves_and_the_dry_run_shows_it() {
+        let db = match test_support::database(test_support::runtime_config()).await {
+            Some(db) => db,
+            None => return,
+        };
+        // A title line, then "Returns (%)" merged over the periods on the row below
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let sheet = workbook.add_worksheet();
+        sheet.set_name("Large Cap").unwrap();
+        sheet.write_string(0, 0, "Monthly factsheet").unwrap();
+        sheet.write_string(1, 0, "Scheme Name").unwrap();
+        sheet.write_string(1, 1, "Launch Date").unwrap();
+        sheet.merge_range(1, 2, 1, 3, "Returns (%)", &rust_xlsxwriter::Format::new()).unwrap();
+        sheet.write_string(2, 2, "1M").unwrap();
+        sheet.write_string(2, 3, "1Y").unwrap();
+        sheet.write_string(3, 0, "Axis Bluechip Fund").unwrap();
+        sheet.write_string(3, 1, "2010-01-05").unwrap();
+        sheet.write_number(3, 2, 1.5).unwrap();
+        sheet.write_number(3, 3, 14.25).unwrap();
+        let content = workbook.save_to_buffer().unwrap();
+
+        let parts: [(&str, Option<&str>, &[u8]); 1] = [("excel_file", Some("amc.xlsx"), &content)];
+        let request = test_support::multipart("/api/v1/upload?dry_run=true", &parts);
+        let (status, body) = test_support::call_json(&db.state, test_support::as_admin(request)).await;
+        assert_eq!(status, 200, "{}", body);
+        let report = &body["files"][0]["report"];
+        let resolved =
+            serde_json::json!({"0": "Scheme Name", "1": "Launch Date", "2": "Returns (%) | 1M", "3": "Returns (%) | 1Y"});
+        assert_eq!(report["resolved_headers"]["Large Cap"], resolved, "{}", body);
+        assert_eq!(report["column_mappings"]["Large Cap"]["year_1"], "Returns (%) | 1Y");
+        assert_eq!(report["sheet_rows"]["Large Cap"], 1);
+        assert_eq!(report["new_schemes"], 1);
+    }
+}
@@ -0,0 +1,184 @@
+use chrono::NaiveDate;
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::categories::UNCATEGORIZED;
+use crate::CombinedSchemeData;
+
+// Largest export built in memory; bigger tables have to be narrowed by category or company
+pub const MAX_ROWS: usize = 100_000;
+
+// Excel's limit, and the characters it refuses in a sheet name
+const MAX_SHEET_NAME_CHARS: usize = 31;
+const INVALID_SHEET_NAME_CHARS: [char; 7] = ['[', ']', ':', '*', '?', '/', '\\'];
+
+const HEADERS: [&str; 26] = [
+    "Scheme Name",
+    "Fund ID",
+    "Launch Date",
+    "Fund Size Apr25",
+    "Fund Size May25",
+    "Latest NAV",
+    "1 Month",
+    "3 Months",
+    "6 Months",
+    "YTD",
+    "1 Year",
+    "2 Years",
+    "3 Years",
+    "5 Years",
+    "Rate ID",
+    "ARN",
+    "Company",
+    "Scheme Category",
+    "Brokerage Type",
+    "Start Date",
+    "End Date",
+    "Base Year 1",
+    "Base Year 2",
+    "Base Year 3",
+    "Rate Active",
+    "Incomplete",
+];
+
+// One sheet per fund category (records without one go to "Uncategorized"), each with a bold,
+// frozen header row and the return columns formatted as percentages
+pub fn build_workbook(records: &[CombinedSchemeData]) -> Result<Vec<u8>, XlsxError> {
+    let mut by_category: BTreeMap<&str, Vec<&CombinedSchemeData>> = BTreeMap::new();
+    for record in records {
+        let category = record.fund_category.as_deref().unwrap_or(UNCATEGORIZED);
+        by_category.entry(category).or_default().push(record);
+    }
+
+    let mut workbook = Workbook::new();
+    let bold = Format::new().set_bold();
+    let percent = Format::new().set_num_format("0.00%");
+    let mut sheet_names = HashSet::new();
+
+    for (category, mut records) in by_category {
+        records.sort_by(|a, b| a.scheme_name.cmp(&b.scheme_name).then(a.rate_id.cmp(&b.rate_id)));
+
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(sheet_name(category, &mut sheet_names))?;
+        for (col, header) in HEADERS.iter().enumerate() {
+            worksheet.write_string_with_format(0, col as u16, *header, &bold)?;
+        }
+        worksheet.set_freeze_panes(1, 0)?;
+        worksheet.set_column_width(0, 50)?;
+
+        for (i, record) in records.iter().enumerate() {
+            write_record(worksheet, (i + 1) as u32, record, &percent)?;
+        }
+    }
+
+    // A workbook needs at least one sheet, even for an empty export
+    if sheet_names.is_empty() {
+        let worksheet = workbook.add_worksheet();
+        for (col, header) in HEADERS.iter().enumerate() {
+            worksheet.write_string_with_format(0, col as u16, *header, &bold)?;
+        }
+        worksheet.set_freeze_panes(1, 0)?;
+    }
+
+    workbook.save_to_buffer()
+}
+
+fn write_record(
+    worksheet: &mut Worksheet,
+    row: u32,
+    record: &CombinedSchemeData,
+    percent: &Format,
+) -> Result<(), XlsxError> {
+    worksheet.write_string(row, 0, &*record.scheme_name)?;
+    write_optional_integer(worksheet, row, 1, record.fund_id)?;
+    write_optional_date(worksheet, row, 2, record.launch_date)?;
+    write_optional_number(worksheet, row, 3, record.fund_size_apr25)?;
+    write_optional_number(worksheet, row, 4, record.fund_size_may25)?;
+    write_optional_number(worksheet, row, 5, record.latest_nav)?;
+
+    let returns = [
+        record.month_1,
+        record.months_3,
+        record.months_6,
+        record.ytd,
+        record.year_1,
+        record.years_2,
+        record.years_3,
+        record.years_5,
+    ];
+    for (i, value) in returns.into_iter().enumerate() {
+        // Stored as percent figures (12.5 for 12.5%); Excel's % format expects the fraction
+        if let Some(value) = value {
+            worksheet.write_number_with_format(row, 6 + i as u16, value as f64 / 100.0, percent)?;
+        }
+    }
+
+    write_optional_integer(worksheet, row, 14, record.rate_id)?;
+    write_optional_text(worksheet, row, 15, record.arn.as_deref())?;
+    write_optional_text(worksheet, row, 16, record.company.as_deref())?;
+    write_optional_text(worksheet, row, 17, record.scheme_category.as_deref())?;
+    write_optional_text(worksheet, row, 18, record.brokerage_type.as_deref())?;
+    write_optional_date(worksheet, row, 19, record.start_date)?;
+    write_optional_date(worksheet, row, 20, record.end_date)?;
+    write_optional_number(worksheet, row, 21, record.base_year_1)?;
+    write_optional_number(worksheet, row, 22, record.base_year_2)?;
+    write_optional_number(worksheet, row, 23, record.base_year_3)?;
+    worksheet.write_boolean(row, 24, record.is_rate_active)?;
+    worksheet.write_boolean(row, 25, record.incomplete)?;
+    Ok(())
+}
+
+// "Debt: Short/Medium Duration" -> "Debt Short Medium Duration", cut to 31 characters and made
+// unique (case-insensitively, as Excel compares them) with a " (2)"-style suffix
+fn sheet_name(category: &str, taken: &mut HashSet<String>) -> String {
+    let cleaned: String = category
+        .chars()
+        .map(|c| if INVALID_SHEET_NAME_CHARS.contains(&c) { ' ' } else { c })
+        .collect();
+    let cleaned = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    let cleaned = cleaned.trim_matches('\'');
+    let base = if cleaned.is_empty() { UNCATEGORIZED } else { cleaned };
+
+    let mut name: String = base.chars().take(MAX_SHEET_NAME_CHARS).collect();
+    let mut n = 2;
+    while !taken.insert(name.to_lowercase()) {
+        let suffix = format!(" ({})", n);
+        let kept = MAX_SHEET_NAME_CHARS - suffix.len();
+        name = base.chars().take(kept).collect::<String>() + &suffix;
+        n += 1;
+    }
+    name
+}
+
+fn write_optional_text(worksheet: &mut Worksheet, row: u32, col: u16, value: Option<&str>) -> Result<(), XlsxError> {
+    if let Some(text) = value {
+        worksheet.write_string(row, col, text)?;
+    }
+    Ok(())
+}
+
+fn write_optional_integer(worksheet: &mut Worksheet, row: u32, col: u16, value: Option<i32>) -> Result<(), XlsxError> {
+    if let Some(number) = value {
+        worksheet.write_number(row, col, number)?;
+    }
+    Ok(())
+}
+
+fn write_optional_number(worksheet: &mut Worksheet, row: u32, col: u16, value: Option<f32>) -> Result<(), XlsxError> {
+    if let Some(number) = value {
+        worksheet.write_number(row, col, number)?;
+    }
+    Ok(())
+}
+
+fn write_optional_date(
+    worksheet: &mut Worksheet,
+    row: u32,
+    col: u16,
+    value: Option<NaiveDate>,
+) -> Result<(), XlsxError> {
+    if let Some(date) = value {
+        worksheet.write_string(row, col, date.format("%Y-%m-%d").to_string())?;
+    }
+    Ok(())
+}
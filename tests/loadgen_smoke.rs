@@ -0,0 +1,101 @@
+// Runs the load generator's --smoke mode against the real server binary, so the example keeps up
+// with the routes, auth and upload format it exercises. Needs PostgreSQL like the DB-backed unit
+// tests, and is skipped the same way when TEST_DATABASE_URL is unset.
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+const TEST_DATABASE_URL_ENV: &str = "TEST_DATABASE_URL";
+const API_KEY: &str = "0123456789abcdef";
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Kills the server however the test ends
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+#[test]
+fn a_smoke_run_meets_its_slos() {
+    let database_url = match std::env::var(TEST_DATABASE_URL_ENV) {
+        Ok(url) => url,
+        Err(_) => {
+            eprintln!("{} is not set; skipping a test that needs PostgreSQL", TEST_DATABASE_URL_ENV);
+            return;
+        }
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let port = free_port();
+
+    // Rate limits off: the generator sends far more than one client's allowance
+    let config_path = dir.path().join("config.json");
+    let config = serde_json::json!({
+        "bind_addr": "127.0.0.1",
+        "port": port,
+        "read_rate_limit_per_minute": 0,
+        "expensive_rate_limit_per_minute": 0,
+        "api_keys": [{"id": "loadgen", "secret": API_KEY}],
+    });
+    std::fs::write(&config_path, config.to_string()).unwrap();
+    let loadgen_path = dir.path().join("loadgen.json");
+    let loadgen = serde_json::json!({"target_url": format!("http://127.0.0.1:{}", port), "api_key": API_KEY});
+    std::fs::write(&loadgen_path, loadgen.to_string()).unwrap();
+
+    let server = Command::new(env!("CARGO_BIN_EXE_excel-to-sqlite"))
+        .env("PERFTRACKER_CONFIG", &config_path)
+        .env("DATABASE_URL", &database_url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("start the server");
+    let mut server = Server(server);
+
+    let started = Instant::now();
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        assert!(server.0.try_wait().unwrap().is_none(), "the server exited during startup");
+        assert!(started.elapsed() < STARTUP_TIMEOUT, "the server did not start listening");
+        std::thread::sleep(Duration::from_millis(100));
+    }
+
+    // The nested cargo gets none of the package variables cargo set for this test: build scripts
+    // that watch them would otherwise see a change and rebuild, for this run and the next
+    let mut loadgen = Command::new(env!("CARGO"));
+    for (key, _) in std::env::vars_os() {
+        let key = key.to_string_lossy();
+        if ["CARGO_PKG_", "CARGO_MANIFEST_", "CARGO_CRATE_", "CARGO_BIN_", "CARGO_PRIMARY_", "CARGO_TARGET_TMPDIR"]
+            .iter()
+            .any(|prefix| key.starts_with(prefix))
+        {
+            loadgen.env_remove(&*key);
+        }
+    }
+    let output = loadgen
+        .args(["run", "--quiet", "--example", "perftracker-loadgen", "--"])
+        .arg(&loadgen_path)
+        .arg("--smoke")
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("run the load generator");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(output.status.success(), "{}\n{}", stdout, stderr);
+    assert!(stdout.contains("All SLOs met"), "{}", stdout);
+    // At least one upload ran through to a finished import, and none failed
+    let uploads: Vec<&str> = stdout
+        .lines()
+        .find(|line| line.starts_with("upload"))
+        .expect("an upload row in the report")
+        .split_whitespace()
+        .collect();
+    assert!(uploads[1].parse::<usize>().unwrap() >= 1, "{}", stdout);
+    assert_eq!(uploads[2], "0", "{}", stdout);
+}